@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::{PI, Vec3f};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    AmbientOcclusion,
+    BentNormal,
+    Diffuse,
+    Specular,
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "ao" => Ok(Self::AmbientOcclusion),
+            "bent-normal" => Ok(Self::BentNormal),
+            "diffuse" => Ok(Self::Diffuse),
+            "specular" => Ok(Self::Specular),
+            _ => bail!("Unknown AOV: {name}"),
+        }
+    }
+}
+
+/// Auxiliary buffers rendered alongside the beauty image instead of replacing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AovBuffer {
+    Depth,
+    Normal,
+    Albedo,
+    ShapeId,
+}
+
+impl AovBuffer {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "depth" => Ok(Self::Depth),
+            "normal" => Ok(Self::Normal),
+            "albedo" => Ok(Self::Albedo),
+            "id" => Ok(Self::ShapeId),
+            _ => bail!("Unknown AOV buffer: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Depth => "depth",
+            Self::Normal => "normal",
+            Self::Albedo => "albedo",
+            Self::ShapeId => "id",
+        }
+    }
+
+    pub const fn channels(self) -> usize {
+        match self {
+            Self::Depth | Self::ShapeId => 1,
+            Self::Normal | Self::Albedo => 3,
+        }
+    }
+}
+
+/// One AOV buffer as raw floats, `channels` values per pixel in row-major order.
+///
+/// Depth is the hit distance along the primary ray and infinite where the ray escapes,
+/// normals are world-space and turned towards the camera, and shape ids are the
+/// [`crate::scene::ShapeId`] plus one, with zero for the background.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatBuffer {
+    kind: AovBuffer,
+    width: u32,
+    height: u32,
+    data: Vec<f64>,
+}
+
+impl FloatBuffer {
+    pub fn new(kind: AovBuffer, width: u32, height: u32, data: Vec<f64>) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * kind.channels(),
+            "{} buffer has the wrong number of values",
+            kind.name()
+        );
+
+        Self {
+            kind,
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub const fn kind(&self) -> AovBuffer {
+        self.kind
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.kind.name()
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> &[f64] {
+        let channels = self.kind.channels();
+        let start = (y as usize * self.width as usize + x as usize) * channels;
+        &self.data[start..start + channels]
+    }
+
+    /// The buffer as a viewable RGBA image: depth scaled so the farthest hit is white,
+    /// normals mapped from [-1, 1] to [0, 1] and every shape id given its own color.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let far = self
+            .data
+            .iter()
+            .copied()
+            .filter(|depth| depth.is_finite())
+            .fold(0.0, f64::max);
+
+        self.data
+            .chunks_exact(self.kind.channels())
+            .flat_map(|pixel| {
+                let color = match self.kind {
+                    AovBuffer::Depth if pixel[0].is_finite() && far > 0.0 => {
+                        Vec3f::new(pixel[0] / far)
+                    }
+                    AovBuffer::Depth => Vec3f::new(1.0),
+                    AovBuffer::Normal => {
+                        Vec3f::new_with_data([pixel[0], pixel[1], pixel[2]]) * 0.5 + Vec3f::new(0.5)
+                    }
+                    AovBuffer::Albedo => Vec3f::new_with_data([pixel[0], pixel[1], pixel[2]]),
+                    AovBuffer::ShapeId => id_color(pixel[0]),
+                };
+                [to_u8(color[0]), to_u8(color[1]), to_u8(color[2]), 255]
+            })
+            .collect()
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_u8(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn id_color(id: f64) -> Vec3f {
+    if id <= 0.0 {
+        return Vec3f::new(0.0);
+    }
+
+    let (red, green) = pixel_rotation(id as u32, 0x5bd1_e995);
+    let (blue, _) = pixel_rotation(0x1b87_3593, id as u32);
+    Vec3f::new_with_data([red, green, blue]) * 0.8 + Vec3f::new(0.2)
+}
+
+/// Where an AOV buffer for the image at `path` is written: `render.png` gets
+/// `render.depth.png` and so on.
+pub fn buffer_path(path: &Path, buffer: AovBuffer) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or_else(|| "png".into(), |extension| extension.to_string_lossy());
+    path.with_file_name(format!("{stem}.{}.{extension}", buffer.name()))
+}
+
+pub fn pixel_rotation(x: u32, y: u32) -> (f64, f64) {
+    let mut hash = x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b_3c6d);
+    hash ^= hash >> 12;
+
+    (
+        f64::from(hash & 0xffff) / 65536.0,
+        f64::from(hash >> 16) / 65536.0,
+    )
+}
+
+pub fn cosine_hemisphere(normal: Vec3f, u1: f64, u2: f64) -> Vec3f {
+    let helper = if normal[0].abs() > 0.9 {
+        Vec3f::new_with_data([0.0, 1.0, 0.0])
+    } else {
+        Vec3f::new_with_data([1.0, 0.0, 0.0])
+    };
+    let tangent = helper.cross(&normal).normalize(None);
+    let bitangent = normal.cross(&tangent);
+
+    let radius = u1.sqrt();
+    let angle = 2.0 * PI * u2;
+
+    tangent * (radius * angle.cos())
+        + bitangent * (radius * angle.sin())
+        + normal * (1.0 - u1).sqrt()
+}
@@ -0,0 +1,147 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+const INITIAL_CAPACITY: usize = 64;
+
+static RAYS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Rays traced and scratch buffers allocated for them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    rays: u64,
+    allocations: usize,
+}
+
+impl ArenaStats {
+    pub const fn rays(&self) -> u64 {
+        self.rays
+    }
+
+    /// Buffers allocated or grown, including the pools holding them.
+    pub const fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn allocations_per_ray(&self) -> f64 {
+        self.allocations as f64 / self.rays.max(1) as f64
+    }
+}
+
+impl fmt::Display for ArenaStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} allocations over {} rays ({:.6} per ray)",
+            self.allocations,
+            self.rays,
+            self.allocations_per_ray()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Arena {
+    indices: Vec<Vec<usize>>,
+    segments: Vec<Vec<(usize, (f64, f64))>>,
+    packet_nodes: Vec<Vec<(usize, u32)>>,
+    stats: ArenaStats,
+}
+
+thread_local! {
+    static ARENA: RefCell<Arena> = RefCell::new(Arena::default());
+}
+
+fn with_buffer<T, R>(
+    pool: fn(&mut Arena) -> &mut Vec<Vec<T>>,
+    scope: impl FnOnce(&mut Vec<T>) -> R,
+) -> R {
+    let mut buffer = ARENA.with_borrow_mut(|arena| {
+        pool(arena).pop().unwrap_or_else(|| {
+            arena.stats.allocations += 1;
+            Vec::with_capacity(INITIAL_CAPACITY)
+        })
+    });
+    let capacity = buffer.capacity();
+
+    let result = scope(&mut buffer);
+
+    buffer.clear();
+    let grew = buffer.capacity() != capacity;
+    ARENA.with_borrow_mut(|arena| {
+        let pool = pool(arena);
+        let pool_grew = pool.len() == pool.capacity();
+        pool.push(buffer);
+        arena.stats.allocations += usize::from(grew) + usize::from(pool_grew);
+    });
+    result
+}
+
+/// Lends `scope` an empty index buffer from this thread's pool.
+pub fn with_indices<R>(scope: impl FnOnce(&mut Vec<usize>) -> R) -> R {
+    with_buffer(|arena| &mut arena.indices, scope)
+}
+
+/// Lends `scope` an empty buffer of `(volume, (entry, exit))` ray intervals.
+pub fn with_segments<R>(scope: impl FnOnce(&mut Vec<(usize, (f64, f64))>) -> R) -> R {
+    with_buffer(|arena| &mut arena.segments, scope)
+}
+
+/// Lends `scope` an empty buffer of `(node, lane mask)` pairs for packet traversal.
+pub fn with_packet_nodes<R>(scope: impl FnOnce(&mut Vec<(usize, u32)>) -> R) -> R {
+    with_buffer(|arena| &mut arena.packet_nodes, scope)
+}
+
+/// Returns this thread's statistics since the last call, charging its allocations to the
+/// `rays` the tile traced, and adds them to the totals.
+pub fn finish_tile(rays: u64) -> ArenaStats {
+    let mut stats = ARENA.with_borrow_mut(|arena| std::mem::take(&mut arena.stats));
+    stats.rays = rays;
+    RAYS.fetch_add(stats.rays, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(stats.allocations, Ordering::Relaxed);
+    stats
+}
+
+/// Totals over every finished tile on every thread.
+pub fn stats() -> ArenaStats {
+    ArenaStats {
+        rays: RAYS.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Vec3f, lights::init_default_lights, scene::Scene, shapes::init_default_shapes, stats,
+    };
+
+    #[test]
+    fn warm_arenas_trace_rays_without_allocating() {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let origin = Vec3f::new(0.0);
+        let trace_fan = || {
+            for step in 0..64 {
+                let x = f64::from(step).mul_add(0.02, -0.64);
+                scene.trace(
+                    origin,
+                    Vec3f::new_with_data([x, -0.1, -1.0]).normalize(None),
+                );
+            }
+        };
+
+        trace_fan();
+        finish_tile(stats::finish_tile().rays());
+        trace_fan();
+        let warm = finish_tile(stats::finish_tile().rays());
+
+        assert!(warm.rays() >= 64);
+        assert_eq!(warm.allocations(), 0);
+        assert!(stats().rays() >= warm.rays());
+    }
+}
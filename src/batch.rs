@@ -0,0 +1,318 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Instant,
+};
+
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Vec3f, camera::Camera, renderer::Renderer, scene_file::SceneFile, settings::RenderSettings,
+};
+
+const fn default_parallelism() -> usize {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default = "default_parallelism")]
+    parallelism: usize,
+    jobs: Vec<Job>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    scene: Option<PathBuf>,
+    camera: Option<CameraOverride>,
+    #[serde(default)]
+    settings: BTreeMap<String, Value>,
+    output: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraOverride {
+    position: Option<[f64; 3]>,
+    fov: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+struct JobResult {
+    index: usize,
+    output: PathBuf,
+    status: JobStatus,
+    duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchSummary {
+    succeeded: usize,
+    failed: usize,
+    duration_ms: u128,
+    jobs: Vec<JobResult>,
+}
+
+impl BatchSummary {
+    pub const fn failed(&self) -> usize {
+        self.failed
+    }
+}
+
+fn settings_args(settings: &BTreeMap<String, Value>) -> Result<Vec<String>> {
+    let mut args = Vec::with_capacity(settings.len() * 2);
+
+    for (key, value) in settings {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            Value::Number(value) => value.to_string(),
+            _ => bail!("Setting '{key}' must be a string or a number"),
+        };
+
+        args.push(format!("--{}", key.replace('_', "-")));
+        args.push(value);
+    }
+
+    Ok(args)
+}
+
+/// Where `job` writes its image, or would have if it failed before rendering.
+fn output_path(job: &Job, base_settings: &RenderSettings) -> PathBuf {
+    let output_dir = settings_args(&job.settings)
+        .and_then(|args| base_settings.with_args(args))
+        .map_or_else(
+            |_| base_settings.output_dir().to_path_buf(),
+            |settings| settings.output_dir().to_path_buf(),
+        );
+    output_dir.join(&job.output)
+}
+
+fn render_job(job: &Job, manifest_dir: &Path, base_settings: &RenderSettings) -> Result<PathBuf> {
+    let scene_file = match &job.scene {
+        Some(path) => SceneFile::load(&manifest_dir.join(path))?,
+        None => SceneFile::default(),
+    };
+    let settings = base_settings
+        .with_args(settings_args(&job.settings)?)?
+        .with_scene_overrides(scene_file.overrides());
+    let (scene, mut camera) = scene_file.into_parts();
+
+    if let Some(camera_override) = &job.camera {
+        if let Some(position) = camera_override.position {
+            camera = Camera::new(Vec3f::new_with_data(position), camera.fov());
+        }
+        if let Some(fov) = camera_override.fov {
+            camera.set_fov(fov);
+        }
+    }
+
+    let output_path = settings.output_dir().join(&job.output);
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    let stripe_rows = settings.stripe_rows();
+    let renderer = Renderer::new(scene, settings, job.width, job.height, camera);
+    renderer.render_to_png(&output_path, stripe_rows)?;
+
+    Ok(output_path)
+}
+
+pub fn run_manifest(path: &Path, base_settings: &RenderSettings) -> Result<BatchSummary> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read job manifest {}", path.display()))?;
+    let manifest: Manifest = serde_json::from_str(&source)
+        .with_context(|| format!("Failed to parse job manifest {}", path.display()))?;
+
+    if manifest.parallelism == 0 {
+        bail!("Manifest parallelism must be at least 1");
+    }
+
+    let manifest_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let start = Instant::now();
+    let next_job = AtomicUsize::new(0);
+    let workers = manifest.parallelism.min(manifest.jobs.len()).max(1);
+
+    let mut results: Vec<JobResult> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+
+                    loop {
+                        let index = next_job.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = manifest.jobs.get(index) else {
+                            break;
+                        };
+
+                        let job_start = Instant::now();
+                        let outcome = render_job(job, manifest_dir, base_settings);
+                        let duration_ms = job_start.elapsed().as_millis();
+
+                        results.push(match outcome {
+                            Ok(output) => {
+                                info!("Job {index} rendered to {}", output.display());
+                                JobResult {
+                                    index,
+                                    output,
+                                    status: JobStatus::Succeeded,
+                                    duration_ms,
+                                    error: None,
+                                }
+                            }
+                            Err(err) => {
+                                error!("Job {index} failed: {err:#}");
+                                JobResult {
+                                    index,
+                                    output: output_path(job, base_settings),
+                                    status: JobStatus::Failed,
+                                    duration_ms,
+                                    error: Some(format!("{err:#}")),
+                                }
+                            }
+                        });
+                    }
+
+                    results
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle.join().unwrap_or_else(|_| {
+                    error!("A batch worker panicked; its jobs are reported as failed");
+                    Vec::new()
+                })
+            })
+            .collect()
+    });
+
+    // Jobs claimed by a panicked worker never made it into the results.
+    let mut reported = vec![false; manifest.jobs.len()];
+    for result in &results {
+        reported[result.index] = true;
+    }
+    for (index, job) in manifest.jobs.iter().enumerate() {
+        if !reported[index] {
+            results.push(JobResult {
+                index,
+                output: output_path(job, base_settings),
+                status: JobStatus::Failed,
+                duration_ms: 0,
+                error: Some("Batch worker panicked".to_owned()),
+            });
+        }
+    }
+
+    results.sort_by_key(|result| result.index);
+    let failed = results
+        .iter()
+        .filter(|result| matches!(result.status, JobStatus::Failed))
+        .count();
+
+    Ok(BatchSummary {
+        succeeded: results.len() - failed,
+        failed,
+        duration_ms: start.elapsed().as_millis(),
+        jobs: results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn settings_become_cli_flags() -> Result<()> {
+        let settings = BTreeMap::from([
+            ("max_depth".to_owned(), json!(2)),
+            ("tone_map".to_owned(), json!("reinhard")),
+        ]);
+        assert_eq!(
+            settings_args(&settings)?,
+            ["--max-depth", "2", "--tone-map", "reinhard"]
+        );
+
+        let invalid = BTreeMap::from([("denoise".to_owned(), json!(true))]);
+        assert!(settings_args(&invalid).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn manifests_render_every_job_and_count_failures() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        fs::create_dir_all(&directory)?;
+        let manifest = json!({
+            "parallelism": 2,
+            "jobs": [
+                {
+                    "output": "frames/good.png",
+                    "width": 8,
+                    "height": 6,
+                    "camera": { "fov": 1.0 },
+                    "settings": { "spp": 1, "max_depth": 2 }
+                },
+                {
+                    "output": "frames/bad.png",
+                    "width": 8,
+                    "height": 6,
+                    "settings": { "tone_map": "sepia" }
+                }
+            ]
+        });
+        let manifest_path = directory.join("jobs.json");
+        fs::write(&manifest_path, manifest.to_string())?;
+        let settings = RenderSettings::default()
+            .with_args(["--output-dir".to_owned(), directory.display().to_string()])?;
+
+        let summary = run_manifest(&manifest_path, &settings)?;
+        assert_eq!(summary.failed(), 1);
+        let report = serde_json::to_value(&summary)?;
+        assert_eq!(report["succeeded"], 1);
+        assert_eq!(report["jobs"][0]["status"], "succeeded");
+        assert_eq!(report["jobs"][1]["status"], "failed");
+        assert_eq!(
+            report["jobs"][0]["output"].as_str().map(PathBuf::from),
+            Some(directory.join("frames/good.png"))
+        );
+        assert_eq!(
+            report["jobs"][1]["output"].as_str().map(PathBuf::from),
+            Some(directory.join("frames/bad.png"))
+        );
+        assert!(
+            report["jobs"][1]["error"]
+                .as_str()
+                .is_some_and(|error| error.contains("sepia"))
+        );
+        assert!(directory.join("frames/good.png").exists());
+        assert!(!directory.join("frames/bad.png").exists());
+
+        fs::write(&manifest_path, r#"{ "parallelism": 0, "jobs": [] }"#)?;
+        assert!(run_manifest(&manifest_path, &settings).is_err());
+        fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
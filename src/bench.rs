@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+
+use crate::{
+    FOV, Vec3f, camera::Camera, lights::init_default_lights, renderer::Renderer, scene::Scene,
+    settings::RenderSettings, shapes::init_default_shapes, stats::FrameStats,
+};
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct BenchRun {
+    render_ms: f64,
+    rays: u64,
+    shadow_rays: u64,
+    bvh_visits: u64,
+    rays_per_second: f64,
+}
+
+impl BenchRun {
+    fn new(stats: &FrameStats) -> Self {
+        Self {
+            render_ms: millis(stats.render_time()),
+            rays: stats.counts().rays(),
+            shadow_rays: stats.counts().shadow_rays(),
+            bvh_visits: stats.counts().bvh_visits(),
+            rays_per_second: stats.rays_per_second(),
+        }
+    }
+
+    pub const fn rays_per_second(&self) -> f64 {
+        self.rays_per_second
+    }
+}
+
+/// Stage durations in milliseconds.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct StageTimings {
+    #[serde(rename = "scene_setup_ms")]
+    scene_setup: f64,
+    #[serde(rename = "bvh_build_ms")]
+    bvh_build: f64,
+    #[serde(rename = "warmup_ms")]
+    warmup: f64,
+    #[serde(rename = "min_render_ms")]
+    min_render: f64,
+    #[serde(rename = "avg_render_ms")]
+    avg_render: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    width: u32,
+    height: u32,
+    shapes: usize,
+    samples_per_pixel: u32,
+    integrator: &'static str,
+    min_rays_per_second: f64,
+    avg_rays_per_second: f64,
+    max_rays_per_second: f64,
+    stages: StageTimings,
+    runs: Vec<BenchRun>,
+}
+
+impl BenchReport {
+    pub fn runs(&self) -> &[BenchRun] {
+        &self.runs
+    }
+
+    pub const fn min_rays_per_second(&self) -> f64 {
+        self.min_rays_per_second
+    }
+
+    pub const fn avg_rays_per_second(&self) -> f64 {
+        self.avg_rays_per_second
+    }
+}
+
+/// Renders the built-in default scene off-screen `runs` times after one untimed warm-up
+/// render, so results only depend on the code under test, the settings and the machine.
+pub fn run_benchmark(
+    settings: &RenderSettings,
+    runs: u32,
+    (width, height): (u32, u32),
+) -> Result<BenchReport> {
+    if runs == 0 {
+        bail!("Benchmark needs at least one run");
+    }
+
+    let start = Instant::now();
+    let shapes = init_default_shapes();
+    let lights = init_default_lights();
+    let scene_setup = start.elapsed();
+
+    let shape_count = shapes.len();
+    let start = Instant::now();
+    let scene = Scene::new(shapes, lights);
+    let bvh_build = start.elapsed();
+
+    let camera = Camera::new(Vec3f::new_with_data([0.0, 0.0, 2.0]), FOV);
+    let renderer = Renderer::new(scene, settings.clone(), width, height, camera);
+    let mut frame = vec![0; width as usize * height as usize * 4];
+
+    let start = Instant::now();
+    renderer.render_into(&mut frame)?;
+    let warmup = start.elapsed();
+
+    let runs = (0..runs)
+        .map(|_| -> Result<BenchRun> {
+            let before = renderer.ray_counts();
+            let start = Instant::now();
+            renderer.render_into(&mut frame)?;
+            let stats = FrameStats::new(start.elapsed(), renderer.ray_counts().since(before));
+            Ok(BenchRun::new(&stats))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    #[allow(clippy::cast_precision_loss)]
+    let count = runs.len() as f64;
+    let rates = runs.iter().map(BenchRun::rays_per_second);
+    let render_times = runs.iter().map(|run| run.render_ms);
+
+    Ok(BenchReport {
+        width,
+        height,
+        shapes: shape_count,
+        samples_per_pixel: settings.samples_per_pixel(),
+        integrator: settings.integrator().name(),
+        min_rays_per_second: rates.clone().fold(f64::INFINITY, f64::min),
+        avg_rays_per_second: rates.clone().sum::<f64>() / count,
+        max_rays_per_second: rates.fold(0.0, f64::max),
+        stages: StageTimings {
+            scene_setup: millis(scene_setup),
+            bvh_build: millis(bvh_build),
+            warmup: millis(warmup),
+            min_render: render_times.clone().fold(f64::INFINITY, f64::min),
+            avg_render: render_times.sum::<f64>() / count,
+        },
+        runs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark_reports_every_run_as_json() -> Result<()> {
+        let report = run_benchmark(&RenderSettings::default(), 3, (32, 24))?;
+
+        assert_eq!(report.runs().len(), 3);
+        assert!(report.runs().iter().all(|run| run.rays >= 32 * 24));
+        assert!(report.min_rays_per_second() > 0.0);
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["runs"].as_array().map(Vec::len), Some(3));
+        assert!(json["stages"]["bvh_build_ms"].as_f64().is_some());
+        Ok(())
+    }
+}
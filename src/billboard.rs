@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    EPSILON, Material, Vec3f, Vec4f,
+    bvh::Aabb,
+    output,
+    shapes::{Hit, Intersectable, Shape},
+};
+
+const ALPHA_CUTOFF: f64 = 0.5;
+const DEFAULT_VIEWER_OFFSET: Vec3f = Vec3f::const_new_with_data([0.0, 0.0, 1.0]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Facing {
+    Spherical,
+    Cylindrical(Vec3f),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpriteSource {
+    Glow,
+    Image(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sprite {
+    source: SpriteSource,
+    columns: usize,
+    rows: usize,
+    texels: Vec<Vec4f>,
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn texel_index(coordinate: f64, count: usize) -> usize {
+    ((coordinate * count as f64) as usize).min(count - 1)
+}
+
+impl Sprite {
+    pub const fn glow() -> Self {
+        Self {
+            source: SpriteSource::Glow,
+            columns: 0,
+            rows: 0,
+            texels: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path, source: PathBuf) -> Result<Self> {
+        let (columns, rows, texels) = output::read_rgba_png(path)?;
+        if columns == 0 || rows == 0 {
+            bail!("Sprite {} has no pixels", path.display());
+        }
+
+        Ok(Self {
+            source: SpriteSource::Image(source),
+            columns,
+            rows,
+            texels: texels.into_iter().map(Vec4f::new_with_data).collect(),
+        })
+    }
+
+    pub const fn source(&self) -> &SpriteSource {
+        &self.source
+    }
+
+    pub fn sample(&self, u: f64, v: f64) -> Vec4f {
+        match self.source {
+            SpriteSource::Glow => {
+                let radius = (u - 0.5).hypot(v - 0.5) * 2.0;
+                let brightness = (1.0 - radius).max(0.0);
+                let alpha = if radius < 1.0 { 1.0 } else { 0.0 };
+                Vec4f::new_with_data([brightness, brightness, brightness, alpha])
+            }
+            SpriteSource::Image(_) => {
+                let column = texel_index(u, self.columns);
+                let row = texel_index(v, self.rows);
+                self.texels[row * self.columns + column]
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Billboard {
+    center: Vec3f,
+    width: f64,
+    height: f64,
+    facing: Facing,
+    sprite: Sprite,
+    material: Material,
+    viewer: Vec3f,
+    right: Vec3f,
+    up: Vec3f,
+    normal: Vec3f,
+}
+
+fn perpendicular(vector: Vec3f) -> Vec3f {
+    let helper = if vector.y().abs() < 0.9 {
+        Vec3f::new_with_data([0.0, 1.0, 0.0])
+    } else {
+        Vec3f::new_with_data([0.0, 0.0, 1.0])
+    };
+    helper.cross(&vector).unit()
+}
+
+impl Billboard {
+    pub fn new(
+        center: Vec3f,
+        width: f64,
+        height: f64,
+        facing: Facing,
+        sprite: Sprite,
+        material: Material,
+    ) -> Result<Self> {
+        if !(width > 0.0 && height > 0.0) {
+            bail!("Billboard size must be positive, got {width}x{height}");
+        }
+        if let Facing::Cylindrical(axis) = facing
+            && !axis.length().is_normal()
+        {
+            bail!("Billboard axis must not be zero");
+        }
+
+        let mut billboard = Self {
+            center,
+            width,
+            height,
+            facing,
+            sprite,
+            material,
+            viewer: center + DEFAULT_VIEWER_OFFSET,
+            right: Vec3f::new(0.0),
+            up: Vec3f::new(0.0),
+            normal: Vec3f::new(0.0),
+        };
+        billboard.face(billboard.viewer);
+        Ok(billboard)
+    }
+
+    pub fn face(&mut self, viewer: Vec3f) {
+        self.viewer = viewer;
+        let toward = viewer - self.center;
+        match self.facing {
+            Facing::Spherical => {
+                self.normal = if toward.length() > 0.0 {
+                    toward.unit()
+                } else {
+                    DEFAULT_VIEWER_OFFSET
+                };
+                self.right = perpendicular(self.normal);
+                self.up = self.normal.cross(&self.right);
+            }
+            Facing::Cylindrical(axis) => {
+                self.up = axis.unit();
+                let flat = toward - self.up * toward.dot(self.up);
+                self.normal = if flat.length() > 0.0 {
+                    flat.unit()
+                } else {
+                    perpendicular(self.up)
+                };
+                self.right = self.up.cross(&self.normal);
+            }
+        }
+    }
+
+    pub const fn center(&self) -> Vec3f {
+        self.center
+    }
+
+    pub const fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub const fn height(&self) -> f64 {
+        self.height
+    }
+
+    pub const fn facing(&self) -> Facing {
+        self.facing
+    }
+
+    pub const fn sprite(&self) -> &Sprite {
+        &self.sprite
+    }
+
+    fn sprite_coordinates(&self, point: Vec3f) -> (f64, f64) {
+        let offset = point - self.center;
+        (
+            offset.dot(self.right) / self.width + 0.5,
+            0.5 - offset.dot(self.up) / self.height,
+        )
+    }
+}
+
+impl Intersectable for Billboard {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let denominator = direction.dot(self.normal);
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let distance = (self.center - origin).dot(self.normal) / denominator;
+        if distance <= EPSILON {
+            return None;
+        }
+
+        let (u, v) = self.sprite_coordinates(origin + direction * distance);
+        ((0.0..=1.0).contains(&u)
+            && (0.0..=1.0).contains(&v)
+            && self.sprite.sample(u, v)[3] >= ALPHA_CUTOFF)
+            .then_some(Hit::new(distance, self.normal))
+    }
+}
+
+impl Shape for Billboard {
+    fn get_normal(&self, _hit_point: Vec3f) -> Vec3f {
+        self.normal
+    }
+
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn get_material_at(&self, hit_point: Vec3f) -> Material {
+        let (u, v) = self.sprite_coordinates(hit_point);
+        let texel = self.sprite.sample(u.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let tint = Vec3f::new_with_data([texel[0], texel[1], texel[2]]);
+        self.material
+            .with_diffuse_color(self.material.diffuse_color().hadamard(tint))
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.center += offset;
+        self.face(self.viewer);
+    }
+
+    fn thickness(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let radius = Vec3f::new(self.width.hypot(self.height) / 2.0);
+        Some(Aabb::from_points(&[
+            self.center - radius,
+            self.center + radius,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RED_MATERIAL;
+
+    #[test]
+    fn billboards_turn_towards_the_viewer_and_cut_out_transparent_texels() -> Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let mut sprite = Billboard::new(
+            Vec3f::new(0.0),
+            2.0,
+            2.0,
+            Facing::Spherical,
+            Sprite::glow(),
+            RED_MATERIAL,
+        )?;
+        let mut tree = Billboard::new(
+            Vec3f::new(0.0),
+            1.0,
+            4.0,
+            Facing::Cylindrical(up),
+            Sprite::glow(),
+            RED_MATERIAL,
+        )?;
+
+        for viewer in [
+            Vec3f::new_with_data([5.0, 0.0, 0.0]),
+            Vec3f::new_with_data([-3.0, 4.0, 2.0]),
+        ] {
+            sprite.face(viewer);
+            tree.face(viewer);
+            let toward_center = -viewer.unit();
+            assert!((sprite.get_normal(Vec3f::new(0.0)) - viewer.unit()).length() < 1e-12);
+            assert!(tree.get_normal(Vec3f::new(0.0)).dot(up).abs() < 1e-12);
+            assert!(
+                sprite
+                    .ray_intersect(viewer, toward_center)
+                    .is_some_and(|distance| (distance - viewer.length()).abs() < 1e-9)
+            );
+            assert!(tree.ray_intersect(viewer, toward_center).is_some());
+        }
+
+        let corner = Vec3f::new_with_data([0.9, 0.9, 5.0]);
+        sprite.face(Vec3f::new_with_data([0.0, 0.0, 5.0]));
+        assert!(
+            sprite
+                .ray_intersect(corner, Vec3f::new_with_data([0.0, 0.0, -1.0]))
+                .is_none()
+        );
+        let center_color = sprite.get_material_at(Vec3f::new(0.0)).diffuse_color();
+        let edge_color = sprite
+            .get_material_at(Vec3f::new_with_data([0.8, 0.0, 0.0]))
+            .diffuse_color();
+        assert_eq!(center_color, RED_MATERIAL.diffuse_color());
+        assert!(edge_color.x() < center_color.x());
+
+        assert!(
+            Billboard::new(
+                Vec3f::new(0.0),
+                0.0,
+                1.0,
+                Facing::Spherical,
+                Sprite::glow(),
+                RED_MATERIAL
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+}
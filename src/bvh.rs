@@ -0,0 +1,454 @@
+use crate::{Vec3f, arena, geometry::inverse_direction, stats};
+
+const MAX_LEAF_SIZE: usize = 4;
+pub const MAX_PACKET_SIZE: usize = 32;
+
+fn consider(closest: &mut Option<(f64, usize)>, index: usize, distance: Option<f64>) {
+    let Some(distance) = distance.filter(|distance| !distance.is_nan()) else {
+        return;
+    };
+    let nearer = closest.is_none_or(|(best, best_index)| {
+        distance < best || (distance.total_cmp(&best).is_eq() && index < best_index)
+    });
+    if nearer {
+        *closest = Some((distance, index));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    min: Vec3f,
+    max: Vec3f,
+}
+
+impl Aabb {
+    pub fn from_points(points: &[Vec3f]) -> Self {
+        let empty = Self {
+            min: Vec3f::new(f64::INFINITY),
+            max: Vec3f::new(f64::NEG_INFINITY),
+        };
+
+        points
+            .iter()
+            .fold(empty, |bounds, &point| bounds.grow(point))
+    }
+
+    pub const fn grow(self, point: Vec3f) -> Self {
+        Self {
+            min: Vec3f::new_with_data([
+                self.min.x().min(point.x()),
+                self.min.y().min(point.y()),
+                self.min.z().min(point.z()),
+            ]),
+            max: Vec3f::new_with_data([
+                self.max.x().max(point.x()),
+                self.max.y().max(point.y()),
+                self.max.z().max(point.z()),
+            ]),
+        }
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub const fn min(&self) -> Vec3f {
+        self.min
+    }
+
+    pub const fn max(&self) -> Vec3f {
+        self.max
+    }
+
+    pub fn extent(&self) -> Vec3f {
+        self.max - self.min
+    }
+
+    fn centroid(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn translated(self, offset: Vec3f) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    pub fn encloses(&self, other: &Self) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    fn contains(&self, point: Vec3f, tolerance: f64) -> bool {
+        (0..3).all(|axis| {
+            (self.min[axis] - tolerance..=self.max[axis] + tolerance).contains(&point[axis])
+        })
+    }
+
+    pub fn interval(&self, origin: Vec3f, direction: Vec3f) -> Option<(f64, f64)> {
+        self.slab(origin, inverse_direction(direction))
+    }
+
+    fn entry_distance(&self, origin: Vec3f, inverse_direction: Vec3f) -> Option<f64> {
+        self.slab(origin, inverse_direction).map(|(near, _)| near)
+    }
+
+    fn slab(&self, origin: Vec3f, inverse_direction: Vec3f) -> Option<(f64, f64)> {
+        let mut near: f64 = 0.0;
+        let mut far = f64::INFINITY;
+
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t2 = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+
+        (near <= far).then_some((near, far))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BvhNode {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+    second_child: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(bounds: impl IntoIterator<Item = Option<Aabb>>) -> Self {
+        let mut primitives = Vec::new();
+        let mut unbounded = Vec::new();
+        for (index, bounds) in bounds.into_iter().enumerate() {
+            match bounds {
+                Some(bounds) => primitives.push((index, bounds)),
+                None => unbounded.push(index),
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: Vec::with_capacity(primitives.len()),
+            unbounded,
+        };
+        if !primitives.is_empty() {
+            bvh.build(&mut primitives);
+        }
+
+        bvh
+    }
+
+    fn build(&mut self, primitives: &mut [(usize, Aabb)]) -> usize {
+        let node_index = self.nodes.len();
+        let bounds = primitives[1..]
+            .iter()
+            .fold(primitives[0].1, |bounds, (_, primitive)| {
+                bounds.union(*primitive)
+            });
+        self.nodes.push(BvhNode {
+            bounds,
+            first: self.indices.len(),
+            count: 0,
+            second_child: 0,
+        });
+
+        if primitives.len() <= MAX_LEAF_SIZE {
+            self.indices
+                .extend(primitives.iter().map(|(index, _)| index));
+            self.nodes[node_index].count = primitives.len();
+            return node_index;
+        }
+
+        let centroids = Aabb::from_points(
+            &primitives
+                .iter()
+                .map(|(_, bounds)| bounds.centroid())
+                .collect::<Vec<_>>(),
+        );
+        let extent = centroids.extent();
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+
+        let middle = primitives.len() / 2;
+        primitives.select_nth_unstable_by(middle, |a, b| {
+            a.1.centroid()[axis].total_cmp(&b.1.centroid()[axis])
+        });
+
+        let (left, right) = primitives.split_at_mut(middle);
+        self.build(left);
+        self.nodes[node_index].second_child = self.build(right);
+
+        node_index
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bounds)
+    }
+
+    pub fn translate(&mut self, offset: Vec3f) {
+        for node in &mut self.nodes {
+            node.bounds = node.bounds.translated(offset);
+        }
+    }
+
+    pub fn closest_hit(
+        &self,
+        origin: Vec3f,
+        inverse_direction: Vec3f,
+        mut intersect: impl FnMut(usize) -> Option<f64>,
+    ) -> Option<(f64, usize)> {
+        let mut closest: Option<(f64, usize)> = None;
+        for &index in &self.unbounded {
+            consider(&mut closest, index, intersect(index));
+        }
+
+        arena::with_indices(|stack| {
+            if !self.nodes.is_empty() {
+                stack.push(0);
+            }
+
+            let mut visits = 0;
+            while let Some(node_index) = stack.pop() {
+                visits += 1;
+                let node = &self.nodes[node_index];
+                let Some(entry) = node.bounds.entry_distance(origin, inverse_direction) else {
+                    continue;
+                };
+                if closest.is_some_and(|(best, _)| entry > best) {
+                    continue;
+                }
+
+                if node.count > 0 {
+                    for &index in &self.indices[node.first..node.first + node.count] {
+                        consider(&mut closest, index, intersect(index));
+                    }
+                } else {
+                    stack.push(node.second_child);
+                    stack.push(node_index + 1);
+                }
+            }
+
+            stats::count_bvh_visits(visits);
+            closest
+        })
+    }
+
+    /// Finds the closest hit of every ray in a packet of `(origin, inverse_direction)` pairs
+    /// in one traversal. Each node is tested against the rays still active in it, so
+    /// coherent rays share node fetches; the hits match [`Self::closest_hit`] ray by ray.
+    pub fn closest_hits(
+        &self,
+        rays: &[(Vec3f, Vec3f)],
+        mut intersect: impl FnMut(usize, usize) -> Option<f64>,
+        hits: &mut [Option<(f64, usize)>],
+    ) {
+        assert!(rays.len() <= MAX_PACKET_SIZE && rays.len() == hits.len());
+        hits.fill(None);
+        if rays.is_empty() {
+            return;
+        }
+
+        for &index in &self.unbounded {
+            for (ray, hit) in hits.iter_mut().enumerate() {
+                consider(hit, index, intersect(ray, index));
+            }
+        }
+
+        arena::with_packet_nodes(|stack| {
+            if !self.nodes.is_empty() {
+                stack.push((0, u32::MAX >> (MAX_PACKET_SIZE - rays.len())));
+            }
+
+            let mut visits = 0;
+            while let Some((node_index, mask)) = stack.pop() {
+                visits += 1;
+                let node = &self.nodes[node_index];
+                let active = (0..rays.len())
+                    .filter(|&ray| mask & (1 << ray) != 0)
+                    .filter(|&ray| {
+                        let (origin, inverse_direction) = rays[ray];
+                        node.bounds
+                            .entry_distance(origin, inverse_direction)
+                            .is_some_and(|entry| hits[ray].is_none_or(|(best, _)| entry <= best))
+                    })
+                    .fold(0, |active, ray| active | 1 << ray);
+                if active == 0 {
+                    continue;
+                }
+
+                if node.count > 0 {
+                    for &index in &self.indices[node.first..node.first + node.count] {
+                        for ray in (0..rays.len()).filter(|&ray| active & (1 << ray) != 0) {
+                            consider(&mut hits[ray], index, intersect(ray, index));
+                        }
+                    }
+                } else {
+                    stack.push((node.second_child, active));
+                    stack.push((node_index + 1, active));
+                }
+            }
+
+            stats::count_bvh_visits(visits);
+        });
+    }
+
+    /// Reports whether any primitive is hit closer than `max_distance`, stopping at the
+    /// first one instead of searching for the closest.
+    pub fn any_hit(
+        &self,
+        origin: Vec3f,
+        inverse_direction: Vec3f,
+        max_distance: f64,
+        mut hits: impl FnMut(usize) -> bool,
+    ) -> bool {
+        if self.unbounded.iter().any(|&index| hits(index)) {
+            return true;
+        }
+
+        arena::with_indices(|stack| {
+            if !self.nodes.is_empty() {
+                stack.push(0);
+            }
+
+            let mut visits = 0;
+            let mut found = false;
+            while let Some(node_index) = stack.pop() {
+                visits += 1;
+                let node = &self.nodes[node_index];
+                let reachable = node
+                    .bounds
+                    .entry_distance(origin, inverse_direction)
+                    .is_some_and(|entry| entry < max_distance);
+                if !reachable {
+                    continue;
+                }
+
+                if node.count > 0 {
+                    found = self.indices[node.first..node.first + node.count]
+                        .iter()
+                        .any(|&index| hits(index));
+                    if found {
+                        break;
+                    }
+                } else {
+                    stack.push(node.second_child);
+                    stack.push(node_index + 1);
+                }
+            }
+
+            stats::count_bvh_visits(visits);
+            found
+        })
+    }
+
+    pub fn containing(&self, point: Vec3f, tolerance: f64, found: &mut Vec<usize>) {
+        found.extend_from_slice(&self.unbounded);
+        arena::with_indices(|stack| {
+            if !self.nodes.is_empty() {
+                stack.push(0);
+            }
+
+            while let Some(node_index) = stack.pop() {
+                let node = &self.nodes[node_index];
+                if !node.bounds.contains(point, tolerance) {
+                    continue;
+                }
+
+                if node.count > 0 {
+                    found.extend_from_slice(&self.indices[node.first..node.first + node.count]);
+                } else {
+                    stack.push(node.second_child);
+                    stack.push(node_index + 1);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_bounds(index: usize) -> Option<Aabb> {
+        let x = f64::from(u32::try_from(index % 10).ok()?);
+        let y = f64::from(u32::try_from(index / 10).ok()?);
+        Some(Aabb::from_points(&[
+            Vec3f::new_with_data([x, y, -1.0]),
+            Vec3f::new_with_data([x + 0.5, y + 0.5, -2.0]),
+        ]))
+    }
+
+    #[test]
+    fn closest_hit_matches_brute_force() {
+        let bounds: Vec<_> = (0..100).map(grid_bounds).chain([None]).collect();
+        let bvh = Bvh::new(bounds.iter().copied());
+        let inverse_direction = inverse_direction(Vec3f::new_with_data([0.0, 0.0, -1.0]));
+
+        for target in [0, 37, 99] {
+            let origin = bounds[target].map_or(Vec3f::new(0.0), |bounds| bounds.centroid())
+                + Vec3f::new_with_data([0.0, 0.0, 10.0]);
+            let intersect = |index: usize| {
+                bounds[index].map_or(Some(50.0), |bounds| {
+                    bounds.entry_distance(origin, inverse_direction)
+                })
+            };
+
+            let brute_force = (0..bounds.len())
+                .filter_map(|index| intersect(index).map(|distance| (distance, index)))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            assert_eq!(
+                bvh.closest_hit(origin, inverse_direction, intersect),
+                brute_force
+            );
+            assert_eq!(brute_force, Some((9.5, target)));
+        }
+    }
+
+    #[test]
+    fn any_hit_agrees_with_closest_hit_within_range() {
+        let bounds: Vec<_> = (0..100).map(grid_bounds).collect();
+        let bvh = Bvh::new(bounds.iter().copied());
+        let inverse_direction = inverse_direction(Vec3f::new_with_data([0.0, 0.0, -1.0]));
+        let origin = Vec3f::new_with_data([3.25, 4.25, 10.0]);
+        let intersect = |index: usize| {
+            bounds[index].and_then(|bounds| bounds.entry_distance(origin, inverse_direction))
+        };
+
+        let closest = bvh.closest_hit(origin, inverse_direction, intersect);
+        assert_eq!(closest, Some((11.0, 43)));
+        for (max_distance, expected) in [(11.5, true), (11.0, false), (f64::INFINITY, true)] {
+            let any = bvh.any_hit(origin, inverse_direction, max_distance, |index| {
+                intersect(index).is_some_and(|distance| distance < max_distance)
+            });
+            assert_eq!(any, expected);
+        }
+    }
+
+    #[test]
+    fn containing_returns_overlapping_leaves_and_unbounded() {
+        let bvh = Bvh::new((0..100).map(grid_bounds).chain([None]));
+        let mut found = Vec::new();
+        bvh.containing(Vec3f::new_with_data([3.25, 4.25, -1.5]), 0.0, &mut found);
+
+        assert!(found.contains(&100));
+        assert!(found.contains(&43));
+        assert!(found.len() < 10);
+    }
+}
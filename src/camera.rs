@@ -0,0 +1,165 @@
+use crate::{PI, Vec3f};
+
+const MIN_FOV: f64 = PI / 18.0;
+const MAX_FOV: f64 = PI * 2.0 / 3.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    #[default]
+    Perspective,
+    Equirectangular,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera {
+    position: Vec3f,
+    fov: f64,
+    forward: Vec3f,
+    up: Vec3f,
+    projection: Projection,
+    eye_offset: f64,
+}
+
+impl Camera {
+    pub const fn new(position: Vec3f, fov: f64) -> Self {
+        Self {
+            position,
+            fov,
+            forward: Vec3f::new_with_data([0.0, 0.0, -1.0]),
+            up: Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            projection: Projection::Perspective,
+            eye_offset: 0.0,
+        }
+    }
+
+    pub fn oriented(self, forward: Vec3f, up: Vec3f) -> Self {
+        let forward = forward.unit();
+        let right = forward.cross(&up).unit();
+
+        Self {
+            forward,
+            up: right.cross(&forward),
+            ..self
+        }
+    }
+
+    pub const fn with_projection(self, projection: Projection) -> Self {
+        Self { projection, ..self }
+    }
+
+    pub const fn with_eye_offset(self, eye_offset: f64) -> Self {
+        Self { eye_offset, ..self }
+    }
+
+    pub const fn eye_offset(&self) -> f64 {
+        self.eye_offset
+    }
+
+    pub const fn position(&self) -> Vec3f {
+        self.position
+    }
+
+    pub const fn fov(&self) -> f64 {
+        self.fov
+    }
+
+    pub const fn forward(&self) -> Vec3f {
+        self.forward
+    }
+
+    pub const fn up(&self) -> Vec3f {
+        self.up
+    }
+
+    pub const fn set_position(&mut self, position: Vec3f) {
+        self.position = position;
+    }
+
+    pub const fn set_fov(&mut self, fov: f64) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub const fn zoom(&mut self, delta: f64) {
+        self.set_fov(self.fov + delta);
+    }
+
+    pub fn ray_generator(&self, width: u32, height: u32) -> RayGenerator {
+        let (width, height) = (f64::from(width), f64::from(height));
+        let right = self.forward.cross(&self.up);
+        let origin = match self.projection {
+            Projection::Perspective if self.eye_offset != 0.0 => {
+                self.position + right * self.eye_offset
+            }
+            _ => self.position,
+        };
+
+        RayGenerator {
+            origin,
+            forward: self.forward,
+            up: self.up,
+            right,
+            projection: self.projection,
+            eye_offset: self.eye_offset,
+            width,
+            height,
+            fov_tan: (self.fov / 2.0).tan(),
+            aspect_ratio: width / height,
+        }
+    }
+
+    pub fn ray_direction(&self, x: f64, y: f64, width: u32, height: u32) -> Vec3f {
+        self.ray_generator(width, height).direction(x, y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayGenerator {
+    origin: Vec3f,
+    forward: Vec3f,
+    up: Vec3f,
+    right: Vec3f,
+    projection: Projection,
+    eye_offset: f64,
+    width: f64,
+    height: f64,
+    fov_tan: f64,
+    aspect_ratio: f64,
+}
+
+impl RayGenerator {
+    pub const fn origin(&self) -> Vec3f {
+        self.origin
+    }
+
+    pub fn ray(&self, x: f64, y: f64) -> (Vec3f, Vec3f) {
+        let direction = self.direction(x, y);
+        if self.projection == Projection::Perspective || self.eye_offset == 0.0 {
+            return (self.origin, direction);
+        }
+
+        let longitude = (x / self.width - 0.5) * 2.0 * PI;
+        let tangent = self.right * longitude.cos() - self.forward * longitude.sin();
+        (self.origin + tangent * self.eye_offset, direction)
+    }
+
+    pub fn direction(&self, x: f64, y: f64) -> Vec3f {
+        match self.projection {
+            Projection::Perspective => {
+                let screen_x = (2.0 * x / self.width - 1.0) * self.fov_tan * self.aspect_ratio;
+                let screen_y = -(2.0 * y / self.height - 1.0) * self.fov_tan;
+
+                (self.right * screen_x + self.up * screen_y + self.forward).normalize(None)
+            }
+            Projection::Equirectangular => {
+                let longitude = (x / self.width - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - y / self.height) * PI;
+
+                self.right * (longitude.sin() * latitude.cos())
+                    + self.up * latitude.sin()
+                    + self.forward * (longitude.cos() * latitude.cos())
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    scene::{Scene, ShapeId},
+    shapes::{Shape, ShapeType},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CleanupReport {
+    degenerate_triangles: usize,
+    empty_meshes: usize,
+    empty_boxes: usize,
+    duplicate_shapes: usize,
+}
+
+impl CleanupReport {
+    pub const fn is_clean(&self) -> bool {
+        self.degenerate_triangles == 0
+            && self.empty_meshes == 0
+            && self.empty_boxes == 0
+            && self.duplicate_shapes == 0
+    }
+
+    pub const fn degenerate_triangles(&self) -> usize {
+        self.degenerate_triangles
+    }
+
+    pub const fn empty_meshes(&self) -> usize {
+        self.empty_meshes
+    }
+
+    pub const fn empty_boxes(&self) -> usize {
+        self.empty_boxes
+    }
+
+    pub const fn duplicate_shapes(&self) -> usize {
+        self.duplicate_shapes
+    }
+}
+
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} degenerate triangles, {} empty meshes, {} zero-extent boxes, {} duplicate shapes",
+            self.degenerate_triangles, self.empty_meshes, self.empty_boxes, self.duplicate_shapes
+        )
+    }
+}
+
+type BoundsKey = (&'static str, Option<[u64; 6]>);
+
+fn bounds_key(shape: &ShapeType) -> BoundsKey {
+    let bounds = shape.bounds().map(|bounds| {
+        let (min, max) = (bounds.min(), bounds.max());
+        [min.x(), min.y(), min.z(), max.x(), max.y(), max.z()].map(f64::to_bits)
+    });
+    (shape.kind(), bounds)
+}
+
+fn plan(scene: &Scene) -> (CleanupReport, HashSet<ShapeId>) {
+    let mut report = CleanupReport::default();
+    let mut removed = HashSet::new();
+    let mut seen: HashMap<BoundsKey, Vec<(ShapeId, &ShapeType)>> = HashMap::new();
+
+    for (id, shape) in scene.shape_entries() {
+        match shape {
+            ShapeType::Mesh(mesh) => {
+                let degenerate = mesh.degenerate_triangles();
+                report.degenerate_triangles += degenerate;
+                if degenerate == mesh.triangle_count() {
+                    report.empty_meshes += 1;
+                    removed.insert(id);
+                    continue;
+                }
+            }
+            ShapeType::BoxShape(box_shape) => {
+                let extent = box_shape.max_point() - box_shape.min_point();
+                if !(extent.x().abs() > 0.0 && extent.y().abs() > 0.0 && extent.z().abs() > 0.0) {
+                    report.empty_boxes += 1;
+                    removed.insert(id);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        let candidates = seen.entry(bounds_key(shape)).or_default();
+        let binding = scene.material_binding(id);
+        if candidates
+            .iter()
+            .any(|&(other_id, other)| other == shape && scene.material_binding(other_id) == binding)
+        {
+            report.duplicate_shapes += 1;
+            removed.insert(id);
+        } else {
+            candidates.push((id, shape));
+        }
+    }
+
+    (report, removed)
+}
+
+pub fn inspect(scene: &Scene) -> CleanupReport {
+    plan(scene).0
+}
+
+pub fn clean(scene: &mut Scene) -> CleanupReport {
+    let (report, removed) = plan(scene);
+    if report.is_clean() {
+        return report;
+    }
+
+    scene.retain_shapes(|id, shape| {
+        if removed.contains(&id) {
+            return false;
+        }
+        if let ShapeType::Mesh(mesh) = shape {
+            mesh.remove_degenerate_triangles();
+        }
+        true
+    });
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        GLASS_MATERIAL, RED_MATERIAL, Vec3f,
+        mesh::TriangleMesh,
+        shapes::{BoxShape, Sphere},
+    };
+
+    #[test]
+    fn degenerate_and_duplicate_shapes_are_reported_then_removed() -> anyhow::Result<()> {
+        let sphere = ShapeType::Sphere(Sphere::new(Vec3f::new(0.0), 1.0, RED_MATERIAL));
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [2.0, 0.0, 0.0],
+        ]
+        .map(Vec3f::new_with_data)
+        .to_vec();
+        let mesh = TriangleMesh::from_triangles(
+            positions.clone(),
+            &[[0, 1, 2], [0, 1, 3], [2, 2, 1]],
+            "mesh.obj".into(),
+            RED_MATERIAL,
+        )?;
+        let flat_mesh =
+            TriangleMesh::from_triangles(positions, &[[0, 1, 3]], "flat.obj".into(), RED_MATERIAL)?;
+        let flat_box = BoxShape::new(
+            Vec3f::new_with_data([1.0, 0.0, 1.0]),
+            Vec3f::new(0.0),
+            RED_MATERIAL,
+        );
+        let mut scene = Scene::new(
+            vec![
+                sphere.clone(),
+                ShapeType::Mesh(mesh),
+                sphere,
+                ShapeType::Sphere(Sphere::new(Vec3f::new(0.0), 1.0, GLASS_MATERIAL)),
+                ShapeType::Mesh(flat_mesh),
+                ShapeType::BoxShape(flat_box),
+            ],
+            Vec::new(),
+        );
+
+        let report = inspect(&scene);
+        assert_eq!(
+            (
+                report.degenerate_triangles(),
+                report.empty_meshes(),
+                report.empty_boxes(),
+                report.duplicate_shapes()
+            ),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(scene.shapes().len(), 6);
+
+        assert_eq!(clean(&mut scene), report);
+        assert_eq!(scene.shapes().len(), 3);
+        let ShapeType::Mesh(mesh) = &scene.shapes()[1] else {
+            panic!("the mesh should survive with its valid triangle");
+        };
+        assert_eq!(mesh.triangle_count(), 1);
+        assert!(inspect(&scene).is_clean());
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::Result;
+use log::info;
+
+use crate::{
+    camera::Camera, integrator::Integrator, output, overlay, renderer::Renderer, scene::Scene,
+    settings::RenderSettings,
+};
+
+const MAX_SAMPLES: u32 = 4096;
+const LABEL_MARGIN: u32 = 4;
+const LABEL_BACKGROUND: [u8; 4] = [0, 0, 0, 255];
+const LABEL_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparisonCell {
+    integrator: Integrator,
+    samples_per_pixel: u32,
+    seconds: f64,
+}
+
+impl ComparisonCell {
+    pub fn label(&self) -> String {
+        format!(
+            "{} {} SPP {:.1}S",
+            self.integrator.name(),
+            self.samples_per_pixel,
+            self.seconds
+        )
+    }
+}
+
+fn render_timed(
+    scene: &Scene,
+    settings: &RenderSettings,
+    (width, height): (u32, u32),
+    camera: Camera,
+    frame: &mut [u8],
+) -> Result<f64> {
+    let renderer = Renderer::new(scene.clone(), settings.clone(), width, height, camera);
+    let start = Instant::now();
+    renderer.render_into(frame)?;
+
+    Ok(start.elapsed().as_secs_f64())
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn budget_samples(budget: f64, seconds_per_sample: f64) -> u32 {
+    if seconds_per_sample <= 0.0 {
+        return MAX_SAMPLES;
+    }
+
+    (budget / seconds_per_sample)
+        .floor()
+        .clamp(1.0, f64::from(MAX_SAMPLES)) as u32
+}
+
+fn label_cell(frame: &mut [u8], size: (u32, u32), label: &str) {
+    let scale = (size.1 / 120).max(1);
+    let (text_width, text_height) = overlay::text_size(label, scale);
+
+    overlay::fill_rect(
+        frame,
+        size,
+        (0, 0),
+        (
+            text_width + LABEL_MARGIN * 2,
+            text_height + LABEL_MARGIN * 2,
+        ),
+        LABEL_BACKGROUND,
+    );
+    overlay::draw_text(
+        frame,
+        size,
+        (LABEL_MARGIN, LABEL_MARGIN),
+        label,
+        scale,
+        LABEL_COLOR,
+    );
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn grid_columns(cells: usize) -> u32 {
+    let mut columns = 1;
+    while columns * columns < cells {
+        columns += 1;
+    }
+    columns as u32
+}
+
+pub fn render_comparison(
+    scene: &Scene,
+    settings: &RenderSettings,
+    camera: Camera,
+    (width, height): (u32, u32),
+    path: &Path,
+) -> Result<Vec<ComparisonCell>> {
+    let cell_count = Integrator::ALL.len();
+    let columns = grid_columns(cell_count);
+    let rows = u32::try_from(cell_count)?.div_ceil(columns);
+    let (grid_width, grid_height) = (width * columns, height * rows);
+    let cell_bytes = width as usize * height as usize * 4;
+    let row_bytes = width as usize * 4;
+    let grid_row_bytes = grid_width as usize * 4;
+
+    let mut grid = vec![0; grid_width as usize * grid_height as usize * 4];
+    let mut cell = vec![0; cell_bytes];
+    let mut cells = Vec::with_capacity(cell_count);
+
+    for (index, integrator) in (0..).zip(Integrator::ALL) {
+        let calibration = settings.with_args([
+            "--integrator".to_owned(),
+            integrator.name().to_owned(),
+            "--spp".to_owned(),
+            "1".to_owned(),
+        ])?;
+        let seconds_per_sample =
+            render_timed(scene, &calibration, (width, height), camera, &mut cell)?;
+        let samples_per_pixel = budget_samples(settings.time_budget(), seconds_per_sample);
+
+        let budgeted =
+            calibration.with_args(["--spp".to_owned(), samples_per_pixel.to_string()])?;
+        let seconds = render_timed(scene, &budgeted, (width, height), camera, &mut cell)?;
+        info!(
+            "Rendered {} with {samples_per_pixel} spp",
+            integrator.name()
+        );
+
+        let result = ComparisonCell {
+            integrator,
+            samples_per_pixel,
+            seconds,
+        };
+        label_cell(&mut cell, (width, height), &result.label());
+
+        let (left, top) = (index % columns * width, index / columns * height);
+        for (row, cell_row) in (0..).zip(cell.chunks_exact(row_bytes)) {
+            let start = (top + row) as usize * grid_row_bytes + left as usize * 4;
+            grid[start..start + row_bytes].copy_from_slice(cell_row);
+        }
+        cells.push(result);
+    }
+
+    output::write_png(path, grid_width, grid_height, &grid)?;
+
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_fill_the_time_budget() {
+        assert_eq!(budget_samples(2.0, 0.5), 4);
+        assert_eq!(budget_samples(2.0, 10.0), 1);
+        assert_eq!(budget_samples(2.0, 0.0), MAX_SAMPLES);
+        assert_eq!(grid_columns(4), 2);
+        assert_eq!(grid_columns(5), 3);
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    fs, panic,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{output, settings::RenderSettings};
+
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        *LAST_PANIC.lock().unwrap_or_else(PoisonError::into_inner) = Some(info.to_string());
+        default_hook(info);
+    }));
+}
+
+fn last_panic() -> String {
+    LAST_PANIC
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
+
+pub fn dump_framebuffer(
+    frame: &[u8],
+    (width, rows): (u32, u32),
+    first_row: u32,
+    settings: &RenderSettings,
+) -> Result<PathBuf> {
+    let output_dir = settings.output_dir();
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create directory {}", output_dir.display()))?;
+
+    let image_path = output::timestamped_path(output_dir, "crash", "png");
+    output::write_png(&image_path, width, rows, frame)?;
+
+    let report_path = image_path.with_extension("txt");
+    let report = format!(
+        "panic: {}\nrows: {first_row}..{}\nsettings: {settings:#?}\n",
+        last_panic(),
+        first_row + rows
+    );
+    fs::write(&report_path, report)
+        .with_context(|| format!("Failed to write crash report {}", report_path.display()))?;
+
+    Ok(image_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_writes_image_and_report() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("crash-dump-{}", std::process::id()));
+        let settings = RenderSettings::default()
+            .with_args(["--output-dir".to_owned(), directory.display().to_string()])?;
+
+        let image_path = dump_framebuffer(&[255; 2 * 3 * 4], (2, 3), 5, &settings)?;
+        let report = fs::read_to_string(image_path.with_extension("txt"))?;
+
+        assert!(image_path.exists());
+        assert!(report.contains("rows: 5..8"));
+        fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
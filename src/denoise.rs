@@ -0,0 +1,164 @@
+use rayon::prelude::*;
+
+use crate::{Vec3f, aov::FloatBuffer};
+
+/// The B3-spline taps the À-Trous filter spreads further apart on every pass.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+const PASSES: u32 = 5;
+const COLOR_SIGMA: f64 = 0.5;
+const ALBEDO_SIGMA: f64 = 0.1;
+const NORMAL_POWER: i32 = 64;
+
+fn vector_at(buffer: &FloatBuffer, x: u32, y: u32) -> Vec3f {
+    let pixel = buffer.pixel(x, y);
+    Vec3f::new_with_data([pixel[0], pixel[1], pixel[2]])
+}
+
+fn normal_weight(normal: Vec3f, other: Vec3f) -> f64 {
+    // Escaped rays have no normal; they only blend with each other.
+    match (normal.length() > 0.0, other.length() > 0.0) {
+        (false, false) => 1.0,
+        (true, true) => (normal * other).max(0.0).powi(NORMAL_POWER),
+        _ => 0.0,
+    }
+}
+
+/// Edge-avoiding À-Trous wavelet filter (Dammertz et al. 2010) over an RGBA frame.
+///
+/// Each pass blurs with a 5x5 B3-spline whose taps are spread twice as far as in the
+/// previous pass, and weights every tap by how closely its color, albedo and normal match
+/// the center pixel, so noise is smoothed within surfaces while edges, texture detail and
+/// silhouettes stay sharp. The guides are the normal and albedo AOVs of the same view.
+pub fn denoise(
+    frame: &mut [u8],
+    (width, height): (u32, u32),
+    normal: &FloatBuffer,
+    albedo: &FloatBuffer,
+) {
+    let mut colors: Vec<_> = frame
+        .chunks_exact(4)
+        .map(|pixel| {
+            Vec3f::new_with_data([0, 1, 2].map(|channel| f64::from(pixel[channel]) / 255.0))
+        })
+        .collect();
+
+    for pass in 0..PASSES {
+        let step = 1_i64 << pass;
+        let color_sigma = COLOR_SIGMA / f64::from(1_u32 << pass);
+        let source = &colors;
+        colors = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..width).map(move |x| {
+                    let center = source[(y * width + x) as usize];
+                    let center_normal = vector_at(normal, x, y);
+                    let center_albedo = vector_at(albedo, x, y);
+
+                    let mut sum = Vec3f::new(0.0);
+                    let mut total = 0.0;
+                    for (dy, tap_y) in (-2..=2).zip(KERNEL) {
+                        for (dx, tap_x) in (-2..=2).zip(KERNEL) {
+                            let (Ok(qx), Ok(qy)) = (
+                                u32::try_from(i64::from(x) + dx * step),
+                                u32::try_from(i64::from(y) + dy * step),
+                            ) else {
+                                continue;
+                            };
+                            if qx >= width || qy >= height {
+                                continue;
+                            }
+
+                            let color = source[(qy * width + qx) as usize];
+                            let color_distance = (color - center).length();
+                            let albedo_distance =
+                                (vector_at(albedo, qx, qy) - center_albedo).length();
+                            let weight = tap_x
+                                * tap_y
+                                * (-color_distance * color_distance / (color_sigma * color_sigma))
+                                    .exp()
+                                * (-albedo_distance * albedo_distance
+                                    / (ALBEDO_SIGMA * ALBEDO_SIGMA))
+                                    .exp()
+                                * normal_weight(center_normal, vector_at(normal, qx, qy));
+
+                            sum += color * weight;
+                            total += weight;
+                        }
+                    }
+
+                    if total > 0.0 { sum / total } else { center }
+                })
+            })
+            .collect();
+    }
+
+    for (pixel, color) in frame.chunks_exact_mut(4).zip(colors) {
+        for channel in 0..3 {
+            pixel[channel] = to_u8(color[channel]);
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_u8(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aov::AovBuffer;
+
+    #[test]
+    fn noise_is_smoothed_within_a_surface_but_not_across_albedo_edges() {
+        let (width, height) = (32, 16);
+        let pixels = (width * height) as usize;
+        let normal = FloatBuffer::new(
+            AovBuffer::Normal,
+            width,
+            height,
+            [0.0, 0.0, 1.0].repeat(pixels),
+        );
+        // The left half is dark and the right half bright, in albedo and in the frame.
+        let bright = |index: usize| index % width as usize >= width as usize / 2;
+        let albedo = FloatBuffer::new(
+            AovBuffer::Albedo,
+            width,
+            height,
+            (0..pixels)
+                .flat_map(|index| [if bright(index) { 0.8 } else { 0.2 }; 3])
+                .collect(),
+        );
+
+        let mut frame: Vec<u8> = (0..pixels)
+            .flat_map(|index| {
+                let base: u8 = if bright(index) { 200 } else { 50 };
+                let noise = [0_u8, 12, 4, 9, 2, 14, 6][index % 7];
+                let value = base - 7 + noise;
+                [value, value, value, 255]
+            })
+            .collect();
+        let spread = |frame: &[u8], bright_half: bool| {
+            let values: Vec<_> = frame
+                .chunks_exact(4)
+                .enumerate()
+                .filter(|&(index, _)| bright(index) == bright_half)
+                .map(|(_, pixel)| pixel[0])
+                .collect();
+            values.iter().max().copied().unwrap_or(0) - values.iter().min().copied().unwrap_or(0)
+        };
+        let noisy_spread = spread(&frame, false);
+
+        denoise(&mut frame, (width, height), &normal, &albedo);
+
+        assert!(spread(&frame, false) * 3 < noisy_spread);
+        assert!(spread(&frame, true) * 3 < noisy_spread);
+        let dark_edge = frame[(width as usize / 2 - 1) * 4];
+        let bright_edge = frame[(width as usize / 2) * 4];
+        assert!(
+            dark_edge < 60 && bright_edge > 190,
+            "{dark_edge} {bright_edge}"
+        );
+        assert!(frame.chunks_exact(4).all(|pixel| pixel[3] == 255));
+    }
+}
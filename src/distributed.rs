@@ -0,0 +1,332 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{info, warn};
+
+use crate::renderer::Renderer;
+
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+/// How long the coordinator waits on a silent worker before handing its stripe to another.
+pub const WORKER_TIMEOUT: Duration = Duration::from_mins(5);
+const READY: &str = "READY";
+const DONE: &str = "DONE";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stripe {
+    first_row: u32,
+    rows: u32,
+}
+
+struct Coordinator {
+    width: u32,
+    height: u32,
+    pending: Mutex<VecDeque<Stripe>>,
+    frame: Mutex<Vec<u8>>,
+    remaining: AtomicUsize,
+}
+
+impl Coordinator {
+    fn new(width: u32, height: u32, stripe_rows: u32) -> Self {
+        let pending: VecDeque<_> = (0..height)
+            .step_by(stripe_rows as usize)
+            .map(|first_row| Stripe {
+                first_row,
+                rows: stripe_rows.min(height - first_row),
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            remaining: AtomicUsize::new(pending.len()),
+            pending: Mutex::new(pending),
+            frame: Mutex::new(vec![0; width as usize * height as usize * 4]),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.remaining.load(Ordering::Acquire) == 0
+    }
+
+    fn next_stripe(&self) -> Option<Stripe> {
+        loop {
+            let stripe = self
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front();
+            if stripe.is_some() {
+                return stripe;
+            }
+            // Stripes still out with other workers come back here if those workers drop.
+            if self.is_finished() {
+                return None;
+            }
+            thread::sleep(ACCEPT_POLL);
+        }
+    }
+
+    fn requeue(&self, stripe: Stripe) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(stripe);
+    }
+
+    fn composite(&self, stripe: Stripe, pixels: &[u8]) {
+        let start = stripe.first_row as usize * self.width as usize * 4;
+        self.frame.lock().unwrap_or_else(PoisonError::into_inner)[start..start + pixels.len()]
+            .copy_from_slice(pixels);
+        self.remaining.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn serve(&self, stream: TcpStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.trim() != READY {
+            bail!("Expected {READY} from worker, got {:?}", line.trim());
+        }
+
+        while let Some(stripe) = self.next_stripe() {
+            if let Err(err) = self.assign(stripe, &mut reader, &mut writer) {
+                self.requeue(stripe);
+                return Err(err);
+            }
+        }
+
+        writeln!(writer, "{DONE}")?;
+        Ok(())
+    }
+
+    fn assign(
+        &self,
+        stripe: Stripe,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "TILE {} {} {} {}",
+            self.width, self.height, stripe.first_row, stripe.rows
+        )?;
+        writer.flush()?;
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let [first_row, rows] = parse_fields::<2>(line.trim(), "RESULT")?;
+        if (Stripe { first_row, rows }) != stripe {
+            bail!("Worker returned rows {first_row}+{rows} for stripe {stripe:?}");
+        }
+
+        let mut pixels = vec![0; self.width as usize * rows as usize * 4];
+        reader.read_exact(&mut pixels)?;
+        self.composite(stripe, &pixels);
+        Ok(())
+    }
+}
+
+fn parse_fields<const N: usize>(line: &str, keyword: &str) -> Result<[u32; N]> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some(keyword) {
+        bail!("Expected {keyword} message, got {line:?}");
+    }
+
+    let fields = words
+        .map(|word| {
+            word.parse()
+                .map_err(|_| anyhow!("Invalid number {word:?} in {line:?}"))
+        })
+        .collect::<Result<Vec<u32>>>()?;
+    fields
+        .try_into()
+        .map_err(|_| anyhow!("Expected {N} numbers in {line:?}"))
+}
+
+/// Hands out row stripes of a `width`x`height` image to workers connecting on `listener`
+/// and returns the composited RGBA frame once every stripe has come back.
+///
+/// Stripes held by a worker that disconnects or sends nothing for `worker_timeout` are
+/// handed to the next one that asks.
+pub fn coordinate(
+    listener: &TcpListener,
+    (width, height): (u32, u32),
+    stripe_rows: u32,
+    worker_timeout: Duration,
+) -> Result<Vec<u8>> {
+    if stripe_rows == 0 {
+        bail!("Stripe height must be at least 1 row");
+    }
+
+    let coordinator = Coordinator::new(width, height, stripe_rows);
+    listener.set_nonblocking(true)?;
+
+    thread::scope(|scope| -> Result<()> {
+        while !coordinator.is_finished() {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(worker_timeout))?;
+                    info!("Worker {peer} connected");
+                    let coordinator = &coordinator;
+                    scope.spawn(move || {
+                        if let Err(err) = coordinator.serve(stream) {
+                            warn!("Worker {peer} dropped: {err:#}");
+                        }
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                Err(err) => return Err(err).context("Failed to accept worker connection"),
+            }
+        }
+
+        Ok(())
+    })?;
+
+    // Workers that connected while the last stripes came back are told to stop straight away.
+    while let Ok((stream, peer)) = listener.accept() {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(worker_timeout))?;
+        if let Err(err) = coordinator.serve(stream) {
+            warn!("Worker {peer} dropped: {err:#}");
+        }
+    }
+
+    Ok(coordinator
+        .frame
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Renders the stripes a coordinator assigns over `stream` with `renderer` until told to
+/// stop, returning how many stripes this worker rendered.
+pub fn run_worker(stream: TcpStream, renderer: &mut Renderer) -> Result<usize> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    writeln!(writer, "{READY}")?;
+    writer.flush()?;
+
+    let mut stripes = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("Coordinator closed the connection");
+        }
+        if line.trim() == DONE {
+            return Ok(stripes);
+        }
+
+        let [width, height, first_row, rows] = parse_fields::<4>(line.trim(), "TILE")?;
+        if first_row
+            .checked_add(rows)
+            .is_none_or(|end_row| end_row > height)
+        {
+            bail!("Stripe {first_row}+{rows} is outside of the {height} row image");
+        }
+        if (renderer.width(), renderer.height()) != (width, height) {
+            renderer.resize(width, height);
+        }
+
+        let mut pixels = vec![0; width as usize * rows as usize * 4];
+        renderer.render_rows_into(&mut pixels, first_row, rows)?;
+        writeln!(writer, "RESULT {first_row} {rows}")?;
+        writer.write_all(&pixels)?;
+        writer.flush()?;
+        stripes += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        FOV, Vec3f, camera::Camera, lights::init_default_lights, scene::Scene,
+        settings::RenderSettings, shapes::init_default_shapes,
+    };
+
+    fn renderer() -> Renderer {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        Renderer::new(scene, RenderSettings::default(), 1, 1, camera)
+    }
+
+    #[test]
+    fn workers_render_stripes_that_composite_into_the_full_frame() -> Result<()> {
+        let (width, height) = (24, 18);
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        let streams = [TcpStream::connect(address)?, TcpStream::connect(address)?];
+
+        let (frame, rendered) = thread::scope(|scope| {
+            let workers =
+                streams.map(|stream| scope.spawn(move || run_worker(stream, &mut renderer())));
+            let frame = coordinate(&listener, (width, height), 5, WORKER_TIMEOUT);
+            let rendered = workers
+                .into_iter()
+                .map(|worker| worker.join().map_err(|_| anyhow!("worker panicked"))?)
+                .sum::<Result<usize>>();
+            (frame, rendered)
+        });
+
+        assert_eq!(rendered?, 4);
+        let mut reference = renderer();
+        reference.resize(width, height);
+        let mut expected = vec![0; width as usize * height as usize * 4];
+        reference.render_into(&mut expected)?;
+        assert_eq!(frame?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn stripes_of_silent_workers_are_handed_to_the_next_one() -> Result<()> {
+        let (width, height) = (8, 6);
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        // One worker never says it's ready and another takes a stripe and then stalls.
+        let _silent = TcpStream::connect(address)?;
+        let mut stalled = TcpStream::connect(address)?;
+        writeln!(stalled, "{READY}")?;
+
+        let (frame, rendered) = thread::scope(|scope| {
+            let (taken, stripe_taken) = std::sync::mpsc::channel();
+            scope.spawn(move || -> Result<()> {
+                let mut reader = BufReader::new(stalled);
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                taken.send(())?;
+                // Blocks until the coordinator gives up and closes the connection.
+                reader.read_line(&mut line)?;
+                Ok(())
+            });
+            let worker = scope.spawn(move || {
+                stripe_taken.recv()?;
+                run_worker(TcpStream::connect(address)?, &mut renderer())
+            });
+
+            let frame = coordinate(&listener, (width, height), 3, Duration::from_millis(200));
+            let rendered = worker.join().map_err(|_| anyhow!("worker panicked"));
+            (frame, rendered)
+        });
+
+        assert_eq!(rendered??, 2);
+        let mut reference = renderer();
+        reference.resize(width, height);
+        let mut expected = vec![0; width as usize * height as usize * 4];
+        reference.render_into(&mut expected)?;
+        assert_eq!(frame?, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use log::info;
+use winit::keyboard::KeyCode;
+
+use raytracer_rust::{
+    Vec3f,
+    materials::GOLD_MATERIAL,
+    renderer::Renderer,
+    scene::ShapeId,
+    shapes::{ShapeType, Sphere},
+};
+
+const SPAWN_DISTANCE: f64 = 3.0;
+const SPAWN_RADIUS: f64 = 0.5;
+const NUDGE_STEP: f64 = 0.1;
+
+#[derive(Debug, Default)]
+pub struct Editor {
+    selected: Option<ShapeId>,
+}
+
+impl Editor {
+    pub const fn new() -> Self {
+        Self { selected: None }
+    }
+
+    pub fn select_at(&mut self, renderer: &Renderer, x: f64, y: f64) {
+        self.selected =
+            renderer
+                .scene()
+                .pick(x, y, renderer.width(), renderer.height(), renderer.camera());
+
+        match self.selected {
+            Some(id) => info!("Selected shape {id}"),
+            None => info!("Selection cleared"),
+        }
+    }
+
+    pub fn handle_key(&mut self, renderer: &mut Renderer, key: KeyCode) -> bool {
+        match key {
+            KeyCode::KeyN => {
+                self.spawn_sphere(renderer);
+                true
+            }
+            KeyCode::Delete | KeyCode::Backspace => self.delete_selected(renderer),
+            KeyCode::ArrowLeft => self.nudge(renderer, [-NUDGE_STEP, 0.0, 0.0]),
+            KeyCode::ArrowRight => self.nudge(renderer, [NUDGE_STEP, 0.0, 0.0]),
+            KeyCode::ArrowUp => self.nudge(renderer, [0.0, NUDGE_STEP, 0.0]),
+            KeyCode::ArrowDown => self.nudge(renderer, [0.0, -NUDGE_STEP, 0.0]),
+            KeyCode::PageUp => self.nudge(renderer, [0.0, 0.0, -NUDGE_STEP]),
+            KeyCode::PageDown => self.nudge(renderer, [0.0, 0.0, NUDGE_STEP]),
+            _ => false,
+        }
+    }
+
+    fn spawn_sphere(&mut self, renderer: &mut Renderer) {
+        let camera = renderer.camera();
+        let forward = camera.ray_direction(
+            f64::from(renderer.width()) / 2.0,
+            f64::from(renderer.height()) / 2.0,
+            renderer.width(),
+            renderer.height(),
+        );
+        let center = camera.position() + forward * SPAWN_DISTANCE;
+
+        self.selected = Some(
+            renderer
+                .scene_mut()
+                .push_shape(ShapeType::Sphere(Sphere::new(
+                    center,
+                    SPAWN_RADIUS,
+                    GOLD_MATERIAL,
+                ))),
+        );
+    }
+
+    fn delete_selected(&mut self, renderer: &mut Renderer) -> bool {
+        self.selected
+            .take()
+            .and_then(|id| renderer.scene_mut().remove_shape(id))
+            .is_some()
+    }
+
+    fn nudge(&self, renderer: &mut Renderer, offset: [f64; 3]) -> bool {
+        self.selected.is_some_and(|id| {
+            renderer
+                .scene_mut()
+                .translate_shape(id, Vec3f::new_with_data(offset))
+        })
+    }
+}
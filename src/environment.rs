@@ -0,0 +1,284 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::{PI, Vec3f, output};
+
+fn luminance(color: Vec3f) -> f64 {
+    0.2126_f64.mul_add(color.x(), 0.7152_f64.mul_add(color.y(), 0.0722 * color.z()))
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn texel_index(coordinate: f64, count: usize) -> usize {
+    ((coordinate * count as f64) as usize).min(count - 1)
+}
+
+/// Index of the entry of the running sum `cumulative` that `target` falls in, and how far
+/// into that entry's share it lies, in [0, 1).
+fn pick(cumulative: &[f64], target: f64) -> (usize, f64) {
+    let index = cumulative
+        .partition_point(|&sum| sum <= target)
+        .min(cumulative.len() - 1);
+    let start = if index == 0 {
+        0.0
+    } else {
+        cumulative[index - 1]
+    };
+    let share = cumulative[index] - start;
+    let fraction = if share > 0.0 {
+        ((target - start) / share).clamp(0.0, 1.0 - f64::EPSILON)
+    } else {
+        0.5
+    };
+
+    (index, fraction)
+}
+
+/// An equirectangular environment image lighting the scene from infinitely far away, with
+/// tables for picking directions in proportion to how much light they bring.
+///
+/// The image's center column looks down -z and its top row straight up, matching an
+/// equirectangular render from the default camera.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvironmentMap {
+    source: PathBuf,
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3f>,
+    /// Running sum over rows of each row's total weight.
+    rows: Vec<f64>,
+    /// Running sum of texel weights within each row, row after row.
+    columns: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    /// Builds the sampling tables. Each texel is weighted by its luminance and by the
+    /// solid angle it covers, which shrinks towards the poles.
+    pub fn new(source: PathBuf, width: usize, height: usize, texels: Vec<Vec3f>) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("Environment map {} has no pixels", source.display());
+        }
+        if texels.len() != width * height {
+            bail!(
+                "Environment map {} has {} texels, expected {width}x{height}",
+                source.display(),
+                texels.len()
+            );
+        }
+
+        let mut rows = Vec::with_capacity(height);
+        let mut columns = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for (row, texels) in texels.chunks_exact(width).enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let sin_theta = (PI * (row as f64 + 0.5) / height as f64).sin();
+            let mut sum = 0.0;
+            for &texel in texels {
+                sum = luminance(texel).max(0.0).mul_add(sin_theta, sum);
+                columns.push(sum);
+            }
+            total += sum;
+            rows.push(total);
+        }
+
+        Ok(Self {
+            source,
+            width,
+            height,
+            texels,
+            rows,
+            columns,
+        })
+    }
+
+    /// Loads a Radiance `.hdr` image, or a PNG for low dynamic range maps.
+    pub fn load(path: &Path, source: PathBuf) -> Result<Self> {
+        let is_hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+        let (width, height, texels) = if is_hdr {
+            output::read_hdr(path)?
+        } else {
+            let (width, height, texels) = output::read_rgba_png(path)?;
+            let texels = texels
+                .into_iter()
+                .map(|[red, green, blue, _]| [red, green, blue])
+                .collect();
+            (width, height, texels)
+        };
+
+        Self::new(
+            source,
+            width,
+            height,
+            texels.into_iter().map(Vec3f::new_with_data).collect(),
+        )
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Radiance arriving from `direction`.
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        let (u, v) = Self::coordinates(direction.unit());
+        let column = texel_index(u, self.width);
+        let row = texel_index(v, self.height);
+        self.texels[row * self.width + column]
+    }
+
+    /// A direction drawn in proportion to the light the map sends from it, and its
+    /// probability density per steradian. `None` for a black map.
+    pub fn sample(&self, u1: f64, u2: f64) -> Option<(Vec3f, f64)> {
+        let total = self.rows.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return None;
+        }
+
+        let (row, row_fraction) = pick(&self.rows, u1 * total);
+        let columns = &self.columns[row * self.width..(row + 1) * self.width];
+        let row_total = columns.last().copied().unwrap_or(0.0);
+        let (column, column_fraction) = pick(columns, u2 * row_total);
+
+        #[allow(clippy::cast_precision_loss)]
+        let (u, v) = (
+            (column as f64 + column_fraction) / self.width as f64,
+            (row as f64 + row_fraction) / self.height as f64,
+        );
+        let direction = Self::direction(u, v);
+        let pdf = self.pdf(direction);
+
+        (pdf > 0.0).then_some((direction, pdf))
+    }
+
+    /// Probability density per steradian of [`Self::sample`] returning `direction`.
+    pub fn pdf(&self, direction: Vec3f) -> f64 {
+        let total = self.rows.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return 0.0;
+        }
+
+        // The texel's sine weight cancels against the solid angle of its patch.
+        #[allow(clippy::cast_precision_loss)]
+        let texels = (self.width * self.height) as f64;
+        luminance(self.radiance(direction)).max(0.0) * texels / (total * 2.0 * PI * PI)
+    }
+
+    /// Radiance averaged over the sphere.
+    pub fn average(&self) -> Vec3f {
+        let (sum, weight) = self.texels.chunks_exact(self.width).enumerate().fold(
+            (Vec3f::new(0.0), 0.0),
+            |(sum, weight), (row, texels)| {
+                #[allow(clippy::cast_precision_loss)]
+                let sin_theta = (PI * (row as f64 + 0.5) / self.height as f64).sin();
+                let row_sum = texels
+                    .iter()
+                    .fold(Vec3f::new(0.0), |total, &texel| total + texel);
+                #[allow(clippy::cast_precision_loss)]
+                let row_weight = sin_theta * self.width as f64;
+                (sum + row_sum * sin_theta, weight + row_weight)
+            },
+        );
+
+        sum / weight
+    }
+
+    fn coordinates(direction: Vec3f) -> (f64, f64) {
+        let longitude = direction.x().atan2(-direction.z());
+        let latitude = direction.y().clamp(-1.0, 1.0).asin();
+        (longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI)
+    }
+
+    fn direction(u: f64, v: f64) -> Vec3f {
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - v) * PI;
+        Vec3f::new_with_data([
+            longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_with_sun() -> Result<EnvironmentMap> {
+        let (width, height) = (32, 16);
+        let texels = (0..width * height)
+            .map(|index| {
+                if index == 4 * width + 20 {
+                    Vec3f::new(500.0)
+                } else {
+                    Vec3f::new_with_data([0.1, 0.2, 0.4])
+                }
+            })
+            .collect();
+        EnvironmentMap::new(PathBuf::from("sun.hdr"), width, height, texels)
+    }
+
+    #[test]
+    fn samples_favor_bright_texels_and_match_their_density() -> Result<()> {
+        let map = map_with_sun()?;
+        let sun = EnvironmentMap::direction(20.5 / 32.0, 4.5 / 16.0);
+        assert!((map.radiance(sun) - Vec3f::new(500.0)).length() < 1e-9);
+        assert!(
+            (map.radiance(Vec3f::new_with_data([0.0, 0.0, -1.0]))
+                - Vec3f::new_with_data([0.1, 0.2, 0.4]))
+            .length()
+                < 1e-9
+        );
+
+        let mut towards_sun = 0;
+        for index in 0..256 {
+            let u1 = (f64::from(index) + 0.5) / 256.0;
+            let u2 = (f64::from(index) * 0.618_033_988_749_895).fract();
+            let (direction, pdf) = map.sample(u1, u2).ok_or_else(|| anyhow::anyhow!("black"))?;
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            assert!((pdf - map.pdf(direction)).abs() < 1e-9 * pdf);
+            if map.radiance(direction).x() > 1.0 {
+                towards_sun += 1;
+            }
+        }
+        assert!(towards_sun > 128, "{towards_sun}");
+
+        let black = EnvironmentMap::new(PathBuf::new(), 2, 1, vec![Vec3f::new(0.0); 2])?;
+        assert!(black.sample(0.5, 0.5).is_none());
+        assert!(EnvironmentMap::new(PathBuf::new(), 2, 2, vec![Vec3f::new(0.0); 3]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn density_integrates_to_one_over_the_sphere() -> Result<()> {
+        let map = map_with_sun()?;
+        let (columns, rows) = (256, 128);
+        let mut integral = 0.0;
+        for row in 0..rows {
+            for column in 0..columns {
+                let u = (f64::from(column) + 0.5) / f64::from(columns);
+                let v = (f64::from(row) + 0.5) / f64::from(rows);
+                let solid_angle = 2.0 * PI * PI * (PI * v).sin() / f64::from(columns * rows);
+                integral = map
+                    .pdf(EnvironmentMap::direction(u, v))
+                    .mul_add(solid_angle, integral);
+            }
+        }
+
+        assert!((integral - 1.0).abs() < 0.02, "{integral}");
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::{error::Error, io, path::PathBuf};
+
+use thiserror::Error;
+
+pub type Result<T, E = RaytracerError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum RaytracerError {
+    #[error("Failed to create window")]
+    Window(#[source] Box<dyn Error + Send + Sync>),
+    #[error("Surface error")]
+    Surface(#[source] Box<dyn Error + Send + Sync>),
+    #[error("Failed to parse scene file {}", path.display())]
+    SceneParse {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+    #[error("Render cancelled")]
+    Cancelled,
+    #[error("Failed to write render to {}", path.display())]
+    Output {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+impl RaytracerError {
+    pub fn window(source: impl Error + Send + Sync + 'static) -> Self {
+        Self::Window(Box::new(source))
+    }
+
+    pub fn surface(source: impl Error + Send + Sync + 'static) -> Self {
+        Self::Surface(Box::new(source))
+    }
+
+    pub const fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_file::SceneFile;
+
+    #[test]
+    fn scene_errors_keep_their_kind_and_cause() {
+        let missing = std::env::temp_dir().join("missing-scene-file.scene");
+        let err = SceneFile::load(&missing).err();
+        assert!(matches!(err, Some(RaytracerError::Io { ref path, .. }) if *path == missing));
+
+        let invalid = std::env::temp_dir().join(format!("invalid-{}.scene", std::process::id()));
+        let written = std::fs::write(&invalid, "sphere radius=oops\n");
+        let err = SceneFile::load(&invalid).err();
+        let _ = std::fs::remove_file(&invalid);
+        assert!(written.is_ok());
+        assert!(matches!(err, Some(RaytracerError::SceneParse { .. })));
+        assert!(
+            err.and_then(|err| err.source().map(ToString::to_string))
+                .is_some_and(|cause| !cause.is_empty())
+        );
+
+        assert!(RaytracerError::Cancelled.is_cancelled());
+        assert_eq!(
+            RaytracerError::InvalidParameter("spp".to_owned()).to_string(),
+            "Invalid parameter: spp"
+        );
+    }
+}
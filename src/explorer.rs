@@ -0,0 +1,149 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use raytracer_rust::{
+    PI, Vec3f, camera::Camera, output, renderer::Renderer, scene::Scene, shapes::Shape,
+};
+
+const COLOR_JITTER: f64 = 0.3;
+const SPECULAR_OCTAVES: f64 = 2.0;
+const LIGHT_OFFSET: f64 = 1.5;
+const INTENSITY_JITTER: f64 = 0.5;
+const CAMERA_OFFSET: f64 = 0.3;
+const FOV_JITTER: f64 = PI / 36.0;
+
+#[derive(Debug)]
+pub struct Explorer {
+    state: u64,
+    base: Option<(Scene, Camera)>,
+}
+
+impl Explorer {
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            base: None,
+        }
+    }
+
+    /// Drops the remembered starting point, so the next mutation starts from whatever scene
+    /// is loaded then.
+    pub fn forget(&mut self) {
+        self.base = None;
+    }
+
+    fn next(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut mixed = self.state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^= mixed >> 31;
+
+        #[allow(clippy::cast_precision_loss)]
+        let unit = (mixed >> 11) as f64 / (1_u64 << 53) as f64;
+        unit
+    }
+
+    fn signed(&mut self) -> f64 {
+        self.next().mul_add(2.0, -1.0)
+    }
+
+    fn offset(&mut self, scale: f64) -> Vec3f {
+        Vec3f::new_with_data([self.signed(), self.signed(), self.signed()]) * scale
+    }
+
+    pub fn mutate(&mut self, renderer: &mut Renderer) {
+        let (mut scene, mut camera) = self
+            .base
+            .get_or_insert_with(|| (renderer.scene().clone(), *renderer.camera()))
+            .clone();
+
+        for id in scene.shape_ids().to_vec() {
+            let Some(material) = scene.get_shape(id).map(Shape::get_material) else {
+                continue;
+            };
+            let diffuse = material.diffuse_color();
+            let jittered = Vec3f::new_with_data([0, 1, 2].map(|channel| {
+                (diffuse[channel] * self.signed().mul_add(COLOR_JITTER, 1.0)).clamp(0.0, 1.0)
+            }));
+            let specular = material.specular_exponent() * (self.signed() * SPECULAR_OCTAVES).exp2();
+            scene.set_shape_material(
+                id,
+                material
+                    .with_diffuse_color(jittered)
+                    .with_specular_exponent(specular),
+            );
+        }
+
+        for id in scene.light_ids().to_vec() {
+            let offset = self.offset(LIGHT_OFFSET);
+            let factor = self.signed().mul_add(INTENSITY_JITTER, 1.0);
+            scene.update_light(id, |light| {
+                light.translate(offset);
+                light.scale_intensity(factor);
+            });
+        }
+
+        camera.set_position(camera.position() + self.offset(CAMERA_OFFSET));
+        camera.zoom(self.signed() * FOV_JITTER);
+
+        *renderer.scene_mut() = scene;
+        renderer.set_camera(camera);
+        renderer.mark_interaction();
+    }
+
+    pub fn save_favorite(renderer: &Renderer, frame: &[u8]) -> Result<PathBuf> {
+        let output_dir = renderer.settings().output_dir();
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create directory {}", output_dir.display()))?;
+
+        let scene_path = output::timestamped_path(output_dir, "favorite", "scene");
+        renderer.scene().save(renderer.camera(), &scene_path)?;
+        output::write_png(
+            &scene_path.with_extension("png"),
+            renderer.width(),
+            renderer.height(),
+            frame,
+        )?;
+
+        Ok(scene_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytracer_rust::{
+        FOV,
+        lights::{Light, init_default_lights},
+        settings::RenderSettings,
+        shapes::init_default_shapes,
+    };
+
+    fn renderer() -> Renderer {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        Renderer::new(scene, RenderSettings::default(), 8, 6, camera)
+    }
+
+    #[test]
+    fn mutations_are_seeded_and_stay_near_the_original() {
+        let (mut first, mut second) = (renderer(), renderer());
+        let original = first.scene().clone();
+
+        for renderer in [&mut first, &mut second] {
+            let mut explorer = Explorer::new(42);
+            explorer.mutate(renderer);
+            explorer.mutate(renderer);
+        }
+
+        assert_eq!(first.scene(), second.scene());
+        assert_ne!(first.scene(), &original);
+        assert!(first.camera().position().length() <= CAMERA_OFFSET * 3.0_f64.sqrt());
+        for (light, base) in first.scene().lights().iter().zip(original.lights()) {
+            let factor = light.intensity() / base.intensity();
+            assert!((1.0 - INTENSITY_JITTER..=1.0 + INTENSITY_JITTER).contains(&factor));
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use std::{fmt, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    Vec3f,
+    camera::Camera,
+    geometry::Transform,
+    lights::{Light, LightType},
+    materials::{self, Material},
+    mesh::TriangleMesh,
+    scene::Scene,
+    shapes::{Shape, ShapeType},
+};
+
+const PLANE_EXTENT: f64 = 1000.0;
+const CUBE_CORNERS: [[f64; 3]; 8] = [
+    [-1.0, -1.0, -1.0],
+    [1.0, -1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    [-1.0, 1.0, -1.0],
+    [-1.0, -1.0, 1.0],
+    [1.0, -1.0, 1.0],
+    [1.0, 1.0, 1.0],
+    [-1.0, 1.0, 1.0],
+];
+const CUBE_INDICES: [usize; 36] = [
+    0, 2, 1, 0, 3, 2, 4, 5, 6, 4, 6, 7, 0, 1, 5, 0, 5, 4, 3, 7, 6, 3, 6, 2, 0, 4, 7, 0, 7, 3, 1, 2,
+    6, 1, 6, 5,
+];
+const QUAD_CORNERS: [[f64; 3]; 4] = [
+    [-1.0, -1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+];
+const QUAD_INDICES: [usize; 6] = [0, 1, 2, 0, 2, 3];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pbrt,
+    Mitsuba,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("pbrt") => Ok(Self::Pbrt),
+            Some("xml") => Ok(Self::Mitsuba),
+            _ => bail!(
+                "Unknown export format for {}, expected .pbrt or .xml",
+                path.display()
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Surface {
+    Diffuse { reflectance: Vec3f },
+    Plastic { reflectance: Vec3f, roughness: f64 },
+    Mirror { reflectance: Vec3f },
+    Dielectric { eta: f64 },
+}
+
+impl Surface {
+    fn approximate(material: Material) -> Self {
+        let albedo = material.albedo();
+        let (diffuse, specular, reflect, refract) = (albedo[0], albedo[1], albedo[2], albedo[3]);
+        let reflectance = Vec3f::new_with_data(
+            [0, 1, 2].map(|channel| (material.diffuse_color()[channel] * diffuse).clamp(0.0, 1.0)),
+        );
+
+        if refract > diffuse.max(reflect) && (material.refractive_index() - 1.0).abs() > 1e-6 {
+            Self::Dielectric {
+                eta: material.refractive_index(),
+            }
+        } else if reflect > diffuse {
+            Self::Mirror {
+                reflectance: Vec3f::new(reflect.clamp(0.0, 1.0)),
+            }
+        } else if specular > 0.0 {
+            Self::Plastic {
+                reflectance,
+                roughness: materials::roughness(material.specular_exponent()),
+            }
+        } else {
+            Self::Diffuse { reflectance }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Primitive<'a> {
+    Sphere,
+    Cube,
+    Rectangle,
+    Mesh(&'a TriangleMesh),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Placed<'a> {
+    primitive: Primitive<'a>,
+    frame: Transform,
+    material: Material,
+}
+
+fn plane_axes(normal: Vec3f) -> [Vec3f; 3] {
+    let helper = if normal.x().abs() < 0.9 {
+        Vec3f::new_with_data([1.0, 0.0, 0.0])
+    } else {
+        Vec3f::new_with_data([0.0, 1.0, 0.0])
+    };
+    let tangent = normal.cross(&helper).normalize(None);
+    let bitangent = normal.cross(&tangent);
+
+    [tangent * PLANE_EXTENT, bitangent * PLANE_EXTENT, normal]
+}
+
+fn place<'a>(shape: &'a ShapeType, outer: Transform, placed: &mut Vec<Placed<'a>>) {
+    let (primitive, local) = match shape {
+        ShapeType::Sphere(sphere) => (
+            Primitive::Sphere,
+            Transform::from_scale(sphere.radius())
+                .map(|scale| scale.then(Transform::from_translation(sphere.center()))),
+        ),
+        ShapeType::BoxShape(box_shape) => {
+            let half = (box_shape.max_point() - box_shape.min_point()) * 0.5;
+            let axes = [0, 1, 2].map(|axis| {
+                let mut data = [0.0; 3];
+                data[axis] = half[axis];
+                Vec3f::new_with_data(data)
+            });
+            (
+                Primitive::Cube,
+                Transform::from_axes(axes, box_shape.min_point() + half),
+            )
+        }
+        ShapeType::OrientedBox(oriented) => {
+            let rotation = Transform::from_rotation(oriented.axis(), oriented.angle());
+            let half = oriented.half_extents();
+            let axes = [0, 1, 2].map(|axis| {
+                let mut data = [0.0; 3];
+                data[axis] = half[axis];
+                rotation.apply_vector(Vec3f::new_with_data(data))
+            });
+            (
+                Primitive::Cube,
+                Transform::from_axes(axes, oriented.center()),
+            )
+        }
+        ShapeType::InfinityPlane(plane) => (
+            Primitive::Rectangle,
+            Transform::from_axes(plane_axes(plane.normal()), plane.position()),
+        ),
+        ShapeType::Quad(quad) => (
+            Primitive::Rectangle,
+            Transform::from_axes(
+                [
+                    quad.edge_u() * 0.5,
+                    quad.edge_v() * 0.5,
+                    quad.get_normal(quad.origin()),
+                ],
+                quad.center(),
+            ),
+        ),
+        ShapeType::Mesh(mesh) => (Primitive::Mesh(mesh), Some(Transform::IDENTITY)),
+        ShapeType::Instance(instance) => {
+            place(instance.shape(), instance.transform().then(outer), placed);
+            return;
+        }
+        ShapeType::Sdf(_)
+        | ShapeType::Heightfield(_)
+        | ShapeType::Prism(_)
+        | ShapeType::Billboard(_)
+        | ShapeType::Custom(_) => return,
+    };
+
+    if let Some(local) = local {
+        placed.push(Placed {
+            primitive,
+            frame: local.then(outer),
+            material: shape.get_material(),
+        });
+    }
+}
+
+fn area_light_frame(light: &LightType) -> Option<Transform> {
+    let LightType::Area(light) = light else {
+        return None;
+    };
+    let (u, v) = (light.edge_u() * 0.5, light.edge_v() * 0.5);
+
+    Transform::from_axes([u, v, light.normal()], light.corner() + u + v)
+}
+
+fn spaced(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(f64::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn rgb(color: Vec3f) -> String {
+    spaced(&[color.x(), color.y(), color.z()])
+}
+
+fn pbrt_matrix(transform: Transform) -> String {
+    let [row0, row1, row2] = transform.rows();
+    let translation = transform.translation();
+    let mut values = Vec::with_capacity(16);
+    for column in 0..3 {
+        values.extend([row0[column], row1[column], row2[column], 0.0]);
+    }
+    values.extend([translation.x(), translation.y(), translation.z(), 1.0]);
+
+    spaced(&values)
+}
+
+fn mitsuba_matrix(transform: Transform) -> String {
+    let translation = transform.translation();
+    let mut values = Vec::with_capacity(16);
+    for (row, offset) in
+        transform
+            .rows()
+            .iter()
+            .zip([translation.x(), translation.y(), translation.z()])
+    {
+        values.extend([row.x(), row.y(), row.z(), offset]);
+    }
+    values.extend([0.0, 0.0, 0.0, 1.0]);
+
+    spaced(&values)
+}
+
+fn pbrt_triangles(corners: &[[f64; 3]], indices: &[usize]) -> String {
+    format!(
+        "Shape \"trianglemesh\" \"point3 P\" [{}] \"integer indices\" [{}]",
+        spaced(&corners.concat()),
+        indices
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+fn pbrt_material(surface: Surface) -> String {
+    match surface {
+        Surface::Diffuse { reflectance } => {
+            format!(
+                "Material \"diffuse\" \"rgb reflectance\" [{}]",
+                rgb(reflectance)
+            )
+        }
+        Surface::Plastic {
+            reflectance,
+            roughness,
+        } => format!(
+            "Material \"coateddiffuse\" \"rgb reflectance\" [{}] \"float roughness\" [{roughness}]",
+            rgb(reflectance)
+        ),
+        Surface::Mirror { reflectance } => format!(
+            "Material \"conductor\" \"rgb reflectance\" [{}] \"float roughness\" [0]",
+            rgb(reflectance)
+        ),
+        Surface::Dielectric { eta } => format!("Material \"dielectric\" \"float eta\" [{eta}]"),
+    }
+}
+
+fn mitsuba_bsdf(surface: Surface) -> String {
+    match surface {
+        Surface::Diffuse { reflectance } => format!(
+            "<bsdf type=\"diffuse\"><rgb name=\"reflectance\" value=\"{}\"/></bsdf>",
+            rgb(reflectance)
+        ),
+        Surface::Plastic {
+            reflectance,
+            roughness,
+        } => format!(
+            "<bsdf type=\"roughplastic\"><rgb name=\"diffuse_reflectance\" value=\"{}\"/><float name=\"alpha\" value=\"{roughness}\"/></bsdf>",
+            rgb(reflectance)
+        ),
+        Surface::Mirror { reflectance } => format!(
+            "<bsdf type=\"conductor\"><string name=\"material\" value=\"none\"/><rgb name=\"specular_reflectance\" value=\"{}\"/></bsdf>",
+            rgb(reflectance)
+        ),
+        Surface::Dielectric { eta } => format!(
+            "<bsdf type=\"dielectric\"><float name=\"int_ior\" value=\"{eta}\"/><float name=\"ext_ior\" value=\"1\"/></bsdf>"
+        ),
+    }
+}
+
+fn write_pbrt_light(f: &mut fmt::Formatter<'_>, light: &LightType) -> fmt::Result {
+    let intensity = rgb(Vec3f::new(light.intensity()));
+    match light {
+        LightType::Ambient(_) => {
+            writeln!(f, "LightSource \"infinite\" \"rgb L\" [{intensity}]")?;
+        }
+        LightType::Point(point) => writeln!(
+            f,
+            "LightSource \"point\" \"rgb I\" [{intensity}] \"point3 from\" [{}]",
+            rgb(point.position())
+        )?,
+        LightType::Directional(directional) => writeln!(
+            f,
+            "LightSource \"distant\" \"rgb L\" [{intensity}] \"point3 from\" [{}] \"point3 to\" [0 0 0]",
+            rgb(directional.direction())
+        )?,
+        LightType::Area(area) => {
+            let Some(frame) = area_light_frame(light) else {
+                return Ok(());
+            };
+            writeln!(f, "AttributeBegin")?;
+            writeln!(
+                f,
+                "  AreaLightSource \"diffuse\" \"rgb L\" [{intensity}] \"bool twosided\" [{}]",
+                area.two_sided()
+            )?;
+            writeln!(f, "  ConcatTransform [{}]", pbrt_matrix(frame))?;
+            writeln!(f, "  {}", pbrt_triangles(&QUAD_CORNERS, &QUAD_INDICES))?;
+            writeln!(f, "AttributeEnd")?;
+        }
+        LightType::Sky(sky) => writeln!(
+            f,
+            "LightSource \"infinite\" \"rgb L\" [{}]",
+            rgb(sky.average())
+        )?,
+        LightType::SunSky(sky) => {
+            writeln!(
+                f,
+                "LightSource \"distant\" \"rgb L\" [{intensity}] \"point3 from\" [{}] \"point3 to\" [0 0 0]",
+                rgb(sky.sky().sun_direction())
+            )?;
+            writeln!(
+                f,
+                "LightSource \"infinite\" \"rgb L\" [{}]",
+                rgb(sky.average())
+            )?;
+        }
+        LightType::Environment(environment) => writeln!(
+            f,
+            "LightSource \"infinite\" \"string filename\" \"{}\" \"float scale\" [{}]",
+            environment.map().source().display(),
+            environment.intensity()
+        )?,
+        LightType::Custom(_) => writeln!(f, "# custom light omitted")?,
+    }
+
+    Ok(())
+}
+
+fn write_mitsuba_light(f: &mut fmt::Formatter<'_>, light: &LightType) -> fmt::Result {
+    let intensity = rgb(Vec3f::new(light.intensity()));
+    match light {
+        LightType::Ambient(_) => writeln!(
+            f,
+            "  <emitter type=\"constant\"><rgb name=\"radiance\" value=\"{intensity}\"/></emitter>"
+        )?,
+        LightType::Point(point) => writeln!(
+            f,
+            "  <emitter type=\"point\"><point name=\"position\" value=\"{}\"/><rgb name=\"intensity\" value=\"{intensity}\"/></emitter>",
+            rgb(point.position())
+        )?,
+        LightType::Directional(directional) => writeln!(
+            f,
+            "  <emitter type=\"directional\"><vector name=\"direction\" value=\"{}\"/><rgb name=\"irradiance\" value=\"{intensity}\"/></emitter>",
+            rgb(-directional.direction())
+        )?,
+        LightType::Area(_) => {
+            let Some(frame) = area_light_frame(light) else {
+                return Ok(());
+            };
+            writeln!(
+                f,
+                "  <shape type=\"rectangle\"><transform name=\"to_world\"><matrix value=\"{}\"/></transform><emitter type=\"area\"><rgb name=\"radiance\" value=\"{intensity}\"/></emitter></shape>",
+                mitsuba_matrix(frame)
+            )?;
+        }
+        LightType::Sky(sky) => writeln!(
+            f,
+            "  <emitter type=\"constant\"><rgb name=\"radiance\" value=\"{}\"/></emitter>",
+            rgb(sky.average())
+        )?,
+        LightType::SunSky(sky) => {
+            writeln!(
+                f,
+                "  <emitter type=\"directional\"><vector name=\"direction\" value=\"{}\"/><rgb name=\"irradiance\" value=\"{intensity}\"/></emitter>",
+                rgb(-sky.sky().sun_direction())
+            )?;
+            writeln!(
+                f,
+                "  <emitter type=\"constant\"><rgb name=\"radiance\" value=\"{}\"/></emitter>",
+                rgb(sky.average())
+            )?;
+        }
+        LightType::Environment(environment) => writeln!(
+            f,
+            "  <emitter type=\"envmap\"><string name=\"filename\" value=\"{}\"/><float name=\"scale\" value=\"{}\"/></emitter>",
+            environment.map().source().display(),
+            environment.intensity()
+        )?,
+        LightType::Custom(_) => writeln!(f, "  <!-- custom light omitted -->")?,
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SceneExport<'a> {
+    scene: &'a Scene,
+    camera: &'a Camera,
+    resolution: (u32, u32),
+    format: ExportFormat,
+}
+
+impl<'a> SceneExport<'a> {
+    pub const fn new(
+        scene: &'a Scene,
+        camera: &'a Camera,
+        resolution: (u32, u32),
+        format: ExportFormat,
+    ) -> Self {
+        Self {
+            scene,
+            camera,
+            resolution,
+            format,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn placed(&self) -> Vec<Placed<'a>> {
+        let mut placed = Vec::new();
+        for shape in self.scene.shapes() {
+            place(shape, Transform::IDENTITY, &mut placed);
+        }
+
+        placed
+    }
+
+    fn short_axis_fov(&self) -> f64 {
+        let (width, height) = self.resolution;
+        let fov = self.camera.fov();
+        if width < height {
+            2.0 * ((fov / 2.0).tan() * f64::from(width) / f64::from(height)).atan()
+        } else {
+            fov
+        }
+    }
+
+    fn write_pbrt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.resolution;
+        let position = self.camera.position();
+        writeln!(f, "Scale -1 1 1")?;
+        writeln!(
+            f,
+            "LookAt {} {} {}",
+            rgb(position),
+            rgb(position + self.camera.forward()),
+            rgb(self.camera.up())
+        )?;
+        writeln!(
+            f,
+            "Camera \"perspective\" \"float fov\" [{}]",
+            self.short_axis_fov().to_degrees()
+        )?;
+        writeln!(
+            f,
+            "Film \"rgb\" \"integer xresolution\" [{width}] \"integer yresolution\" [{height}]"
+        )?;
+        writeln!(f, "WorldBegin")?;
+
+        for light in self.scene.lights() {
+            write_pbrt_light(f, light)?;
+        }
+
+        for placed in self.placed() {
+            writeln!(f, "AttributeBegin")?;
+            writeln!(
+                f,
+                "  {}",
+                pbrt_material(Surface::approximate(placed.material))
+            )?;
+            writeln!(f, "  ConcatTransform [{}]", pbrt_matrix(placed.frame))?;
+            let shape = match placed.primitive {
+                Primitive::Sphere => "Shape \"sphere\" \"float radius\" [1]".to_string(),
+                Primitive::Cube => pbrt_triangles(&CUBE_CORNERS, &CUBE_INDICES),
+                Primitive::Rectangle => pbrt_triangles(&QUAD_CORNERS, &QUAD_INDICES),
+                Primitive::Mesh(mesh) => {
+                    let corners = mesh
+                        .triangles()
+                        .flatten()
+                        .map(|corner| [corner.x(), corner.y(), corner.z()])
+                        .collect::<Vec<_>>();
+                    pbrt_triangles(&corners, &(0..corners.len()).collect::<Vec<_>>())
+                }
+            };
+            writeln!(f, "  {shape}")?;
+            writeln!(f, "AttributeEnd")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_mitsuba(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.resolution;
+        let position = self.camera.position();
+        let target = position + self.camera.forward();
+        let up = self.camera.up();
+        writeln!(f, "<scene version=\"3.0.0\">")?;
+        writeln!(f, "  <sensor type=\"perspective\">")?;
+        writeln!(
+            f,
+            "    <float name=\"fov\" value=\"{}\"/>",
+            self.camera.fov().to_degrees()
+        )?;
+        writeln!(f, "    <string name=\"fov_axis\" value=\"y\"/>")?;
+        writeln!(
+            f,
+            "    <transform name=\"to_world\"><lookat origin=\"{}\" target=\"{}\" up=\"{}\"/></transform>",
+            rgb(position),
+            rgb(target),
+            rgb(up)
+        )?;
+        writeln!(
+            f,
+            "    <film type=\"hdrfilm\"><integer name=\"width\" value=\"{width}\"/><integer name=\"height\" value=\"{height}\"/></film>"
+        )?;
+        writeln!(f, "  </sensor>")?;
+
+        for light in self.scene.lights() {
+            write_mitsuba_light(f, light)?;
+        }
+
+        for placed in self.placed() {
+            let (kind, extra) = match placed.primitive {
+                Primitive::Sphere => ("sphere", String::new()),
+                Primitive::Cube => ("cube", String::new()),
+                Primitive::Rectangle => ("rectangle", String::new()),
+                Primitive::Mesh(mesh) => (
+                    "obj",
+                    format!(
+                        "<string name=\"filename\" value=\"{}\"/><boolean name=\"face_normals\" value=\"{}\"/>",
+                        mesh.source().display(),
+                        !mesh.smooth()
+                    ),
+                ),
+            };
+            writeln!(
+                f,
+                "  <shape type=\"{kind}\">{extra}<transform name=\"to_world\"><matrix value=\"{}\"/></transform>{}</shape>",
+                mitsuba_matrix(placed.frame),
+                mitsuba_bsdf(Surface::approximate(placed.material))
+            )?;
+        }
+
+        writeln!(f, "</scene>")
+    }
+}
+
+impl fmt::Display for SceneExport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            ExportFormat::Pbrt => self.write_pbrt(f),
+            ExportFormat::Mitsuba => self.write_mitsuba(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FOV, lights::init_default_lights, shapes::init_default_shapes};
+
+    #[test]
+    fn exports_shapes_lights_and_camera_for_both_formats() -> Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let shapes = scene.shapes().len();
+
+        let pbrt = SceneExport::new(
+            &scene,
+            &camera,
+            (800, 600),
+            ExportFormat::from_path(Path::new("scene.pbrt"))?,
+        )
+        .to_string();
+        assert!(pbrt.contains("LookAt 0 0 0 0 0 -1 0 1 0"));
+        assert!(pbrt.contains("\"integer xresolution\" [800]"));
+        assert!(pbrt.contains("Material \"dielectric\""));
+        assert!(pbrt.contains("Material \"conductor\""));
+        assert!(pbrt.contains("LightSource \"point\""));
+        assert_eq!(pbrt.matches("Material ").count(), shapes);
+
+        let mitsuba = SceneExport::new(
+            &scene,
+            &camera,
+            (800, 600),
+            ExportFormat::from_path(Path::new("scene.xml"))?,
+        )
+        .to_string();
+        assert!(mitsuba.starts_with("<scene version=\"3.0.0\">"));
+        assert!(mitsuba.contains("<shape type=\"sphere\">"));
+        assert_eq!(mitsuba.matches("<bsdf ").count(), shapes);
+        assert!(ExportFormat::from_path(Path::new("scene.obj")).is_err());
+        Ok(())
+    }
+}
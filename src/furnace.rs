@@ -0,0 +1,166 @@
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::{
+    FOV, Vec3f, Vec4f,
+    camera::Camera,
+    lights::{AmbientLight, LightType},
+    materials::Material,
+    scene::Scene,
+    shapes::{ShapeType, Sphere},
+};
+
+pub const FURNACE_TOLERANCE: f64 = 1e-6;
+
+const ENVIRONMENT: Vec3f = Vec3f::const_new_with_data([1.0, 1.0, 1.0]);
+const BLACK: Vec3f = Vec3f::const_new_with_data([0.0, 0.0, 0.0]);
+
+pub const FURNACE_MATERIALS: [(&str, Material); 3] = [
+    (
+        "lambert",
+        Material::new(
+            Vec4f::const_new_with_data([1.0, 0.0, 0.0, 0.0]),
+            ENVIRONMENT,
+            ENVIRONMENT,
+            1.0,
+            1.0,
+        ),
+    ),
+    (
+        "mirror",
+        Material::new(
+            Vec4f::const_new_with_data([0.0, 0.0, 1.0, 0.0]),
+            ENVIRONMENT,
+            BLACK,
+            1000.0,
+            1.0,
+        ),
+    ),
+    (
+        "dielectric",
+        Material::new(
+            Vec4f::const_new_with_data([0.0, 0.0, 0.0, 1.0]),
+            ENVIRONMENT,
+            BLACK,
+            1.0,
+            1.5,
+        ),
+    ),
+];
+
+const SPHERE_CENTERS: [Vec3f; 3] = [
+    Vec3f::const_new_with_data([-2.5, 0.0, -6.0]),
+    Vec3f::const_new_with_data([0.0, 0.0, -6.0]),
+    Vec3f::const_new_with_data([2.5, 0.0, -6.0]),
+];
+
+fn furnace(shapes: Vec<ShapeType>) -> Scene {
+    Scene::new(shapes, vec![LightType::Ambient(AmbientLight::new(1.0))])
+        .with_background(ENVIRONMENT)
+}
+
+pub fn furnace_scene() -> (Scene, Camera) {
+    let spheres = SPHERE_CENTERS
+        .iter()
+        .zip(FURNACE_MATERIALS)
+        .map(|(&center, (_, material))| ShapeType::Sphere(Sphere::new(center, 1.0, material)))
+        .collect();
+
+    (furnace(spheres), Camera::new(Vec3f::new(0.0), FOV))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FurnaceResult {
+    name: &'static str,
+    min: f64,
+    max: f64,
+}
+
+impl FurnaceResult {
+    pub fn passed(&self) -> bool {
+        self.min >= 1.0 - FURNACE_TOLERANCE && self.max <= 1.0 + FURNACE_TOLERANCE
+    }
+}
+
+impl fmt::Display for FurnaceResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<12} min {:.6} max {:.6} {}",
+            self.name,
+            self.min,
+            self.max,
+            if self.passed() { "ok" } else { "FAILED" }
+        )
+    }
+}
+
+pub fn measure(name: &'static str, material: Material, width: u32, height: u32) -> FurnaceResult {
+    let scene = furnace(vec![ShapeType::Sphere(Sphere::new(
+        Vec3f::new_with_data([0.0, 0.0, -3.0]),
+        1.0,
+        material,
+    ))]);
+    let camera = Camera::new(Vec3f::new(0.0), FOV);
+
+    let (min, max) = (0..height)
+        .into_par_iter()
+        .flat_map_iter(|row| (0..width).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let direction =
+                camera.ray_direction(f64::from(column) + 0.5, f64::from(row) + 0.5, width, height);
+            let radiance = scene.trace(camera.position(), direction);
+            (
+                radiance[0].min(radiance[1]).min(radiance[2]),
+                radiance[0].max(radiance[1]).max(radiance[2]),
+            )
+        })
+        .reduce(
+            || (f64::INFINITY, f64::NEG_INFINITY),
+            |a, b| (a.0.min(b.0), a.1.max(b.1)),
+        );
+
+    FurnaceResult { name, min, max }
+}
+
+pub fn run_furnace_test(width: u32, height: u32) -> Vec<FurnaceResult> {
+    FURNACE_MATERIALS
+        .iter()
+        .map(|&(name, material)| measure(name, material, width, height))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_albedo_materials_vanish_in_the_furnace() {
+        for result in run_furnace_test(64, 48) {
+            assert!(result.passed(), "{result}");
+        }
+    }
+
+    #[test]
+    fn energy_gain_and_loss_are_detected() {
+        let (_, lambert) = FURNACE_MATERIALS[0];
+        let glowing = Material::new(
+            Vec4f::const_new_with_data([1.0, 0.0, 0.5, 0.0]),
+            ENVIRONMENT,
+            ENVIRONMENT,
+            1.0,
+            1.0,
+        );
+        let gain = measure("glowing", glowing, 32, 24);
+        assert!(gain.max > 1.0 + FURNACE_TOLERANCE);
+
+        let (_, dielectric) = FURNACE_MATERIALS[2];
+        let absorbing = dielectric.with_absorption(Vec3f::new(0.5));
+        let loss = measure("absorbing", absorbing, 32, 24);
+        assert!(loss.min < 1.0 - FURNACE_TOLERANCE);
+        assert!(loss.max <= 1.0 + FURNACE_TOLERANCE);
+
+        assert!(measure("lambert", lambert, 32, 24).passed());
+    }
+}
@@ -1,15 +1,66 @@
 use std::iter::Sum;
-use std::ops::{Add, Div, Neg, Sub};
+use std::mem::swap;
+use std::ops::{Add, AddAssign, Div, DivAssign, MulAssign, Neg, Sub, SubAssign};
 use std::{
     ops::{Index, IndexMut, Mul},
     slice::SliceIndex,
 };
 
+const UNIT_LENGTH_TOLERANCE: f64 = 1e-12;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct RaytracerVector<T, const N: usize> {
     data: [T; N],
 }
 
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, const N: usize> serde::Serialize for RaytracerVector<T, N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+
+        let mut components = serializer.serialize_tuple(N)?;
+        for component in &self.data {
+            components.serialize_element(component)?;
+        }
+        components.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, const N: usize> serde::Deserialize<'de>
+    for RaytracerVector<T, N>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Components<T, const N: usize>(std::marker::PhantomData<T>);
+
+        impl<'de, T: serde::Deserialize<'de>, const N: usize> serde::de::Visitor<'de> for Components<T, N> {
+            type Value = [T; N];
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "{N} vector components")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut components = Vec::with_capacity(N);
+                while let Some(component) = seq.next_element()? {
+                    components.push(component);
+                }
+                let found = components.len();
+                components
+                    .try_into()
+                    .map_err(|_| serde::de::Error::invalid_length(found, &self))
+            }
+        }
+
+        deserializer
+            .deserialize_tuple(N, Components(std::marker::PhantomData))
+            .map(|data| Self { data })
+    }
+}
+
 #[allow(dead_code)]
 pub type Vec2f = RaytracerVector<f64, 2>;
 pub type Vec3f = RaytracerVector<f64, 3>;
@@ -17,6 +68,54 @@ pub type Vec4f = RaytracerVector<f64, 4>;
 #[allow(dead_code)]
 pub type Vec3i = RaytracerVector<i32, 3>;
 
+#[cfg(feature = "glam")]
+macro_rules! impl_glam_conversion {
+    ($vector:ty, $glam:ty) => {
+        impl From<$vector> for $glam {
+            fn from(vector: $vector) -> Self {
+                Self::from_array(vector.data)
+            }
+        }
+
+        impl From<$glam> for $vector {
+            fn from(vector: $glam) -> Self {
+                Self {
+                    data: vector.to_array(),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "glam")]
+impl_glam_conversion!(Vec2f, glam::DVec2);
+#[cfg(feature = "glam")]
+impl_glam_conversion!(Vec3f, glam::DVec3);
+#[cfg(feature = "glam")]
+impl_glam_conversion!(Vec4f, glam::DVec4);
+#[cfg(feature = "glam")]
+impl_glam_conversion!(Vec3i, glam::IVec3);
+
+#[cfg(feature = "nalgebra")]
+impl<T: nalgebra::Scalar + Copy, const N: usize> From<RaytracerVector<T, N>>
+    for nalgebra::SVector<T, N>
+{
+    fn from(vector: RaytracerVector<T, N>) -> Self {
+        Self::from_array_storage(nalgebra::ArrayStorage([vector.data]))
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T: nalgebra::Scalar + Copy, const N: usize> From<nalgebra::SVector<T, N>>
+    for RaytracerVector<T, N>
+{
+    fn from(vector: nalgebra::SVector<T, N>) -> Self {
+        Self {
+            data: vector.data.0[0],
+        }
+    }
+}
+
 impl<T: Copy + From<f64>, const N: usize> RaytracerVector<T, N> {
     pub const fn new(value: T) -> Self {
         Self { data: [value; N] }
@@ -30,6 +129,20 @@ impl<T: Copy + From<f64>, const N: usize> RaytracerVector<T, N> {
         Self { data }
     }
 
+    pub fn hadamard(self, rhs: Self) -> Self
+    where
+        T: Mul<Output = T>,
+    {
+        self.apply_op_vector(rhs, |x, y| x * y)
+    }
+
+    pub fn lerp(self, target: Self, t: f64) -> Self
+    where
+        T: Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+    {
+        self + (target - self) * t
+    }
+
     fn apply_op<F>(self, rhs: f64, op: F) -> Self
     where
         F: Fn(T, T) -> T,
@@ -48,6 +161,98 @@ impl<T: Copy + From<f64>, const N: usize> RaytracerVector<T, N> {
     }
 }
 
+impl<T: Copy, const N: usize> RaytracerVector<T, N> {
+    pub fn dot(self, rhs: Self) -> T
+    where
+        T: Mul<Output = T> + Sum,
+    {
+        self.data
+            .iter()
+            .zip(rhs.data.iter())
+            .map(|(a, b)| (*a) * (*b))
+            .sum()
+    }
+
+    pub fn min(self, rhs: Self) -> Self
+    where
+        T: PartialOrd,
+    {
+        Self {
+            data: core::array::from_fn(|i| {
+                if rhs.data[i] < self.data[i] {
+                    rhs.data[i]
+                } else {
+                    self.data[i]
+                }
+            }),
+        }
+    }
+
+    pub fn max(self, rhs: Self) -> Self
+    where
+        T: PartialOrd,
+    {
+        Self {
+            data: core::array::from_fn(|i| {
+                if rhs.data[i] > self.data[i] {
+                    rhs.data[i]
+                } else {
+                    self.data[i]
+                }
+            }),
+        }
+    }
+
+    pub fn clamp(self, low: Self, high: Self) -> Self
+    where
+        T: PartialOrd,
+    {
+        self.max(low).min(high)
+    }
+}
+
+impl<T: Copy> RaytracerVector<T, 2> {
+    pub const fn x(&self) -> T {
+        self.data[0]
+    }
+
+    pub const fn y(&self) -> T {
+        self.data[1]
+    }
+}
+
+impl<T: Copy> RaytracerVector<T, 3> {
+    pub const fn x(&self) -> T {
+        self.data[0]
+    }
+
+    pub const fn y(&self) -> T {
+        self.data[1]
+    }
+
+    pub const fn z(&self) -> T {
+        self.data[2]
+    }
+}
+
+impl<T: Copy> RaytracerVector<T, 4> {
+    pub const fn x(&self) -> T {
+        self.data[0]
+    }
+
+    pub const fn y(&self) -> T {
+        self.data[1]
+    }
+
+    pub const fn z(&self) -> T {
+        self.data[2]
+    }
+
+    pub const fn w(&self) -> T {
+        self.data[3]
+    }
+}
+
 impl<T, const N: usize, Idx> Index<Idx> for RaytracerVector<T, N>
 where
     Idx: SliceIndex<[T], Output = T>,
@@ -84,11 +289,7 @@ where
     type Output = T;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        self.data
-            .iter()
-            .zip(rhs.data.iter())
-            .map(|(a, b)| (*a) * (*b))
-            .sum()
+        self.dot(rhs)
     }
 }
 
@@ -142,12 +343,55 @@ macro_rules! impl_op_vector {
     };
 }
 
+macro_rules! impl_assign_scalar {
+    ($op:tt, $trait:ident, $method:ident, $op_fn:expr) => {
+        impl<T, const N: usize> $trait<f64> for RaytracerVector<T, N>
+        where
+            T: Copy + $op<Output = T> + From<f64>,
+        {
+            fn $method(&mut self, rhs: f64) {
+                *self = self.apply_op(rhs, $op_fn);
+            }
+        }
+    };
+}
+
+macro_rules! impl_assign_vector {
+    ($op:tt, $trait:ident, $method:ident, $op_fn:expr) => {
+        impl<T, const N: usize> $trait<RaytracerVector<T, N>> for RaytracerVector<T, N>
+        where
+            T: Copy + $op<Output = T> + From<f64>,
+        {
+            fn $method(&mut self, rhs: RaytracerVector<T, N>) {
+                *self = self.apply_op_vector(rhs, $op_fn);
+            }
+        }
+    };
+}
+
 impl_op_scalar!(Div, Div, div, |x, y| x / y);
 impl_op_scalar!(Mul, Mul, mul, |x, y| x * y);
 
 impl_op_vector!(Add, Add, add, |x, y| x + y);
 impl_op_vector!(Sub, Sub, sub, |x, y| x - y);
 
+impl_assign_scalar!(Div, DivAssign, div_assign, |x, y| x / y);
+impl_assign_scalar!(Mul, MulAssign, mul_assign, |x, y| x * y);
+
+impl_assign_vector!(Add, AddAssign, add_assign, |x, y| x + y);
+impl_assign_vector!(Sub, SubAssign, sub_assign, |x, y| x - y);
+
+impl<T, const N: usize> Mul<RaytracerVector<T, N>> for f64
+where
+    T: Copy + Mul<Output = T> + From<Self>,
+{
+    type Output = RaytracerVector<T, N>;
+
+    fn mul(self, rhs: RaytracerVector<T, N>) -> Self::Output {
+        rhs.apply_op(self, |x, y| y * x)
+    }
+}
+
 impl<T, const N: usize> RaytracerVector<T, N>
 where
     T: Div<Output = T> + Into<f64> + From<f64> + Copy,
@@ -165,24 +409,27 @@ where
 
         self / length
     }
-}
 
-impl<T> RaytracerVector<T, 3>
-where
-    T: Mul<Output = T> + Sub<Output = T> + From<f64> + Copy,
-{
-    pub const fn x(&self) -> T {
-        self.data[0]
-    }
+    pub fn unit(&self) -> Self {
+        if (self.length() - 1.0).abs() <= UNIT_LENGTH_TOLERANCE {
+            return *self;
+        }
 
-    pub const fn y(&self) -> T {
-        self.data[1]
+        self.normalize(None)
     }
 
-    pub const fn z(&self) -> T {
-        self.data[2]
+    pub fn distance(&self, other: &Self) -> f64
+    where
+        T: Sub<Output = T>,
+    {
+        (*self - *other).length()
     }
+}
 
+impl<T> RaytracerVector<T, 3>
+where
+    T: Mul<Output = T> + Sub<Output = T> + From<f64> + Copy,
+{
     pub fn cross(self, vector: &Self) -> Self {
         let [x1, y1, z1] = self.data;
         let [x2, y2, z2] = vector.data;
@@ -205,3 +452,502 @@ where
         Self::new_with_data([cx, cy, cz])
     }
 }
+
+fn transpose(rows: [Vec3f; 3]) -> [Vec3f; 3] {
+    core::array::from_fn(|column| Vec3f::new_with_data(rows.map(|row| row[column])))
+}
+
+fn multiply(left: [Vec3f; 3], right: [Vec3f; 3]) -> [Vec3f; 3] {
+    let columns = transpose(right);
+    left.map(|row| Vec3f::new_with_data(columns.map(|column| row * column)))
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    rows: [Vec3f; 3],
+    inverse_rows: [Vec3f; 3],
+    translation: Vec3f,
+}
+
+impl PartialEq for Transform {
+    fn eq(&self, other: &Self) -> bool {
+        self.rows == other.rows && self.translation == other.translation
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        rows: [
+            Vec3f::const_new_with_data([1.0, 0.0, 0.0]),
+            Vec3f::const_new_with_data([0.0, 1.0, 0.0]),
+            Vec3f::const_new_with_data([0.0, 0.0, 1.0]),
+        ],
+        inverse_rows: [
+            Vec3f::const_new_with_data([1.0, 0.0, 0.0]),
+            Vec3f::const_new_with_data([0.0, 1.0, 0.0]),
+            Vec3f::const_new_with_data([0.0, 0.0, 1.0]),
+        ],
+        translation: Vec3f::const_new_with_data([0.0, 0.0, 0.0]),
+    };
+
+    pub fn new(rows: [Vec3f; 3], translation: Vec3f) -> Option<Self> {
+        let [r0, r1, r2] = rows;
+        let determinant = r0 * r1.cross(&r2);
+        if determinant.abs() < f64::EPSILON || !determinant.is_finite() {
+            return None;
+        }
+
+        let columns =
+            [r1.cross(&r2), r2.cross(&r0), r0.cross(&r1)].map(|column| column / determinant);
+
+        Some(Self {
+            rows,
+            inverse_rows: transpose(columns),
+            translation,
+        })
+    }
+
+    pub const fn from_translation(translation: Vec3f) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: f64) -> Option<Self> {
+        Self::new(Self::IDENTITY.rows.map(|row| row * scale), Vec3f::new(0.0))
+    }
+
+    pub fn from_rotation(axis: Vec3f, angle: f64) -> Self {
+        let [x, y, z] = [axis.unit().x(), axis.unit().y(), axis.unit().z()];
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        let rows = [
+            Vec3f::new_with_data([
+                (t * x).mul_add(x, cos),
+                (t * x).mul_add(y, -sin * z),
+                (t * x).mul_add(z, sin * y),
+            ]),
+            Vec3f::new_with_data([
+                (t * x).mul_add(y, sin * z),
+                (t * y).mul_add(y, cos),
+                (t * y).mul_add(z, -sin * x),
+            ]),
+            Vec3f::new_with_data([
+                (t * x).mul_add(z, -sin * y),
+                (t * y).mul_add(z, sin * x),
+                (t * z).mul_add(z, cos),
+            ]),
+        ];
+
+        Self {
+            rows,
+            inverse_rows: transpose(rows),
+            translation: Vec3f::new(0.0),
+        }
+    }
+
+    pub fn then(self, next: Self) -> Self {
+        Self {
+            rows: multiply(next.rows, self.rows),
+            inverse_rows: multiply(self.inverse_rows, next.inverse_rows),
+            translation: next.apply_vector(self.translation) + next.translation,
+        }
+    }
+
+    pub fn from_axes(axes: [Vec3f; 3], origin: Vec3f) -> Option<Self> {
+        Self::new(
+            core::array::from_fn(|row| Vec3f::new_with_data(axes.map(|axis| axis[row]))),
+            origin,
+        )
+    }
+
+    pub fn inverse(self) -> Self {
+        let inverse = Self {
+            rows: self.inverse_rows,
+            inverse_rows: self.rows,
+            translation: Vec3f::new(0.0),
+        };
+
+        Self {
+            translation: -inverse.apply_vector(self.translation),
+            ..inverse
+        }
+    }
+
+    pub const fn rows(&self) -> [Vec3f; 3] {
+        self.rows
+    }
+
+    pub const fn translation(&self) -> Vec3f {
+        self.translation
+    }
+
+    pub const fn translate(&mut self, offset: Vec3f) {
+        self.translation = Vec3f::const_new_with_data([
+            self.translation.x() + offset.x(),
+            self.translation.y() + offset.y(),
+            self.translation.z() + offset.z(),
+        ]);
+    }
+
+    pub fn apply_point(&self, point: Vec3f) -> Vec3f {
+        self.apply_vector(point) + self.translation
+    }
+
+    pub fn apply_vector(&self, vector: Vec3f) -> Vec3f {
+        Vec3f::new_with_data(self.rows.map(|row| row * vector))
+    }
+
+    pub fn apply_normal(&self, normal: Vec3f) -> Vec3f {
+        Vec3f::new_with_data(transpose(self.inverse_rows).map(|column| column * normal))
+            .normalize(None)
+    }
+
+    pub fn invert_point(&self, point: Vec3f) -> Vec3f {
+        self.invert_vector(point - self.translation)
+    }
+
+    pub fn invert_vector(&self, vector: Vec3f) -> Vec3f {
+        Vec3f::new_with_data(self.inverse_rows.map(|row| row * vector))
+    }
+
+    pub fn min_scale(&self) -> f64 {
+        transpose(self.rows)
+            .iter()
+            .map(Vec3f::length)
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+pub fn inverse_direction(direction: Vec3f) -> Vec3f {
+    Vec3f::new_with_data([
+        1.0 / direction.x(),
+        1.0 / direction.y(),
+        1.0 / direction.z(),
+    ])
+}
+
+pub fn reflect(direction: Vec3f, normal: Vec3f) -> Vec3f {
+    direction - normal * direction.dot(normal) * 2.0
+}
+
+pub fn refract(direction: Vec3f, normal: Vec3f, ior_outside: f64, ior_inside: f64) -> Vec3f {
+    let mut cosi = -direction.dot(normal).clamp(-1.0, 1.0);
+    let mut ior_from = ior_outside;
+    let mut ior_to = ior_inside;
+    let mut n = normal;
+
+    if cosi < 0.0 {
+        cosi *= -1.0;
+        swap(&mut ior_from, &mut ior_to);
+        n = -n;
+    }
+
+    let eta = ior_from / ior_to;
+    let k = (eta * eta).mul_add(-cosi.mul_add(-cosi, 1.0), 1.0);
+
+    if k < 0.0 {
+        return Vec3f::new_with_data([0.0, 0.0, 0.0]);
+    }
+    direction * eta + n * eta.mul_add(cosi, -k.sqrt())
+}
+
+pub fn intersect_triangle(
+    origin: Vec3f,
+    direction: Vec3f,
+    [first, second, third]: [Vec3f; 3],
+) -> Option<f64> {
+    let edge1 = second - first;
+    let edge2 = third - first;
+
+    let pvec = direction.cross(&edge2);
+    let determinant = edge1 * pvec;
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let tvec = origin - first;
+    let u = (tvec * pvec) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let v = (direction * qvec) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = (edge2 * qvec) * inverse;
+    (distance > 0.0).then_some(distance)
+}
+
+pub fn terminator_safe_point(
+    point: Vec3f,
+    vertices: [Vec3f; 3],
+    normals: [Vec3f; 3],
+    barycentrics: [f64; 3],
+) -> Vec3f {
+    vertices.iter().zip(normals).zip(barycentrics).fold(
+        point,
+        |offset_point, ((&vertex, normal), weight)| {
+            let height = (point - vertex) * normal;
+            offset_point - normal * (height.min(0.0) * weight)
+        },
+    )
+}
+
+const OFFSET_ORIGIN: f64 = 1.0 / 32.0;
+const OFFSET_FLOAT_SCALE: f64 = 1.0 / 137_438_953_472.0;
+const OFFSET_INT_SCALE: f64 = 1_048_576.0;
+
+/// Nudges `point` off a surface along `normal` by a number of ULPs.
+///
+/// The step grows with the coordinate's magnitude (Wächter and Binder, Ray Tracing Gems
+/// ch. 6), so the new origin stays clear of the surface no matter how far from the world
+/// origin it lies. Coordinates close to zero, where ULPs get tiny, move by a fixed float
+/// step instead.
+#[allow(clippy::cast_possible_truncation)]
+pub fn offset_ray_origin(point: Vec3f, normal: Vec3f) -> Vec3f {
+    let offset = |coordinate: f64, normal: f64| {
+        if coordinate.abs() < OFFSET_ORIGIN {
+            return normal.mul_add(OFFSET_FLOAT_SCALE, coordinate);
+        }
+
+        let ulps = (OFFSET_INT_SCALE * normal) as i64;
+        let ulps = if coordinate < 0.0 { -ulps } else { ulps };
+        f64::from_bits(coordinate.to_bits().wrapping_add_signed(ulps))
+    };
+
+    Vec3f::new_with_data([
+        offset(point.x(), normal.x()),
+        offset(point.y(), normal.y()),
+        offset(point.z(), normal.z()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const TRIANGLE: [Vec3f; 3] = [
+        Vec3f::const_new_with_data([0.0, 0.0, 0.0]),
+        Vec3f::const_new_with_data([1.0, 0.0, 0.0]),
+        Vec3f::const_new_with_data([0.0, 0.0, 1.0]),
+    ];
+
+    #[test]
+    fn scalars_multiply_from_either_side_and_assign_in_place() {
+        let vector = Vec3f::new_with_data([1.0, -2.0, 3.0]);
+        assert_eq!(2.0 * vector, vector * 2.0);
+        assert_eq!(0.5 * Vec4f::new(4.0), Vec4f::new(2.0));
+
+        let mut accumulated = Vec3f::new(0.0);
+        accumulated += vector;
+        accumulated += vector;
+        accumulated -= vector * 0.5;
+        accumulated *= 4.0;
+        accumulated /= 2.0;
+        assert_eq!(accumulated, 3.0 * vector);
+    }
+
+    fn vector() -> impl Strategy<Value = Vec3f> {
+        prop::array::uniform3(-1e3..1e3_f64).prop_map(Vec3f::new_with_data)
+    }
+
+    proptest! {
+        #[test]
+        fn compound_assignment_matches_binary_operators(
+            a in vector(),
+            b in vector(),
+            s in -1e3..1e3_f64,
+        ) {
+            let mut sum = a;
+            sum += b;
+            prop_assert_eq!(sum, a + b);
+
+            let mut difference = a;
+            difference -= b;
+            prop_assert_eq!(difference, a - b);
+
+            let mut scaled = a;
+            scaled *= s;
+            prop_assert_eq!(scaled, a * s);
+
+            let mut divided = a;
+            divided /= s;
+            prop_assert_eq!(divided, a / s);
+        }
+
+        #[test]
+        fn vector_operators_obey_algebraic_laws(
+            a in vector(),
+            b in vector(),
+            c in vector(),
+            s in -1e3..1e3_f64,
+        ) {
+            prop_assert_eq!(a + b, b + a);
+            prop_assert_eq!(s * a, a * s);
+            prop_assert_eq!(a + -a, Vec3f::new(0.0));
+            prop_assert_eq!(a.dot(b).to_bits(), b.dot(a).to_bits());
+
+            let tolerance = 1e-9 * (1.0 + a.length() + b.length() + c.length());
+            prop_assert!(((a + b) + c - (a + (b + c))).length() <= tolerance);
+            prop_assert!((a - b + b - a).length() <= tolerance);
+            prop_assert!(
+                ((a + b) * s - (a * s + b * s)).length() <= tolerance * s.abs().max(1.0)
+            );
+            prop_assert!(
+                ((a + b).dot(c) - (a.dot(c) + b.dot(c))).abs()
+                    <= tolerance * c.length().max(1.0) * 10.0
+            );
+        }
+    }
+
+    #[test]
+    fn named_accessors_and_component_wise_helpers() {
+        let vector = Vec3f::new_with_data([1.0, -2.0, 3.0]);
+        assert_eq!(
+            Vec3f::new_with_data([vector.x(), vector.y(), vector.z()]),
+            vector
+        );
+        let uv = Vec2f::new_with_data([0.25, 0.75]);
+        assert_eq!(Vec2f::new_with_data([uv.x(), uv.y()]), uv);
+        let color = Vec4f::new_with_data([0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(
+            Vec4f::new_with_data([color.x(), color.y(), color.z(), color.w()]),
+            color
+        );
+
+        let other = Vec3f::new_with_data([4.0, 2.0, -1.0]);
+        assert!((vector.dot(other) + 3.0).abs() < 1e-12);
+        assert_eq!(
+            vector.hadamard(other),
+            Vec3f::new_with_data([4.0, -4.0, -3.0])
+        );
+        assert_eq!(vector.lerp(other, 0.0), vector);
+        assert_eq!(vector.lerp(other, 1.0), other);
+        assert_eq!(
+            vector.lerp(other, 0.5),
+            Vec3f::new_with_data([2.5, 0.0, 1.0])
+        );
+        assert_eq!(vector.min(other), Vec3f::new_with_data([1.0, -2.0, -1.0]));
+        assert_eq!(vector.max(other), Vec3f::new_with_data([4.0, 2.0, 3.0]));
+        assert_eq!(
+            vector.clamp(Vec3f::new(-1.0), Vec3f::new(2.0)),
+            Vec3f::new_with_data([1.0, -1.0, 2.0])
+        );
+        assert!((vector.distance(&other) - 41.0_f64.sqrt()).abs() < 1e-12);
+        assert!((other.unit().length() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reflection_mirrors_about_the_normal() {
+        let normal = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let incoming = Vec3f::new_with_data([1.0, -1.0, 0.0]).unit();
+
+        let reflected = reflect(incoming, normal);
+        assert!((reflected - Vec3f::new_with_data([1.0, 1.0, 0.0]).unit()).length() < 1e-12);
+        assert!((reflect(reflected, normal) - incoming).length() < 1e-12);
+    }
+
+    #[test]
+    fn refraction_follows_snells_law_between_arbitrary_media() {
+        let normal = Vec3f::new_with_data([0.0, 0.0, 1.0]);
+        let incoming = Vec3f::new_with_data([0.5, 0.0, -(0.75_f64.sqrt())]);
+
+        let into_glass = refract(incoming, normal, 1.0, 1.5).normalize(None);
+        assert!((into_glass.x() - 0.5 / 1.5).abs() < 1e-12);
+
+        let glass_into_water = refract(incoming, normal, 1.5, 1.33).normalize(None);
+        assert!((glass_into_water.x() - 0.5 * 1.5 / 1.33).abs() < 1e-12);
+
+        let same_medium = refract(incoming, normal, 1.33, 1.33);
+        assert!((same_medium - incoming).length() < 1e-12);
+
+        let grazing = Vec3f::new_with_data([0.9, 0.0, -(0.19_f64.sqrt())]);
+        assert_eq!(refract(grazing, normal, 1.5, 1.0), Vec3f::new(0.0));
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn vectors_convert_to_and_from_glam() {
+        let vector = Vec3f::new_with_data([1.0, -2.0, 3.0]);
+        let converted: glam::DVec3 = vector.into();
+        assert_eq!(converted, glam::DVec3::new(1.0, -2.0, 3.0));
+        assert_eq!(Vec3f::from(converted), vector);
+        assert_eq!(Vec3i::from(glam::IVec3::new(1, 2, 3)).data, [1, 2, 3]);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn vectors_convert_to_and_from_nalgebra() {
+        let vector = Vec4f::new_with_data([1.0, -2.0, 3.0, 4.0]);
+        let converted: nalgebra::Vector4<f64> = vector.into();
+        assert_eq!(converted, nalgebra::Vector4::new(1.0, -2.0, 3.0, 4.0));
+        assert_eq!(Vec4f::from(converted), vector);
+    }
+
+    #[test]
+    fn flat_normals_leave_point_unchanged() {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let point = Vec3f::new_with_data([0.25, 0.0, 0.25]);
+
+        let offset = terminator_safe_point(point, TRIANGLE, [up; 3], [0.5, 0.25, 0.25]);
+
+        assert_eq!(offset, point);
+    }
+
+    #[test]
+    fn diverging_normals_lift_point_above_flat_triangle() {
+        let normals = [
+            Vec3f::new_with_data([-1.0, 1.0, -1.0]).normalize(None),
+            Vec3f::new_with_data([1.0, 1.0, 0.0]).normalize(None),
+            Vec3f::new_with_data([0.0, 1.0, 1.0]).normalize(None),
+        ];
+        let point = Vec3f::new_with_data([0.25, 0.0, 0.25]);
+
+        let offset = terminator_safe_point(point, TRIANGLE, normals, [0.5, 0.25, 0.25]);
+
+        assert!(offset.y() > 0.0);
+        assert!((offset.x() - point.x()).abs() < 0.25);
+    }
+
+    #[test]
+    fn ray_origin_offset_scales_with_coordinate_magnitude() {
+        let normal = Vec3f::new_with_data([0.0, -0.6, 0.8]);
+        for scale in [0.0, 1e-3, 1.0, 1e4, 1e8] {
+            let point = Vec3f::new_with_data([scale, -scale, scale]);
+            let offset = offset_ray_origin(point, normal) - point;
+
+            assert!(offset * normal > 0.0, "{scale}");
+            assert!(offset.length() <= 1e-9 * scale.max(1.0), "{scale}");
+        }
+    }
+
+    #[test]
+    fn composed_transform_inverts_and_keeps_normals_perpendicular() {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let transform = Transform::from_scale(2.0)
+            .unwrap_or(Transform::IDENTITY)
+            .then(Transform::from_rotation(up, std::f64::consts::FRAC_PI_2))
+            .then(Transform::from_translation(Vec3f::new_with_data([
+                1.0, 2.0, 3.0,
+            ])));
+        let point = Vec3f::new_with_data([1.0, 0.0, 0.0]);
+
+        let moved = transform.apply_point(point);
+        assert!((moved - Vec3f::new_with_data([1.0, 2.0, 1.0])).length() < 1e-12);
+        assert!((transform.invert_point(moved) - point).length() < 1e-12);
+
+        let tangent = transform.apply_vector(Vec3f::new_with_data([0.0, 1.0, 1.0]));
+        let normal = transform.apply_normal(Vec3f::new_with_data([0.0, 1.0, -1.0]));
+        assert!((tangent * normal).abs() < 1e-12);
+
+        assert!(Transform::from_scale(0.0).is_none());
+    }
+}
@@ -0,0 +1,384 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    Material, Vec3f,
+    bvh::Aabb,
+    geometry::intersect_triangle,
+    output,
+    shapes::{Hit, Intersectable, Shape},
+    volume::fractal_noise,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeightSource {
+    Noise {
+        resolution: usize,
+        frequency: f64,
+        seed: u32,
+    },
+    Image(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeightGrid {
+    source: HeightSource,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn grid_coordinate(index: usize, count: usize) -> f64 {
+    index as f64 / (count - 1) as f64
+}
+
+impl HeightGrid {
+    pub fn noise(resolution: usize, frequency: f64, seed: u32) -> Self {
+        let resolution = resolution.max(2);
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for row in 0..resolution {
+            for column in 0..resolution {
+                let point = [
+                    grid_coordinate(column, resolution) * frequency,
+                    0.5,
+                    grid_coordinate(row, resolution) * frequency,
+                ];
+                heights.push(fractal_noise(point, seed));
+            }
+        }
+
+        let lowest = heights.iter().copied().fold(f64::INFINITY, f64::min);
+        let highest = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let range = (highest - lowest).max(f64::EPSILON);
+        for height in &mut heights {
+            *height = (*height - lowest) / range;
+        }
+
+        Self {
+            source: HeightSource::Noise {
+                resolution,
+                frequency,
+                seed,
+            },
+            columns: resolution,
+            rows: resolution,
+            heights,
+        }
+    }
+
+    pub fn load(path: &Path, source: PathBuf) -> Result<Self> {
+        let (columns, rows, heights) = output::read_grayscale_png(path)?;
+        if columns < 2 || rows < 2 {
+            bail!(
+                "Height map {} must be at least 2x2 pixels, found {columns}x{rows}",
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            source: HeightSource::Image(source),
+            columns,
+            rows,
+            heights,
+        })
+    }
+
+    pub const fn source(&self) -> &HeightSource {
+        &self.source
+    }
+
+    fn height(&self, column: usize, row: usize) -> f64 {
+        self.heights[row * self.columns + column]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heightfield {
+    min_point: Vec3f,
+    size: Vec3f,
+    grid: HeightGrid,
+    cell_ranges: Vec<(f64, f64)>,
+    material: Material,
+}
+
+impl Heightfield {
+    pub fn new(min_point: Vec3f, size: Vec3f, grid: HeightGrid, material: Material) -> Self {
+        let mut cell_ranges = Vec::with_capacity((grid.columns - 1) * (grid.rows - 1));
+        for row in 0..grid.rows - 1 {
+            for column in 0..grid.columns - 1 {
+                let corners = [
+                    grid.height(column, row),
+                    grid.height(column + 1, row),
+                    grid.height(column, row + 1),
+                    grid.height(column + 1, row + 1),
+                ];
+                cell_ranges.push((
+                    corners.iter().copied().fold(f64::INFINITY, f64::min),
+                    corners.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                ));
+            }
+        }
+
+        Self {
+            min_point,
+            size,
+            grid,
+            cell_ranges,
+            material,
+        }
+    }
+
+    pub const fn min_point(&self) -> Vec3f {
+        self.min_point
+    }
+
+    pub const fn size(&self) -> Vec3f {
+        self.size
+    }
+
+    pub const fn grid(&self) -> &HeightGrid {
+        &self.grid
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        #[allow(clippy::cast_precision_loss)]
+        let cells = ((self.grid.columns - 1) as f64, (self.grid.rows - 1) as f64);
+        (self.size.x() / cells.0, self.size.z() / cells.1)
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> Vec3f {
+        self.min_point
+            + Vec3f::new_with_data([
+                self.size.x() * grid_coordinate(column, self.grid.columns),
+                self.size.y() * self.grid.height(column, row),
+                self.size.z() * grid_coordinate(row, self.grid.rows),
+            ])
+    }
+
+    fn cell_triangles(&self, column: usize, row: usize) -> [[Vec3f; 3]; 2] {
+        let near_left = self.vertex(column, row);
+        let near_right = self.vertex(column + 1, row);
+        let far_left = self.vertex(column, row + 1);
+        let far_right = self.vertex(column + 1, row + 1);
+
+        [
+            [near_left, far_left, near_right],
+            [near_right, far_left, far_right],
+        ]
+    }
+
+    fn vertex_normal(&self, column: usize, row: usize) -> Vec3f {
+        let (cell_width, cell_depth) = self.cell_size();
+        let height = |column: usize, row: usize| self.grid.height(column, row) * self.size.y();
+        let (left, right) = (
+            column.saturating_sub(1),
+            (column + 1).min(self.grid.columns - 1),
+        );
+        let (near, far) = (row.saturating_sub(1), (row + 1).min(self.grid.rows - 1));
+
+        #[allow(clippy::cast_precision_loss)]
+        let slope_x =
+            (height(right, row) - height(left, row)) / ((right - left) as f64 * cell_width);
+        #[allow(clippy::cast_precision_loss)]
+        let slope_z =
+            (height(column, far) - height(column, near)) / ((far - near) as f64 * cell_depth);
+
+        Vec3f::new_with_data([-slope_x, 1.0, -slope_z]).normalize(None)
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn locate(&self, point: Vec3f) -> (usize, usize, f64, f64) {
+        let (cell_width, cell_depth) = self.cell_size();
+        let local_x = ((point.x() - self.min_point.x()) / cell_width)
+            .clamp(0.0, (self.grid.columns - 1) as f64);
+        let local_z =
+            ((point.z() - self.min_point.z()) / cell_depth).clamp(0.0, (self.grid.rows - 1) as f64);
+        let column = (local_x as usize).min(self.grid.columns - 2);
+        let row = (local_z as usize).min(self.grid.rows - 2);
+
+        (column, row, local_x - column as f64, local_z - row as f64)
+    }
+}
+
+fn first_boundary(start: f64, origin: f64, direction: f64, cell_size: f64, cell: usize) -> f64 {
+    #[allow(clippy::cast_precision_loss)]
+    let cell = cell as f64;
+    if direction > 0.0 {
+        (cell + 1.0).mul_add(cell_size, start - origin) / direction
+    } else if direction < 0.0 {
+        cell.mul_add(cell_size, start - origin) / direction
+    } else {
+        f64::INFINITY
+    }
+}
+
+impl Intersectable for Heightfield {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let bounds = self.bounds()?;
+        let (near, far) = bounds.interval(origin, direction)?;
+        let (cell_width, cell_depth) = self.cell_size();
+        let (mut column, mut row, _, _) = self.locate(origin + direction * near);
+
+        let mut next_x = first_boundary(
+            self.min_point.x(),
+            origin.x(),
+            direction.x(),
+            cell_width,
+            column,
+        );
+        let mut next_z = first_boundary(
+            self.min_point.z(),
+            origin.z(),
+            direction.z(),
+            cell_depth,
+            row,
+        );
+        let delta_x = (cell_width / direction.x()).abs();
+        let delta_z = (cell_depth / direction.z()).abs();
+
+        let mut entry = near;
+        loop {
+            if entry > far {
+                return None;
+            }
+            let exit = next_x.min(next_z).min(far);
+            let (lowest, highest) = self.cell_ranges[row * (self.grid.columns - 1) + column];
+            let entry_y = direction.y().mul_add(entry, origin.y());
+            let exit_y = direction.y().mul_add(exit, origin.y());
+            let base = self.min_point.y();
+            if entry_y.min(exit_y) <= self.size.y().mul_add(highest, base)
+                && entry_y.max(exit_y) >= self.size.y().mul_add(lowest, base)
+            {
+                let hit = self
+                    .cell_triangles(column, row)
+                    .into_iter()
+                    .filter_map(|triangle| {
+                        intersect_triangle(origin, direction, triangle)
+                            .map(|distance| (distance, triangle))
+                    })
+                    .min_by(|a, b| a.0.total_cmp(&b.0));
+                if let Some((distance, [first, second, third])) = hit {
+                    let face_normal = (second - first).cross(&(third - first)).normalize(None);
+                    let normal = self.get_normal(origin + direction * distance);
+                    return Some(Hit::new(distance, normal).with_geometric_normal(face_normal));
+                }
+            }
+
+            if next_x < next_z {
+                if direction.x() > 0.0 && column + 2 < self.grid.columns {
+                    column += 1;
+                } else if direction.x() < 0.0 && column > 0 {
+                    column -= 1;
+                } else {
+                    return None;
+                }
+                entry = next_x;
+                next_x += delta_x;
+            } else {
+                if direction.z() > 0.0 && row + 2 < self.grid.rows {
+                    row += 1;
+                } else if direction.z() < 0.0 && row > 0 {
+                    row -= 1;
+                } else {
+                    return None;
+                }
+                entry = next_z;
+                next_z += delta_z;
+            }
+        }
+    }
+}
+
+impl Shape for Heightfield {
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        let (column, row, fraction_x, fraction_z) = self.locate(hit_point);
+        let near = self.vertex_normal(column, row) * (1.0 - fraction_x)
+            + self.vertex_normal(column + 1, row) * fraction_x;
+        let far = self.vertex_normal(column, row + 1) * (1.0 - fraction_x)
+            + self.vertex_normal(column + 1, row + 1) * fraction_x;
+
+        (near * (1.0 - fraction_z) + far * fraction_z).normalize(None)
+    }
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        let (column, row, fraction_x, fraction_z) = self.locate(hit_point);
+        let triangles = self.cell_triangles(column, row);
+        let [first, second, third] = if fraction_x + fraction_z <= 1.0 {
+            triangles[0]
+        } else {
+            triangles[1]
+        };
+
+        (second - first).cross(&(third - first)).normalize(None)
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.min_point += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        self.size.x().min(self.size.y()).min(self.size.z())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[
+            self.min_point,
+            self.min_point + self.size,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RED_MATERIAL;
+
+    #[test]
+    fn traversal_finds_the_nearest_cell_and_matches_brute_force() {
+        let grid = HeightGrid::noise(17, 3.0, 5);
+        let terrain = Heightfield::new(
+            Vec3f::new_with_data([-4.0, -2.0, -12.0]),
+            Vec3f::new_with_data([8.0, 1.5, 8.0]),
+            grid,
+            RED_MATERIAL,
+        );
+
+        let origin = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let mut hits = 0;
+        for step in 0..32 {
+            let angle = f64::from(step).mul_add(0.05, -0.8);
+            let direction =
+                Vec3f::new_with_data([angle.sin(), -0.35, -angle.cos()]).normalize(None);
+            let brute_force = (0..16)
+                .flat_map(|row| (0..16).map(move |column| (column, row)))
+                .flat_map(|(column, row)| terrain.cell_triangles(column, row))
+                .filter_map(|triangle| intersect_triangle(origin, direction, triangle))
+                .min_by(f64::total_cmp);
+            let traversed = terrain.ray_intersect(origin, direction);
+            assert_eq!(traversed.is_some(), brute_force.is_some());
+            if let (Some(traversed), Some(brute_force)) = (traversed, brute_force) {
+                assert!((traversed - brute_force).abs() < 1e-9);
+                assert!(terrain.get_normal(origin + direction * traversed).y() > 0.0);
+                hits += 1;
+            }
+        }
+        assert!(hits > 0);
+    }
+}
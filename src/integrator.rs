@@ -0,0 +1,38 @@
+use anyhow::{Result, bail};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    #[default]
+    Whitted,
+    PathTracing,
+    AmbientOcclusion,
+    Toon,
+}
+
+impl Integrator {
+    pub const ALL: [Self; 4] = [
+        Self::Whitted,
+        Self::PathTracing,
+        Self::AmbientOcclusion,
+        Self::Toon,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "whitted" => Ok(Self::Whitted),
+            "path" => Ok(Self::PathTracing),
+            "ao" => Ok(Self::AmbientOcclusion),
+            "toon" => Ok(Self::Toon),
+            _ => bail!("Unknown integrator: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Whitted => "whitted",
+            Self::PathTracing => "path",
+            Self::AmbientOcclusion => "ao",
+            Self::Toon => "toon",
+        }
+    }
+}
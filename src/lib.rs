@@ -0,0 +1,145 @@
+//! A CPU ray tracer.
+//!
+//! Build a [`scene::Scene`] from shapes and lights (or load one with
+//! [`scene_file::SceneFile::load`]), pick a [`camera::Camera`] and hand both to a
+//! [`renderer::Renderer`] to fill an RGBA frame or write a PNG:
+//!
+//! ```no_run
+//! use raytracer_rust::{
+//!     FOV, Vec3f, camera::Camera, lights::init_default_lights, renderer::Renderer,
+//!     scene::Scene, settings::RenderSettings, shapes::init_default_shapes,
+//! };
+//!
+//! let scene = Scene::new(init_default_shapes(), init_default_lights());
+//! let camera = Camera::new(Vec3f::new(0.0), FOV);
+//! let renderer = Renderer::new(scene, RenderSettings::default(), 640, 480, camera);
+//!
+//! let mut frame = vec![0; 640 * 480 * 4];
+//! renderer.render_into(&mut frame)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The windowed viewer lives in the binary; nothing here depends on a display.
+
+#![warn(clippy::all)]
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+//#![warn(clippy::cargo)]
+#![allow(clippy::missing_panics_doc)]
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::must_use_candidate)]
+#![allow(clippy::return_self_not_must_use)]
+#![warn(clippy::unwrap_used)]
+#![warn(clippy::expect_used)]
+#![warn(clippy::todo)]
+#![warn(clippy::dbg_macro)]
+
+use std::f64;
+
+/// Arbitrary output variables: ambient occlusion, bent normals and lighting components.
+pub mod aov;
+/// Per-thread scratch buffers that keep ray traversal allocation-free.
+pub mod arena;
+/// Batch rendering of job manifests.
+pub mod batch;
+/// Off-screen render benchmarks reporting rays per second and stage timings.
+pub mod bench;
+/// Camera-facing textured sprites for cheap impostors and glows.
+pub mod billboard;
+/// Bounding boxes and the bounding volume hierarchy used to accelerate intersection.
+pub mod bvh;
+/// Perspective and equirectangular cameras.
+pub mod camera;
+/// Detection and removal of degenerate and duplicate scene geometry.
+pub mod cleanup;
+/// Side-by-side integrator comparison grids.
+pub mod compare;
+/// Panic hook and framebuffer dumps for crash reports.
+pub mod crash;
+/// Edge-aware denoising of low-sample renders guided by normal and albedo buffers.
+pub mod denoise;
+/// Tile rendering spread over worker processes that connect to a coordinator over TCP.
+pub mod distributed;
+/// HDR environment maps that light a scene from every direction.
+pub mod environment;
+/// The crate-wide error type returned by scene loading and rendering.
+pub mod error;
+/// Scene export to PBRT-v4 and Mitsuba 3.
+pub mod export;
+/// White-furnace energy conservation test scenes.
+pub mod furnace;
+/// Vectors, transforms and ray-primitive helpers.
+pub mod geometry;
+/// Heightfield terrain shapes.
+pub mod heightfield;
+/// Light transport algorithms selectable per render.
+pub mod integrator;
+/// Ambient, point, directional, area, sky and environment lights.
+pub mod lights;
+/// Surface materials, specular models and the named material registry.
+pub mod materials;
+/// Triangle meshes loaded from OBJ files.
+pub mod mesh;
+/// Per-tile reuse of radiance along nearly identical mirror reflection rays.
+pub mod mirror_cache;
+/// PNG encoding and decoding, and Radiance HDR decoding.
+pub mod output;
+/// Bitmap text and rectangle drawing on RGBA frames.
+pub mod overlay;
+/// Cube-map, equirectangular and stereo panorama renders.
+pub mod panorama;
+/// Import of a subset of the PBRT-v4 scene format.
+pub mod pbrt;
+/// Photon maps that gather the light mirrors and glass focus into caustics.
+pub mod photon;
+/// Tone mapping, LUTs and other post-processing.
+pub mod post;
+/// Render thread priority and core-count controls.
+pub mod priority;
+/// Extruded polygon shapes.
+pub mod prism;
+/// Spherical-harmonic light probes sampled from a scene.
+pub mod probes;
+/// Flat parallelogram shapes with UV coordinates.
+pub mod quad;
+/// The renderer: turns a scene, camera and settings into pixels.
+pub mod renderer;
+/// Direction and origin sorting of secondary ray batches for traversal coherence.
+pub mod reorder;
+/// Direction and point sampling shared by photon emission and rough bounces.
+pub mod sampling;
+/// Scenes: shapes, lights and volumes plus the ray tracing core.
+pub mod scene;
+/// The text scene file format.
+pub mod scene_file;
+/// Built-in scenes such as the Cornell box.
+pub mod scenes;
+/// Sphere-traced signed distance field shapes.
+pub mod sdf;
+/// Render settings parsed from the command line and environment.
+pub mod settings;
+/// Shape traits and the built-in primitives.
+pub mod shapes;
+/// The Preetham daylight model behind sun-and-sky lights.
+pub mod sky;
+/// Per-frame ray statistics and the performance HUD.
+pub mod stats;
+/// Labeled grids of renders sweeping one or two material parameters.
+pub mod sweep;
+/// Flat and extruded text label geometry built from the overlay font.
+pub mod text;
+/// Participating media with density grids.
+pub mod volume;
+
+pub use geometry::{Vec3f, Vec4f};
+pub use materials::{
+    BLUE_MATERIAL, GLASS_MATERIAL, GREEN_MATERIAL, MIRROR_MATERIAL, Material, RED_MATERIAL,
+};
+
+pub const PI: f64 = f64::consts::PI;
+pub const MAX_DEPTH: u32 = 4;
+pub const EPSILON: f64 = 1e-3;
+pub const MAX_DISTANCE: f64 = 1000.0;
+pub const FOV: f64 = PI / 3.0;
+
+pub const BACKGROUND_COLOR: Vec3f = Vec3f::const_new_with_data([0.2, 0.7, 0.8]);
@@ -1,4 +1,9 @@
-use crate::Vec3f;
+use std::{fmt, sync::Arc};
+
+use crate::{Vec3f, environment::EnvironmentMap, sky::SunSky};
+
+const DEFAULT_AREA_LIGHT_SAMPLES: u32 = 16;
+const DEFAULT_ENVIRONMENT_SAMPLES: u32 = 16;
 
 pub fn init_default_lights() -> Vec<LightType> {
     vec![
@@ -15,6 +20,35 @@ pub fn init_default_lights() -> Vec<LightType> {
     ]
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightSample {
+    direction: Vec3f,
+    distance: f64,
+    weight: f64,
+}
+
+impl LightSample {
+    pub const fn new(direction: Vec3f, distance: f64, weight: f64) -> Self {
+        Self {
+            direction,
+            distance,
+            weight,
+        }
+    }
+
+    pub const fn direction(&self) -> Vec3f {
+        self.direction
+    }
+
+    pub const fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    pub const fn weight(&self) -> f64 {
+        self.weight
+    }
+}
+
 pub trait Light {
     fn intensity(&self) -> f64;
     fn get_distance(&self, _point: Vec3f) -> f64;
@@ -22,9 +56,22 @@ pub trait Light {
     fn is_ambient(&self) -> bool {
         false
     }
+
+    fn sample_count(&self) -> u32 {
+        1
+    }
+
+    fn sample(&self, point: Vec3f, _index: u32) -> LightSample {
+        LightSample {
+            direction: self.get_direction(point),
+            distance: self.get_distance(point),
+            weight: 1.0,
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AmbientLight {
     intensity: f64,
 }
@@ -53,7 +100,8 @@ impl Light for AmbientLight {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointLight {
     intensity: f64,
     position: Vec3f,
@@ -66,6 +114,10 @@ impl PointLight {
             position,
         }
     }
+
+    pub const fn position(&self) -> Vec3f {
+        self.position
+    }
 }
 
 impl Light for PointLight {
@@ -82,7 +134,8 @@ impl Light for PointLight {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirectionalLight {
     intensity: f64,
     direction: Vec3f,
@@ -92,9 +145,13 @@ impl DirectionalLight {
     pub fn new(intensity: f64, direction: Vec3f) -> Self {
         Self {
             intensity,
-            direction: direction.normalize(None),
+            direction: direction.unit(),
         }
     }
+
+    pub const fn direction(&self) -> Vec3f {
+        self.direction
+    }
 }
 
 impl Light for DirectionalLight {
@@ -111,11 +168,454 @@ impl Light for DirectionalLight {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AreaLight {
+    intensity: f64,
+    corner: Vec3f,
+    edge_u: Vec3f,
+    edge_v: Vec3f,
+    two_sided: bool,
+    samples: u32,
+}
+
+impl AreaLight {
+    pub const fn new(intensity: f64, corner: Vec3f, edge_u: Vec3f, edge_v: Vec3f) -> Self {
+        Self {
+            intensity,
+            corner,
+            edge_u,
+            edge_v,
+            two_sided: false,
+            samples: DEFAULT_AREA_LIGHT_SAMPLES,
+        }
+    }
+
+    pub const fn with_two_sided(self, two_sided: bool) -> Self {
+        Self { two_sided, ..self }
+    }
+
+    pub const fn with_samples(self, samples: u32) -> Self {
+        Self {
+            samples: if samples == 0 { 1 } else { samples },
+            ..self
+        }
+    }
+
+    pub const fn corner(&self) -> Vec3f {
+        self.corner
+    }
+
+    pub const fn edge_u(&self) -> Vec3f {
+        self.edge_u
+    }
+
+    pub const fn edge_v(&self) -> Vec3f {
+        self.edge_v
+    }
+
+    pub const fn two_sided(&self) -> bool {
+        self.two_sided
+    }
+
+    pub const fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn normal(&self) -> Vec3f {
+        self.edge_u.cross(&self.edge_v).normalize(None)
+    }
+
+    fn center(&self) -> Vec3f {
+        self.corner + self.edge_u * 0.5 + self.edge_v * 0.5
+    }
+
+    pub fn emission_towards(&self, direction: Vec3f) -> f64 {
+        let cosine = self.normal() * direction;
+        if self.two_sided {
+            cosine.abs()
+        } else {
+            cosine.max(0.0)
+        }
+    }
+
+    pub fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
+        let n = self.edge_u.cross(&self.edge_v);
+        let denominator = n * direction;
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let distance = (n * (self.corner - origin)) / denominator;
+        if distance <= 0.0 {
+            return None;
+        }
+
+        let local = origin + direction * distance - self.corner;
+        let w = n / (n * n);
+        let alpha = w * local.cross(&self.edge_v);
+        let beta = w * self.edge_u.cross(&local);
+
+        ((0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta)).then_some(distance)
+    }
+
+    const fn grid_size(&self) -> u32 {
+        let root = self.samples.isqrt();
+        if root * root < self.samples {
+            root + 1
+        } else {
+            root
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn get_direction(&self, point: Vec3f) -> Vec3f {
+        (self.center() - point).normalize(None)
+    }
+
+    fn get_distance(&self, point: Vec3f) -> f64 {
+        (self.center() - point).length()
+    }
+
+    fn sample_count(&self) -> u32 {
+        let grid = self.grid_size();
+        grid * grid
+    }
+
+    fn sample(&self, point: Vec3f, index: u32) -> LightSample {
+        let grid = self.grid_size();
+        let u = (f64::from(index % grid) + 0.5) / f64::from(grid);
+        let v = (f64::from(index / grid) + 0.5) / f64::from(grid);
+
+        let target = self.corner + self.edge_u * u + self.edge_v * v;
+        let offset = target - point;
+        let direction = offset.normalize(None);
+
+        LightSample {
+            direction,
+            distance: offset.length(),
+            weight: self.emission_towards(-direction) / f64::from(grid * grid),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkyLight {
+    intensity: f64,
+    zenith: Vec3f,
+    horizon: Vec3f,
+    ground: Vec3f,
+}
+
+impl SkyLight {
+    pub const fn new(intensity: f64, zenith: Vec3f, horizon: Vec3f, ground: Vec3f) -> Self {
+        Self {
+            intensity,
+            zenith,
+            horizon,
+            ground,
+        }
+    }
+
+    pub const fn zenith(&self) -> Vec3f {
+        self.zenith
+    }
+
+    pub const fn horizon(&self) -> Vec3f {
+        self.horizon
+    }
+
+    pub const fn ground(&self) -> Vec3f {
+        self.ground
+    }
+
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        let height = direction.unit().y();
+        let (pole, blend) = if height >= 0.0 {
+            (self.zenith, height)
+        } else {
+            (self.ground, -height)
+        };
+
+        (self.horizon + (pole - self.horizon) * blend.sqrt()) * self.intensity
+    }
+
+    pub fn irradiance(&self, normal: Vec3f) -> Vec3f {
+        let upward = 0.5 * (1.0 + normal.unit().y());
+        let sky = (self.zenith + self.horizon) * 0.5;
+        let ground = (self.ground + self.horizon) * 0.5;
+
+        (ground + (sky - ground) * upward) * self.intensity
+    }
+
+    pub fn average(&self) -> Vec3f {
+        (self.zenith + self.horizon * 2.0 + self.ground) * (0.25 * self.intensity)
+    }
+}
+
+impl Light for SkyLight {
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn get_direction(&self, _point: Vec3f) -> Vec3f {
+        Vec3f::new_with_data([0.0, 0.0, 0.0])
+    }
+
+    fn get_distance(&self, _point: Vec3f) -> f64 {
+        0.0
+    }
+
+    fn is_ambient(&self) -> bool {
+        true
+    }
+
+    fn sample_count(&self) -> u32 {
+        0
+    }
+}
+
+/// A Preetham daylight sky that is both the environment behind the scene and the sun
+/// shining into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SunSkyLight {
+    intensity: f64,
+    sky: SunSky,
+}
+
+impl SunSkyLight {
+    pub const fn new(intensity: f64, sky: SunSky) -> Self {
+        Self { intensity, sky }
+    }
+
+    pub const fn sky(&self) -> &SunSky {
+        &self.sky
+    }
+
+    /// The brightness the sky and the sun are scaled by, before the atmosphere dims the sun.
+    pub const fn scale(&self) -> f64 {
+        self.intensity
+    }
+
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        self.sky.radiance(direction) * self.intensity
+    }
+
+    pub fn irradiance(&self, normal: Vec3f) -> Vec3f {
+        self.sky.irradiance(normal) * self.intensity
+    }
+
+    pub fn average(&self) -> Vec3f {
+        self.sky.average() * self.intensity
+    }
+}
+
+impl Light for SunSkyLight {
+    fn intensity(&self) -> f64 {
+        self.intensity * self.sky.sun_transmittance()
+    }
+
+    fn get_direction(&self, _point: Vec3f) -> Vec3f {
+        self.sky.sun_direction()
+    }
+
+    fn get_distance(&self, _point: Vec3f) -> f64 {
+        f64::INFINITY
+    }
+}
+
+/// An HDR environment map around the scene. It fills the background and reflections, and
+/// lights surfaces through shadow rays importance-sampled towards its brightest texels.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvironmentLight {
+    intensity: f64,
+    map: Arc<EnvironmentMap>,
+    samples: u32,
+}
+
+impl EnvironmentLight {
+    pub const fn new(intensity: f64, map: Arc<EnvironmentMap>) -> Self {
+        Self {
+            intensity,
+            map,
+            samples: DEFAULT_ENVIRONMENT_SAMPLES,
+        }
+    }
+
+    /// Sets how many shadow rays a primary hit sends towards the map.
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub const fn map(&self) -> &Arc<EnvironmentMap> {
+        &self.map
+    }
+
+    pub const fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        self.map.radiance(direction) * self.intensity
+    }
+
+    pub fn average(&self) -> Vec3f {
+        self.map.average() * self.intensity
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn get_direction(&self, _point: Vec3f) -> Vec3f {
+        Vec3f::new_with_data([0.0, 0.0, 0.0])
+    }
+
+    fn get_distance(&self, _point: Vec3f) -> f64 {
+        0.0
+    }
+
+    fn is_ambient(&self) -> bool {
+        true
+    }
+
+    fn sample_count(&self) -> u32 {
+        0
+    }
+}
+
+#[derive(Clone)]
+pub struct CustomLight {
+    light: Arc<dyn Light + Send + Sync>,
+    offset: Vec3f,
+    scale: f64,
+}
+
+impl CustomLight {
+    pub fn new(light: Arc<dyn Light + Send + Sync>) -> Self {
+        Self {
+            light,
+            offset: Vec3f::new(0.0),
+            scale: 1.0,
+        }
+    }
+
+    pub const fn light(&self) -> &Arc<dyn Light + Send + Sync> {
+        &self.light
+    }
+
+    pub const fn offset(&self) -> Vec3f {
+        self.offset
+    }
+}
+
+impl fmt::Debug for CustomLight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomLight")
+            .field("offset", &self.offset)
+            .field("intensity", &self.intensity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for CustomLight {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.light, &other.light)
+            && self.offset == other.offset
+            && self.scale == other.scale
+    }
+}
+
+impl Light for CustomLight {
+    fn intensity(&self) -> f64 {
+        self.light.intensity() * self.scale
+    }
+
+    fn get_direction(&self, point: Vec3f) -> Vec3f {
+        self.light.get_direction(point - self.offset)
+    }
+
+    fn get_distance(&self, point: Vec3f) -> f64 {
+        self.light.get_distance(point - self.offset)
+    }
+
+    fn is_ambient(&self) -> bool {
+        self.light.is_ambient()
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.light.sample_count()
+    }
+
+    fn sample(&self, point: Vec3f, index: u32) -> LightSample {
+        self.light.sample(point - self.offset, index)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LightType {
     Point(PointLight),
     Directional(DirectionalLight),
     Ambient(AmbientLight),
+    Area(AreaLight),
+    Sky(SkyLight),
+    SunSky(Box<SunSkyLight>),
+    Environment(EnvironmentLight),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(CustomLight),
+}
+
+impl LightType {
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Point(_) => "point_light",
+            Self::Directional(_) => "directional_light",
+            Self::Ambient(_) => "ambient_light",
+            Self::Area(_) => "area_light",
+            Self::Sky(_) => "sky_light",
+            Self::SunSky(_) => "sun_sky",
+            Self::Environment(_) => "environment_light",
+            Self::Custom(_) => "custom_light",
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vec3f) {
+        match self {
+            Self::Point(light) => light.position += offset,
+            Self::Area(light) => light.corner += offset,
+            Self::Custom(light) => light.offset += offset,
+            Self::Ambient(_)
+            | Self::Directional(_)
+            | Self::Sky(_)
+            | Self::SunSky(_)
+            | Self::Environment(_) => {}
+        }
+    }
+
+    pub fn scale_intensity(&mut self, factor: f64) {
+        match self {
+            Self::Ambient(light) => light.intensity *= factor,
+            Self::Point(light) => light.intensity *= factor,
+            Self::Directional(light) => light.intensity *= factor,
+            Self::Area(light) => light.intensity *= factor,
+            Self::Sky(light) => light.intensity *= factor,
+            Self::SunSky(light) => light.intensity *= factor,
+            Self::Environment(light) => light.intensity *= factor,
+            Self::Custom(light) => light.scale *= factor,
+        }
+    }
 }
 
 impl Light for LightType {
@@ -124,6 +624,11 @@ impl Light for LightType {
             Self::Ambient(light) => light.intensity(),
             Self::Directional(light) => light.intensity(),
             Self::Point(light) => light.intensity(),
+            Self::Area(light) => light.intensity(),
+            Self::Sky(light) => light.intensity(),
+            Self::SunSky(light) => light.intensity(),
+            Self::Environment(light) => light.intensity(),
+            Self::Custom(light) => light.intensity(),
         }
     }
     fn get_direction(&self, point: Vec3f) -> Vec3f {
@@ -131,6 +636,11 @@ impl Light for LightType {
             Self::Ambient(light) => light.get_direction(point),
             Self::Point(light) => light.get_direction(point),
             Self::Directional(light) => light.get_direction(point),
+            Self::Area(light) => light.get_direction(point),
+            Self::Sky(light) => light.get_direction(point),
+            Self::SunSky(light) => light.get_direction(point),
+            Self::Environment(light) => light.get_direction(point),
+            Self::Custom(light) => light.get_direction(point),
         }
     }
 
@@ -139,10 +649,42 @@ impl Light for LightType {
             Self::Ambient(light) => light.get_distance(point),
             Self::Point(light) => light.get_distance(point),
             Self::Directional(light) => light.get_distance(point),
+            Self::Area(light) => light.get_distance(point),
+            Self::Sky(light) => light.get_distance(point),
+            Self::SunSky(light) => light.get_distance(point),
+            Self::Environment(light) => light.get_distance(point),
+            Self::Custom(light) => light.get_distance(point),
         }
     }
 
     fn is_ambient(&self) -> bool {
-        matches!(self, Self::Ambient(_))
+        match self {
+            Self::Ambient(_) | Self::Sky(_) | Self::Environment(_) => true,
+            Self::Custom(light) => light.is_ambient(),
+            Self::Point(_) | Self::Directional(_) | Self::Area(_) | Self::SunSky(_) => false,
+        }
+    }
+
+    fn sample_count(&self) -> u32 {
+        match self {
+            Self::Area(light) => light.sample_count(),
+            Self::Sky(light) => light.sample_count(),
+            Self::Environment(light) => light.sample_count(),
+            Self::Custom(light) => light.sample_count(),
+            Self::Ambient(_) | Self::Point(_) | Self::Directional(_) | Self::SunSky(_) => 1,
+        }
+    }
+
+    fn sample(&self, point: Vec3f, index: u32) -> LightSample {
+        match self {
+            Self::Ambient(light) => light.sample(point, index),
+            Self::Point(light) => light.sample(point, index),
+            Self::Directional(light) => light.sample(point, index),
+            Self::Area(light) => light.sample(point, index),
+            Self::Sky(light) => light.sample(point, index),
+            Self::SunSky(light) => light.sample(point, index),
+            Self::Environment(light) => light.sample(point, index),
+            Self::Custom(light) => light.sample(point, index),
+        }
     }
 }
@@ -9,59 +9,230 @@
 #![warn(clippy::todo)]
 #![warn(clippy::dbg_macro)]
 
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{error::Error, f64};
 
-use lights::init_default_lights;
+use editor::Editor;
+use explorer::Explorer;
+use log::{debug, error, info, warn};
+use measure::Measure;
 use pixels::{Pixels, SurfaceTexture};
-use scene::Scene;
-use shapes::init_default_shapes;
+use raytracer_rust::{
+    PI, arena, batch, bench, camera::Camera, cleanup, compare, crash, distributed,
+    error::RaytracerError, export, furnace, mirror_cache, output, panorama, priority, probes,
+    renderer, reorder, scene::Scene, scene_file, scenes::BuiltinScene, settings, sweep,
+};
+use renderer::{RenderProgress, Renderer};
+use scene_file::SceneFile;
+use settings::RenderSettings;
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
-    event::{KeyEvent, StartCause, WindowEvent},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, StartCause, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowAttributes, WindowId},
 };
 
-mod geometry;
-mod lights;
-mod materials;
-mod scene;
-mod shapes;
-
-use geometry::{Vec3f, Vec4f};
-use materials::{
-    BLUE_MATERIAL, GLASS_MATERIAL, GREEN_MATERIAL, MIRROR_MATERIAL, Material, RED_MATERIAL,
-};
+mod editor;
+mod explorer;
+mod measure;
 
-const PI: f64 = f64::consts::PI;
-const MAX_DEPTH: u32 = 4;
-const EPSILON: f64 = 1e-3;
 const WIDTH: u32 = 1920;
 const HEIGHT: u32 = 1080;
-const FOV: f64 = PI / 3.0;
-
-const BACKGROUND_COLOR: Vec3f = Vec3f::const_new_with_data([0.2, 0.7, 0.8]);
+const ZOOM_STEP: f64 = PI / 90.0;
+const PIXELS_PER_SCROLL_LINE: f64 = 40.0;
 
 struct Raytracer<'win> {
     window: Option<Arc<Window>>,
     pixels: Option<Pixels<'win>>,
+    renderer: Renderer,
+    editor: Editor,
+    explorer: Explorer,
+    measure: Measure,
+    cursor_position: Option<(f64, f64)>,
+    last_frame: Option<Instant>,
+    error: Option<RaytracerError>,
 }
 
 impl Raytracer<'_> {
-    const fn new() -> Self {
+    fn new(renderer: Renderer) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
         Self {
             window: None,
             pixels: None,
+            renderer,
+            editor: Editor::new(),
+            explorer: Explorer::new(seed),
+            measure: Measure::new(),
+            cursor_position: None,
+            last_frame: None,
+            error: None,
+        }
+    }
+
+    fn request_redraw(&self) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    fn save_screenshot(&self) {
+        let Some(pixels) = &self.pixels else {
+            return;
+        };
+
+        let output_dir = self.renderer.settings().output_dir();
+        if let Err(err) = fs::create_dir_all(output_dir) {
+            error!(
+                "Failed to create output directory {}: {err}",
+                output_dir.display()
+            );
+            return;
+        }
+
+        let path = output::timestamped_path(output_dir, "screenshot", "png");
+        match output::write_png(
+            &path,
+            self.renderer.width(),
+            self.renderer.height(),
+            pixels.frame(),
+        ) {
+            Ok(()) => info!("Saved screenshot to {}", path.display()),
+            Err(err) => error!("Failed to save screenshot: {err:#}"),
+        }
+    }
+
+    fn save_scene(&self) {
+        let output_dir = self.renderer.settings().output_dir();
+        if let Err(err) = fs::create_dir_all(output_dir) {
+            error!(
+                "Failed to create output directory {}: {err}",
+                output_dir.display()
+            );
+            return;
+        }
+
+        let path = output::timestamped_path(output_dir, "scene", "scene");
+        match self.renderer.scene().save(self.renderer.camera(), &path) {
+            Ok(()) => info!("Saved scene to {}", path.display()),
+            Err(err) => error!("Failed to save scene: {err:#}"),
+        }
+    }
+
+    fn show_builtin(&mut self, builtin: BuiltinScene) {
+        match builtin.build() {
+            Ok((scene, camera)) => {
+                *self.renderer.scene_mut() = scene;
+                self.renderer.set_camera(camera);
+                self.explorer.forget();
+                self.renderer.mark_interaction();
+                self.request_redraw();
+                info!("Switched to scene builtin:{}", builtin.name());
+            }
+            Err(err) => error!("Failed to build scene {}: {err:#}", builtin.name()),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyCode) {
+        let preset = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+        ]
+        .iter()
+        .position(|&digit| digit == key);
+        if let Some(index) = preset {
+            return self.show_builtin(BuiltinScene::ALL[index]);
+        }
+
+        match key {
+            KeyCode::KeyW => self.save_scene(),
+            KeyCode::KeyM => {
+                self.explorer.mutate(&mut self.renderer);
+                self.request_redraw();
+            }
+            KeyCode::KeyT => {
+                self.measure.toggle();
+                self.request_redraw();
+            }
+            KeyCode::KeyF => {
+                let Some(pixels) = &self.pixels else {
+                    return;
+                };
+                match Explorer::save_favorite(&self.renderer, pixels.frame()) {
+                    Ok(path) => info!("Saved favorite to {}", path.display()),
+                    Err(err) => error!("Failed to save favorite: {err:#}"),
+                }
+            }
+            _ => {
+                if self.editor.handle_key(&mut self.renderer, key) {
+                    self.renderer.mark_interaction();
+                    self.request_redraw();
+                }
+            }
+        }
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: RaytracerError) {
+        self.error = Some(err);
+        event_loop.exit();
+    }
+
+    fn redraw(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(pixels) = &mut self.pixels else {
+            return;
+        };
+
+        let now = Instant::now();
+        let dt = self
+            .last_frame
+            .map_or(0.0, |last_frame| (now - last_frame).as_secs_f64());
+        self.last_frame = Some(now);
+        self.renderer.update(dt);
+
+        match self.renderer.render_interactive_into(pixels.frame_mut()) {
+            Ok(()) => (),
+            Err(err) if err.is_cancelled() => {
+                warn!("Dropped frame: {err}");
+                return;
+            }
+            Err(err) => return self.fail(event_loop, err),
+        }
+        self.measure.draw(
+            pixels.frame_mut(),
+            (self.renderer.width(), self.renderer.height()),
+        );
+
+        if let Err(err) = pixels.render() {
+            self.fail(event_loop, RaytracerError::surface(err));
         }
     }
+
+    fn render_fallback(&mut self, event_loop: &ActiveEventLoop, cause: &RaytracerError) {
+        let reason = std::error::Error::source(cause)
+            .map_or_else(String::new, |source| format!(": {source}"));
+        warn!("{cause}{reason}, rendering to an image file instead");
+        if let Err(err) = render_to_output_dir(&self.renderer) {
+            self.error = Some(err);
+        }
+
+        event_loop.exit();
+    }
 }
 
 impl ApplicationHandler for Raytracer<'_> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        println!("App resumed!");
+        debug!("App resumed");
 
         let window = match event_loop.create_window(
             WindowAttributes::default()
@@ -70,7 +241,7 @@ impl ApplicationHandler for Raytracer<'_> {
         ) {
             Ok(w) => w,
             Err(e) => {
-                eprintln!("Failed to create window: {e}");
+                self.render_fallback(event_loop, &RaytracerError::window(e));
                 return;
             }
         };
@@ -82,9 +253,10 @@ impl ApplicationHandler for Raytracer<'_> {
         match Pixels::new(WIDTH, HEIGHT, surface_texture) {
             Ok(p) => self.pixels = Some(p),
             Err(e) => {
-                eprintln!("Failed to create Pixels: {e}");
+                self.window = None;
+                self.render_fallback(event_loop, &RaytracerError::surface(e));
             }
-        };
+        }
     }
 
     fn window_event(
@@ -95,7 +267,7 @@ impl ApplicationHandler for Raytracer<'_> {
     ) {
         match event {
             WindowEvent::CloseRequested => {
-                println!("Close requested");
+                debug!("Close requested");
                 event_loop.exit();
             }
 
@@ -108,31 +280,91 @@ impl ApplicationHandler for Raytracer<'_> {
                     },
                 ..
             } => {
-                println!("Escape pressed");
+                debug!("Escape pressed");
                 event_loop.exit();
             }
 
-            WindowEvent::RedrawRequested => {
-                if let Some(pixels) = &mut self.pixels {
-                    let frame = pixels.frame_mut();
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyS),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => self.save_screenshot(),
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.handle_key(key),
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some((position.x, position.y));
+            }
+
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let Some((x, y)) = self.cursor_position else {
+                    return;
+                };
+                if !self.measure.active() {
+                    self.editor.select_at(&self.renderer, x, y);
+                } else if self.measure.pick_at(&self.renderer, x, y) {
+                    self.request_redraw();
+                }
+            }
 
-                    let scene = Scene::new(init_default_shapes(), init_default_lights());
-                    scene.render_scene(frame, HEIGHT, WIDTH, FOV);
+            WindowEvent::Resized(size) => {
+                if size.width == 0 || size.height == 0 {
+                    return;
+                }
 
-                    match pixels.render() {
-                        Ok(()) => (),
-                        Err(err) => eprint!("Error with render pixels: {err}"),
-                    };
+                if let Some(pixels) = &mut self.pixels {
+                    let resized = pixels
+                        .resize_surface(size.width, size.height)
+                        .and_then(|()| pixels.resize_buffer(size.width, size.height));
+                    if let Err(err) = resized {
+                        return self.fail(event_loop, RaytracerError::surface(err));
+                    }
                 }
+
+                self.renderer.resize(size.width, size.height);
+                self.renderer.mark_interaction();
+
+                self.request_redraw();
             }
 
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => f64::from(y),
+                    MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_SCROLL_LINE,
+                };
+
+                self.renderer.camera_mut().zoom(-lines * ZOOM_STEP);
+                self.renderer.mark_interaction();
+
+                self.request_redraw();
+            }
+
+            WindowEvent::RedrawRequested => self.redraw(event_loop),
+
             _ => {}
         }
     }
 
     fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
         if cause == StartCause::Init {
-            println!("Starting app!");
+            info!("Starting app");
         }
 
         if let Some(window) = &self.window {
@@ -141,9 +373,316 @@ impl ApplicationHandler for Raytracer<'_> {
     }
 }
 
+fn render_to_output_dir(renderer: &Renderer) -> Result<(), RaytracerError> {
+    let output_dir = renderer.settings().output_dir();
+    fs::create_dir_all(output_dir).map_err(|source| RaytracerError::Io {
+        path: output_dir.to_owned(),
+        source,
+    })?;
+
+    let path = output::timestamped_path(output_dir, "render", "png");
+    renderer.render_to_png(&path, renderer.settings().stripe_rows())?;
+    info!("Saved render to {}", path.display());
+
+    Ok(())
+}
+
+fn write_json(
+    value: &impl serde::Serialize,
+    output_path: Option<&Path>,
+    description: &str,
+) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(value)?;
+    match output_path {
+        Some(output_path) => {
+            fs::write(output_path, json)?;
+            info!("Saved {description} to {}", output_path.display());
+        }
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+fn run_furnace_test(
+    width: u32,
+    height: u32,
+    render: bool,
+) -> Result<Option<SceneFile>, Box<dyn Error>> {
+    let results = furnace::run_furnace_test(width, height);
+    for result in &results {
+        println!("{result}");
+    }
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    if failed > 0 {
+        return Err(format!("{failed} materials failed the furnace test").into());
+    }
+    if !render {
+        return Ok(None);
+    }
+
+    let (scene, camera) = furnace::furnace_scene();
+    Ok(Some(SceneFile::new(scene, camera)))
+}
+
+fn load_scene_file(settings: &RenderSettings) -> anyhow::Result<SceneFile> {
+    if let Some(builtin) = settings.builtin_scene() {
+        let (scene, camera) = builtin.build()?;
+        info!("Built scene builtin:{}", builtin.name());
+        return Ok(SceneFile::new(scene, camera));
+    }
+
+    let Some(scene_path) = settings.scene_path() else {
+        return Ok(SceneFile::default());
+    };
+
+    let mut scene_file = SceneFile::load(scene_path)?;
+    info!(
+        "Loaded scene {} with {} shapes and {} lights",
+        scene_path.display(),
+        scene_file.scene().shapes().len(),
+        scene_file.scene().lights().len()
+    );
+
+    if settings.cleanup() {
+        let report = cleanup::clean(scene_file.scene_mut());
+        if !report.is_clean() {
+            info!("Cleaned scene {}: removed {report}", scene_path.display());
+        }
+    } else {
+        let report = cleanup::inspect(scene_file.scene());
+        if !report.is_clean() {
+            warn!(
+                "Scene {} has {report}; pass --cleanup to remove them",
+                scene_path.display()
+            );
+        }
+    }
+    Ok(scene_file)
+}
+
+fn render_grid(
+    scene: &Scene,
+    settings: &RenderSettings,
+    camera: Camera,
+    size: (u32, u32),
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    if settings.sweeps().is_empty() {
+        for cell in compare::render_comparison(scene, settings, camera, size, output_path)? {
+            println!("{}", cell.label());
+        }
+        info!("Saved integrator comparison to {}", output_path.display());
+    } else {
+        let material = settings.sweep_material();
+        for cell in sweep::render_sweep(settings, material, settings.sweeps(), size, output_path)? {
+            println!("{}", cell.label());
+        }
+        info!("Saved material sweep to {}", output_path.display());
+    }
+
+    Ok(())
+}
+
+fn render_views(
+    scene: Scene,
+    settings: RenderSettings,
+    camera: Camera,
+    size: (u32, u32),
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let on_progress = |progress: RenderProgress| {
+        eprint!("\rRendering panorama {:5.1}%", progress.fraction() * 100.0);
+    };
+    if let Some(layout) = settings.panorama() {
+        panorama::render_panorama(scene, settings, camera, layout, output_path, on_progress)?;
+        eprintln!();
+        info!("Saved panorama to {}", output_path.display());
+    } else {
+        panorama::render_stereo(scene, settings, camera, size, output_path, on_progress)?;
+        eprintln!();
+        info!("Saved stereo pair to {}", output_path.display());
+    }
+
+    Ok(())
+}
+
+fn coordinate_render(
+    address: &str,
+    size: (u32, u32),
+    stripe_rows: u32,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!(
+        "Waiting for workers on {}, rendering {}x{} in {stripe_rows} row stripes",
+        listener.local_addr()?,
+        size.0,
+        size.1
+    );
+    let frame = distributed::coordinate(&listener, size, stripe_rows, distributed::WORKER_TIMEOUT)?;
+    output::write_png(output_path, size.0, size.1, &frame)?;
+    info!("Saved distributed render to {}", output_path.display());
+
+    Ok(())
+}
+
+fn work_for_coordinator(address: &str, renderer: &mut Renderer) -> anyhow::Result<()> {
+    let stream = TcpStream::connect(address)?;
+    info!("Connected to coordinator {address}");
+    let stripes = distributed::run_worker(stream, renderer)?;
+    info!("Rendered {stripes} stripes for {address}");
+
+    Ok(())
+}
+
+fn render_to_file(
+    renderer: &mut Renderer,
+    output_path: &Path,
+    stripe_rows: u32,
+) -> anyhow::Result<()> {
+    renderer.set_progress_callback(|progress| {
+        eprint!("\rRendering {:5.1}%", progress.fraction() * 100.0);
+    });
+    renderer.set_completion_callback(|stats| {
+        eprintln!();
+        info!("Render stats: {stats}");
+    });
+    renderer.render_to_png(output_path, stripe_rows)?;
+    info!("Saved render to {}", output_path.display());
+    if !renderer.settings().aov_buffers().is_empty() {
+        renderer.write_aov_buffers(output_path)?;
+        info!("Saved AOV buffers next to {}", output_path.display());
+    }
+    log_traversal_stats();
+
+    Ok(())
+}
+
+fn log_traversal_stats() {
+    debug!("Scratch arenas: {}", arena::stats());
+    let sorting = reorder::stats();
+    if sorting.rays() > 0 {
+        debug!("Ray sorting: {sorting}");
+    }
+    let (hits, lookups) = mirror_cache::hit_counts();
+    if lookups > 0 {
+        debug!("Mirror cache: {hits} hits over {lookups} lookups");
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let event_loop = EventLoop::new()?;
-    let mut app = Raytracer::new();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    crash::install_panic_hook();
+    let settings = RenderSettings::from_env_and_args(std::env::args().skip(1))?;
+    priority::configure_global_pool(settings.render_threads(), settings.priority())?;
+
+    if let Some(jobs_path) = settings.jobs_path() {
+        let summary = batch::run_manifest(jobs_path, &settings)?;
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        if summary.failed() > 0 {
+            return Err(format!("{} render jobs failed", summary.failed()).into());
+        }
+        return Ok(());
+    }
+
+    let (width, height) = settings.resolution().unwrap_or((WIDTH, HEIGHT));
+    let output_path = settings
+        .output_path()
+        .map(|path| settings.output_dir().join(path));
+    let stripe_rows = settings.stripe_rows();
+
+    if let Some(runs) = settings.bench() {
+        let report = bench::run_benchmark(&settings, runs, (width, height))?;
+        return write_json(&report, output_path.as_deref(), "benchmark report");
+    }
+
+    if let Some(address) = settings.coordinator() {
+        let Some(output_path) = output_path else {
+            return Err("Coordinator mode requires --output".into());
+        };
+        coordinate_render(address, (width, height), stripe_rows, &output_path)?;
+        return Ok(());
+    }
+
+    let scene_file = if settings.furnace_test() {
+        let Some(scene_file) = run_furnace_test(width, height, output_path.is_some())? else {
+            return Ok(());
+        };
+        scene_file
+    } else {
+        load_scene_file(&settings)?
+    };
+    let settings = settings.with_scene_overrides(scene_file.overrides());
+    let (scene, camera) = scene_file.into_parts();
+
+    if let Some(probes_path) = settings.probes_path() {
+        let probes = probes::extract_probes(probes_path, &scene)?;
+        return write_json(&probes, output_path.as_deref(), "light probes");
+    }
+
+    if let Some((format, export_path)) = settings.export() {
+        export::SceneExport::new(&scene, &camera, (width, height), format).save(export_path)?;
+        info!("Exported scene to {}", export_path.display());
+        return Ok(());
+    }
+
+    if let Some((x, y)) = settings.debug_pixel() {
+        if x >= width || y >= height {
+            return Err(
+                format!("Debug pixel {x},{y} is outside the {width}x{height} image").into(),
+            );
+        }
+        let trace = scene.debug_pixel((x, y), (width, height), &camera, &settings);
+        return write_json(&trace, output_path.as_deref(), "pixel trace");
+    }
+
+    if settings.compare_integrators() || !settings.sweeps().is_empty() {
+        let Some(output_path) = output_path else {
+            return Err("Comparison and sweep modes require --output".into());
+        };
+        render_grid(&scene, &settings, camera, (width, height), &output_path)?;
+        return Ok(());
+    }
+
+    if settings.panorama().is_some() || settings.stereo().is_some() {
+        let Some(output_path) = output_path else {
+            return Err("Panorama and stereo modes require --output".into());
+        };
+        render_views(scene, settings, camera, (width, height), &output_path)?;
+        return Ok(());
+    }
+
+    if let Some(address) = settings.worker().map(str::to_owned) {
+        let mut renderer = Renderer::new(scene, settings, width, height, camera);
+        work_for_coordinator(&address, &mut renderer)?;
+        return Ok(());
+    }
+
+    let mut renderer = Renderer::new(scene, settings, width, height, camera);
+
+    if let Some(output_path) = output_path {
+        render_to_file(&mut renderer, &output_path, stripe_rows)?;
+        return Ok(());
+    }
+
+    renderer.set_completion_callback(|stats| {
+        debug!("Frame rendered: {stats}");
+    });
+
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(err) => {
+            warn!("Failed to create event loop ({err}), rendering to an image file instead");
+            render_to_output_dir(&renderer)?;
+            return Ok(());
+        }
+    };
+    let mut app = Raytracer::new(renderer);
     event_loop.run_app(&mut app)?;
+    if let Some(err) = app.error {
+        return Err(err.into());
+    }
+
     Ok(())
 }
@@ -1,12 +1,187 @@
-use crate::{Vec3f, Vec4f};
+use std::collections::BTreeMap;
 
-#[derive(Clone, Copy, Debug)]
+use anyhow::{Result, bail};
+
+use crate::{PI, Vec3f, Vec4f};
+
+const MAX_INTEGER_EXPONENT: f64 = 4096.0;
+/// The sodium D line, where a material's `refractive_index` is quoted.
+const REFERENCE_WAVELENGTH: f64 = 0.5893;
+
+/// Wavelengths in micrometres that the red, green and blue channels refract at inside
+/// dispersive materials.
+pub const CHANNEL_WAVELENGTHS: [f64; 3] = [0.65, 0.55, 0.45];
+const WAVE_COMPONENTS: [([f64; 2], f64); 3] =
+    [([1.0, 0.0], 1.0), ([0.6, 0.8], 0.61), ([-0.8, 0.45], 0.37)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Waves {
+    amplitude: f64,
+    wavelength: f64,
+    speed: f64,
+}
+
+impl Waves {
+    pub const fn new(amplitude: f64, wavelength: f64, speed: f64) -> Self {
+        Self {
+            amplitude,
+            wavelength,
+            speed,
+        }
+    }
+
+    pub const fn amplitude(&self) -> f64 {
+        self.amplitude
+    }
+
+    pub const fn wavelength(&self) -> f64 {
+        self.wavelength
+    }
+
+    pub const fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn perturb(&self, normal: Vec3f, point: Vec3f, time: f64) -> Vec3f {
+        let helper = if normal.x().abs() < 0.9 {
+            Vec3f::new_with_data([1.0, 0.0, 0.0])
+        } else {
+            Vec3f::new_with_data([0.0, 1.0, 0.0])
+        };
+        let tangent = normal.cross(&helper).normalize(None);
+        let bitangent = normal.cross(&tangent);
+        let (u, v) = (point * tangent, point * bitangent);
+
+        let (slope_u, slope_v) =
+            WAVE_COMPONENTS
+                .iter()
+                .fold((0.0, 0.0), |(slope_u, slope_v), &([du, dv], scale)| {
+                    let wavenumber = 2.0 * PI / (self.wavelength * scale);
+                    let phase = wavenumber * self.speed.mul_add(-time, du.mul_add(u, dv * v));
+                    let slope = self.amplitude * scale * wavenumber * phase.cos();
+                    (du.mul_add(slope, slope_u), dv.mul_add(slope, slope_v))
+                });
+
+        (normal - tangent * slope_u - bitangent * slope_v).normalize(None)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecularModel {
+    #[default]
+    Phong,
+    BlinnPhong,
+    Ggx,
+}
+
+impl SpecularModel {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "phong" => Ok(Self::Phong),
+            "blinn" => Ok(Self::BlinnPhong),
+            "ggx" => Ok(Self::Ggx),
+            _ => bail!("Unknown specular model: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Phong => "phong",
+            Self::BlinnPhong => "blinn",
+            Self::Ggx => "ggx",
+        }
+    }
+
+    pub fn highlight(
+        self,
+        normal: Vec3f,
+        light_direction: Vec3f,
+        view_direction: Vec3f,
+        exponent: f64,
+    ) -> f64 {
+        let half = (light_direction - view_direction).normalize(None);
+        match self {
+            Self::Phong => {
+                let reflected = light_direction - normal * (light_direction * normal) * 2.0;
+                specular_power((reflected * view_direction).max(0.0), exponent)
+            }
+            Self::BlinnPhong => specular_power((normal * half).max(0.0), exponent),
+            Self::Ggx => {
+                let cosine = (normal * half).max(0.0);
+                let alpha_squared = roughness(exponent).powi(2);
+                let denominator = (cosine * cosine).mul_add(alpha_squared - 1.0, 1.0);
+                alpha_squared * alpha_squared / (denominator * denominator)
+            }
+        }
+    }
+}
+
+/// How a material treats hits on the side its geometric normal points away from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sidedness {
+    /// Both sides are shaded, each as if its normal faced the viewer.
+    #[default]
+    Double,
+    /// Back faces keep the outward normal, so they are lit as the front side would be.
+    Single,
+    /// Back faces are skipped by camera and bounce rays, leaving only the front side visible.
+    Culled,
+}
+
+impl Sidedness {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "double" => Ok(Self::Double),
+            "single" => Ok(Self::Single),
+            "cull" => Ok(Self::Culled),
+            _ => bail!("Unknown sidedness: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Double => "double",
+            Self::Single => "single",
+            Self::Culled => "cull",
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn specular_power(cosine: f64, exponent: f64) -> f64 {
+    if exponent.fract() == 0.0 && (0.0..=MAX_INTEGER_EXPONENT).contains(&exponent) {
+        cosine.powi(exponent as i32)
+    } else {
+        cosine.powf(exponent)
+    }
+}
+
+pub fn roughness(specular_exponent: f64) -> f64 {
+    (2.0 / (specular_exponent + 2.0)).sqrt()
+}
+
+pub fn exponent_for_roughness(roughness: f64) -> f64 {
+    2.0 / (roughness * roughness) - 2.0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     albedo: Vec4f,
     diffuse_color: Vec3f,
     ambient_color: Vec3f,
     specular_exponent: f64,
     refractive_index: f64,
+    dispersion: f64,
+    reflect_roughness: f64,
+    refract_roughness: f64,
+    absorption: Vec3f,
+    waves: Option<Waves>,
+    specular_model: Option<SpecularModel>,
+    sidedness: Sidedness,
 }
 
 impl Material {
@@ -23,6 +198,94 @@ impl Material {
             ambient_color,
             specular_exponent,
             refractive_index,
+            dispersion: 0.0,
+            reflect_roughness: 0.0,
+            refract_roughness: 0.0,
+            absorption: Vec3f::const_new_with_data([0.0, 0.0, 0.0]),
+            waves: None,
+            specular_model: None,
+            sidedness: Sidedness::Double,
+        }
+    }
+
+    pub const fn with_waves(self, waves: Waves) -> Self {
+        Self {
+            waves: Some(waves),
+            ..self
+        }
+    }
+
+    pub const fn waves(&self) -> Option<Waves> {
+        self.waves
+    }
+
+    pub const fn with_specular_model(self, specular_model: SpecularModel) -> Self {
+        Self {
+            specular_model: Some(specular_model),
+            ..self
+        }
+    }
+
+    pub const fn specular_model(&self) -> Option<SpecularModel> {
+        self.specular_model
+    }
+
+    pub const fn with_sidedness(self, sidedness: Sidedness) -> Self {
+        Self { sidedness, ..self }
+    }
+
+    pub const fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    pub const fn with_absorption(self, absorption: Vec3f) -> Self {
+        Self { absorption, ..self }
+    }
+
+    pub const fn with_diffuse_color(self, diffuse_color: Vec3f) -> Self {
+        Self {
+            diffuse_color,
+            ..self
+        }
+    }
+
+    pub const fn with_specular_exponent(self, specular_exponent: f64) -> Self {
+        Self {
+            specular_exponent,
+            ..self
+        }
+    }
+
+    pub const fn with_albedo(self, albedo: Vec4f) -> Self {
+        Self { albedo, ..self }
+    }
+
+    pub const fn with_refractive_index(self, refractive_index: f64) -> Self {
+        Self {
+            refractive_index,
+            ..self
+        }
+    }
+
+    /// Sets the Cauchy `B` coefficient in square micrometres; 0 leaves the material
+    /// non-dispersive. Crown glass is around 0.004, dense flint around 0.015.
+    pub const fn with_dispersion(self, dispersion: f64) -> Self {
+        Self { dispersion, ..self }
+    }
+
+    /// Blurs reflections over a GGX lobe of this perceptual roughness; 0 keeps them sharp.
+    pub const fn with_reflect_roughness(self, reflect_roughness: f64) -> Self {
+        Self {
+            reflect_roughness,
+            ..self
+        }
+    }
+
+    /// Blurs refractions over a GGX lobe of this perceptual roughness, for frosted glass.
+    pub const fn with_refract_roughness(self, refract_roughness: f64) -> Self {
+        Self {
+            refract_roughness,
+            ..self
         }
     }
 
@@ -45,6 +308,47 @@ impl Material {
     pub const fn refractive_index(&self) -> f64 {
         self.refractive_index
     }
+
+    pub const fn dispersion(&self) -> f64 {
+        self.dispersion
+    }
+
+    pub const fn reflect_roughness(&self) -> f64 {
+        self.reflect_roughness
+    }
+
+    pub const fn refract_roughness(&self) -> f64 {
+        self.refract_roughness
+    }
+
+    pub fn disperses(&self) -> bool {
+        self.dispersion != 0.0
+    }
+
+    /// Cauchy's equation `n = A + B / λ²` for a wavelength in micrometres, with `A` chosen
+    /// so the index at the sodium D line stays `refractive_index`.
+    pub fn refractive_index_at(&self, wavelength: f64) -> f64 {
+        self.dispersion.mul_add(
+            wavelength.powi(-2) - REFERENCE_WAVELENGTH.powi(-2),
+            self.refractive_index,
+        )
+    }
+
+    pub const fn absorption(&self) -> Vec3f {
+        self.absorption
+    }
+
+    pub fn transmittance(&self, distance: f64) -> Vec3f {
+        Vec3f::new_with_data([
+            (-self.absorption[0] * distance).exp(),
+            (-self.absorption[1] * distance).exp(),
+            (-self.absorption[2] * distance).exp(),
+        ])
+    }
+
+    pub fn absorbs(&self) -> bool {
+        self.absorption != Vec3f::new(0.0)
+    }
 }
 
 const RED_MATERIAL_ALBEDO: Vec4f = Vec4f::const_new_with_data([0.6, 0.3, 0.0, 0.1]);
@@ -107,11 +411,12 @@ pub const GLASS_MATERIAL: Material = Material::new(
     1.5,
 );
 
+pub const FROSTED_GLASS_MATERIAL: Material = GLASS_MATERIAL.with_refract_roughness(0.3);
+
 const GOLD_MATERIAL_ALBEDO: Vec4f = Vec4f::const_new_with_data([0.8, 0.3, 0.0, 0.0]);
 const GOLD_MATERIAL_DIFFUSE_COLOR: Vec3f = Vec3f::const_new_with_data([1.0, 0.843, 0.0]);
 const GOLD_MATERIAL_AMBIENT_COLOR: Vec3f = Vec3f::const_new_with_data([0.2, 0.17, 0.05]);
 
-#[allow(dead_code)]
 pub const GOLD_MATERIAL: Material = Material::new(
     GOLD_MATERIAL_ALBEDO,
     GOLD_MATERIAL_DIFFUSE_COLOR,
@@ -119,3 +424,126 @@ pub const GOLD_MATERIAL: Material = Material::new(
     500.0,
     1.0,
 );
+
+const WATER_MATERIAL_ALBEDO: Vec4f = Vec4f::const_new_with_data([0.1, 0.5, 0.3, 0.6]);
+const WATER_MATERIAL_DIFFUSE_COLOR: Vec3f = Vec3f::const_new_with_data([0.1, 0.35, 0.45]);
+const WATER_MATERIAL_AMBIENT_COLOR: Vec3f = Vec3f::const_new_with_data([0.02, 0.06, 0.08]);
+
+pub const WATER_MATERIAL: Material = Material::new(
+    WATER_MATERIAL_ALBEDO,
+    WATER_MATERIAL_DIFFUSE_COLOR,
+    WATER_MATERIAL_AMBIENT_COLOR,
+    400.0,
+    1.33,
+)
+.with_waves(Waves::new(0.02, 2.0, 0.5));
+
+const BUILTIN_MATERIALS: [(&str, Material); 8] = [
+    ("red", RED_MATERIAL),
+    ("green", GREEN_MATERIAL),
+    ("blue", BLUE_MATERIAL),
+    ("mirror", MIRROR_MATERIAL),
+    ("glass", GLASS_MATERIAL),
+    ("frosted_glass", FROSTED_GLASS_MATERIAL),
+    ("gold", GOLD_MATERIAL),
+    ("water", WATER_MATERIAL),
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaterialRegistry {
+    materials: BTreeMap<String, Material>,
+}
+
+impl MaterialRegistry {
+    pub fn insert(&mut self, name: impl Into<String>, material: Material) -> Option<Material> {
+        self.materials.insert(name.into(), material)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Material> {
+        self.materials.get(name).copied().or_else(|| {
+            BUILTIN_MATERIALS
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, material)| *material)
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Material)> {
+        self.materials
+            .iter()
+            .map(|(name, material)| (name.as_str(), *material))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_exponents_match_powf_for_every_supported_exponent() {
+        for exponent in 0..=4096 {
+            let exponent = f64::from(exponent);
+            for cosine in [0.0_f64, 0.125, 0.5, 0.9, 0.999, 1.0] {
+                let exact = cosine.powf(exponent);
+                assert!(
+                    (specular_power(cosine, exponent) - exact).abs() <= exact * 1e-12,
+                    "{cosine}^{exponent}"
+                );
+            }
+        }
+
+        assert!((specular_power(0.5, 2.5) - 0.5_f64.powf(2.5)).abs() < f64::EPSILON);
+        let beyond_fast_path = MAX_INTEGER_EXPONENT * 2.0;
+        assert!(
+            (specular_power(0.999, beyond_fast_path) - 0.999_f64.powf(beyond_fast_path)).abs()
+                < f64::EPSILON
+        );
+    }
+
+    #[test]
+    fn specular_models_peak_at_the_mirror_direction_with_distinct_falloff() {
+        let normal = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let light = Vec3f::new_with_data([0.6, 0.8, 0.0]);
+        let mirror_view = Vec3f::new_with_data([0.6, -0.8, 0.0]);
+        let off_view = Vec3f::new_with_data([0.5, -0.8, 0.2]).normalize(None);
+
+        for model in [
+            SpecularModel::Phong,
+            SpecularModel::BlinnPhong,
+            SpecularModel::Ggx,
+        ] {
+            assert_eq!(SpecularModel::from_name(model.name()).ok(), Some(model));
+            assert!((model.highlight(normal, light, mirror_view, 50.0) - 1.0).abs() < 1e-9);
+            assert!(model.highlight(normal, light, off_view, 50.0) < 1.0);
+        }
+
+        let grazing_view = Vec3f::new_with_data([-0.2, -0.8, 0.5]).normalize(None);
+        let tail = |model: SpecularModel| model.highlight(normal, light, grazing_view, 50.0);
+        assert!(tail(SpecularModel::BlinnPhong) > tail(SpecularModel::Phong));
+        assert!(tail(SpecularModel::Ggx) > tail(SpecularModel::BlinnPhong));
+        assert!(SpecularModel::from_name("cook-torrance").is_err());
+    }
+
+    #[test]
+    fn dispersion_bends_blue_more_than_red_around_the_quoted_index() {
+        let flint = GLASS_MATERIAL.with_dispersion(0.015);
+        let [red, green, blue] =
+            CHANNEL_WAVELENGTHS.map(|wavelength| flint.refractive_index_at(wavelength));
+        assert!(red < green && green < blue);
+        assert!(
+            red < GLASS_MATERIAL.refractive_index() && blue > GLASS_MATERIAL.refractive_index()
+        );
+        assert!(
+            (flint.refractive_index_at(REFERENCE_WAVELENGTH) - GLASS_MATERIAL.refractive_index())
+                .abs()
+                < 1e-12
+        );
+
+        assert!(!GLASS_MATERIAL.disperses());
+        assert!(
+            (GLASS_MATERIAL.refractive_index_at(0.45) - GLASS_MATERIAL.refractive_index()).abs()
+                < f64::EPSILON
+        );
+    }
+}
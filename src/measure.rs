@@ -0,0 +1,153 @@
+use log::info;
+
+use raytracer_rust::{Vec3f, overlay, renderer::Renderer};
+
+const MAX_POINTS: usize = 3;
+const PANEL_COLOR: [u8; 4] = [255, 220, 0, 255];
+
+#[derive(Debug, Default)]
+pub struct Measure {
+    active: bool,
+    points: Vec<Vec3f>,
+}
+
+fn format_point(point: Vec3f) -> String {
+    format!("{:.3},{:.3},{:.3}", point.x(), point.y(), point.z())
+}
+
+impl Measure {
+    pub const fn new() -> Self {
+        Self {
+            active: false,
+            points: Vec::new(),
+        }
+    }
+
+    pub const fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.points.clear();
+        info!(
+            "Measurement mode {}",
+            if self.active { "enabled" } else { "disabled" }
+        );
+    }
+
+    pub fn pick_at(&mut self, renderer: &Renderer, x: f64, y: f64) -> bool {
+        let Some(point) = renderer.scene().pick_point(
+            x,
+            y,
+            renderer.width(),
+            renderer.height(),
+            renderer.camera(),
+        ) else {
+            info!("Measurement point missed the scene");
+            return false;
+        };
+
+        self.add_point(point);
+        for line in self.lines() {
+            info!("{line}");
+        }
+        true
+    }
+
+    fn add_point(&mut self, point: Vec3f) {
+        if self.points.len() == MAX_POINTS {
+            self.points.clear();
+        }
+        self.points.push(point);
+    }
+
+    pub fn distances(&self) -> impl Iterator<Item = f64> + '_ {
+        self.points
+            .windows(2)
+            .map(|pair| pair[0].distance(&pair[1]))
+    }
+
+    pub fn angle(&self) -> Option<f64> {
+        let [first, vertex, last] = self.points[..] else {
+            return None;
+        };
+        let (a, b) = (first - vertex, last - vertex);
+        let lengths = a.length() * b.length();
+        if !lengths.is_normal() {
+            return None;
+        }
+        Some((a.dot(b) / lengths).clamp(-1.0, 1.0).acos().to_degrees())
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec!["measure".to_owned()];
+        lines.extend(
+            (1..)
+                .zip(&self.points)
+                .map(|(index, point)| format!("p{index} {}", format_point(*point))),
+        );
+        lines.extend(
+            (1..)
+                .zip(self.distances())
+                .map(|(index, distance)| format!("p{index} p{} {distance:.4}", index + 1)),
+        );
+        lines.extend(self.angle().map(|angle| format!("angle {angle:.2}")));
+        lines
+    }
+
+    pub fn draw(&self, frame: &mut [u8], size: (u32, u32)) {
+        if !self.active {
+            return;
+        }
+
+        let scale = (size.1 / 240).max(1);
+        let lines = self.lines();
+        let panel_height = overlay::panel_size(&lines, scale).1;
+        overlay::draw_panel(
+            frame,
+            size,
+            (0, size.1.saturating_sub(panel_height)),
+            &lines,
+            scale,
+            PANEL_COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_points_give_a_distance_and_three_an_angle() {
+        let mut measure = Measure::new();
+        measure.toggle();
+        measure.add_point(Vec3f::new_with_data([3.0, 0.0, 0.0]));
+        measure.add_point(Vec3f::new(0.0));
+        assert_eq!(measure.distances().collect::<Vec<_>>(), [3.0]);
+        assert_eq!(measure.angle(), None);
+
+        measure.add_point(Vec3f::new_with_data([0.0, 4.0, 0.0]));
+        assert_eq!(measure.distances().collect::<Vec<_>>(), [3.0, 4.0]);
+        assert!(
+            measure
+                .angle()
+                .is_some_and(|angle| (angle - 90.0).abs() < 1e-9)
+        );
+        assert_eq!(
+            measure.lines().last().map(String::as_str),
+            Some("angle 90.00")
+        );
+
+        let (width, height) = (160, 120);
+        let mut frame = vec![0; (width * height * 4) as usize];
+        measure.draw(&mut frame, (width, height));
+        assert!(frame.chunks_exact(4).any(|pixel| pixel == PANEL_COLOR));
+
+        measure.add_point(Vec3f::new(1.0));
+        assert_eq!(measure.distances().count(), 0);
+        measure.toggle();
+        assert!(!measure.active());
+    }
+}
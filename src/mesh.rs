@@ -0,0 +1,552 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    Vec3f, arena,
+    bvh::{Aabb, Bvh},
+    geometry::{intersect_triangle, inverse_direction, terminator_safe_point},
+    materials::Material,
+    shapes::{Hit, Intersectable, Shape},
+    text::TextLabel,
+};
+
+const PLANE_TOLERANCE: f64 = 1e-6;
+const BARYCENTRIC_TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct MeshTriangle {
+    vertices: [usize; 3],
+    normals: [usize; 3],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriangleMesh {
+    source: PathBuf,
+    positions: Vec<Vec3f>,
+    normals: Vec<Vec3f>,
+    triangles: Vec<MeshTriangle>,
+    bvh: Bvh,
+    material: Material,
+    smooth: bool,
+    terminator_fix: bool,
+    label: Option<TextLabel>,
+}
+
+fn triangle_bvh(positions: &[Vec3f], triangles: &[MeshTriangle]) -> Bvh {
+    Bvh::new(triangles.iter().map(|triangle| {
+        Some(Aabb::from_points(
+            &triangle.vertices.map(|vertex| positions[vertex]),
+        ))
+    }))
+}
+
+fn obj_index(token: &str, count: usize, line_number: usize) -> Result<usize> {
+    let index: i64 = token
+        .parse()
+        .with_context(|| format!("Invalid index '{token}' on line {line_number}"))?;
+    let count = i64::try_from(count)?;
+
+    let resolved = match index {
+        0 => bail!("OBJ indices start at 1, found 0 on line {line_number}"),
+        index if index < 0 => count + index,
+        index => index - 1,
+    };
+    if !(0..count).contains(&resolved) {
+        bail!("Index {index} out of range on line {line_number}");
+    }
+
+    Ok(usize::try_from(resolved)?)
+}
+
+fn parse_vec3<'a>(tokens: impl Iterator<Item = &'a str>, line_number: usize) -> Result<Vec3f> {
+    let values = tokens
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .with_context(|| format!("Invalid number '{token}' on line {line_number}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let [x, y, z] = values[..] else {
+        bail!("Expected 3 values on line {line_number}");
+    };
+
+    Ok(Vec3f::new_with_data([x, y, z]))
+}
+
+impl TriangleMesh {
+    pub fn load(path: &Path, source: PathBuf, material: Material) -> Result<Self> {
+        let obj = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read mesh file {}", path.display()))?;
+        Self::parse_obj(&obj, source, material)
+            .with_context(|| format!("Failed to parse mesh file {}", path.display()))
+    }
+
+    pub fn parse_obj(obj: &str, source: PathBuf, material: Material) -> Result<Self> {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut faces: Vec<[(usize, Option<usize>); 3]> = Vec::new();
+
+        for (index, line) in obj.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => positions.push(parse_vec3(tokens.take(3), line_number)?),
+                Some("vn") => normals.push(parse_vec3(tokens, line_number)?.normalize(None)),
+                Some("f") => {
+                    let corners = tokens
+                        .map(|corner| {
+                            let mut parts = corner.split('/');
+                            let vertex = parts.next().unwrap_or_default();
+                            let normal = parts.nth(1).filter(|normal| !normal.is_empty());
+
+                            Ok((
+                                obj_index(vertex, positions.len(), line_number)?,
+                                normal
+                                    .map(|normal| obj_index(normal, normals.len(), line_number))
+                                    .transpose()?,
+                            ))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+
+                    if corners.len() < 3 {
+                        bail!("Face with fewer than 3 vertices on line {line_number}");
+                    }
+                    for pair in corners[1..].windows(2) {
+                        faces.push([corners[0], pair[0], pair[1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self::from_faces(source, positions, normals, &faces, material)
+    }
+
+    pub fn from_triangles(
+        positions: Vec<Vec3f>,
+        indices: &[[usize; 3]],
+        source: PathBuf,
+        material: Material,
+    ) -> Result<Self> {
+        if let Some(index) = indices
+            .iter()
+            .flatten()
+            .find(|&&index| index >= positions.len())
+        {
+            bail!(
+                "Index {index} out of range for {} vertices",
+                positions.len()
+            );
+        }
+
+        let faces: Vec<_> = indices
+            .iter()
+            .map(|triangle| triangle.map(|vertex| (vertex, None)))
+            .collect();
+        Self::from_faces(source, positions, Vec::new(), &faces, material)
+    }
+
+    fn from_faces(
+        source: PathBuf,
+        positions: Vec<Vec3f>,
+        mut normals: Vec<Vec3f>,
+        faces: &[[(usize, Option<usize>); 3]],
+        material: Material,
+    ) -> Result<Self> {
+        if faces.is_empty() {
+            bail!("Mesh has no faces");
+        }
+
+        let generated_offset = normals.len();
+        let needs_generated = faces.iter().flatten().any(|(_, normal)| normal.is_none());
+        if needs_generated {
+            normals.extend(generate_vertex_normals(&positions, faces));
+        }
+
+        let triangles: Vec<_> = faces
+            .iter()
+            .map(|face| MeshTriangle {
+                vertices: face.map(|(vertex, _)| vertex),
+                normals: face.map(|(vertex, normal)| normal.unwrap_or(generated_offset + vertex)),
+            })
+            .collect();
+        let bvh = triangle_bvh(&positions, &triangles);
+
+        Ok(Self {
+            source,
+            positions,
+            normals,
+            triangles,
+            bvh,
+            material,
+            smooth: true,
+            terminator_fix: true,
+            label: None,
+        })
+    }
+
+    pub const fn with_smooth_shading(mut self, smooth: bool) -> Self {
+        self.smooth = smooth;
+        self
+    }
+
+    pub fn with_label(mut self, label: TextLabel) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub const fn set_terminator_fix(&mut self, terminator_fix: bool) {
+        self.terminator_fix = terminator_fix;
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub const fn smooth(&self) -> bool {
+        self.smooth
+    }
+
+    pub const fn label(&self) -> Option<&TextLabel> {
+        self.label.as_ref()
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3f; 3]> + '_ {
+        self.triangles.iter().map(|triangle| self.corners(triangle))
+    }
+
+    pub const fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn degenerate_triangles(&self) -> usize {
+        self.triangles
+            .iter()
+            .filter(|triangle| self.is_degenerate(triangle))
+            .count()
+    }
+
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let before = self.triangles.len();
+        let triangles = std::mem::take(&mut self.triangles);
+        self.triangles = triangles
+            .into_iter()
+            .filter(|triangle| !self.is_degenerate(triangle))
+            .collect();
+        self.bvh = triangle_bvh(&self.positions, &self.triangles);
+        before - self.triangles.len()
+    }
+
+    fn is_degenerate(&self, triangle: &MeshTriangle) -> bool {
+        let [a, b, c] = self.corners(triangle);
+        !(b - a).cross(&(c - a)).length().is_normal()
+    }
+
+    fn corners(&self, triangle: &MeshTriangle) -> [Vec3f; 3] {
+        triangle.vertices.map(|vertex| self.positions[vertex])
+    }
+
+    fn face_normal(&self, triangle: &MeshTriangle) -> Vec3f {
+        let [a, b, c] = self.corners(triangle);
+        (b - a).cross(&(c - a)).normalize(None)
+    }
+
+    fn locate(&self, point: Vec3f) -> Option<(&MeshTriangle, [f64; 3])> {
+        arena::with_indices(|candidates| {
+            self.bvh.containing(point, PLANE_TOLERANCE, candidates);
+            candidates
+                .iter()
+                .filter_map(|&index| {
+                    let triangle = &self.triangles[index];
+                    let corners = self.corners(triangle);
+                    let normal = self.face_normal(triangle);
+                    let plane_distance = ((point - corners[0]) * normal).abs();
+                    if plane_distance > PLANE_TOLERANCE {
+                        return None;
+                    }
+
+                    let barycentrics = barycentrics(point, corners)?;
+                    barycentrics
+                        .iter()
+                        .all(|&weight| {
+                            (-BARYCENTRIC_TOLERANCE..=1.0 + BARYCENTRIC_TOLERANCE).contains(&weight)
+                        })
+                        .then_some((plane_distance, triangle, barycentrics))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, triangle, barycentrics)| (triangle, barycentrics))
+        })
+    }
+
+    /// Shading and geometric normal at a point on `triangle`; the face normal is flipped to
+    /// the side of the vertex normals.
+    fn normals_at(&self, triangle: &MeshTriangle, barycentrics: [f64; 3]) -> (Vec3f, Vec3f) {
+        let interpolated = self.interpolated_normal(triangle, barycentrics);
+        let face_normal = self.face_normal(triangle);
+        let geometric_normal = if face_normal * interpolated < 0.0 {
+            -face_normal
+        } else {
+            face_normal
+        };
+        let normal = if self.smooth {
+            interpolated
+        } else {
+            face_normal
+        };
+
+        (normal, geometric_normal)
+    }
+
+    fn interpolated_normal(&self, triangle: &MeshTriangle, barycentrics: [f64; 3]) -> Vec3f {
+        triangle
+            .normals
+            .iter()
+            .zip(barycentrics)
+            .fold(Vec3f::new(0.0), |normal, (&index, weight)| {
+                normal + self.normals[index] * weight
+            })
+            .normalize(None)
+    }
+}
+
+fn barycentrics(point: Vec3f, [origin, second, third]: [Vec3f; 3]) -> Option<[f64; 3]> {
+    let ab = second - origin;
+    let ac = third - origin;
+    let ap = point - origin;
+
+    let d00 = ab * ab;
+    let d01 = ab * ac;
+    let d11 = ac * ac;
+    let d20 = ap * ab;
+    let d21 = ap * ac;
+
+    let denominator = d00.mul_add(d11, -d01 * d01);
+    if denominator.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let second_weight = d11.mul_add(d20, -d01 * d21) / denominator;
+    let third_weight = d00.mul_add(d21, -d01 * d20) / denominator;
+
+    Some([
+        1.0 - second_weight - third_weight,
+        second_weight,
+        third_weight,
+    ])
+}
+
+fn generate_vertex_normals(
+    positions: &[Vec3f],
+    faces: &[[(usize, Option<usize>); 3]],
+) -> Vec<Vec3f> {
+    let mut normals = vec![Vec3f::new(0.0); positions.len()];
+
+    for face in faces {
+        let [a, b, c] = face.map(|(vertex, _)| positions[vertex]);
+        let area_weighted = (b - a).cross(&(c - a));
+        for (vertex, _) in face {
+            normals[*vertex] += area_weighted;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.length() > 0.0 {
+                normal.normalize(None)
+            } else {
+                Vec3f::new_with_data([0.0, 1.0, 0.0])
+            }
+        })
+        .collect()
+}
+
+impl Intersectable for TriangleMesh {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        self.ray_hit_inverse(origin, direction, inverse_direction(direction))
+    }
+
+    fn ray_hit_inverse(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        inverse_direction: Vec3f,
+    ) -> Option<Hit> {
+        let (distance, index) = self.bvh.closest_hit(origin, inverse_direction, |index| {
+            intersect_triangle(origin, direction, self.corners(&self.triangles[index]))
+        })?;
+        let triangle = &self.triangles[index];
+        let barycentrics = barycentrics(origin + direction * distance, self.corners(triangle))
+            .unwrap_or([1.0 / 3.0; 3]);
+        let (normal, geometric_normal) = self.normals_at(triangle, barycentrics);
+
+        Some(Hit::new(distance, normal).with_geometric_normal(geometric_normal))
+    }
+
+    fn intersects_any(&self, origin: Vec3f, direction: Vec3f, max_distance: f64) -> bool {
+        self.bvh.any_hit(
+            origin,
+            inverse_direction(direction),
+            max_distance,
+            |index| {
+                intersect_triangle(origin, direction, self.corners(&self.triangles[index]))
+                    .is_some_and(|distance| distance < max_distance)
+            },
+        )
+    }
+}
+
+impl Shape for TriangleMesh {
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.locate(hit_point).map_or_else(
+            || Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            |(triangle, barycentrics)| self.normals_at(triangle, barycentrics).0,
+        )
+    }
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.locate(hit_point).map_or_else(
+            || Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            |(triangle, barycentrics)| self.normals_at(triangle, barycentrics).1,
+        )
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        for position in &mut self.positions {
+            *position += offset;
+        }
+        self.bvh.translate(offset);
+        if let Some(label) = &mut self.label {
+            label.translate(offset);
+        }
+    }
+
+    fn thickness(&self) -> f64 {
+        self.bvh.bounds().map_or(f64::INFINITY, |bounds| {
+            let extent = bounds.extent();
+            extent.x().min(extent.y()).min(extent.z())
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+
+    fn get_shadow_point(&self, hit_point: Vec3f) -> Vec3f {
+        if !self.smooth || !self.terminator_fix {
+            return hit_point;
+        }
+
+        self.locate(hit_point)
+            .map_or(hit_point, |(triangle, barycentrics)| {
+                let normals = triangle.normals.map(|index| self.normals[index]);
+                terminator_safe_point(hit_point, self.corners(triangle), normals, barycentrics)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::GOLD_MATERIAL;
+    use anyhow::anyhow;
+
+    const PYRAMID: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+v 0.5 1 0.5
+f 2 1 5
+f 3 2 5
+f 4 3 5
+f 1 4 5
+f 1 2 3 4
+";
+
+    fn pyramid() -> Result<TriangleMesh> {
+        TriangleMesh::parse_obj(PYRAMID, PathBuf::from("pyramid.obj"), GOLD_MATERIAL)
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated_and_normals_generated() -> Result<()> {
+        let mesh = pyramid()?;
+
+        assert_eq!(mesh.triangles.len(), 6);
+        assert_eq!(mesh.normals.len(), mesh.positions.len());
+
+        let apex_normal = mesh.normals[4];
+        assert!((apex_normal - Vec3f::new_with_data([0.0, 1.0, 0.0])).length() < 1e-12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn smooth_normals_blend_between_faces() -> Result<()> {
+        let mesh = pyramid()?;
+        let origin = Vec3f::new_with_data([0.5, 0.5, -1.0]);
+        let direction = Vec3f::new_with_data([0.0, 0.0, 1.0]);
+
+        let distance = mesh
+            .ray_intersect(origin, direction)
+            .ok_or_else(|| anyhow!("ray missed the pyramid"))?;
+        let hit = origin + direction * distance;
+
+        let face = mesh.get_geometric_normal(hit);
+        let smooth = mesh.get_normal(hit);
+        let flat = mesh.with_smooth_shading(false).get_normal(hit);
+
+        assert!((flat - face).length() < 1e-12);
+        assert!((smooth - face).length() > 1e-3);
+        assert!(smooth * face > 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hits_carry_the_normals_of_the_triangle_they_landed_on() -> Result<()> {
+        let mesh = pyramid()?;
+        let origin = Vec3f::new_with_data([0.3, 0.4, -1.0]);
+        let direction = Vec3f::new_with_data([0.1, 0.0, 1.0]).normalize(None);
+
+        let hit = mesh
+            .ray_hit(origin, direction)
+            .ok_or_else(|| anyhow!("ray missed the pyramid"))?;
+        let point = origin + direction * hit.distance();
+
+        assert!((hit.normal() - mesh.get_normal(point)).length() < 1e-9);
+        assert!((hit.geometric_normal() - mesh.get_geometric_normal(point)).length() < 1e-9);
+        assert!(hit.geometric_normal() * direction < 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn explicit_normals_and_bad_indices() -> Result<()> {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 2\nf 1//1 2//1 3//1\n";
+        let mesh = TriangleMesh::parse_obj(obj, PathBuf::new(), GOLD_MATERIAL)?;
+        assert_eq!(mesh.normals, vec![Vec3f::new_with_data([0.0, 0.0, 1.0])]);
+
+        let out_of_range = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        assert!(TriangleMesh::parse_obj(out_of_range, PathBuf::new(), GOLD_MATERIAL).is_err());
+
+        Ok(())
+    }
+}
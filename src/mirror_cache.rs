@@ -0,0 +1,149 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{Vec3f, materials::Material};
+
+const MAX_ENTRIES: usize = 4096;
+const MAX_DIFFUSE_ALBEDO: f64 = 1e-3;
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static LOOKUPS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct RayKey {
+    origin: [i64; 3],
+    direction: [i64; 3],
+    depth: u32,
+    medium: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    origin: Vec3f,
+    direction: Vec3f,
+    radiance: Vec3f,
+}
+
+#[derive(Debug, Default)]
+struct TileCache {
+    entries: HashMap<RayKey, Entry>,
+    hits: u64,
+    lookups: u64,
+}
+
+thread_local! {
+    static CACHE: RefCell<TileCache> = RefCell::new(TileCache::default());
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(vector: Vec3f, step: f64) -> [i64; 3] {
+    [vector.x(), vector.y(), vector.z()].map(|component| (component / step).floor() as i64)
+}
+
+pub fn is_pure_mirror(material: &Material) -> bool {
+    let albedo = material.albedo();
+    albedo[2] > albedo[3] && albedo[0] <= MAX_DIFFUSE_ALBEDO
+}
+
+pub fn trace_cached(
+    cell_size: f64,
+    (origin, direction): (Vec3f, Vec3f),
+    depth: u32,
+    medium: f64,
+    trace: impl FnOnce() -> Vec3f,
+) -> Vec3f {
+    let key = RayKey {
+        origin: quantize(origin, cell_size),
+        direction: quantize(direction, cell_size),
+        depth,
+        medium: medium.to_bits(),
+    };
+    let cached = CACHE.with_borrow_mut(|cache| {
+        cache.lookups += 1;
+        let entry = cache.entries.get(&key).filter(|entry| {
+            entry.origin.distance(&origin) <= cell_size
+                && entry.direction.distance(&direction) <= cell_size
+        })?;
+        cache.hits += 1;
+        Some(entry.radiance)
+    });
+    if let Some(radiance) = cached {
+        return radiance;
+    }
+
+    let radiance = trace();
+    CACHE.with_borrow_mut(|cache| {
+        if cache.entries.len() < MAX_ENTRIES {
+            cache.entries.insert(
+                key,
+                Entry {
+                    origin,
+                    direction,
+                    radiance,
+                },
+            );
+        }
+    });
+    radiance
+}
+
+pub fn finish_tile() {
+    let (hits, lookups) = CACHE.with_borrow_mut(|cache| {
+        cache.entries.clear();
+        (
+            std::mem::take(&mut cache.hits),
+            std::mem::take(&mut cache.lookups),
+        )
+    });
+    HITS.fetch_add(hits, Ordering::Relaxed);
+    LOOKUPS.fetch_add(lookups, Ordering::Relaxed);
+}
+
+pub fn hit_counts() -> (u64, u64) {
+    (
+        HITS.load(Ordering::Relaxed),
+        LOOKUPS.load(Ordering::Relaxed),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{GLASS_MATERIAL, GOLD_MATERIAL, MIRROR_MATERIAL};
+
+    #[test]
+    fn nearby_mirror_rays_reuse_radiance_until_the_tile_ends() {
+        let direction = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let traced = std::cell::Cell::new(0);
+        let trace = |radiance: f64| {
+            traced.set(traced.get() + 1);
+            Vec3f::new(radiance)
+        };
+
+        let first = trace_cached(0.1, (Vec3f::new(0.01), direction), 1, 1.0, || trace(0.5));
+        let nearby = trace_cached(0.1, (Vec3f::new(0.02), direction), 1, 1.0, || trace(0.9));
+        assert_eq!(nearby, first);
+        assert_eq!(traced.get(), 1);
+
+        let deeper = trace_cached(0.1, (Vec3f::new(0.02), direction), 2, 1.0, || trace(0.9));
+        let underwater = trace_cached(0.1, (Vec3f::new(0.02), direction), 1, 1.33, || trace(0.9));
+        let far = trace_cached(0.1, (Vec3f::new(0.5), direction), 1, 1.0, || trace(0.9));
+        assert_eq!([deeper, underwater, far], [Vec3f::new(0.9); 3]);
+        assert_eq!(traced.get(), 4);
+
+        let before = hit_counts();
+        finish_tile();
+        let after = hit_counts();
+        assert!(after.0 > before.0 && after.1 >= before.1 + 5);
+        trace_cached(0.1, (Vec3f::new(0.01), direction), 1, 1.0, || trace(0.5));
+        assert_eq!(traced.get(), 5);
+        finish_tile();
+
+        assert!(is_pure_mirror(&MIRROR_MATERIAL));
+        assert!(!is_pure_mirror(&GOLD_MATERIAL));
+        assert!(!is_pure_mirror(&GLASS_MATERIAL));
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    if rgba.len() != width as usize * height as usize * 4 {
+        bail!(
+            "Image buffer has {} bytes, expected {} for {width}x{height} RGBA",
+            rgba.len(),
+            width as usize * height as usize * 4
+        );
+    }
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create image file {}", path.display()))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .with_context(|| format!("Failed to write PNG header to {}", path.display()))?;
+    writer
+        .write_image_data(rgba)
+        .with_context(|| format!("Failed to write PNG data to {}", path.display()))?;
+
+    Ok(())
+}
+
+fn read_png(path: &Path) -> Result<(png::OutputInfo, Vec<u8>)> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open image {}", path.display()))?;
+
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .with_context(|| format!("Failed to read PNG header from {}", path.display()))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .with_context(|| format!("Failed to decode PNG {}", path.display()))?;
+    buffer.truncate(info.buffer_size());
+
+    Ok((info, buffer))
+}
+
+pub fn read_grayscale_png(path: &Path) -> Result<(usize, usize, Vec<f64>)> {
+    let (info, buffer) = read_png(path)?;
+    let channels = info.color_type.samples();
+    let luminance = buffer
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let color_channels: u8 = if channels >= 3 { 3 } else { 1 };
+            let sum: f64 = pixel[..usize::from(color_channels)]
+                .iter()
+                .map(|&value| f64::from(value))
+                .sum();
+            sum / (255.0 * f64::from(color_channels))
+        })
+        .collect();
+
+    Ok((info.width as usize, info.height as usize, luminance))
+}
+
+pub fn read_rgba_png(path: &Path) -> Result<(usize, usize, Vec<[f64; 4]>)> {
+    let (info, buffer) = read_png(path)?;
+    let channels = info.color_type.samples();
+    let texels = buffer
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let channel = |index: usize| f64::from(pixel[index]) / 255.0;
+            match channels {
+                1 => [channel(0), channel(0), channel(0), 1.0],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), 1.0],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            }
+        })
+        .collect();
+
+    Ok((info.width as usize, info.height as usize, texels))
+}
+
+/// Reads a Radiance RGBE `.hdr` image as linear RGB texels, top row first.
+pub fn read_hdr(path: &Path) -> Result<(usize, usize, Vec<[f64; 3]>)> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to open image {}", path.display()))?;
+    decode_hdr(&bytes).with_context(|| format!("Failed to decode HDR image {}", path.display()))
+}
+
+fn header_line<'a>(bytes: &'a [u8], position: &mut usize) -> Result<&'a str> {
+    let rest = bytes.get(*position..).unwrap_or_default();
+    let Some(end) = rest.iter().position(|&byte| byte == b'\n') else {
+        bail!("HDR header ends early");
+    };
+    *position += end + 1;
+    std::str::from_utf8(&rest[..end]).context("HDR header is not text")
+}
+
+fn decode_hdr(bytes: &[u8]) -> Result<(usize, usize, Vec<[f64; 3]>)> {
+    let mut position = 0;
+    if !header_line(bytes, &mut position)?.starts_with("#?") {
+        bail!("Missing the #? Radiance signature");
+    }
+    loop {
+        let line = header_line(bytes, &mut position)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            bail!("Unsupported HDR pixel format {format}");
+        }
+    }
+
+    let resolution = header_line(bytes, &mut position)?;
+    let fields: Vec<_> = resolution.split_whitespace().collect();
+    let (height, width): (usize, usize) = match fields[..] {
+        ["-Y", height, "+X", width] => (height.parse()?, width.parse()?),
+        _ => bail!("Unsupported HDR orientation '{resolution}'"),
+    };
+    if width == 0 || height == 0 {
+        bail!("HDR image has no pixels");
+    }
+
+    let mut data = bytes.get(position..).unwrap_or_default().iter().copied();
+    let mut scanline = vec![[0; 4]; width];
+    let mut texels = Vec::with_capacity(width * height);
+    for _ in 0..height {
+        read_scanline(&mut data, &mut scanline)?;
+        texels.extend(scanline.iter().map(|&rgbe| rgbe_to_rgb(rgbe)));
+    }
+
+    Ok((width, height, texels))
+}
+
+/// Reads one scanline, either flat RGBE pixels or the run-length encoding that stores
+/// each channel of the line separately.
+fn read_scanline(data: &mut impl Iterator<Item = u8>, scanline: &mut [[u8; 4]]) -> Result<()> {
+    let mut next = || {
+        data.next()
+            .ok_or_else(|| anyhow!("HDR pixel data ends early"))
+    };
+    let width = scanline.len();
+    let first = [next()?, next()?, next()?, next()?];
+    let encoded_width = (usize::from(first[2]) << 8) | usize::from(first[3]);
+    if !((8..0x8000).contains(&width) && first[..2] == [2, 2] && encoded_width == width) {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            *pixel = [next()?, next()?, next()?, next()?];
+        }
+        return Ok(());
+    }
+
+    for channel in 0..4 {
+        let mut column = 0;
+        while column < width {
+            let count = next()?;
+            let (run, repeated) = if count > 128 {
+                (usize::from(count - 128), Some(next()?))
+            } else {
+                (usize::from(count), None)
+            };
+            if run == 0 || column + run > width {
+                bail!("Corrupt HDR run length");
+            }
+            for pixel in &mut scanline[column..column + run] {
+                pixel[channel] = match repeated {
+                    Some(value) => value,
+                    None => next()?,
+                };
+            }
+            column += run;
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_rgb([red, green, blue, exponent]: [u8; 4]) -> [f64; 3] {
+    if exponent == 0 {
+        return [0.0; 3];
+    }
+
+    let scale = 2.0_f64.powi(i32::from(exponent) - 136);
+    [red, green, blue].map(|mantissa| (f64::from(mantissa) + 0.5) * scale)
+}
+
+pub fn write_png_stripes(
+    path: &Path,
+    width: u32,
+    height: u32,
+    stripe_rows: u32,
+    mut render_stripe: impl FnMut(u32, &mut [u8]) -> Result<()>,
+) -> Result<()> {
+    if stripe_rows == 0 {
+        bail!("Stripe height must be at least 1 row");
+    }
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create image file {}", path.display()))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .with_context(|| format!("Failed to write PNG header to {}", path.display()))?
+        .into_stream_writer()
+        .with_context(|| format!("Failed to start PNG stream for {}", path.display()))?;
+
+    let row_bytes = width as usize * 4;
+    let mut stripe = vec![0; row_bytes * stripe_rows.min(height) as usize];
+
+    let mut first_row = 0;
+    while first_row < height {
+        let rows = stripe_rows.min(height - first_row);
+        let stripe = &mut stripe[..row_bytes * rows as usize];
+
+        render_stripe(first_row, stripe)?;
+        writer
+            .write_all(stripe)
+            .with_context(|| format!("Failed to write PNG data to {}", path.display()))?;
+
+        first_row += rows;
+    }
+
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finish PNG stream for {}", path.display()))?;
+
+    Ok(())
+}
+
+pub fn timestamped_path(directory: &Path, prefix: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+
+    directory.join(format!("{prefix}-{millis}.{extension}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hdr_images_decode_flat_and_run_length_encoded_scanlines() -> Result<()> {
+        let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        flat.extend([128, 64, 32, 129, 0, 0, 0, 0]);
+        let (width, height, texels) = decode_hdr(&flat)?;
+        assert_eq!((width, height), (2, 1));
+        assert!((texels[0][0] - 128.5 / 128.0).abs() < 1e-12);
+        assert!((texels[0][2] - 32.5 / 128.0).abs() < 1e-12);
+        assert!(texels[1].iter().all(|channel| channel.abs() < 1e-12));
+
+        let mut encoded = b"#?RGBE\n\n-Y 1 +X 8\n".to_vec();
+        encoded.extend([2, 2, 0, 8]);
+        encoded.extend([136, 128]);
+        encoded.extend([130, 64, 6, 1, 2, 3, 4, 5, 6]);
+        encoded.extend([136, 0]);
+        encoded.extend([136, 136]);
+        let (_, _, texels) = decode_hdr(&encoded)?;
+        assert!((texels[0][0] - 128.5).abs() < 1e-12);
+        assert!((texels[1][1] - 64.5).abs() < 1e-12);
+        assert!((texels[7][1] - 6.5).abs() < 1e-12);
+
+        assert!(decode_hdr(b"P6\n").is_err());
+        assert!(decode_hdr(&encoded[..encoded.len() - 1]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+pub const GLYPH_SPACING: u32 = 1;
+
+const PANEL_MARGIN: u32 = 4;
+const PANEL_BACKGROUND: [u8; 4] = [0, 0, 0, 192];
+
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; 7]); 47] = [
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (' ', [0; 7]),
+    ('.', [0, 0, 0, 0, 0, 0b01100, 0b01100]),
+    (',', [0, 0, 0, 0, 0b01100, 0b00100, 0b01000]),
+    ('-', [0, 0, 0, 0b11111, 0, 0, 0]),
+    ('+', [0, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0]),
+    ('=', [0, 0, 0b11111, 0, 0b11111, 0, 0]),
+    (':', [0, 0b01100, 0b01100, 0, 0b01100, 0b01100, 0]),
+    ('/', [0b00001, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b10000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+];
+
+const UNKNOWN_GLYPH: [u8; 7] = [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0, 0b00100];
+
+pub fn glyph(character: char) -> [u8; 7] {
+    let character = character.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .map_or(UNKNOWN_GLYPH, |(_, rows)| *rows)
+}
+
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let characters = u32::try_from(text.chars().count()).unwrap_or(u32::MAX);
+    let width = characters.saturating_mul(GLYPH_WIDTH + GLYPH_SPACING);
+
+    (
+        width.saturating_sub(GLYPH_SPACING).saturating_mul(scale),
+        GLYPH_HEIGHT * scale,
+    )
+}
+
+fn put_pixel(frame: &mut [u8], width: u32, height: u32, x: u32, y: u32, color: [u8; 4]) {
+    if x < width && y < height {
+        let index = (y as usize * width as usize + x as usize) * 4;
+        frame[index..index + 4].copy_from_slice(&color);
+    }
+}
+
+pub fn fill_rect(
+    frame: &mut [u8],
+    (width, height): (u32, u32),
+    (x, y): (u32, u32),
+    (rect_width, rect_height): (u32, u32),
+    color: [u8; 4],
+) {
+    for row in y..y.saturating_add(rect_height).min(height) {
+        for column in x..x.saturating_add(rect_width).min(width) {
+            put_pixel(frame, width, height, column, row, color);
+        }
+    }
+}
+
+pub fn draw_text(
+    frame: &mut [u8],
+    (width, height): (u32, u32),
+    (x, y): (u32, u32),
+    text: &str,
+    scale: u32,
+    color: [u8; 4],
+) {
+    for (index, character) in (0..).zip(text.chars()) {
+        let left = x + index * (GLYPH_WIDTH + GLYPH_SPACING) * scale;
+        for (row, bits) in (0..).zip(glyph(character)) {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                fill_rect(
+                    frame,
+                    (width, height),
+                    (left + column * scale, y + row * scale),
+                    (scale, scale),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+pub fn panel_size(lines: &[String], scale: u32) -> (u32, u32) {
+    let line_height = text_size("0", scale).1 + PANEL_MARGIN;
+    let text_width = lines
+        .iter()
+        .map(|line| text_size(line, scale).0)
+        .max()
+        .unwrap_or(0);
+    let rows = u32::try_from(lines.len()).unwrap_or(u32::MAX);
+
+    (
+        text_width + PANEL_MARGIN * 2,
+        line_height.saturating_mul(rows) + PANEL_MARGIN,
+    )
+}
+
+pub fn draw_panel(
+    frame: &mut [u8],
+    size: (u32, u32),
+    (x, y): (u32, u32),
+    lines: &[String],
+    scale: u32,
+    color: [u8; 4],
+) {
+    let line_height = text_size("0", scale).1 + PANEL_MARGIN;
+
+    fill_rect(
+        frame,
+        size,
+        (x, y),
+        panel_size(lines, scale),
+        PANEL_BACKGROUND,
+    );
+    for (row, line) in (0..).zip(lines) {
+        draw_text(
+            frame,
+            size,
+            (x + PANEL_MARGIN, y + PANEL_MARGIN + row * line_height),
+            line,
+            scale,
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_drawn_inside_its_measured_box() {
+        let (width, height) = (64, 16);
+        let mut frame = vec![0; (width * height * 4) as usize];
+        let (text_width, text_height) = text_size("HI 1", 2);
+
+        draw_text(&mut frame, (width, height), (1, 1), "HI 1", 2, [255; 4]);
+
+        let lit: Vec<_> = (0..width * height)
+            .filter(|pixel| frame[*pixel as usize * 4] == 255)
+            .map(|pixel| (pixel % width, pixel / width))
+            .collect();
+        assert!(!lit.is_empty());
+        assert!(
+            lit.iter()
+                .all(|&(x, y)| { (1..=text_width).contains(&x) && (1..=text_height).contains(&y) })
+        );
+        assert_eq!(text_size("", 3), (0, 21));
+    }
+}
@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    Vec3f,
+    camera::{Camera, Projection},
+    output,
+    renderer::{RenderProgress, Renderer},
+    scene::Scene,
+    settings::RenderSettings,
+};
+
+const CUBE_FACES: [([f64; 3], [f64; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanoramaLayout {
+    CubeStrip,
+    Equirect,
+}
+
+impl PanoramaLayout {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "cube" => Ok(Self::CubeStrip),
+            "equirect" => Ok(Self::Equirect),
+            _ => bail!("Unknown panorama layout: {name}"),
+        }
+    }
+}
+
+fn eye_cameras(camera: Camera, ipd: f64) -> [Camera; 2] {
+    [-ipd / 2.0, ipd / 2.0].map(|offset| camera.with_eye_offset(offset))
+}
+
+fn render_side_by_side(
+    mut renderer: Renderer,
+    cameras: &[Camera],
+    path: &Path,
+    stripe_rows: u32,
+    on_progress: impl Fn(RenderProgress),
+) -> Result<()> {
+    let (width, height) = (renderer.width(), renderer.height());
+    let view_row_bytes = width as usize * 4;
+    let mut view = Vec::new();
+    let image_width = width * u32::try_from(cameras.len())?;
+    let total_pixels = image_width as usize * height as usize;
+
+    output::write_png_stripes(
+        path,
+        image_width,
+        height,
+        stripe_rows,
+        |first_row, stripe| {
+            let rows = stripe.len() / (view_row_bytes * cameras.len());
+            view.resize(rows * view_row_bytes, 0);
+
+            for (view_index, camera) in cameras.iter().enumerate() {
+                renderer.set_camera(*camera);
+                renderer.render_rows_into(&mut view, first_row, u32::try_from(rows)?)?;
+
+                for (row, view_row) in view.chunks_exact(view_row_bytes).enumerate() {
+                    let start = (row * cameras.len() + view_index) * view_row_bytes;
+                    stripe[start..start + view_row_bytes].copy_from_slice(view_row);
+                }
+            }
+
+            on_progress(RenderProgress::new(
+                (first_row as usize + rows) * image_width as usize,
+                total_pixels,
+            ));
+            Ok(())
+        },
+    )
+}
+
+/// Renders a 360 degree view around `camera` to `path`, reporting how far along it is
+/// through `on_progress`.
+pub fn render_panorama(
+    scene: Scene,
+    settings: RenderSettings,
+    camera: Camera,
+    layout: PanoramaLayout,
+    path: &Path,
+    on_progress: impl Fn(RenderProgress) + Send + Sync + 'static,
+) -> Result<()> {
+    let size = settings.panorama_size();
+    let stripe_rows = settings.stripe_rows();
+    let stereo = settings.stereo();
+
+    match layout {
+        PanoramaLayout::Equirect => {
+            let camera = camera.with_projection(Projection::Equirectangular);
+            let mut renderer = Renderer::new(scene, settings, size * 2, size, camera);
+            if let Some(ipd) = stereo {
+                render_side_by_side(
+                    renderer,
+                    &eye_cameras(camera, ipd),
+                    path,
+                    stripe_rows,
+                    on_progress,
+                )?;
+            } else {
+                renderer.set_progress_callback(on_progress);
+                renderer.render_to_png(path, stripe_rows)?;
+            }
+        }
+        PanoramaLayout::CubeStrip => {
+            if stereo.is_some() {
+                bail!("Stereo panoramas require the equirect layout");
+            }
+            let faces: Vec<_> = CUBE_FACES
+                .into_iter()
+                .map(|(forward, up)| {
+                    camera.oriented(Vec3f::new_with_data(forward), Vec3f::new_with_data(up))
+                })
+                .collect();
+            let renderer = Renderer::new(scene, settings, size, size, camera);
+            render_side_by_side(renderer, &faces, path, stripe_rows, on_progress)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the left and right eye views of `camera` side by side to `path`, reporting
+/// how far along it is through `on_progress`.
+pub fn render_stereo(
+    scene: Scene,
+    settings: RenderSettings,
+    camera: Camera,
+    (width, height): (u32, u32),
+    path: &Path,
+    on_progress: impl Fn(RenderProgress),
+) -> Result<()> {
+    let Some(ipd) = settings.stereo() else {
+        bail!("Stereo rendering requires --stereo");
+    };
+    let stripe_rows = settings.stripe_rows();
+
+    let renderer = Renderer::new(scene, settings, width, height, camera);
+    render_side_by_side(
+        renderer,
+        &eye_cameras(camera, ipd),
+        path,
+        stripe_rows,
+        on_progress,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FOV, lights::init_default_lights, shapes::init_default_shapes};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn stereo_renders_place_offset_eyes_side_by_side() -> Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let args = ["--stereo", "0.5", "--panorama-size", "8"].map(str::to_owned);
+        let settings = RenderSettings::from_sources(args, |_| None)?;
+        let path = std::env::temp_dir().join(format!("stereo-{}.png", std::process::id()));
+
+        let stripes = AtomicUsize::new(0);
+        render_stereo(
+            scene.clone(),
+            settings.clone(),
+            camera,
+            (12, 8),
+            &path,
+            |progress| {
+                assert_eq!(progress.total_pixels(), 24 * 8);
+                stripes.fetch_add(1, Ordering::Relaxed);
+            },
+        )?;
+        assert!(stripes.load(Ordering::Relaxed) > 0);
+        let (width, height, pixels) = output::read_rgba_png(&path)?;
+        assert_eq!((width, height), (24, 8));
+        assert!(
+            pixels
+                .chunks_exact(24 * 4)
+                .any(|row| row[..12 * 4] != row[12 * 4..])
+        );
+
+        render_panorama(
+            scene.clone(),
+            settings.clone(),
+            camera,
+            PanoramaLayout::Equirect,
+            &path,
+            |_| {},
+        )?;
+        assert_eq!(output::read_rgba_png(&path)?.0, 32);
+        assert!(
+            render_panorama(
+                scene,
+                settings,
+                camera,
+                PanoramaLayout::CubeStrip,
+                &path,
+                |_| {}
+            )
+            .is_err()
+        );
+
+        let rays = camera
+            .with_projection(Projection::Equirectangular)
+            .with_eye_offset(0.5)
+            .ray_generator(16, 8);
+        let (origin, direction) = rays.ray(4.0, 4.0);
+        assert!((origin.length() - 0.5).abs() < 1e-12);
+        assert!(origin.dot(direction).abs() < 1e-12);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,777 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::warn;
+
+use crate::{
+    Vec3f, Vec4f,
+    camera::Camera,
+    geometry::Transform,
+    lights::{AmbientLight, DirectionalLight, LightType, PointLight},
+    materials::{Material, exponent_for_roughness},
+    mesh::TriangleMesh,
+    scene::Scene,
+    scene_file::SceneFile,
+    shapes::{Instance, ShapeType, Sphere},
+};
+
+const DEFAULT_FOV_DEGREES: f64 = 90.0;
+const DEFAULT_RESOLUTION: (f64, f64) = (1280.0, 720.0);
+const DEFAULT_REFLECTANCE: f64 = 0.5;
+const DEFAULT_CONDUCTOR_REFLECTANCE: f64 = 0.9;
+const DEFAULT_ETA: f64 = 1.5;
+const MAX_SPECULAR_EXPONENT: f64 = 1000.0;
+const SIMILARITY_TOLERANCE: f64 = 1e-9;
+
+const IGNORED_DIRECTIVES: [&str; 13] = [
+    "Option",
+    "ColorSpace",
+    "Sampler",
+    "Integrator",
+    "PixelFilter",
+    "Accelerator",
+    "MakeNamedMedium",
+    "MediumInterface",
+    "Attribute",
+    "ReverseOrientation",
+    "TransformTimes",
+    "ActiveTransform",
+    "WorldEnd",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Text(&'a str),
+    Number(f64),
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token<'_>, usize)>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\n' => {
+                line += 1;
+                index += 1;
+            }
+            byte if byte.is_ascii_whitespace() => index += 1,
+            b'#' => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    index += 1;
+                }
+            }
+            b'[' => {
+                tokens.push((Token::Open, line));
+                index += 1;
+            }
+            b']' => {
+                tokens.push((Token::Close, line));
+                index += 1;
+            }
+            b'"' => {
+                let start = index + 1;
+                let end = source[start..]
+                    .find('"')
+                    .map(|offset| start + offset)
+                    .ok_or_else(|| anyhow!("Unterminated string on line {line}"))?;
+                tokens.push((Token::Text(&source[start..end]), line));
+                line += source[start..end].matches('\n').count();
+                index = end + 1;
+            }
+            _ => {
+                let start = index;
+                while index < bytes.len()
+                    && !bytes[index].is_ascii_whitespace()
+                    && !matches!(bytes[index], b'[' | b']' | b'"' | b'#')
+                {
+                    index += 1;
+                }
+                let word = &source[start..index];
+                tokens.push((word.parse().map_or(Token::Word(word), Token::Number), line));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value<'a> {
+    Numbers(Vec<f64>),
+    Texts(Vec<&'a str>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Directive<'a> {
+    name: &'a str,
+    line: usize,
+    args: Vec<Value<'a>>,
+}
+
+fn directives<'a>(tokens: Vec<(Token<'a>, usize)>) -> Result<Vec<Directive<'a>>> {
+    let mut directives: Vec<Directive<'a>> = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    while let Some((token, line)) = tokens.next() {
+        let value = match token {
+            Token::Word(name) => {
+                directives.push(Directive {
+                    name,
+                    line,
+                    args: Vec::new(),
+                });
+                continue;
+            }
+            Token::Number(number) => Value::Numbers(vec![number]),
+            Token::Text(text) => Value::Texts(vec![text]),
+            Token::Open => {
+                let (mut numbers, mut texts) = (Vec::new(), Vec::new());
+                loop {
+                    match tokens.next() {
+                        Some((Token::Close, _)) => break,
+                        Some((Token::Number(number), _)) => numbers.push(number),
+                        Some((Token::Text(text) | Token::Word(text), _)) => texts.push(text),
+                        Some((Token::Open, line)) => bail!("Nested '[' on line {line}"),
+                        None => bail!("Unterminated '[' on line {line}"),
+                    }
+                }
+                match (numbers.is_empty(), texts.is_empty()) {
+                    (_, true) => Value::Numbers(numbers),
+                    (true, false) => Value::Texts(texts),
+                    (false, false) => bail!("Mixed numbers and strings in list on line {line}"),
+                }
+            }
+            Token::Close => bail!("Unexpected ']' on line {line}"),
+        };
+
+        directives
+            .last_mut()
+            .ok_or_else(|| anyhow!("Expected a directive on line {line}"))?
+            .args
+            .push(value);
+    }
+
+    Ok(directives)
+}
+
+struct Params<'a> {
+    entries: Vec<(&'a str, &'a str, Value<'a>)>,
+}
+
+impl<'a> Params<'a> {
+    fn numbers(&self, types: &[&str], name: &str) -> Option<&[f64]> {
+        self.entries
+            .iter()
+            .find(|(kind, key, _)| *key == name && types.contains(kind))
+            .and_then(|(_, _, value)| match value {
+                Value::Numbers(numbers) => Some(numbers.as_slice()),
+                Value::Texts(_) => None,
+            })
+    }
+
+    fn float(&self, name: &str, default: f64) -> f64 {
+        match self.numbers(&["float"], name) {
+            Some([value]) => *value,
+            _ => default,
+        }
+    }
+
+    fn vec3(&self, types: &[&str], name: &str) -> Option<Vec3f> {
+        match self.numbers(types, name)? {
+            [x, y, z] => Some(Vec3f::new_with_data([*x, *y, *z])),
+            _ => None,
+        }
+    }
+
+    fn rgb(&self, name: &str) -> Option<Vec3f> {
+        self.vec3(&["rgb", "color"], name)
+    }
+
+    fn point(&self, name: &str) -> Option<Vec3f> {
+        self.vec3(&["point3", "point"], name)
+    }
+
+    fn text(&self, name: &str) -> Option<&'a str> {
+        self.entries
+            .iter()
+            .find(|(kind, key, _)| *key == name && *kind == "string")
+            .and_then(|(_, _, value)| match value {
+                Value::Texts(texts) => texts.first().copied(),
+                Value::Numbers(_) => None,
+            })
+    }
+
+    fn intensity(&self, name: &str) -> f64 {
+        let color = self.rgb(name).unwrap_or_else(|| Vec3f::new(1.0));
+        (color.x() + color.y() + color.z()) / 3.0 * self.float("scale", 1.0)
+    }
+}
+
+impl<'a> Directive<'a> {
+    fn numbers<const N: usize>(&self) -> Result<[f64; N]> {
+        let numbers: Vec<f64> = self
+            .args
+            .iter()
+            .flat_map(|value| match value {
+                Value::Numbers(numbers) => numbers.clone(),
+                Value::Texts(_) => Vec::new(),
+            })
+            .collect();
+
+        numbers.try_into().map_err(|numbers: Vec<f64>| {
+            anyhow!(
+                "{} expects {N} numbers on line {}, found {}",
+                self.name,
+                self.line,
+                numbers.len()
+            )
+        })
+    }
+
+    fn text(&self) -> Result<&'a str> {
+        match self.args.first() {
+            Some(Value::Texts(texts)) if texts.len() == 1 => Ok(texts[0]),
+            _ => bail!("{} expects a string on line {}", self.name, self.line),
+        }
+    }
+
+    fn params(&self) -> Result<Params<'a>> {
+        let mut entries = Vec::new();
+        let mut args = self.args.iter().skip(1);
+        while let Some(declaration) = args.next() {
+            let Value::Texts(declaration) = declaration else {
+                bail!("Expected a parameter name on line {}", self.line);
+            };
+            let mut parts = declaration
+                .first()
+                .copied()
+                .unwrap_or_default()
+                .split_whitespace();
+            let (Some(kind), Some(name), None) = (parts.next(), parts.next(), parts.next()) else {
+                bail!("Malformed parameter {declaration:?} on line {}", self.line);
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("Missing value for '{name}' on line {}", self.line))?;
+            entries.push((kind, name, value.clone()));
+        }
+
+        Ok(Params { entries })
+    }
+}
+
+const fn diffuse(reflectance: Vec3f) -> Material {
+    Material::new(
+        Vec4f::new_with_data([1.0, 0.0, 0.0, 0.0]),
+        reflectance,
+        reflectance,
+        1.0,
+        1.0,
+    )
+}
+
+fn material(kind: &str, params: &Params<'_>) -> Option<Material> {
+    let reflectance = params
+        .rgb("reflectance")
+        .unwrap_or_else(|| Vec3f::new(DEFAULT_REFLECTANCE));
+
+    match kind {
+        "diffuse" => Some(diffuse(reflectance)),
+        "coateddiffuse" => {
+            let roughness = params.float("roughness", 0.0);
+            let exponent = if roughness > 0.0 {
+                exponent_for_roughness(roughness).clamp(1.0, MAX_SPECULAR_EXPONENT)
+            } else {
+                MAX_SPECULAR_EXPONENT
+            };
+            Some(Material::new(
+                Vec4f::new_with_data([0.9, 0.3, 0.0, 0.0]),
+                reflectance,
+                reflectance,
+                exponent,
+                1.0,
+            ))
+        }
+        "conductor" => {
+            let reflectance = params
+                .rgb("reflectance")
+                .map_or(DEFAULT_CONDUCTOR_REFLECTANCE, |color| {
+                    (color.x() + color.y() + color.z()) / 3.0
+                });
+            Some(Material::new(
+                Vec4f::new_with_data([0.0, 0.0, reflectance, 0.0]),
+                Vec3f::new(1.0),
+                Vec3f::new(0.0),
+                MAX_SPECULAR_EXPONENT,
+                1.0,
+            ))
+        }
+        "dielectric" | "thindielectric" => Some(Material::new(
+            Vec4f::new_with_data([0.0, 0.5, 0.1, 0.8]),
+            Vec3f::new(1.0),
+            Vec3f::new(0.0),
+            MAX_SPECULAR_EXPONENT,
+            params.float("eta", DEFAULT_ETA),
+        )),
+        _ => None,
+    }
+}
+
+fn uniform_scale(transform: &Transform) -> Option<f64> {
+    let rows = transform.rows();
+    let scale = rows[0].length();
+    let similar = (0..3).all(|first| {
+        (0..3).all(|second| {
+            let expected = if first == second { scale * scale } else { 0.0 };
+            (rows[first] * rows[second] - expected).abs() <= SIMILARITY_TOLERANCE * scale * scale
+        })
+    });
+
+    similar.then_some(scale)
+}
+
+fn is_mirrored(transform: &Transform) -> bool {
+    let [x, y, z] = transform.rows();
+    x * y.cross(&z) < 0.0
+}
+
+fn look_at([eye, look, up]: [Vec3f; 3]) -> Option<Transform> {
+    let direction = (look - eye).normalize(None);
+    let right = up.normalize(None).cross(&direction).normalize(None);
+    let up = direction.cross(&right);
+
+    Transform::from_axes([right, up, direction], eye).map(Transform::inverse)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GraphicsState {
+    transform: Transform,
+    material: Material,
+}
+
+struct Importer<'a> {
+    source: &'a Path,
+    handedness: Transform,
+    state: GraphicsState,
+    stack: Vec<GraphicsState>,
+    named_materials: HashMap<&'a str, Material>,
+    camera: Option<(Transform, f64)>,
+    resolution: (f64, f64),
+    object_depth: usize,
+    shapes: Vec<ShapeType>,
+    lights: Vec<LightType>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(source: &'a Path) -> Result<Self> {
+        let mirror = Transform::from_axes(
+            [
+                Vec3f::new_with_data([-1.0, 0.0, 0.0]),
+                Vec3f::new_with_data([0.0, 1.0, 0.0]),
+                Vec3f::new_with_data([0.0, 0.0, 1.0]),
+            ],
+            Vec3f::new(0.0),
+        )
+        .context("Failed to build the handedness transform")?;
+
+        Ok(Self {
+            source,
+            handedness: mirror,
+            state: GraphicsState {
+                transform: Transform::IDENTITY,
+                material: diffuse(Vec3f::new(DEFAULT_REFLECTANCE)),
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            camera: None,
+            resolution: DEFAULT_RESOLUTION,
+            object_depth: 0,
+            shapes: Vec::new(),
+            lights: Vec::new(),
+            warnings: Vec::new(),
+        })
+    }
+
+    fn apply(&mut self, transform: Option<Transform>, directive: &Directive<'_>) -> Result<()> {
+        let transform = transform.ok_or_else(|| {
+            anyhow!(
+                "Degenerate {} transform on line {}",
+                directive.name,
+                directive.line
+            )
+        })?;
+        self.state.transform = transform.then(self.state.transform);
+        Ok(())
+    }
+
+    fn skip(&mut self, what: &str, directive: &Directive<'_>) {
+        self.warnings.push(format!(
+            "Skipping unsupported {what} on line {}",
+            directive.line
+        ));
+    }
+
+    fn matrix(directive: &Directive<'_>) -> Result<Option<Transform>> {
+        let values: [f64; 16] = directive.numbers()?;
+        let column = |index: usize| {
+            Vec3f::new_with_data([
+                values[index * 4],
+                values[index * 4 + 1],
+                values[index * 4 + 2],
+            ])
+        };
+        Ok(Transform::from_axes(
+            [column(0), column(1), column(2)],
+            column(3),
+        ))
+    }
+
+    fn transform(&mut self, directive: &Directive<'_>) -> Result<()> {
+        match directive.name {
+            "Identity" => self.state.transform = Transform::IDENTITY,
+            "Translate" => {
+                let offset = Vec3f::new_with_data(directive.numbers()?);
+                self.apply(Some(Transform::from_translation(offset)), directive)?;
+            }
+            "Scale" => {
+                let [x, y, z] = directive.numbers()?;
+                let scale = Transform::from_axes(
+                    [
+                        Vec3f::new_with_data([x, 0.0, 0.0]),
+                        Vec3f::new_with_data([0.0, y, 0.0]),
+                        Vec3f::new_with_data([0.0, 0.0, z]),
+                    ],
+                    Vec3f::new(0.0),
+                );
+                self.apply(scale, directive)?;
+            }
+            "Rotate" => {
+                let [angle, x, y, z] = directive.numbers()?;
+                let axis = Vec3f::new_with_data([x, y, z]);
+                self.apply(
+                    Some(Transform::from_rotation(axis, angle.to_radians())),
+                    directive,
+                )?;
+            }
+            "LookAt" => {
+                let values: [f64; 9] = directive.numbers()?;
+                let vector = |start: usize| {
+                    Vec3f::new_with_data([values[start], values[start + 1], values[start + 2]])
+                };
+                self.apply(look_at([vector(0), vector(3), vector(6)]), directive)?;
+            }
+            "ConcatTransform" => self.apply(Self::matrix(directive)?, directive)?,
+            "Transform" => {
+                self.state.transform = Transform::IDENTITY;
+                self.apply(Self::matrix(directive)?, directive)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &Directive<'a>) -> Result<()> {
+        match directive.name {
+            "Identity" | "Translate" | "Scale" | "Rotate" | "LookAt" | "ConcatTransform"
+            | "Transform" => self.transform(directive)?,
+            "Camera" => {
+                let kind = directive.text()?;
+                if kind != "perspective" {
+                    self.skip(&format!("camera '{kind}', using perspective"), directive);
+                }
+                let fov = directive.params()?.float("fov", DEFAULT_FOV_DEGREES);
+                self.camera = Some((self.state.transform, fov));
+                if is_mirrored(&self.state.transform) {
+                    self.handedness = Transform::IDENTITY;
+                }
+            }
+            "Film" => {
+                let params = directive.params()?;
+                let size = |name: &str, default: f64| match params.numbers(&["integer"], name) {
+                    Some([value]) => *value,
+                    _ => default,
+                };
+                self.resolution = (
+                    size("xresolution", DEFAULT_RESOLUTION.0),
+                    size("yresolution", DEFAULT_RESOLUTION.1),
+                );
+            }
+            "WorldBegin" => self.state.transform = self.handedness,
+            "AttributeBegin" | "TransformBegin" => self.stack.push(self.state),
+            "AttributeEnd" | "TransformEnd" => {
+                self.state = self.stack.pop().ok_or_else(|| {
+                    anyhow!("Unmatched {} on line {}", directive.name, directive.line)
+                })?;
+            }
+            "ObjectBegin" => {
+                self.skip("object instancing", directive);
+                self.stack.push(self.state);
+                self.object_depth += 1;
+            }
+            "ObjectEnd" => {
+                self.state = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| anyhow!("Unmatched ObjectEnd on line {}", directive.line))?;
+                self.object_depth = self.object_depth.saturating_sub(1);
+            }
+            "Material" => {
+                let kind = directive.text()?;
+                match material(kind, &directive.params()?) {
+                    Some(material) => self.state.material = material,
+                    None => self.skip(&format!("material '{kind}'"), directive),
+                }
+            }
+            "MakeNamedMaterial" => {
+                let name = directive.text()?;
+                let params = directive.params()?;
+                let kind = params.text("type").unwrap_or_default();
+                match material(kind, &params) {
+                    Some(material) => {
+                        self.named_materials.insert(name, material);
+                    }
+                    None => self.skip(&format!("material '{kind}'"), directive),
+                }
+            }
+            "NamedMaterial" => {
+                let name = directive.text()?;
+                if let Some(material) = self.named_materials.get(name) {
+                    self.state.material = *material;
+                } else {
+                    self.skip(&format!("named material '{name}'"), directive);
+                }
+            }
+            "LightSource" => self.light(directive)?,
+            "Shape" => self.shape(directive)?,
+            "AreaLightSource" => self.skip("area light emission", directive),
+            "Texture" | "Include" | "Import" | "ObjectInstance" | "CoordinateSystem"
+            | "CoordSysTransform" => self.skip(directive.name, directive),
+            name if IGNORED_DIRECTIVES.contains(&name) => {}
+            name => bail!("Unknown PBRT directive '{name}' on line {}", directive.line),
+        }
+
+        Ok(())
+    }
+
+    fn light(&mut self, directive: &Directive<'a>) -> Result<()> {
+        let kind = directive.text()?;
+        let params = directive.params()?;
+        let transform = self.state.transform;
+
+        let light = match kind {
+            "point" => LightType::Point(PointLight::new(
+                params.intensity("I"),
+                transform.apply_point(params.point("from").unwrap_or_else(|| Vec3f::new(0.0))),
+            )),
+            "distant" => {
+                let from = params.point("from").unwrap_or_else(|| Vec3f::new(0.0));
+                let to = params
+                    .point("to")
+                    .unwrap_or_else(|| Vec3f::new_with_data([0.0, 0.0, 1.0]));
+                LightType::Directional(DirectionalLight::new(
+                    params.intensity("L"),
+                    transform.apply_vector(from - to),
+                ))
+            }
+            "infinite" => {
+                if params.text("filename").is_some() {
+                    self.skip("environment map, using its scale", directive);
+                }
+                LightType::Ambient(AmbientLight::new(params.intensity("L")))
+            }
+            _ => {
+                self.skip(&format!("light '{kind}'"), directive);
+                return Ok(());
+            }
+        };
+
+        self.lights.push(light);
+        Ok(())
+    }
+
+    fn shape(&mut self, directive: &Directive<'a>) -> Result<()> {
+        let kind = directive.text()?;
+        if self.object_depth > 0 {
+            return Ok(());
+        }
+        let params = directive.params()?;
+        let GraphicsState {
+            transform,
+            material,
+        } = self.state;
+
+        let shape = match kind {
+            "sphere" => {
+                let radius = params.float("radius", 1.0);
+                uniform_scale(&transform).map_or_else(
+                    || {
+                        ShapeType::Instance(Instance::new(
+                            Arc::new(ShapeType::Sphere(Sphere::new(
+                                Vec3f::new(0.0),
+                                radius,
+                                material,
+                            ))),
+                            transform,
+                        ))
+                    },
+                    |scale| {
+                        ShapeType::Sphere(Sphere::new(
+                            transform.apply_point(Vec3f::new(0.0)),
+                            radius * scale,
+                            material,
+                        ))
+                    },
+                )
+            }
+            "trianglemesh" => {
+                let points = params
+                    .numbers(&["point3", "point"], "P")
+                    .unwrap_or_default();
+                let positions = points
+                    .chunks_exact(3)
+                    .map(|point| {
+                        transform.apply_point(Vec3f::new_with_data([point[0], point[1], point[2]]))
+                    })
+                    .collect::<Vec<_>>();
+                let indices = match params.numbers(&["integer"], "indices") {
+                    Some(indices) => indices
+                        .chunks_exact(3)
+                        .map(|triangle| {
+                            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                            [0, 1, 2].map(|corner| triangle[corner] as usize)
+                        })
+                        .collect::<Vec<_>>(),
+                    None if positions.len() == 3 => vec![[0, 1, 2]],
+                    None => bail!("trianglemesh without indices on line {}", directive.line),
+                };
+                let mesh = TriangleMesh::from_triangles(
+                    positions,
+                    &indices,
+                    self.source.to_path_buf(),
+                    material,
+                )
+                .with_context(|| format!("Invalid trianglemesh on line {}", directive.line))?;
+                ShapeType::Mesh(
+                    mesh.with_smooth_shading(params.numbers(&["normal"], "N").is_some()),
+                )
+            }
+            _ => {
+                self.skip(&format!("shape '{kind}'"), directive);
+                return Ok(());
+            }
+        };
+
+        self.shapes.push(shape);
+        Ok(())
+    }
+
+    fn finish(self) -> (SceneFile, Vec<String>) {
+        let (width, height) = self.resolution;
+        let (camera_from_world, fov) = self
+            .camera
+            .unwrap_or((Transform::IDENTITY, DEFAULT_FOV_DEGREES));
+        let fov = fov.to_radians();
+        let fov = if width < height {
+            2.0 * ((fov / 2.0).tan() * height / width).atan()
+        } else {
+            fov
+        };
+
+        let axis = |data: [f64; 3]| {
+            self.handedness
+                .apply_vector(camera_from_world.invert_vector(Vec3f::new_with_data(data)))
+        };
+        let position = self
+            .handedness
+            .apply_point(camera_from_world.invert_point(Vec3f::new(0.0)));
+        let camera =
+            Camera::new(position, fov).oriented(axis([0.0, 0.0, 1.0]), axis([0.0, 1.0, 0.0]));
+
+        (
+            SceneFile::new(Scene::new(self.shapes, self.lights), camera),
+            self.warnings,
+        )
+    }
+}
+
+pub fn parse(source: &str, path: &Path) -> Result<(SceneFile, Vec<String>)> {
+    let mut importer = Importer::new(path)?;
+    for directive in directives(tokenize(source)?)? {
+        importer.directive(&directive)?;
+    }
+
+    Ok(importer.finish())
+}
+
+pub fn load(path: &Path) -> Result<SceneFile> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read PBRT scene {}", path.display()))?;
+    let (scene_file, warnings) = parse(&source, &PathBuf::from(path))
+        .with_context(|| format!("Failed to parse PBRT scene {}", path.display()))?;
+    for warning in warnings {
+        warn!("{}: {warning}", path.display());
+    }
+
+    Ok(scene_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        FOV,
+        export::{ExportFormat, SceneExport},
+        lights::init_default_lights,
+        shapes::init_default_shapes,
+    };
+
+    #[test]
+    fn exported_scenes_import_back_unmirrored_and_pbrt_handedness_is_flipped() -> Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new_with_data([0.5, 0.0, 2.0]), FOV);
+        let exported =
+            SceneExport::new(&scene, &camera, (800, 600), ExportFormat::Pbrt).to_string();
+
+        let (imported, warnings) = parse(&exported, Path::new("scene.pbrt"))?;
+        assert!(warnings.is_empty());
+        assert_eq!(imported.scene().shapes().len(), scene.shapes().len());
+        assert_eq!(imported.scene().lights().len(), scene.lights().len());
+        assert!((imported.camera().fov() - FOV).abs() < 1e-9);
+        assert!((imported.camera().position().x() - 0.5).abs() < 1e-9);
+
+        let (ShapeType::Sphere(original), ShapeType::Sphere(sphere)) =
+            (&scene.shapes()[0], &imported.scene().shapes()[0])
+        else {
+            panic!("first shape should stay a sphere");
+        };
+        assert!((sphere.radius() - original.radius()).abs() < 1e-9);
+        assert!((sphere.center() - original.center()).length() < 1e-9);
+
+        let (native, warnings) = parse(
+            "LookAt 0 0 0  0 0 1  0 1 0\nCamera \"perspective\"\nWorldBegin\n\
+             Translate 1 0 5\nShape \"sphere\"\nShape \"disk\" \"float radius\" [1]\n",
+            Path::new("native.pbrt"),
+        )?;
+        assert_eq!(warnings.len(), 1);
+        let ShapeType::Sphere(sphere) = &native.scene().shapes()[0] else {
+            panic!("pbrt sphere should import as a sphere");
+        };
+        let right = native.camera().forward().cross(&native.camera().up());
+        assert!((right * sphere.center() - 1.0).abs() < 1e-9);
+        assert!(parse("WorldBegin\nFrobnicate 1\n", Path::new("bad.pbrt")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{PI, Vec3f};
+
+/// Cone filter slope from Jensen's photon mapping; 1 weights photons down to zero at the
+/// edge of the gather radius.
+const CONE_FILTER: f64 = 1.0;
+
+/// A packet of light that reached a diffuse surface after at least one mirror or glass bounce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Photon {
+    position: Vec3f,
+    direction: Vec3f,
+    power: Vec3f,
+}
+
+impl Photon {
+    pub const fn new(position: Vec3f, direction: Vec3f, power: Vec3f) -> Self {
+        Self {
+            position,
+            direction,
+            power,
+        }
+    }
+
+    pub const fn position(&self) -> Vec3f {
+        self.position
+    }
+
+    /// The direction the photon was travelling in when it landed.
+    pub const fn direction(&self) -> Vec3f {
+        self.direction
+    }
+
+    pub const fn power(&self) -> Vec3f {
+        self.power
+    }
+}
+
+/// Photons in a balanced KD-tree stored in place: the middle photon of every range splits
+/// the rest of the range along that photon's axis.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+    radius: f64,
+}
+
+impl PhotonMap {
+    /// Builds the tree. `radius` is how far around a shading point photons are gathered.
+    pub fn new(mut photons: Vec<Photon>, radius: f64) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+
+        Self {
+            photons,
+            axes,
+            radius,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    pub const fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Calls `visit` with every photon within the gather radius of `point` and its
+    /// squared distance.
+    pub fn gather(&self, point: Vec3f, mut visit: impl FnMut(&Photon, f64)) {
+        self.gather_range(0, self.photons.len(), point, &mut visit);
+    }
+
+    fn gather_range(
+        &self,
+        start: usize,
+        end: usize,
+        point: Vec3f,
+        visit: &mut impl FnMut(&Photon, f64),
+    ) {
+        if start >= end {
+            return;
+        }
+
+        let middle = start + (end - start) / 2;
+        let photon = &self.photons[middle];
+        let offset = point - photon.position;
+        let distance_squared = offset * offset;
+        if distance_squared <= self.radius * self.radius {
+            visit(photon, distance_squared);
+        }
+
+        let axis = self.axes[middle];
+        let (near, far) = if offset[axis] < 0.0 {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.gather_range(near.0, near.1, point, visit);
+        if offset[axis].abs() <= self.radius {
+            self.gather_range(far.0, far.1, point, visit);
+        }
+    }
+
+    /// Irradiance at `point` estimated from the density of nearby photons arriving on the
+    /// side `normal` faces, cone-filtered so the estimate doesn't blur across edges as much.
+    pub fn irradiance(&self, point: Vec3f, normal: Vec3f) -> Vec3f {
+        let mut total = Vec3f::new(0.0);
+        self.gather(point, |photon, distance_squared| {
+            if photon.direction * normal < 0.0 {
+                let weight = 1.0 - distance_squared.sqrt() / (CONE_FILTER * self.radius);
+                total += photon.power * weight;
+            }
+        });
+
+        let normalization = 1.0 - 2.0 / (3.0 * CONE_FILTER);
+        total / (normalization * PI * self.radius * self.radius)
+    }
+}
+
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    let (min, max) = photons.iter().fold(
+        (Vec3f::new(f64::INFINITY), Vec3f::new(f64::NEG_INFINITY)),
+        |(min, max), photon| {
+            let position = photon.position;
+            (
+                Vec3f::new_with_data([0, 1, 2].map(|axis| min[axis].min(position[axis]))),
+                Vec3f::new_with_data([0, 1, 2].map(|axis| max[axis].max(position[axis]))),
+            )
+        },
+    );
+    let extent = max - min;
+    let split_axis = (0..3)
+        .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+        .unwrap_or(0);
+
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        a.position[split_axis].total_cmp(&b.position[split_axis])
+    });
+    axes[middle] = split_axis;
+
+    let (left, right) = photons.split_at_mut(middle);
+    let (left_axes, right_axes) = axes.split_at_mut(middle);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gathering_finds_exactly_the_photons_within_the_radius() {
+        let down = Vec3f::new_with_data([0.0, -1.0, 0.0]);
+        let photons: Vec<_> = (0..20)
+            .flat_map(|x| (0..20).map(move |z| (x, z)))
+            .map(|(x, z)| {
+                let position = Vec3f::new_with_data([f64::from(x) * 0.1, 0.0, f64::from(z) * 0.1]);
+                Photon::new(position, down, Vec3f::new(1.0))
+            })
+            .collect();
+        let map = PhotonMap::new(photons.clone(), 0.25);
+        assert_eq!(map.len(), 400);
+
+        let point = Vec3f::new_with_data([1.03, 0.0, 0.97]);
+        let mut found = Vec::new();
+        map.gather(point, |photon, _| found.push(photon.position()));
+        let expected = photons
+            .iter()
+            .filter(|photon| (photon.position() - point).length() <= 0.25)
+            .count();
+        assert_eq!(found.len(), expected);
+        assert!(expected > 0);
+
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        assert!(map.irradiance(point, up)[0] > 0.0);
+        assert_eq!(map.irradiance(point, down), Vec3f::new(0.0));
+        assert_eq!(
+            PhotonMap::new(Vec::new(), 0.25).irradiance(point, up),
+            Vec3f::new(0.0)
+        );
+    }
+}
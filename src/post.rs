@@ -0,0 +1,439 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::Vec3f;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Scales colors with a channel above 1 down until that channel is 1, keeping their hue.
+    #[default]
+    Normalize,
+    Clamp,
+    Reinhard,
+}
+
+impl ToneMapping {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "normalize" => Ok(Self::Normalize),
+            "clamp" => Ok(Self::Clamp),
+            "reinhard" => Ok(Self::Reinhard),
+            _ => bail!("Unknown tone mapping: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Normalize => "normalize",
+            Self::Clamp => "clamp",
+            Self::Reinhard => "reinhard",
+        }
+    }
+
+    pub fn apply(self, color: Vec3f) -> Vec3f {
+        match self {
+            Self::Normalize => {
+                let color = color.max(Vec3f::new(0.0));
+                let brightest = color[0].max(color[1]).max(color[2]);
+                if brightest > 1.0 {
+                    color / brightest
+                } else {
+                    color
+                }
+            }
+            Self::Clamp => Vec3f::new_with_data([
+                color[0].clamp(0.0, 1.0),
+                color[1].clamp(0.0, 1.0),
+                color[2].clamp(0.0, 1.0),
+            ]),
+            Self::Reinhard => Vec3f::new_with_data([
+                color[0].max(0.0) / (1.0 + color[0].max(0.0)),
+                color[1].max(0.0) / (1.0 + color[1].max(0.0)),
+                color[2].max(0.0) / (1.0 + color[2].max(0.0)),
+            ]),
+        }
+    }
+}
+
+/// Glow around bright pixels: everything above `threshold` is blurred with a Gaussian
+/// reaching `radius` pixels and added back on top of the frame, scaled by `strength`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    threshold: f64,
+    radius: u32,
+    strength: f64,
+}
+
+impl Bloom {
+    pub const DEFAULT_RADIUS: u32 = 8;
+    pub const DEFAULT_STRENGTH: f64 = 0.5;
+
+    pub fn new(threshold: f64, radius: u32, strength: f64) -> Result<Self> {
+        if threshold.is_nan() || threshold < 0.0 {
+            bail!("Bloom threshold must not be negative");
+        }
+        if radius == 0 {
+            bail!("Bloom radius must be at least 1 pixel");
+        }
+        if strength.is_nan() || strength < 0.0 {
+            bail!("Bloom strength must not be negative");
+        }
+
+        Ok(Self {
+            threshold,
+            radius,
+            strength,
+        })
+    }
+
+    pub const fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub const fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    pub const fn strength(&self) -> f64 {
+        self.strength
+    }
+
+    /// Adds the glow to a row-major HDR frame of `width` x `height` radiance values.
+    pub fn apply(&self, frame: &mut [Vec3f], (width, height): (u32, u32)) {
+        let bright: Vec<_> = frame
+            .iter()
+            .map(|color| {
+                Vec3f::new_with_data(
+                    [0, 1, 2].map(|channel| (color[channel] - self.threshold).max(0.0)),
+                )
+            })
+            .collect();
+
+        // Rows then columns; zero outside the frame so the glow fades at the borders.
+        let kernel = self.kernel();
+        let horizontal = self.blur(&bright, &kernel, (width, height), (1, 0));
+        let glow = self.blur(&horizontal, &kernel, (width, height), (0, 1));
+
+        for (color, glow) in frame.iter_mut().zip(glow) {
+            *color += glow * self.strength;
+        }
+    }
+
+    /// One pass of a separable blur along `(step_x, step_y)`.
+    fn blur(
+        &self,
+        source: &[Vec3f],
+        kernel: &[f64],
+        (width, height): (u32, u32),
+        (step_x, step_y): (i64, i64),
+    ) -> Vec<Vec3f> {
+        let radius = i64::from(self.radius);
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                kernel
+                    .iter()
+                    .zip(-radius..)
+                    .fold(Vec3f::new(0.0), |sum, (&weight, offset)| {
+                        let (Ok(tap_x), Ok(tap_y)) = (
+                            u32::try_from(i64::from(x) + offset * step_x),
+                            u32::try_from(i64::from(y) + offset * step_y),
+                        ) else {
+                            return sum;
+                        };
+                        if tap_x >= width || tap_y >= height {
+                            return sum;
+                        }
+                        sum + source[(tap_y * width + tap_x) as usize] * weight
+                    })
+            })
+            .collect()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn kernel(&self) -> Vec<f64> {
+        let radius = i64::from(self.radius);
+        let sigma = f64::from(self.radius) / 3.0;
+        let weights: Vec<_> = (-radius..=radius)
+            .map(|offset| (-((offset * offset) as f64) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        weights.into_iter().map(|weight| weight / total).collect()
+    }
+}
+
+impl FromStr for Bloom {
+    type Err = anyhow::Error;
+
+    /// Parses `THRESHOLD[,RADIUS[,STRENGTH]]`.
+    fn from_str(value: &str) -> Result<Self> {
+        let mut parts = value.split(',');
+        let threshold = parts.next().unwrap_or_default();
+        let threshold = threshold
+            .parse()
+            .with_context(|| format!("Invalid bloom threshold: {threshold}"))?;
+        let radius = parts.next().map_or(Ok(Self::DEFAULT_RADIUS), |radius| {
+            radius
+                .parse()
+                .with_context(|| format!("Invalid bloom radius: {radius}"))
+        })?;
+        let strength = parts
+            .next()
+            .map_or(Ok(Self::DEFAULT_STRENGTH), |strength| {
+                strength
+                    .parse()
+                    .with_context(|| format!("Invalid bloom strength: {strength}"))
+            })?;
+        if parts.next().is_some() {
+            bail!("Expected THRESHOLD[,RADIUS[,STRENGTH]], got {value}");
+        }
+
+        Self::new(threshold, radius, strength)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: Vec3f,
+    domain_max: Vec3f,
+    table: Vec<Vec3f>,
+}
+
+impl Lut3d {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LUT file {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("Failed to parse LUT file {}", path.display()))
+    }
+
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = Vec3f::new(0.0);
+        let mut domain_max = Vec3f::new(1.0);
+        let mut table = Vec::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => bail!("1D LUTs are not supported"),
+                "LUT_3D_SIZE" => {
+                    let value = tokens
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing LUT_3D_SIZE value"))?;
+                    let parsed: usize = value
+                        .parse()
+                        .with_context(|| format!("Invalid LUT_3D_SIZE: {value}"))?;
+                    if parsed < 2 {
+                        bail!("LUT_3D_SIZE must be at least 2, got {parsed}");
+                    }
+                    size = Some(parsed);
+                }
+                "DOMAIN_MIN" => domain_min = parse_triplet(tokens, line_number)?,
+                "DOMAIN_MAX" => domain_max = parse_triplet(tokens, line_number)?,
+                // Other keywords, like Resolve's LUT_3D_INPUT_RANGE, don't change the table.
+                _ if keyword.starts_with(|first: char| first.is_ascii_uppercase()) => {}
+                _ => table.push(parse_triplet(line.split_whitespace(), line_number)?),
+            }
+        }
+
+        let size = size.ok_or_else(|| anyhow!("Missing LUT_3D_SIZE"))?;
+        let entries = size
+            .checked_mul(size)
+            .and_then(|entries| entries.checked_mul(size))
+            .ok_or_else(|| anyhow!("LUT_3D_SIZE {size} is too large"))?;
+        if table.len() != entries {
+            bail!("Expected {entries} LUT entries, found {}", table.len());
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> Vec3f {
+        self.table[r + self.size * (g + self.size * b)]
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(&self, color: Vec3f) -> Vec3f {
+        let max_index = (self.size - 1) as f64;
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+
+        for axis in 0..3 {
+            let range = self.domain_max[axis] - self.domain_min[axis];
+            let normalized = ((color[axis] - self.domain_min[axis]) / range).clamp(0.0, 1.0);
+            let scaled = normalized * max_index;
+            let floor = scaled.floor().min(max_index - 1.0);
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                base[axis] = floor as usize;
+            }
+            fraction[axis] = scaled - floor;
+        }
+
+        let [r, g, b] = base;
+        let [fr, fg, fb] = fraction;
+
+        let lerp = |a: Vec3f, b: Vec3f, t: f64| a * (1.0 - t) + b * t;
+
+        let c00 = lerp(self.entry(r, g, b), self.entry(r + 1, g, b), fr);
+        let c10 = lerp(self.entry(r, g + 1, b), self.entry(r + 1, g + 1, b), fr);
+        let c01 = lerp(self.entry(r, g, b + 1), self.entry(r + 1, g, b + 1), fr);
+        let c11 = lerp(
+            self.entry(r, g + 1, b + 1),
+            self.entry(r + 1, g + 1, b + 1),
+            fr,
+        );
+
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+}
+
+fn parse_triplet<'a>(tokens: impl Iterator<Item = &'a str>, line_number: usize) -> Result<Vec3f> {
+    let values = tokens
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .with_context(|| format!("Invalid number '{token}' on line {}", line_number + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let [r, g, b] = values[..] else {
+        bail!("Expected 3 values on line {}", line_number + 1);
+    };
+
+    Ok(Vec3f::new_with_data([r, g, b]))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PostProcess {
+    tone_mapping: ToneMapping,
+    exposure: f64,
+    bloom: Option<Bloom>,
+    lut: Option<Lut3d>,
+}
+
+impl PostProcess {
+    pub const fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Scales radiance by `2^stops` before tone mapping.
+    pub const fn set_exposure(&mut self, stops: f64) {
+        self.exposure = stops;
+    }
+
+    pub const fn set_bloom(&mut self, bloom: Option<Bloom>) {
+        self.bloom = bloom;
+    }
+
+    pub fn set_lut(&mut self, lut: Option<Lut3d>) {
+        self.lut = lut;
+    }
+
+    pub const fn exposure(&self) -> f64 {
+        self.exposure
+    }
+
+    /// Bloom works on whole HDR frames, so renders that use it can't be shaded pixel by
+    /// pixel straight into the output.
+    pub const fn bloom(&self) -> Option<&Bloom> {
+        self.bloom.as_ref()
+    }
+
+    pub fn apply(&self, color: Vec3f) -> Vec3f {
+        let mapped = self.tone_mapping.apply(color * self.exposure.exp2());
+
+        self.lut.as_ref().map_or(mapped, |lut| lut.apply(mapped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bloom_spreads_only_the_light_above_the_threshold() -> Result<()> {
+        let (width, height) = (9, 9);
+        let mut frame = vec![Vec3f::new(0.5); 81];
+        frame[4 * 9 + 4] = Vec3f::new(5.0);
+
+        "1.0,3,1.0"
+            .parse::<Bloom>()?
+            .apply(&mut frame, (width, height));
+
+        // The excess 4.0 is spread around the hot pixel without gaining or losing energy,
+        // on top of the 4.5 the hot pixel already had over the others.
+        let added: f64 = frame.iter().map(|color| color[0] - 0.5).sum();
+        assert!((added - 4.5 - 4.0).abs() < 1e-9, "{added}");
+        assert!(frame[4 * 9 + 5][0] > 0.5 && frame[3 * 9 + 4][0] > 0.5);
+        assert!(frame[4 * 9 + 4][0] > 5.0);
+        assert!((frame[0][0] - 0.5).abs() < 1e-12);
+
+        assert!("1.0,0".parse::<Bloom>().is_err());
+        assert!("-1".parse::<Bloom>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn exposure_is_applied_in_stops_before_tone_mapping() {
+        let mut post = PostProcess::default();
+        post.set_exposure(1.0);
+        assert_eq!(post.apply(Vec3f::new(0.25)), Vec3f::new(0.5));
+        assert_eq!(post.apply(Vec3f::new(0.75)), Vec3f::new(1.0));
+    }
+
+    #[test]
+    fn normalizing_keeps_the_hue_of_colors_brighter_than_white() {
+        let bright = Vec3f::new_with_data([2.0, 1.0, 0.5]);
+        assert_eq!(
+            ToneMapping::Normalize.apply(bright),
+            Vec3f::new_with_data([1.0, 0.5, 0.25])
+        );
+        assert_eq!(
+            ToneMapping::Clamp.apply(bright),
+            Vec3f::new_with_data([1.0, 1.0, 0.5])
+        );
+
+        let dim = Vec3f::new_with_data([0.2, 0.4, -0.1]);
+        assert_eq!(
+            ToneMapping::Normalize.apply(dim),
+            Vec3f::new_with_data([0.2, 0.4, 0.0])
+        );
+    }
+
+    #[test]
+    fn luts_skip_unknown_keywords_and_reject_oversized_tables() -> Result<()> {
+        let inverted = "TITLE \"invert\"\n\
+                        LUT_3D_SIZE 2\n\
+                        LUT_3D_INPUT_RANGE 0.0 1.0\n\
+                        1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut3d::parse(inverted)?;
+        assert_eq!(
+            lut.apply(Vec3f::new_with_data([1.0, 0.0, 0.25])),
+            Vec3f::new_with_data([0.0, 1.0, 0.75])
+        );
+
+        let oversized = format!("LUT_3D_SIZE {}\n0 0 0\n", usize::MAX / 2);
+        assert!(Lut3d::parse(&oversized).is_err());
+        assert!(Lut3d::parse("LUT_3D_SIZE 2\nlut_3d_input_range 0 1\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashSet, fmt, fs, path::Path, thread};
+
+use anyhow::{Context, Result, bail};
+use log::warn;
+
+const LOW_NICE: i32 = 10;
+const IDLE_NICE: i32 = 19;
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThreadPriority {
+    nice: i32,
+}
+
+impl ThreadPriority {
+    pub const NORMAL: Self = Self { nice: 0 };
+    pub const LOW: Self = Self { nice: LOW_NICE };
+    pub const IDLE: Self = Self { nice: IDLE_NICE };
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "normal" => Ok(Self::NORMAL),
+            "low" => Ok(Self::LOW),
+            "idle" => Ok(Self::IDLE),
+            _ => {
+                let nice = name
+                    .parse()
+                    .with_context(|| format!("Unknown priority: {name}"))?;
+                if !(0..=IDLE_NICE).contains(&nice) {
+                    bail!("Nice level must be between 0 and {IDLE_NICE}, got {nice}");
+                }
+                Ok(Self { nice })
+            }
+        }
+    }
+
+    pub const fn nice(self) -> i32 {
+        self.nice
+    }
+
+    #[cfg(unix)]
+    pub fn apply_to_current_thread(self) -> Result<()> {
+        // SAFETY: setpriority only reads its integer arguments. On Linux `who == 0`
+        // targets the calling thread, elsewhere it lowers the whole process.
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, self.nice) };
+        if result == -1 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to set nice level {}", self.nice));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply_to_current_thread(self) -> Result<()> {
+        if self != Self::NORMAL {
+            bail!("Thread priorities are only supported on Unix");
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ThreadPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NORMAL => write!(f, "normal"),
+            Self::LOW => write!(f, "low"),
+            Self::IDLE => write!(f, "idle"),
+            Self { nice } => write!(f, "nice {nice}"),
+        }
+    }
+}
+
+fn count_cores(cpu_dir: &Path) -> Option<usize> {
+    let cores: HashSet<String> = fs::read_dir(cpu_dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("cpu"))
+                .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .filter_map(|entry| {
+            fs::read_to_string(entry.path().join("topology/thread_siblings_list")).ok()
+        })
+        .map(|siblings| siblings.trim().to_string())
+        .collect();
+
+    (!cores.is_empty()).then_some(cores.len())
+}
+
+/// Physical cores the process may run on. sysfs counts every core in the machine, so
+/// the count is capped by what CPU quotas and affinity leave this process.
+pub fn physical_cores() -> usize {
+    let available = thread::available_parallelism().ok().map(usize::from);
+    match (count_cores(Path::new(CPU_DIR)), available) {
+        (Some(cores), Some(available)) => cores.min(available),
+        (cores, available) => cores.or(available).unwrap_or(1),
+    }
+}
+
+pub fn configure_global_pool(threads: Option<usize>, priority: ThreadPriority) -> Result<()> {
+    if threads.is_none() && priority == ThreadPriority::NORMAL {
+        return Ok(());
+    }
+
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = threads {
+        builder = builder.num_threads(threads);
+    }
+    if priority != ThreadPriority::NORMAL {
+        builder = builder.start_handler(move |_| {
+            if let Err(err) = priority.apply_to_current_thread() {
+                warn!("{err:#}");
+            }
+        });
+    }
+
+    builder
+        .build_global()
+        .context("Failed to configure the render thread pool")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_parse_and_lower_worker_threads() -> Result<()> {
+        assert_eq!(ThreadPriority::from_name("idle")?, ThreadPriority::IDLE);
+        assert_eq!(ThreadPriority::from_name("5")?.nice(), 5);
+        assert!(ThreadPriority::from_name("-5").is_err());
+        assert!(ThreadPriority::from_name("urgent").is_err());
+        assert_eq!(ThreadPriority::LOW.to_string(), "low");
+
+        let cores = physical_cores();
+        let logical = thread::available_parallelism().map_or(1, usize::from);
+        assert!((1..=logical).contains(&cores));
+
+        thread::spawn(|| ThreadPriority::LOW.apply_to_current_thread())
+            .join()
+            .map_err(|_| anyhow::anyhow!("priority thread panicked"))??;
+        Ok(())
+    }
+}
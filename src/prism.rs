@@ -0,0 +1,378 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    EPSILON, Material, Vec3f,
+    bvh::Aabb,
+    geometry::Transform,
+    shapes::{Hit, Intersectable, Shape},
+};
+
+const CAP_TOLERANCE: f64 = 1e-6;
+
+type Point2 = [f64; 2];
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polygon {
+    rings: Vec<Vec<Point2>>,
+    orientations: Vec<f64>,
+}
+
+fn signed_area(ring: &[Point2]) -> f64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a[0].mul_add(b[1], -(b[0] * a[1])))
+        .sum::<f64>()
+        / 2.0
+}
+
+fn segment_distance(point: Point2, [a, b]: [Point2; 2]) -> f64 {
+    let edge = [b[0] - a[0], b[1] - a[1]];
+    let offset = [point[0] - a[0], point[1] - a[1]];
+    let length_squared = edge[0].mul_add(edge[0], edge[1] * edge[1]);
+    let along = (offset[0].mul_add(edge[0], offset[1] * edge[1]) / length_squared).clamp(0.0, 1.0);
+    edge[0]
+        .mul_add(-along, offset[0])
+        .hypot(edge[1].mul_add(-along, offset[1]))
+}
+
+impl Polygon {
+    pub fn new(outline: Vec<Point2>, holes: Vec<Vec<Point2>>) -> Result<Self> {
+        let rings: Vec<_> = std::iter::once(outline).chain(holes).collect();
+        let mut orientations = Vec::with_capacity(rings.len());
+        for (index, ring) in rings.iter().enumerate() {
+            if ring.len() < 3 {
+                bail!(
+                    "Polygon ring {index} needs at least 3 points, found {}",
+                    ring.len()
+                );
+            }
+            let area = signed_area(ring);
+            if area.abs() < f64::EPSILON {
+                bail!("Polygon ring {index} has zero area");
+            }
+            orientations.push(if index == 0 {
+                area.signum()
+            } else {
+                -area.signum()
+            });
+        }
+
+        Ok(Self {
+            rings,
+            orientations,
+        })
+    }
+
+    pub fn outline(&self) -> &[Point2] {
+        &self.rings[0]
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ([Point2; 2], f64)> + '_ {
+        self.rings
+            .iter()
+            .zip(&self.orientations)
+            .flat_map(|(ring, &orientation)| {
+                ring.iter()
+                    .zip(ring.iter().cycle().skip(1))
+                    .map(move |(&a, &b)| ([a, b], orientation))
+            })
+    }
+
+    pub fn contains(&self, point: Point2) -> bool {
+        self.edges().fold(false, |inside, ([a, b], _)| {
+            let crosses = (a[1] > point[1]) != (b[1] > point[1])
+                && point[0] < (b[0] - a[0]) * (point[1] - a[1]) / (b[1] - a[1]) + a[0];
+            inside != crosses
+        })
+    }
+
+    fn signed_distance(&self, point: Point2) -> f64 {
+        let distance = self
+            .edges()
+            .map(|(edge, _)| segment_distance(point, edge))
+            .fold(f64::INFINITY, f64::min);
+        if self.contains(point) {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    fn extent(&self) -> (Point2, Point2) {
+        self.outline().iter().fold(
+            ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+            |(min, max), point| {
+                (
+                    [min[0].min(point[0]), min[1].min(point[1])],
+                    [max[0].max(point[0]), max[1].max(point[1])],
+                )
+            },
+        )
+    }
+}
+
+fn parse_ring(ring: &str) -> Result<Vec<Point2>> {
+    ring.split(';')
+        .map(|point| {
+            let (x, y) = point
+                .split_once(',')
+                .with_context(|| format!("Expected 'x,y' in polygon point '{point}'"))?;
+            Ok([
+                x.parse()
+                    .with_context(|| format!("Invalid number '{x}' in polygon"))?,
+                y.parse()
+                    .with_context(|| format!("Invalid number '{y}' in polygon"))?,
+            ])
+        })
+        .collect()
+}
+
+impl FromStr for Polygon {
+    type Err = anyhow::Error;
+
+    fn from_str(rings: &str) -> Result<Self> {
+        let mut rings = rings.split('|').map(parse_ring);
+        let outline = rings
+            .next()
+            .ok_or_else(|| anyhow!("Polygon has no outline"))??;
+        Self::new(outline, rings.collect::<Result<_>>()?)
+    }
+}
+
+impl fmt::Display for Polygon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, ring) in self.rings.iter().enumerate() {
+            if index > 0 {
+                write!(f, "|")?;
+            }
+            for (point_index, [x, y]) in ring.iter().enumerate() {
+                if point_index > 0 {
+                    write!(f, ";")?;
+                }
+                write!(f, "{x},{y}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Prism {
+    polygon: Polygon,
+    base: Vec3f,
+    axis: Vec3f,
+    height: f64,
+    frame: Transform,
+    material: Material,
+}
+
+fn extrusion_frame(base: Vec3f, axis: Vec3f) -> Option<Transform> {
+    let normal = axis.normalize(None);
+    let hint = if normal.y().abs() < 0.999 {
+        Vec3f::new_with_data([0.0, 1.0, 0.0])
+    } else {
+        Vec3f::new_with_data([0.0, 0.0, -1.0])
+    };
+    let u = hint.cross(&normal).normalize(None);
+    Transform::from_axes([u, normal.cross(&u), normal], base)
+}
+
+impl Prism {
+    pub fn new(
+        polygon: Polygon,
+        base: Vec3f,
+        axis: Vec3f,
+        height: f64,
+        material: Material,
+    ) -> Result<Self> {
+        if height <= 0.0 {
+            bail!("Prism height must be positive, found {height}");
+        }
+        let frame =
+            extrusion_frame(base, axis).ok_or_else(|| anyhow!("Prism axis must be non-zero"))?;
+
+        Ok(Self {
+            polygon,
+            base,
+            axis,
+            height,
+            frame,
+            material,
+        })
+    }
+
+    pub const fn polygon(&self) -> &Polygon {
+        &self.polygon
+    }
+
+    pub const fn base(&self) -> Vec3f {
+        self.base
+    }
+
+    pub const fn axis(&self) -> Vec3f {
+        self.axis
+    }
+
+    pub const fn height(&self) -> f64 {
+        self.height
+    }
+
+    fn local_normal(&self, point: Vec3f) -> Vec3f {
+        if point.z().abs() < CAP_TOLERANCE {
+            return Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        }
+        if (point.z() - self.height).abs() < CAP_TOLERANCE {
+            return Vec3f::new_with_data([0.0, 0.0, 1.0]);
+        }
+
+        let flat = [point.x(), point.y()];
+        self.polygon
+            .edges()
+            .min_by(|(first, _), (second, _)| {
+                segment_distance(flat, *first).total_cmp(&segment_distance(flat, *second))
+            })
+            .map_or_else(
+                || Vec3f::new_with_data([0.0, 0.0, 1.0]),
+                |([a, b], orientation)| {
+                    Vec3f::new_with_data([b[1] - a[1], a[0] - b[0], 0.0]).normalize(None)
+                        * orientation
+                },
+            )
+    }
+}
+
+impl Intersectable for Prism {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let origin = self.frame.invert_point(origin);
+        let direction = self.frame.invert_vector(direction);
+
+        let caps = [(0.0, -1.0), (self.height, 1.0)]
+            .into_iter()
+            .filter_map(|(cap, facing)| {
+                let distance = (cap - origin.z()) / direction.z();
+                let point = origin + direction * distance;
+                (distance > EPSILON && self.polygon.contains([point.x(), point.y()]))
+                    .then(|| (distance, Vec3f::new_with_data([0.0, 0.0, facing])))
+            });
+
+        let sides = self.polygon.edges().filter_map(|([a, b], orientation)| {
+            let edge = [b[0] - a[0], b[1] - a[1]];
+            let denominator = direction.x().mul_add(edge[1], -(direction.y() * edge[0]));
+            if denominator.abs() < f64::EPSILON {
+                return None;
+            }
+            let offset = [a[0] - origin.x(), a[1] - origin.y()];
+            let distance = offset[0].mul_add(edge[1], -(offset[1] * edge[0])) / denominator;
+            let along =
+                offset[0].mul_add(direction.y(), -(offset[1] * direction.x())) / denominator;
+            let height = direction.z().mul_add(distance, origin.z());
+            (distance > EPSILON
+                && (0.0..=1.0).contains(&along)
+                && (0.0..=self.height).contains(&height))
+            .then(|| {
+                let normal = Vec3f::new_with_data([edge[1], -edge[0], 0.0]).normalize(None);
+                (distance, normal * orientation)
+            })
+        });
+
+        caps.chain(sides)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, normal)| Hit::new(distance, self.frame.apply_vector(normal)))
+    }
+}
+
+impl Shape for Prism {
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.frame
+            .apply_vector(self.local_normal(self.frame.invert_point(hit_point)))
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        let local = self.frame.invert_point(point);
+        let flat = self.polygon.signed_distance([local.x(), local.y()]);
+        let vertical = (-local.z()).max(local.z() - self.height);
+        let outside = flat.max(0.0).hypot(vertical.max(0.0));
+        Some(outside + flat.max(vertical).min(0.0))
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.base += offset;
+        self.frame.translate(offset);
+    }
+
+    fn thickness(&self) -> f64 {
+        let (min, max) = self.polygon.extent();
+        self.height.min(max[0] - min[0]).min(max[1] - min[1])
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (min, max) = self.polygon.extent();
+        let corners: Vec<_> = (0..8)
+            .map(|corner: usize| {
+                let x = if corner & 1 == 0 { min[0] } else { max[0] };
+                let y = if corner & 2 == 0 { min[1] } else { max[1] };
+                let z = if corner & 4 == 0 { 0.0 } else { self.height };
+                self.frame.apply_point(Vec3f::new_with_data([x, y, z]))
+            })
+            .collect();
+
+        Some(Aabb::from_points(&corners))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RED_MATERIAL;
+
+    #[test]
+    fn extruded_square_with_hole_hits_caps_walls_and_passes_through_the_hole() -> Result<()> {
+        let polygon: Polygon = "-1,-1;1,-1;1,1;-1,1|-0.5,-0.5;-0.5,0.5;0.5,0.5;0.5,-0.5".parse()?;
+        assert_eq!(
+            polygon.to_string(),
+            "-1,-1;1,-1;1,1;-1,1|-0.5,-0.5;-0.5,0.5;0.5,0.5;0.5,-0.5"
+        );
+        let prism = Prism::new(
+            polygon,
+            Vec3f::new_with_data([0.0, 0.0, -5.0]),
+            Vec3f::new_with_data([0.0, 0.0, 1.0]),
+            0.5,
+            RED_MATERIAL,
+        )?;
+        let origin = Vec3f::new(0.0);
+
+        let toward = |x: f64, y: f64| Vec3f::new_with_data([x, y, -4.5]).normalize(None);
+        let cap = prism
+            .ray_intersect(origin, toward(0.75, 0.0))
+            .ok_or_else(|| anyhow!("ray should hit the front cap"))?;
+        let cap_normal = prism.get_normal(origin + toward(0.75, 0.0) * cap);
+        assert!((cap_normal - Vec3f::new_with_data([0.0, 0.0, 1.0])).length() < 1e-9);
+        assert!(prism.ray_intersect(origin, toward(0.0, 0.0)).is_none());
+
+        let along_wall = Vec3f::new_with_data([-1.0, 0.0, -4.75]);
+        let wall = prism
+            .ray_intersect(along_wall, Vec3f::new_with_data([1.0, 0.0, 0.0]))
+            .ok_or_else(|| anyhow!("ray should hit the inner wall"))?;
+        assert!((wall - 0.5).abs() < 1e-9);
+        let wall_normal = prism.get_normal(along_wall + Vec3f::new_with_data([wall, 0.0, 0.0]));
+        assert!((wall_normal - Vec3f::new_with_data([1.0, 0.0, 0.0])).length() < 1e-9);
+
+        let inside = prism.signed_distance(Vec3f::new_with_data([0.75, 0.0, -4.75]));
+        assert!(inside.is_some_and(|distance| distance < 0.0));
+        assert!("0,0;1,0".parse::<Polygon>().is_err());
+        Ok(())
+    }
+}
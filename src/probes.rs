@@ -0,0 +1,168 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    FOV, PI, Vec3f,
+    camera::{Camera, Projection},
+    scene::Scene,
+};
+
+const fn default_order() -> u32 {
+    3
+}
+
+const fn default_resolution() -> u32 {
+    64
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProbeManifest {
+    #[serde(default = "default_order")]
+    order: u32,
+    #[serde(default = "default_resolution")]
+    resolution: u32,
+    probes: Vec<[f64; 3]>,
+}
+
+#[derive(Debug, Serialize)]
+struct Probe {
+    position: [f64; 3],
+    coefficients: Vec<[f64; 3]>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProbeSet {
+    order: u32,
+    probes: Vec<Probe>,
+}
+
+fn sh_basis(order: u32, direction: Vec3f) -> Vec<f64> {
+    let (x, y, z) = (direction[0], direction[1], direction[2]);
+    let mut basis = vec![0.282_095];
+
+    if order >= 2 {
+        basis.extend([0.488_603 * y, 0.488_603 * z, 0.488_603 * x]);
+    }
+    if order >= 3 {
+        basis.extend([
+            1.092_548 * x * y,
+            1.092_548 * y * z,
+            0.315_392 * (3.0 * z).mul_add(z, -1.0),
+            1.092_548 * x * z,
+            0.546_274 * x.mul_add(x, -y * y),
+        ]);
+    }
+
+    basis
+}
+
+pub fn project(
+    order: u32,
+    resolution: u32,
+    position: Vec3f,
+    radiance: impl Fn(Vec3f) -> Vec3f + Sync,
+) -> Vec<[f64; 3]> {
+    let width = resolution * 2;
+    let height = resolution;
+    let camera = Camera::new(position, FOV).with_projection(Projection::Equirectangular);
+    let cell_area = (2.0 * PI / f64::from(width)) * (PI / f64::from(height));
+    let coefficient_count = (order * order) as usize;
+
+    (0..height)
+        .into_par_iter()
+        .map(|row| {
+            let y = f64::from(row) + 0.5;
+            let latitude = (0.5 - y / f64::from(height)) * PI;
+            let solid_angle = cell_area * latitude.cos();
+            let mut coefficients = vec![[0.0; 3]; coefficient_count];
+
+            for column in 0..width {
+                let direction = camera.ray_direction(f64::from(column) + 0.5, y, width, height);
+                let color = radiance(direction) * solid_angle;
+
+                for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(order, direction))
+                {
+                    for channel in 0..3 {
+                        coefficient[channel] += color[channel] * basis;
+                    }
+                }
+            }
+
+            coefficients
+        })
+        .reduce(
+            || vec![[0.0; 3]; coefficient_count],
+            |mut total, row| {
+                for (total, row) in total.iter_mut().zip(row) {
+                    for channel in 0..3 {
+                        total[channel] += row[channel];
+                    }
+                }
+                total
+            },
+        )
+}
+
+pub fn extract_probes(path: &Path, scene: &Scene) -> Result<ProbeSet> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read probe manifest {}", path.display()))?;
+    let manifest: ProbeManifest = serde_json::from_str(&source)
+        .with_context(|| format!("Failed to parse probe manifest {}", path.display()))?;
+
+    if !(2..=3).contains(&manifest.order) {
+        bail!("Probe order must be 2 or 3, got {}", manifest.order);
+    }
+    if manifest.resolution == 0 {
+        bail!("Probe resolution must be at least 1");
+    }
+
+    let probes = manifest
+        .probes
+        .iter()
+        .map(|&position| {
+            let origin = Vec3f::new_with_data(position);
+            Probe {
+                position,
+                coefficients: project(manifest.order, manifest.resolution, origin, |direction| {
+                    scene.trace(origin, direction)
+                }),
+            }
+        })
+        .collect();
+
+    Ok(ProbeSet {
+        order: manifest.order,
+        probes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_radiance_only_has_dc_term() {
+        let coefficients = project(3, 64, Vec3f::new(0.0), |_| Vec3f::new(1.0));
+
+        let expected = 0.282_095 * 4.0 * PI;
+        assert!((coefficients[0][0] - expected).abs() < 1e-2);
+        for coefficient in &coefficients[1..] {
+            assert!(coefficient[0].abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn linear_band_points_towards_bright_side() {
+        let coefficients = project(2, 64, Vec3f::new(0.0), |direction| {
+            Vec3f::new(direction[1].max(0.0))
+        });
+
+        assert!(coefficients[1][0] > 0.1);
+        assert!(coefficients[2][0].abs() < 1e-2);
+        assert!(coefficients[3][0].abs() < 1e-2);
+    }
+}
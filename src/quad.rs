@@ -0,0 +1,186 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    EPSILON, Material, Vec3f,
+    billboard::Sprite,
+    bvh::Aabb,
+    shapes::{Hit, Intersectable, Shape},
+};
+
+/// A flat parallelogram spanned by two edges from `origin`. Points on it have UV
+/// coordinates running from 0 to 1 along each edge, which an optional texture is sampled
+/// with.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quad {
+    origin: Vec3f,
+    edge_u: Vec3f,
+    edge_v: Vec3f,
+    normal: Vec3f,
+    texture: Option<Sprite>,
+    material: Material,
+}
+
+impl Quad {
+    pub fn new(origin: Vec3f, edge_u: Vec3f, edge_v: Vec3f, material: Material) -> Result<Self> {
+        let cross = edge_u.cross(&edge_v);
+        if !cross.length().is_normal() {
+            bail!("Quad edges must be non-zero and not parallel");
+        }
+
+        Ok(Self {
+            origin,
+            edge_u,
+            edge_v,
+            normal: cross.unit(),
+            texture: None,
+            material,
+        })
+    }
+
+    pub fn with_texture(self, texture: Sprite) -> Self {
+        Self {
+            texture: Some(texture),
+            ..self
+        }
+    }
+
+    pub const fn origin(&self) -> Vec3f {
+        self.origin
+    }
+
+    pub const fn edge_u(&self) -> Vec3f {
+        self.edge_u
+    }
+
+    pub const fn edge_v(&self) -> Vec3f {
+        self.edge_v
+    }
+
+    pub const fn texture(&self) -> Option<&Sprite> {
+        self.texture.as_ref()
+    }
+
+    pub fn center(&self) -> Vec3f {
+        self.origin + (self.edge_u + self.edge_v) * 0.5
+    }
+
+    /// The coordinates of `point` along the two edges, with the origin at (0, 0) and the
+    /// far corner at (1, 1). Points off the quad land outside that range.
+    pub fn uv(&self, point: Vec3f) -> (f64, f64) {
+        let cross = self.edge_u.cross(&self.edge_v);
+        let scale = cross / cross.dot(cross);
+        let offset = point - self.origin;
+        (
+            scale.dot(offset.cross(&self.edge_v)),
+            scale.dot(self.edge_u.cross(&offset)),
+        )
+    }
+}
+
+impl Intersectable for Quad {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let denominator = direction.dot(self.normal);
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let distance = (self.origin - origin).dot(self.normal) / denominator;
+        if distance <= EPSILON {
+            return None;
+        }
+
+        let (u, v) = self.uv(origin + direction * distance);
+        ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v))
+            .then_some(Hit::new(distance, self.normal))
+    }
+}
+
+impl Shape for Quad {
+    fn get_normal(&self, _hit_point: Vec3f) -> Vec3f {
+        self.normal
+    }
+
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn get_material_at(&self, hit_point: Vec3f) -> Material {
+        let Some(texture) = &self.texture else {
+            return self.material;
+        };
+
+        let (u, v) = self.uv(hit_point);
+        let texel = texture.sample(u.clamp(0.0, 1.0), 1.0 - v.clamp(0.0, 1.0));
+        let tint = Vec3f::new_with_data([texel[0], texel[1], texel[2]]);
+        self.material
+            .with_diffuse_color(self.material.diffuse_color().hadamard(tint))
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.origin += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[
+            self.origin,
+            self.origin + self.edge_u,
+            self.origin + self.edge_v,
+            self.origin + self.edge_u + self.edge_v,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RED_MATERIAL;
+
+    #[test]
+    fn quads_hit_inside_their_edges_and_report_uvs() -> Result<()> {
+        let quad = Quad::new(
+            Vec3f::new_with_data([-1.0, -1.0, -5.0]),
+            Vec3f::new_with_data([4.0, 0.0, 0.0]),
+            Vec3f::new_with_data([1.0, 2.0, 0.0]),
+            RED_MATERIAL,
+        )?;
+        let direction = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+
+        let Some(hit) = quad.ray_hit(Vec3f::new_with_data([1.5, 0.0, 0.0]), direction) else {
+            panic!("ray through the middle missed the quad");
+        };
+        assert!((hit.distance() - 5.0).abs() < 1e-12);
+        assert_eq!(hit.normal(), Vec3f::new_with_data([0.0, 0.0, 1.0]));
+
+        let (u, v) = quad.uv(Vec3f::new_with_data([1.5, 0.0, -5.0]));
+        assert!((u - 0.5).abs() < 1e-12 && (v - 0.5).abs() < 1e-12);
+        let (u, v) = quad.uv(Vec3f::new_with_data([4.0, 1.0, -5.0]));
+        assert!((u - 1.0).abs() < 1e-12 && (v - 1.0).abs() < 1e-12);
+
+        // Outside the slanted edge, though still inside the quad's bounding box.
+        assert!(
+            quad.ray_hit(Vec3f::new_with_data([-0.9, 0.9, 0.0]), direction)
+                .is_none()
+        );
+        assert!(quad.ray_hit(Vec3f::new(0.0), -direction).is_none());
+
+        assert!(
+            Quad::new(
+                Vec3f::new(0.0),
+                Vec3f::new_with_data([1.0, 0.0, 0.0]),
+                Vec3f::new_with_data([2.0, 0.0, 0.0]),
+                RED_MATERIAL,
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,606 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+use crate::{
+    aov::{self, AovBuffer},
+    camera::Camera,
+    crash, denoise,
+    error::{RaytracerError, Result},
+    output,
+    scene::{CancelToken, Fog, ImageRegion, Scene},
+    settings::RenderSettings,
+    stats::{self, FrameStats, RayCounts, RenderStats},
+};
+
+const PREVIEW_DEPTH: u32 = 1;
+const PREVIEW_SETTLE: Duration = Duration::from_millis(200);
+
+/// How many pixels of the current frame are finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderProgress {
+    completed_pixels: usize,
+    total_pixels: usize,
+}
+
+impl RenderProgress {
+    /// Progress with `completed_pixels` of `total_pixels` done.
+    pub const fn new(completed_pixels: usize, total_pixels: usize) -> Self {
+        Self {
+            completed_pixels,
+            total_pixels,
+        }
+    }
+
+    /// Pixels finished so far, counting rows above the stripe being rendered.
+    pub const fn completed_pixels(&self) -> usize {
+        self.completed_pixels
+    }
+
+    /// Pixels in the whole frame.
+    pub const fn total_pixels(&self) -> usize {
+        self.total_pixels
+    }
+
+    /// Finished share of the frame, from 0 to 1.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> f64 {
+        self.completed_pixels as f64 / self.total_pixels as f64
+    }
+}
+
+/// Called from render threads as rows finish.
+pub type ProgressCallback = Box<dyn Fn(RenderProgress) + Send + Sync>;
+/// Called once a full frame is rendered, with its timings and counters.
+pub type CompletionCallback = Box<dyn Fn(FrameStats) + Send + Sync>;
+
+/// Renders a [`Scene`] through a [`Camera`] into RGBA8 frames of a fixed size.
+pub struct Renderer {
+    scene: Scene,
+    settings: RenderSettings,
+    width: u32,
+    height: u32,
+    camera: Camera,
+    time: f64,
+    last_interaction: Option<Instant>,
+    on_progress: Option<ProgressCallback>,
+    on_complete: Option<CompletionCallback>,
+    cancel: CancelToken,
+    stats: RenderStats,
+}
+
+impl Renderer {
+    /// Creates a renderer and applies the scene-wide parts of `settings` (depth, epsilon,
+    /// fog, shadows, ...) to `scene`.
+    pub fn new(
+        mut scene: Scene,
+        settings: RenderSettings,
+        width: u32,
+        height: u32,
+        camera: Camera,
+    ) -> Self {
+        scene.set_terminator_fix(settings.terminator_fix());
+        scene.set_soft_shadows(settings.soft_shadows());
+        scene.set_mirror_cache(settings.mirror_cache());
+        scene.set_shadow_quality(settings.shadow_quality());
+        let time = settings.time();
+        scene.set_time(time);
+        scene.set_viewer(camera.position());
+        if let Some(max_depth) = settings.max_depth() {
+            scene.set_max_depth(max_depth);
+        }
+        if let Some(epsilon) = settings.epsilon() {
+            scene.set_epsilon(epsilon);
+        }
+        if let Some(max_distance) = settings.max_distance() {
+            scene.set_max_distance(max_distance);
+        }
+        if let Some(background) = settings.background() {
+            scene.set_background(background);
+        }
+        if let Some(specular_model) = settings.specular_model() {
+            scene.set_specular_model(specular_model);
+        }
+        if let Some(density) = settings.fog_density() {
+            scene.set_fog(Fog::new(density, settings.fog_color()));
+        }
+        scene.set_caustics(settings.caustics());
+
+        Self {
+            scene,
+            settings,
+            width,
+            height,
+            camera,
+            time,
+            last_interaction: None,
+            on_progress: None,
+            on_complete: None,
+            cancel: CancelToken::default(),
+            stats: RenderStats::new(),
+        }
+    }
+
+    /// Reports progress while [`Self::render_into`] and friends run.
+    pub fn set_progress_callback(
+        &mut self,
+        callback: impl Fn(RenderProgress) + Send + Sync + 'static,
+    ) {
+        self.on_progress = Some(Box::new(callback));
+    }
+
+    /// Reports frame statistics after each [`Self::render_into`].
+    pub fn set_completion_callback(
+        &mut self,
+        callback: impl Fn(FrameStats) + Send + Sync + 'static,
+    ) {
+        self.on_complete = Some(Box::new(callback));
+    }
+
+    /// The scene being rendered.
+    pub const fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Edits the scene between frames; shape and light edits rebuild the BVH themselves.
+    pub const fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    /// The settings this renderer was created with.
+    pub const fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// The camera frames are rendered from.
+    pub const fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Moves the camera in place. Prefer [`Self::set_camera`] when the position
+    /// changes, since it also updates the viewer used for billboards.
+    pub const fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Replaces the camera and keeps the scene's viewer position in sync.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.scene.set_viewer(camera.position());
+        self.camera = camera;
+    }
+
+    /// Animation time in seconds.
+    pub const fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Rays traced by this renderer so far, across every frame and stripe.
+    pub fn ray_counts(&self) -> RayCounts {
+        self.stats.counts()
+    }
+
+    /// A handle that stops the frame being rendered from another thread.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Frame width in pixels.
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Frame height in pixels.
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Changes the size of later frames; the caller resizes its frame buffer.
+    pub const fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Advances the animation time by `dt` seconds.
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+        self.scene.set_time(self.time);
+    }
+
+    /// Records user input so the next frames render as low-resolution previews.
+    pub fn mark_interaction(&mut self) {
+        self.last_interaction = Some(Instant::now());
+    }
+
+    /// Whether input arrived recently enough to keep rendering previews.
+    pub fn is_interacting(&self) -> bool {
+        self.last_interaction
+            .is_some_and(|last_interaction| last_interaction.elapsed() < PREVIEW_SETTLE)
+    }
+
+    /// Renders a preview while the user is interacting and a full frame otherwise,
+    /// with the HUD on top when enabled.
+    pub fn render_interactive_into(&mut self, frame: &mut [u8]) -> Result<()> {
+        let start = Instant::now();
+        let before = self.stats.counts();
+        if self.settings.preview_scale() > 1 && self.is_interacting() {
+            self.render_preview_into(frame)?;
+        } else {
+            self.render_into(frame)?;
+        }
+
+        if self.settings.hud() {
+            let frame_stats = FrameStats::since(start, &self.stats, before);
+            stats::draw_hud(frame, (self.width, self.height), &frame_stats);
+        }
+
+        Ok(())
+    }
+
+    /// Renders one sample per pixel at a reduced resolution and scales it up to fill
+    /// `frame`.
+    pub fn render_preview_into(&mut self, frame: &mut [u8]) -> Result<()> {
+        let scale = self.settings.preview_scale();
+        let (width, height) = (self.width.div_ceil(scale), self.height.div_ceil(scale));
+        let region = ImageRegion::stripe(width, height, 0, height);
+        let settings = self
+            .settings
+            .with_args([
+                "--spp".to_owned(),
+                "1".to_owned(),
+                "--integrator".to_owned(),
+                "whitted".to_owned(),
+            ])
+            .map_err(|err| RaytracerError::InvalidParameter(format!("{err:#}")))?;
+        let mut preview = vec![0; region.pixel_count() * 4];
+
+        self.cancel.reset();
+        let max_depth = self.scene.max_depth();
+        self.scene.set_max_depth(max_depth.min(PREVIEW_DEPTH));
+        let completed = self.scene.render_scene(
+            &mut preview,
+            region,
+            &self.camera,
+            &settings,
+            &self.cancel,
+            &|_, counts| self.stats.add(counts),
+        );
+        self.scene.set_max_depth(max_depth);
+        if !completed {
+            return Err(RaytracerError::Cancelled);
+        }
+
+        upscale_nearest(&preview, width, scale, frame, (self.width, self.height))
+    }
+
+    /// Renders the whole frame, applying bloom and denoising when enabled.
+    pub fn render_into(&self, frame: &mut [u8]) -> Result<()> {
+        let start = Instant::now();
+        let before = self.stats.counts();
+        self.cancel.reset();
+        if self.settings.post().bloom().is_some() {
+            self.render_hdr_into(frame)?;
+        } else {
+            self.render_rows_into(frame, 0, self.height)?;
+        }
+        if self.settings.denoise() {
+            self.denoise(frame);
+        }
+
+        if let Some(on_complete) = &self.on_complete {
+            on_complete(FrameStats::since(start, &self.stats, before));
+        }
+
+        Ok(())
+    }
+
+    /// Renders the rows `first_row..first_row + rows` into `frame`, which holds just
+    /// those rows. Lets callers split a frame into stripes, e.g. across machines.
+    pub fn render_rows_into(&self, frame: &mut [u8], first_row: u32, rows: u32) -> Result<()> {
+        let end_row = first_row.checked_add(rows);
+        if end_row.is_none_or(|end_row| end_row > self.height) {
+            return Err(RaytracerError::InvalidParameter(format!(
+                "{rows} rows from row {first_row} are outside of the {} row image",
+                self.height
+            )));
+        }
+
+        let region = ImageRegion::stripe(self.width, self.height, first_row, rows);
+        if frame.len() != region.pixel_count() * 4 {
+            return Err(RaytracerError::InvalidParameter(format!(
+                "Frame buffer has {} bytes, expected {} for {}x{rows} RGBA",
+                frame.len(),
+                region.pixel_count() * 4,
+                self.width
+            )));
+        }
+
+        self.render_guarded(frame, (first_row, rows), |frame, progress| {
+            self.scene.render_scene(
+                frame,
+                region,
+                &self.camera,
+                &self.settings,
+                &self.cancel,
+                progress,
+            )
+        })
+    }
+
+    /// Runs `render` over the rows `first_row..first_row + rows` held in `frame`, reporting
+    /// its progress through the callback and dumping what it drew so far if it panics.
+    fn render_guarded(
+        &self,
+        frame: &mut [u8],
+        (first_row, rows): (u32, u32),
+        render: impl FnOnce(&mut [u8], &(dyn Fn(usize, RayCounts) + Sync)) -> bool,
+    ) -> Result<()> {
+        let total_pixels = self.width as usize * self.height as usize;
+        let completed_before = first_row as usize * self.width as usize;
+        let progress = |completed_pixels, counts| {
+            self.stats.add(counts);
+            if let Some(on_progress) = &self.on_progress {
+                on_progress(RenderProgress {
+                    completed_pixels: completed_before + completed_pixels,
+                    total_pixels,
+                });
+            }
+        };
+
+        let rendered = panic::catch_unwind(AssertUnwindSafe(|| render(frame, &progress)));
+        let completed = match rendered {
+            Ok(completed) => completed,
+            Err(payload) => {
+                match crash::dump_framebuffer(frame, (self.width, rows), first_row, &self.settings)
+                {
+                    Ok(path) => error!("Saved partial render to {}", path.display()),
+                    Err(err) => error!("Failed to save partial render: {err:#}"),
+                }
+                panic::resume_unwind(payload);
+            }
+        };
+        if !completed {
+            return Err(RaytracerError::Cancelled);
+        }
+
+        Ok(())
+    }
+
+    fn render_hdr_into(&self, frame: &mut [u8]) -> Result<()> {
+        let expected = self.width as usize * self.height as usize * 4;
+        if frame.len() != expected {
+            return Err(RaytracerError::InvalidParameter(format!(
+                "Frame buffer has {} bytes, expected {expected} for {}x{} RGBA",
+                frame.len(),
+                self.width,
+                self.height
+            )));
+        }
+
+        self.render_guarded(frame, (0, self.height), |frame, progress| {
+            self.scene.render_hdr_into(
+                frame,
+                (self.width, self.height),
+                &self.camera,
+                &self.settings,
+                &self.cancel,
+                progress,
+            )
+        })
+    }
+
+    /// Renders straight to a PNG, `stripe_rows` rows at a time so large images never
+    /// need a full frame buffer.
+    pub fn render_to_png(&self, path: &Path, stripe_rows: u32) -> Result<()> {
+        if self.settings.denoise() || self.settings.post().bloom().is_some() {
+            // Both filters reach across stripe boundaries, so the whole frame is needed.
+            let mut frame = vec![0; self.width as usize * self.height as usize * 4];
+            self.render_into(&mut frame)?;
+            return output::write_png(path, self.width, self.height, &frame).map_err(|source| {
+                RaytracerError::Output {
+                    path: path.to_owned(),
+                    source,
+                }
+            });
+        }
+
+        let start = Instant::now();
+        let before = self.stats.counts();
+        self.cancel.reset();
+        output::write_png_stripes(
+            path,
+            self.width,
+            self.height,
+            stripe_rows,
+            |first_row, stripe| {
+                let rows = u32::try_from(stripe.len() / (self.width as usize * 4))?;
+                Ok(self.render_rows_into(stripe, first_row, rows)?)
+            },
+        )
+        .map_err(|source| RaytracerError::Output {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        if let Some(on_complete) = &self.on_complete {
+            on_complete(FrameStats::since(start, &self.stats, before));
+        }
+
+        Ok(())
+    }
+
+    fn denoise(&self, frame: &mut [u8]) {
+        let buffers = self.scene.render_aovs(
+            &self.camera,
+            (self.width, self.height),
+            &[AovBuffer::Normal, AovBuffer::Albedo],
+        );
+        if let [normal, albedo] = buffers.as_slice() {
+            denoise::denoise(frame, (self.width, self.height), normal, albedo);
+        }
+    }
+
+    /// Writes each AOV buffer asked for with `--aov` next to the image at `path`.
+    pub fn write_aov_buffers(&self, path: &Path) -> Result<()> {
+        let buffers = self.scene.render_aovs(
+            &self.camera,
+            (self.width, self.height),
+            self.settings.aov_buffers(),
+        );
+
+        for buffer in buffers {
+            let buffer_path = aov::buffer_path(path, buffer.kind());
+            output::write_png(&buffer_path, self.width, self.height, &buffer.to_rgba()).map_err(
+                |source| RaytracerError::Output {
+                    path: buffer_path.clone(),
+                    source,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn upscale_nearest(
+    source: &[u8],
+    source_width: u32,
+    scale: u32,
+    frame: &mut [u8],
+    (width, height): (u32, u32),
+) -> Result<()> {
+    if frame.len() != width as usize * height as usize * 4 {
+        return Err(RaytracerError::InvalidParameter(format!(
+            "Frame buffer has {} bytes, expected {} for {width}x{height} RGBA",
+            frame.len(),
+            width as usize * height as usize * 4
+        )));
+    }
+
+    for (y, row) in (0..).zip(frame.chunks_exact_mut(width as usize * 4)) {
+        for (x, pixel) in (0..).zip(row.chunks_exact_mut(4)) {
+            let index = ((y / scale) * source_width + x / scale) as usize * 4;
+            pixel.copy_from_slice(&source[index..index + 4]);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FOV, Vec3f, lights::init_default_lights, shapes::init_default_shapes};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn preview_upscales_a_shallow_render_and_restores_depth() -> Result<()> {
+        let source = [1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4];
+        let mut frame = vec![0; 3 * 3 * 4];
+        upscale_nearest(&source, 2, 2, &mut frame, (3, 3))?;
+        let firsts: Vec<_> = frame.chunks_exact(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(firsts, [1, 1, 2, 1, 1, 2, 3, 3, 4]);
+
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let mut renderer = Renderer::new(scene, RenderSettings::default(), 30, 20, camera);
+        let max_depth = renderer.scene().max_depth();
+        let mut frame = vec![0; 30 * 20 * 4];
+
+        assert!(!renderer.is_interacting());
+        renderer.mark_interaction();
+        assert!(renderer.is_interacting());
+        renderer.render_interactive_into(&mut frame)?;
+
+        assert_eq!(renderer.scene().max_depth(), max_depth);
+        assert!(frame.chunks_exact(4).all(|pixel| pixel[3] == 255));
+        Ok(())
+    }
+
+    #[test]
+    fn frame_stats_only_count_rays_of_their_own_renderer() -> Result<()> {
+        let renderer = |reported: Arc<AtomicUsize>| {
+            let scene = Scene::new(init_default_shapes(), init_default_lights());
+            let camera = Camera::new(Vec3f::new(0.0), FOV);
+            let mut renderer = Renderer::new(scene, RenderSettings::default(), 30, 20, camera);
+            renderer.set_completion_callback(move |stats| {
+                let rays = usize::try_from(stats.counts().rays()).unwrap_or(usize::MAX);
+                reported.store(rays, Ordering::Relaxed);
+            });
+            renderer
+        };
+        let reported = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+        let renderers = reported.clone().map(renderer);
+
+        std::thread::scope(|scope| {
+            let renders = renderers
+                .each_ref()
+                .map(|renderer| scope.spawn(|| renderer.render_into(&mut vec![0; 30 * 20 * 4])));
+            renders
+                .into_iter()
+                .try_for_each(|render| render.join().unwrap_or(Err(RaytracerError::Cancelled)))
+        })?;
+
+        for (renderer, reported) in renderers.iter().zip(&reported) {
+            let rays = renderer.ray_counts().rays();
+            assert!(rays >= 30 * 20);
+            assert_eq!(reported.load(Ordering::Relaxed) as u64, rays);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn the_window_shares_the_scene_across_render_threads() -> Result<()> {
+        fn shared_across_threads<T: Send + Sync>() {}
+        shared_across_threads::<Scene>();
+        shared_across_threads::<Renderer>();
+
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let mut renderer = Renderer::new(scene, RenderSettings::default(), 30, 20, camera);
+        let mut windowed = vec![0; 30 * 20 * 4];
+        renderer.render_interactive_into(&mut windowed)?;
+        let mut direct = vec![0; 30 * 20 * 4];
+        renderer.render_into(&mut direct)?;
+
+        assert_eq!(windowed, direct);
+        Ok(())
+    }
+
+    #[test]
+    fn bloom_renders_report_progress() -> anyhow::Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let settings =
+            RenderSettings::default().with_args(["--bloom".to_owned(), "1.0".to_owned()])?;
+        let mut renderer = Renderer::new(scene, settings, 12, 8, camera);
+        let completed = Arc::new(AtomicUsize::new(0));
+        let reported = Arc::clone(&completed);
+        renderer.set_progress_callback(move |progress| {
+            assert_eq!(progress.total_pixels(), 12 * 8);
+            reported.fetch_max(progress.completed_pixels(), Ordering::Relaxed);
+        });
+
+        renderer.render_into(&mut vec![0; 12 * 8 * 4])?;
+        assert_eq!(completed.load(Ordering::Relaxed), 12 * 8);
+        Ok(())
+    }
+
+    #[test]
+    fn stripes_past_the_last_row_are_rejected_without_overflowing() {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), FOV);
+        let renderer = Renderer::new(scene, RenderSettings::default(), 4, 4, camera);
+        let mut stripe = vec![0; 4 * 2 * 4];
+
+        assert!(renderer.render_rows_into(&mut stripe, 2, 2).is_ok());
+        assert!(renderer.render_rows_into(&mut stripe, 3, 2).is_err());
+        assert!(renderer.render_rows_into(&mut stripe, u32::MAX, 2).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::Vec3f;
+
+const MORTON_BITS: u32 = 10;
+const COHERENCE_SHIFT: u32 = 3 * (MORTON_BITS - 3);
+
+static RAYS: AtomicUsize = AtomicUsize::new(0);
+static BATCHES: AtomicUsize = AtomicUsize::new(0);
+static COHERENT_BEFORE: AtomicUsize = AtomicUsize::new(0);
+static COHERENT_AFTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReorderStats {
+    rays: usize,
+    batches: usize,
+    coherent_before: usize,
+    coherent_after: usize,
+}
+
+impl ReorderStats {
+    pub const fn rays(&self) -> usize {
+        self.rays
+    }
+
+    pub const fn batches(&self) -> usize {
+        self.batches
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn fraction(&self, coherent: usize) -> f64 {
+        coherent as f64 / self.rays.saturating_sub(self.batches).max(1) as f64
+    }
+
+    pub fn coherence_before(&self) -> f64 {
+        self.fraction(self.coherent_before)
+    }
+
+    pub fn coherence_after(&self) -> f64 {
+        self.fraction(self.coherent_after)
+    }
+}
+
+impl fmt::Display for ReorderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} secondary rays in {} batches, coherence {:.1}% -> {:.1}%",
+            self.rays,
+            self.batches,
+            self.coherence_before() * 100.0,
+            self.coherence_after() * 100.0
+        )
+    }
+}
+
+const fn spread_bits(value: u64) -> u64 {
+    let mut value = value & 0x3ff;
+    value = (value | (value << 16)) & 0x0300_00ff;
+    value = (value | (value << 8)) & 0x0300_f00f;
+    value = (value | (value << 4)) & 0x030c_30c3;
+    (value | (value << 2)) & 0x0924_9249
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn sort_key(origin: Vec3f, direction: Vec3f, min: Vec3f, extent: Vec3f) -> u64 {
+    let cells = f64::from((1 << MORTON_BITS) - 1);
+    let morton = (0..3).fold(0, |morton, axis| {
+        let relative = (origin[axis] - min[axis]) / extent[axis].max(f64::EPSILON);
+        let cell = (relative.clamp(0.0, 1.0) * cells) as u64;
+        morton | (spread_bits(cell) << axis)
+    });
+    let octant = (0..3).fold(0, |octant, axis| {
+        octant | (u64::from(direction[axis] < 0.0) << axis)
+    });
+
+    (octant << (3 * MORTON_BITS)) | morton
+}
+
+fn coherent_pairs(keys: &[u64]) -> usize {
+    keys.windows(2)
+        .filter(|pair| pair[0] >> COHERENCE_SHIFT == pair[1] >> COHERENCE_SHIFT)
+        .count()
+}
+
+pub fn sort_rays<T>(rays: &mut [T], ray: impl Fn(&T) -> (Vec3f, Vec3f)) {
+    if rays.len() < 2 {
+        return;
+    }
+
+    let (min, max) = rays.iter().fold(
+        (Vec3f::new(f64::INFINITY), Vec3f::new(f64::NEG_INFINITY)),
+        |(min, max), item| {
+            let origin = ray(item).0;
+            (
+                Vec3f::new_with_data([0, 1, 2].map(|axis| min[axis].min(origin[axis]))),
+                Vec3f::new_with_data([0, 1, 2].map(|axis| max[axis].max(origin[axis]))),
+            )
+        },
+    );
+    let key = |item: &T| {
+        let (origin, direction) = ray(item);
+        sort_key(origin, direction, min, max - min)
+    };
+
+    let mut keys: Vec<u64> = rays.iter().map(key).collect();
+    let coherent_before = coherent_pairs(&keys);
+    rays.sort_by_cached_key(key);
+    keys.sort_unstable();
+
+    RAYS.fetch_add(rays.len(), Ordering::Relaxed);
+    BATCHES.fetch_add(1, Ordering::Relaxed);
+    COHERENT_BEFORE.fetch_add(coherent_before, Ordering::Relaxed);
+    COHERENT_AFTER.fetch_add(coherent_pairs(&keys), Ordering::Relaxed);
+}
+
+pub fn stats() -> ReorderStats {
+    ReorderStats {
+        rays: RAYS.load(Ordering::Relaxed),
+        batches: BATCHES.load(Ordering::Relaxed),
+        coherent_before: COHERENT_BEFORE.load(Ordering::Relaxed),
+        coherent_after: COHERENT_AFTER.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorting_groups_rays_by_octant_and_origin_cell() {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let down = -up;
+        let mut rays: Vec<(Vec3f, Vec3f)> = (0..32)
+            .map(|index| {
+                let x = f64::from(index % 4);
+                (
+                    Vec3f::new_with_data([x, 0.0, 0.0]),
+                    if index % 2 == 0 { up } else { down },
+                )
+            })
+            .collect();
+
+        sort_rays(&mut rays, |&ray| ray);
+
+        let flips = rays
+            .windows(2)
+            .filter(|pair| pair[0].1 != pair[1].1)
+            .count();
+        assert_eq!(flips, 1);
+        assert!(
+            rays.windows(2)
+                .all(|pair| { pair[0].1 != pair[1].1 || pair[0].0.x() <= pair[1].0.x() })
+        );
+
+        let stats = stats();
+        assert!(stats.rays() >= rays.len());
+        assert!(stats.coherence_after() > stats.coherence_before());
+    }
+}
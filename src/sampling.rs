@@ -0,0 +1,82 @@
+use crate::{PI, Vec3f};
+
+fn tangent_frame(axis: Vec3f) -> (Vec3f, Vec3f) {
+    let helper = if axis[0].abs() > 0.9 {
+        Vec3f::new_with_data([0.0, 1.0, 0.0])
+    } else {
+        Vec3f::new_with_data([1.0, 0.0, 0.0])
+    };
+    let tangent = helper.cross(&axis).normalize(None);
+    (tangent, axis.cross(&tangent))
+}
+
+fn around(axis: Vec3f, cos_theta: f64, angle: f64) -> Vec3f {
+    let (tangent, bitangent) = tangent_frame(axis);
+    let sin_theta = cos_theta.mul_add(-cos_theta, 1.0).max(0.0).sqrt();
+
+    tangent * (sin_theta * angle.cos()) + bitangent * (sin_theta * angle.sin()) + axis * cos_theta
+}
+
+/// A direction spread uniformly over the cone around `axis` whose half-angle has cosine
+/// `cos_max`, for `u1` and `u2` in [0, 1).
+pub fn cone_direction(axis: Vec3f, cos_max: f64, u1: f64, u2: f64) -> Vec3f {
+    around(axis, u1.mul_add(cos_max - 1.0, 1.0), 2.0 * PI * u2)
+}
+
+/// A point spread uniformly over the disc of `radius` perpendicular to `axis`, relative to
+/// its center.
+pub fn disc_offset(axis: Vec3f, radius: f64, u1: f64, u2: f64) -> Vec3f {
+    let (tangent, bitangent) = tangent_frame(axis);
+    let distance = radius * u1.sqrt();
+    let angle = 2.0 * PI * u2;
+
+    tangent * (distance * angle.cos()) + bitangent * (distance * angle.sin())
+}
+
+/// A microfacet normal drawn from the GGX distribution around `normal`.
+///
+/// `roughness` is the perceptual roughness, squared into the distribution's alpha: 0 always
+/// returns `normal` and 1 spreads facets over most of the hemisphere.
+pub fn ggx_normal(normal: Vec3f, roughness: f64, u1: f64, u2: f64) -> Vec3f {
+    let alpha = roughness * roughness;
+    let tan_squared = alpha * alpha * u1 / (1.0 - u1).max(f64::EPSILON);
+    around(normal, (1.0 + tan_squared).sqrt().recip(), 2.0 * PI * u2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cone_directions_stay_inside_the_cone() {
+        let axis = Vec3f::new_with_data([0.0, 0.6, -0.8]);
+        for (u1, u2) in [(0.0, 0.0), (0.5, 0.25), (0.999, 0.7)] {
+            let direction = cone_direction(axis, 0.9, u1, u2);
+            assert!((direction.length() - 1.0).abs() < 1e-12);
+            assert!(direction * axis >= 0.9 - 1e-12);
+            assert!(disc_offset(axis, 2.0, u1, u2).length() <= 2.0);
+            assert!((disc_offset(axis, 2.0, u1, u2) * axis).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn ggx_normals_spread_with_roughness() {
+        let normal = Vec3f::new_with_data([0.0, 0.0, 1.0]);
+        let mean_cosine = |roughness: f64| {
+            (1..64)
+                .map(|index| {
+                    let u1 = f64::from(index) / 64.0;
+                    let facet = ggx_normal(normal, roughness, u1, u1 * 7.0 % 1.0);
+                    assert!((facet.length() - 1.0).abs() < 1e-12);
+                    facet * normal
+                })
+                .sum::<f64>()
+                / 63.0
+        };
+
+        assert!((mean_cosine(0.0) - 1.0).abs() < 1e-12);
+        assert!(mean_cosine(0.2) > mean_cosine(0.5));
+        assert!(mean_cosine(0.5) > mean_cosine(0.9));
+        assert!(mean_cosine(0.9) > 0.0);
+    }
+}
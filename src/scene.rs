@@ -1,247 +1,4190 @@
 use core::f64;
+use log::debug;
 use rayon::prelude::*;
-use std::mem::swap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use std::time::Instant;
 
 use crate::Vec3f;
-use crate::{BACKGROUND_COLOR, EPSILON, MAX_DEPTH};
+use crate::{BACKGROUND_COLOR, EPSILON, MAX_DEPTH, MAX_DISTANCE, PI};
 use crate::{
-    lights::{Light, LightType},
-    materials::Material,
-    shapes::{Intersectable, Shape, ShapeType},
+    aov::{Aov, AovBuffer, FloatBuffer, cosine_hemisphere, pixel_rotation},
+    arena,
+    bvh::{Aabb, Bvh, MAX_PACKET_SIZE},
+    camera::{Camera, RayGenerator},
+    geometry::{inverse_direction, offset_ray_origin, reflect, refract},
+    integrator::Integrator,
+    lights::{Light, LightSample, LightType},
+    materials::{CHANNEL_WAVELENGTHS, Material, MaterialRegistry, Sidedness, SpecularModel},
+    mirror_cache,
+    photon::{Photon, PhotonMap},
+    reorder,
+    sampling::{cone_direction, disc_offset, ggx_normal},
+    scene_file::SceneFile,
+    settings::RenderSettings,
+    shapes::{Hit, Intersectable, Shape, ShapeType},
+    stats::{self, RayCounts},
+    volume::Volume,
 };
 
 #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
 fn to_u8(color: f64) -> u8 {
-    (color * 255.0).round() as u8
+    (color.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn halton(mut index: u32, base: u32) -> f64 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+
+    while index > 0 {
+        fraction /= f64::from(base);
+        result += fraction * f64::from(index % base);
+        index /= base;
+    }
+
+    result
+}
+
+fn sample_offset(sample: u32, samples_per_pixel: u32) -> (f64, f64) {
+    if samples_per_pixel == 1 {
+        return (0.5, 0.5);
+    }
+
+    (halton(sample + 1, 2), halton(sample + 1, 3))
+}
+
+fn primary_ray(
+    rays: &RayGenerator,
+    pixel: (u32, u32),
+    sample: u32,
+    samples_per_pixel: u32,
+) -> (Vec3f, Vec3f) {
+    let (offset_x, offset_y) = sample_offset(sample, samples_per_pixel);
+    rays.ray(f64::from(pixel.0) + offset_x, f64::from(pixel.1) + offset_y)
+}
+
+/// AOVs and ambient occlusion are data rather than pictures and skip post-processing.
+fn is_post_processed(settings: &RenderSettings) -> bool {
+    settings.aov().is_none() && settings.integrator() != Integrator::AmbientOcclusion
+}
+
+fn pixel_color(radiance: Vec3f, settings: &RenderSettings) -> [u8; 4] {
+    let color = if is_post_processed(settings) {
+        settings.post().apply(radiance)
+    } else {
+        radiance
+    };
+
+    [to_u8(color[0]), to_u8(color[1]), to_u8(color[2]), 255]
+}
+
+fn clamp_radiance(radiance: Vec3f, max: Option<f64>) -> Vec3f {
+    max.map_or(radiance, |max| {
+        Vec3f::new_with_data([
+            radiance[0].min(max),
+            radiance[1].min(max),
+            radiance[2].min(max),
+        ])
+    })
+}
+
+/// Room for a bubble in a glass in water and then some; the stack travels with every
+/// secondary ray, so it stays small now that each medium also carries its absorption.
+const MEDIUM_STACK_SIZE: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct MediumStack {
+    iors: [f64; MEDIUM_STACK_SIZE],
+    /// Beer-Lambert absorption of each medium, attenuating whatever is seen through it.
+    absorptions: [Vec3f; MEDIUM_STACK_SIZE],
+    len: usize,
+    /// The one color channel a ray still carries after a dispersive surface split it up.
+    channel: Option<usize>,
+}
+
+impl MediumStack {
+    const AIR: Self = Self {
+        iors: [1.0; MEDIUM_STACK_SIZE],
+        absorptions: [Vec3f::const_new_with_data([0.0, 0.0, 0.0]); MEDIUM_STACK_SIZE],
+        len: 0,
+        channel: None,
+    };
+
+    const fn split(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    fn ior(&self, material: &Material) -> f64 {
+        self.channel.map_or_else(
+            || material.refractive_index(),
+            |channel| material.refractive_index_at(CHANNEL_WAVELENGTHS[channel]),
+        )
+    }
+
+    const fn current(&self) -> f64 {
+        if self.len == 0 {
+            1.0
+        } else {
+            self.iors[self.len - 1]
+        }
+    }
+
+    /// How much of the light crossing `distance` of the innermost medium gets through.
+    /// Channels that don't absorb stay at 1 even over the infinite distance of an escaping
+    /// ray.
+    fn transmittance(&self, distance: f64) -> Vec3f {
+        if self.len == 0 {
+            return Vec3f::new(1.0);
+        }
+
+        let absorption = self.absorptions[self.len - 1];
+        Vec3f::new_with_data([0, 1, 2].map(|channel| {
+            if absorption[channel] > 0.0 {
+                (-absorption[channel] * distance).exp()
+            } else {
+                1.0
+            }
+        }))
+    }
+
+    const fn entered(mut self, ior: f64, absorption: Vec3f) -> Self {
+        if self.len < MEDIUM_STACK_SIZE {
+            self.iors[self.len] = ior;
+            self.absorptions[self.len] = absorption;
+            self.len += 1;
+        }
+        self
+    }
+
+    fn exited(mut self, ior: f64) -> Self {
+        if let Some(index) = self.iors[..self.len]
+            .iter()
+            .rposition(|&medium| medium.to_bits() == ior.to_bits())
+        {
+            self.iors.copy_within(index + 1..self.len, index);
+            self.absorptions.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+        }
+        self
+    }
 }
 
-fn reflect(direction: Vec3f, normal: Vec3f) -> Vec3f {
-    direction - normal * (direction * normal) * 2.0
+/// Exponential distance fog blended over everything a ray hits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fog {
+    density: f64,
+    color: Option<Vec3f>,
 }
 
-fn refract(direction: Vec3f, normal: Vec3f, refractive_index: f64) -> Vec3f {
-    let mut cosi = (direction * normal).clamp(-1.0, 1.0);
-    let mut ior_in = 1.0;
-    let mut ior_out = refractive_index;
-    let mut n = normal;
+impl Fog {
+    /// No fog at all.
+    pub const NONE: Self = Self {
+        density: 0.0,
+        color: None,
+    };
+
+    /// Fog thickening by `density` per unit of distance, fading towards `color` or, without
+    /// one, towards the background.
+    pub const fn new(density: f64, color: Option<Vec3f>) -> Self {
+        Self { density, color }
+    }
+
+    fn apply(self, radiance: Vec3f, distance: f64, background: Vec3f) -> Vec3f {
+        if self.density <= 0.0 {
+            return radiance;
+        }
 
-    if cosi < 0.0 {
-        cosi *= -1.0;
-        swap(&mut ior_in, &mut ior_out);
-        n = -n;
+        let color = self.color.unwrap_or(background);
+        let transmittance = (-self.density * distance).exp();
+        color + (radiance - color) * transmittance
     }
+}
 
-    let eta = ior_in / ior_out;
-    let k = (eta * eta).mul_add(-cosi.mul_add(-cosi, 1.0), 1.0);
+#[derive(Clone, Copy, Debug)]
+struct Geometry<'a> {
+    shapes: &'a [ShapeType],
+    bvh: &'a Bvh,
+    max_depth: u32,
+    epsilon: f64,
+    max_distance: f64,
+    fog: Fog,
+    soft_shadows: Option<f64>,
+    shadow_quality: Option<f64>,
+    specular_model: SpecularModel,
+    mirror_cache: Option<f64>,
+    volumes: &'a [Volume],
+    caustics: Option<&'a PhotonMap>,
+}
 
-    if k < 0.0 {
-        return Vec3f::new_with_data([0.0, 0.0, 0.0]);
+/// Keeps the nearer of two hits with the same tie-breaking as the BVH, so the hit kept
+/// while traversing is the one the traversal reports as closest.
+fn keep_closest(closest: &mut Option<(Hit, usize)>, index: usize, hit: Hit) {
+    let nearer = closest.is_none_or(|(best, best_index)| {
+        hit.distance() < best.distance()
+            || (hit.distance().total_cmp(&best.distance()).is_eq() && index < best_index)
+    });
+    if nearer {
+        *closest = Some((hit, index));
     }
-    direction * eta + n * eta.mul_add(cosi, -k.sqrt())
 }
 
-fn adjust_ray_origin(direction: Vec3f, point: Vec3f, normal: Vec3f) -> Vec3f {
-    if direction * normal < 0.0 {
-        return point - normal * EPSILON;
+impl Geometry<'_> {
+    /// Intersects one shape, dropping hits outside `[0, max_distance)` (including NaN) and
+    /// back-face hits on shapes whose material culls them.
+    fn visible_hit(
+        self,
+        index: usize,
+        origin: Vec3f,
+        direction: Vec3f,
+        inverse_direction: Vec3f,
+    ) -> Option<Hit> {
+        let shape = &self.shapes[index];
+        let hit = shape
+            .ray_hit_inverse(origin, direction, inverse_direction)
+            .filter(|hit| (0.0..self.max_distance).contains(&hit.distance()))?;
+        if shape.get_material().sidedness() == Sidedness::Culled
+            && direction * hit.geometric_normal() > 0.0
+        {
+            return None;
+        }
+
+        Some(hit)
     }
 
-    point + normal * EPSILON
+    fn closest_hit(self, origin: Vec3f, direction: Vec3f) -> Option<(Hit, usize)> {
+        stats::count_ray();
+        let inverse_direction = inverse_direction(direction);
+        let mut closest = None;
+        self.bvh.closest_hit(origin, inverse_direction, |index| {
+            let hit = self.visible_hit(index, origin, direction, inverse_direction)?;
+            keep_closest(&mut closest, index, hit);
+            Some(hit.distance())
+        });
+        closest
+    }
+
+    /// Any-hit query: whether anything blocks the ray before `max_distance`. An infinite
+    /// `max_distance` is blocked by any hit along the ray.
+    fn occluded(self, origin: Vec3f, direction: Vec3f, max_distance: f64) -> bool {
+        stats::count_ray();
+        self.bvh.any_hit(
+            origin,
+            inverse_direction(direction),
+            max_distance,
+            |index| self.shapes[index].intersects_any(origin, direction, max_distance),
+        )
+    }
+
+    fn closest_hits(self, rays: &[(Vec3f, Vec3f)], hits: &mut [Option<(Hit, usize)>]) {
+        let mut inverse = [(Vec3f::new(0.0), Vec3f::new(0.0)); MAX_PACKET_SIZE];
+        for (slot, &(origin, direction)) in inverse.iter_mut().zip(rays) {
+            stats::count_ray();
+            *slot = (origin, inverse_direction(direction));
+        }
+
+        hits.fill(None);
+        let mut distances = [None; MAX_PACKET_SIZE];
+        self.bvh.closest_hits(
+            &inverse[..rays.len()],
+            |ray, index| {
+                let (origin, direction) = rays[ray];
+                let hit = self.visible_hit(index, origin, direction, inverse[ray].1)?;
+                keep_closest(&mut hits[ray], index, hit);
+                Some(hit.distance())
+            },
+            &mut distances[..rays.len()],
+        );
+    }
 }
 
-fn is_in_shadow(
-    normal: Vec3f,
+const THICKNESS_OFFSET_FRACTION: f64 = 0.25;
+const DISTANCE_OFFSET_FRACTION: f64 = 1e-3;
+
+#[derive(Clone, Copy, Debug)]
+struct SurfaceHit {
+    shape: usize,
     point: Vec3f,
-    light_direction: Vec3f,
-    light_distance: f64,
-    shapes: &[ShapeType],
-) -> (bool, Option<(Vec3f, Vec3f)>) {
-    let shadow_origin = adjust_ray_origin(light_direction, point, normal);
-    let scene_intersect_option = scene_intersect(shadow_origin, light_direction, shapes);
-    let Some(scene_intersect_result) = scene_intersect_option else {
-        return (false, None);
-    };
+    normal: Vec3f,
+    geometric_normal: Vec3f,
+    shadow_point: Vec3f,
+    material: Material,
+    thickness: f64,
+    distance: f64,
+    front_face: bool,
+}
 
-    let (shadow_hit, _, _) = scene_intersect_result;
+impl SurfaceHit {
+    fn facing(self) -> Self {
+        if self.front_face {
+            return self;
+        }
 
-    (
-        (shadow_hit - shadow_origin).length() < light_distance,
-        Some((shadow_origin, shadow_hit)),
-    )
+        Self {
+            normal: -self.normal,
+            geometric_normal: -self.geometric_normal,
+            ..self
+        }
+    }
+
+    /// The hit as seen by lights: single-sided materials keep their outward normal on back
+    /// faces, everything else is lit from whichever side the ray arrived on.
+    fn lit(self) -> Self {
+        match self.material.sidedness() {
+            Sidedness::Single => self,
+            Sidedness::Double | Sidedness::Culled => self.facing(),
+        }
+    }
+
+    /// Moves the hit point off the surface towards `direction`. The bias is `epsilon` at
+    /// most, shrinks for thin shapes and close-up hits so small objects keep their contact
+    /// shadows, and is topped up with a magnitude-aware nudge against self-intersection.
+    fn offset_origin(&self, direction: Vec3f, epsilon: f64) -> Vec3f {
+        let offset = epsilon
+            .min(self.thickness * THICKNESS_OFFSET_FRACTION)
+            .min(self.distance * DISTANCE_OFFSET_FRACTION);
+        let normal = if direction * self.geometric_normal < 0.0 {
+            -self.geometric_normal
+        } else {
+            self.geometric_normal
+        };
+
+        offset_ray_origin(self.point + normal * offset, normal)
+    }
 }
 
-fn scene_intersect(
+/// Casts a shadow ray from `surface` towards a light sample. Point and area lights are only
+/// blocked by geometry closer than the light; directional lights sit at infinite distance,
+/// so any hit along the ray blocks them.
+fn is_in_shadow(surface: &SurfaceHit, sample: &LightSample, geometry: Geometry<'_>) -> bool {
+    let shadow_origin = SurfaceHit {
+        point: surface.shadow_point,
+        ..*surface
+    }
+    .offset_origin(sample.direction(), geometry.epsilon);
+    stats::count_shadow_ray();
+    geometry.occluded(shadow_origin, sample.direction(), sample.distance())
+}
+
+const VOLUME_STEPS: u32 = 48;
+const VOLUME_SHADOW_STEPS: u32 = 8;
+const SHAFT_STEPS: u32 = 32;
+const SHAFT_RANGE: f64 = 50.0;
+const ISOTROPIC_PHASE: f64 = 1.0 / (4.0 * PI);
+const PENUMBRA_STEPS: u32 = 64;
+const PENUMBRA_RANGE: f64 = 100.0;
+
+fn penumbra(
     origin: Vec3f,
     direction: Vec3f,
-    shapes: &[ShapeType],
-) -> Option<(Vec3f, Vec3f, Material)> {
-    shapes
-        .iter()
-        .filter_map(|shape| {
-            shape.ray_intersect(origin, direction).map(|distance| {
-                let hit = origin + direction * distance;
-                let normal = shape.get_normal(hit);
-                let material = shape.get_material();
-                (distance, (hit, normal, material))
-            })
-        })
-        .min_by(
-            |a, b| match (a.0.partial_cmp(&b.0), a.0.is_nan(), b.0.is_nan()) {
-                (Some(order), false, false) => order,
-                (_, true, false) => std::cmp::Ordering::Greater,
-                (_, false, true) => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
-            },
-        )
-        .filter(|(dist, _)| *dist < 1000.0)
-        .map(|(_, result)| result)
+    light_distance: f64,
+    skip: usize,
+    geometry: Geometry<'_>,
+) -> f64 {
+    let Some(sharpness) = geometry.soft_shadows else {
+        return 1.0;
+    };
+
+    let max_distance = light_distance.min(PENUMBRA_RANGE);
+    let mut visibility: f64 = 1.0;
+    let mut distance = geometry.epsilon;
+    for _ in 0..PENUMBRA_STEPS {
+        if distance >= max_distance {
+            break;
+        }
+
+        let point = origin + direction * distance;
+        let Some(closest) = geometry
+            .shapes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != skip)
+            .filter_map(|(_, shape)| shape.signed_distance(point))
+            .reduce(f64::min)
+        else {
+            return 1.0;
+        };
+        if closest < geometry.epsilon {
+            return 0.0;
+        }
+
+        visibility = visibility.min(sharpness * closest / distance);
+        distance += closest;
+    }
+
+    visibility.clamp(0.0, 1.0)
+}
+
+fn surface_hit(
+    (origin, direction): (Vec3f, Vec3f),
+    (hit, index): (Hit, usize),
+    geometry: Geometry<'_>,
+) -> SurfaceHit {
+    let shape = &geometry.shapes[index];
+    let point = origin + direction * hit.distance();
+    SurfaceHit {
+        shape: index,
+        point,
+        normal: hit.normal(),
+        geometric_normal: hit.geometric_normal(),
+        shadow_point: shape.get_shadow_point(point),
+        material: shape.get_material_at(point),
+        thickness: shape.thickness(),
+        distance: hit.distance() * direction.length(),
+        front_face: direction * hit.geometric_normal() <= 0.0,
+    }
+}
+
+fn scene_intersect(origin: Vec3f, direction: Vec3f, geometry: Geometry<'_>) -> Option<SurfaceHit> {
+    geometry
+        .closest_hit(origin, direction)
+        .map(|closest| surface_hit((origin, direction), closest, geometry))
+}
+
+fn scene_intersect_packet(
+    rays: &[(Vec3f, Vec3f)],
+    geometry: Geometry<'_>,
+    surfaces: &mut [Option<SurfaceHit>],
+) {
+    let mut closest = [None; MAX_PACKET_SIZE];
+    let closest = &mut closest[..rays.len()];
+    geometry.closest_hits(rays, closest);
+
+    for ((&ray, surface), &closest) in rays.iter().zip(surfaces).zip(closest.iter()) {
+        *surface = closest.map(|closest| surface_hit(ray, closest, geometry));
+    }
+}
+
+const MIN_SHADOW_PROBES: u32 = 4;
+const PROBE_STRIDE_RATIO: f64 = 0.618_033_988_749_895;
+
+fn add_terms(acc: (f64, f64, f64), val: (f64, f64, f64)) -> (f64, f64, f64) {
+    (acc.0 + val.0, acc.1 + val.1, acc.2 + val.2)
 }
 
 fn compute_lighthing(
-    hit: Vec3f,
-    normal: Vec3f,
+    surface: &SurfaceHit,
     direction: Vec3f,
     lights: &[LightType],
-    material: Material,
-    shapes: &[ShapeType],
+    geometry: Geometry<'_>,
 ) -> (f64, f64, f64) {
     let (ambient, specular, diffuse) = lights
         .iter()
-        .map(|light| {
-            if light.is_ambient() {
-                return (light.intensity(), 0.0, 0.0);
-            }
+        .map(|light| light_terms(surface, direction, light, geometry))
+        .fold((0.0, 0.0, 0.0), add_terms);
 
-            let light_direction = light.get_direction(hit);
-            let light_distance = light.get_distance(hit);
-            let reflect = reflect(light_direction, normal) * direction;
+    (ambient, diffuse, specular)
+}
 
-            let (shadowed, shadow_point) =
-                is_in_shadow(normal, hit, light_direction, light_distance, shapes);
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn shadow_probes(count: u32, quality: f64) -> u32 {
+    let probes = (f64::from(count) * quality).ceil() as u32;
+    probes.clamp(MIN_SHADOW_PROBES.min(count), count)
+}
 
-            if shadowed {
-                if let Some((origin, hit)) = shadow_point {
-                    if (hit - origin).length() < light_distance {
-                        return (0.0, 0.0, 0.0);
-                    }
-                }
-            }
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn probe_stride(count: u32) -> u32 {
+    let coprime = |stride: &u32| {
+        let (mut a, mut b) = (*stride, count);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a == 1
+    };
+    let start = (f64::from(count) * PROBE_STRIDE_RATIO).round() as u32;
 
-            let diffuse = light.intensity() * f64::max(0.0, light_direction * normal);
-            let specular = reflect.max(0.0).powf(material.specular_exponent()) * light.intensity();
+    (start.max(1)..count).find(coprime).unwrap_or(1)
+}
 
-            (0.0, specular, diffuse)
-        })
-        .fold((0.0, 0.0, 0.0), |acc, val| {
-            (acc.0 + val.0, acc.1 + val.1, acc.2 + val.2)
-        });
+fn light_terms(
+    surface: &SurfaceHit,
+    direction: Vec3f,
+    light: &LightType,
+    geometry: Geometry<'_>,
+) -> (f64, f64, f64) {
+    let count = light.sample_count();
+    let evaluate = |index| light_sample_terms(surface, direction, light, index, geometry);
+    let probes = geometry
+        .shadow_quality
+        .map_or(count, |quality| shadow_probes(count, quality));
+    if probes >= count {
+        return (0..count)
+            .filter_map(evaluate)
+            .fold((0.0, 0.0, 0.0), add_terms);
+    }
 
-    (ambient, diffuse, specular)
+    let stride = u64::from(probe_stride(count));
+    let mut order = (0..u64::from(count))
+        .map(|step| step * stride % u64::from(count))
+        .filter_map(|index| u32::try_from(index).ok());
+    let (sampled, lit) = order.by_ref().take(probes as usize).map(evaluate).fold(
+        ((0.0, 0.0, 0.0), 0),
+        |(total, lit), terms| {
+            terms.map_or((total, lit), |terms| (add_terms(total, terms), lit + 1))
+        },
+    );
+
+    if lit == 0 || lit == probes {
+        let scale = f64::from(count) / f64::from(probes);
+        (sampled.0 * scale, sampled.1 * scale, sampled.2 * scale)
+    } else {
+        order.filter_map(evaluate).fold(sampled, add_terms)
+    }
 }
 
-fn cast_ray(
+fn light_sample_terms(
+    surface: &SurfaceHit,
+    direction: Vec3f,
+    light: &LightType,
+    index: u32,
+    geometry: Geometry<'_>,
+) -> Option<(f64, f64, f64)> {
+    let SurfaceHit {
+        point: hit,
+        normal,
+        material,
+        ..
+    } = *surface;
+
+    if light.is_ambient() {
+        return Some((light.intensity(), 0.0, 0.0));
+    }
+
+    let sample = light.sample(hit, index);
+    let light_direction = sample.direction();
+    let light_distance = sample.distance();
+
+    if is_in_shadow(surface, &sample, geometry) {
+        return None;
+    }
+
+    let shadow_origin = SurfaceHit {
+        point: surface.shadow_point,
+        ..*surface
+    }
+    .offset_origin(light_direction, geometry.epsilon);
+    let visibility = penumbra(
+        shadow_origin,
+        light_direction,
+        light_distance,
+        surface.shape,
+        geometry,
+    );
+
+    let visibility =
+        visibility * volume_transmittance(shadow_origin, light_direction, light_distance, geometry);
+
+    let intensity = light.intensity() * sample.weight() * visibility;
+    let diffuse = intensity * f64::max(0.0, light_direction * normal);
+    let specular = material
+        .specular_model()
+        .unwrap_or(geometry.specular_model)
+        .highlight(
+            normal,
+            light_direction,
+            direction,
+            material.specular_exponent(),
+        )
+        * intensity;
+
+    Some((0.0, specular, diffuse))
+}
+
+struct ShadingTerms {
+    surface: SurfaceHit,
+    material: Material,
+    ambient: f64,
+    sky: Vec3f,
+    diffuse: f64,
+    specular: f64,
+    reflect_color: Vec3f,
+    refract_color: Vec3f,
+    /// Light focused onto the surface by mirrors and glass, gathered from the photon map.
+    caustic: Vec3f,
+}
+
+impl ShadingTerms {
+    fn ambient_color(&self) -> Vec3f {
+        self.material.ambient_color() * self.ambient
+            + self
+                .material
+                .diffuse_color()
+                .hadamard(self.sky + self.caustic)
+                * self.material.albedo()[0]
+    }
+
+    fn diffuse_color(&self) -> Vec3f {
+        self.ambient_color()
+            + self.material.diffuse_color() * self.diffuse * self.material.albedo()[0]
+    }
+
+    fn specular_color(&self) -> Vec3f {
+        let albedo = self.material.albedo();
+        Vec3f::new_with_data([1.0, 1.0, 1.0]) * self.specular * albedo[1]
+            + self.reflect_color * albedo[2]
+            + self.refract_color * albedo[3]
+    }
+
+    fn color(&self) -> Vec3f {
+        self.ambient_color() + self.direct_color()
+    }
+
+    fn direct_color(&self) -> Vec3f {
+        let albedo = self.material.albedo();
+        self.material.diffuse_color() * self.diffuse * albedo[0]
+            + Vec3f::new_with_data([1.0, 1.0, 1.0]) * self.specular * albedo[1]
+            + self.reflect_color * albedo[2]
+            + self.refract_color * albedo[3]
+    }
+
+    fn toon_color(&self) -> Vec3f {
+        let albedo = self.material.albedo();
+        let diffuse = (self.diffuse * TOON_BANDS).ceil() / TOON_BANDS;
+        let specular = if self.specular > TOON_HIGHLIGHT {
+            1.0
+        } else {
+            0.0
+        };
+
+        self.ambient_color()
+            + self.material.diffuse_color() * diffuse * albedo[0]
+            + Vec3f::new_with_data([1.0, 1.0, 1.0]) * specular * albedo[1]
+            + self.reflect_color * albedo[2]
+            + self.refract_color * albedo[3]
+    }
+}
+
+const ROUGH_SAMPLES: u32 = 8;
+const TOON_BANDS: f64 = 3.0;
+const TOON_HIGHLIGHT: f64 = 0.5;
+const TOON_OUTLINE: f64 = 0.25;
+const PATH_BASES: [(u32, u32); 5] = [(2, 3), (5, 7), (11, 13), (17, 19), (23, 29)];
+
+enum PathStep {
+    Done(Vec3f),
+    Bounce {
+        direct: Vec3f,
+        weight: Vec3f,
+        origin: Vec3f,
+        direction: Vec3f,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PathState {
+    slot: usize,
+    sample: u32,
+    rotation: (f64, f64),
     origin: Vec3f,
     direction: Vec3f,
-    shapes: &[ShapeType],
-    lights: &[LightType],
+    throughput: Vec3f,
     depth: u32,
-) -> Vec3f {
-    if depth > MAX_DEPTH {
-        return BACKGROUND_COLOR;
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SecondaryRay {
+    origin: Vec3f,
+    direction: Vec3f,
+    media: MediumStack,
+}
+
+fn secondary_rays(
+    surface: &SurfaceHit,
+    direction: Vec3f,
+    media: MediumStack,
+    epsilon: f64,
+) -> (SecondaryRay, SecondaryRay, bool) {
+    let SurfaceHit {
+        normal, material, ..
+    } = *surface;
+
+    let reflect_direction = reflect(direction, normal).normalize(None);
+    let reflected = SecondaryRay {
+        origin: surface.offset_origin(reflect_direction, epsilon),
+        direction: reflect_direction,
+        media,
+    };
+
+    let ior = media.ior(&material);
+    let entering = direction * normal < 0.0;
+    let (ior_outside, refract_media) = if entering {
+        (media.current(), media.entered(ior, material.absorption()))
+    } else {
+        let outside = media.exited(ior);
+        (outside.current(), outside)
+    };
+
+    let refract_direction = refract(direction, normal, ior_outside, ior).normalize(None);
+    let refracted = SecondaryRay {
+        origin: surface.offset_origin(refract_direction, epsilon),
+        direction: refract_direction,
+        media: refract_media,
+    };
+
+    (reflected, refracted, entering)
+}
+
+/// Secondary rays off a microfacet drawn from the GGX lobe of `roughness` instead of the
+/// surface normal. `None` when the facet faces the other way from the surface, which
+/// would confuse entering and leaving the medium.
+fn rough_secondary_rays(
+    surface: &SurfaceHit,
+    direction: Vec3f,
+    media: MediumStack,
+    roughness: f64,
+    (u1, u2): (f64, f64),
+    epsilon: f64,
+) -> Option<(SecondaryRay, SecondaryRay)> {
+    let outside = direction * surface.normal < 0.0;
+    let side = if outside {
+        surface.normal
+    } else {
+        -surface.normal
+    };
+    let facet = ggx_normal(side, roughness, u1, u2);
+    if (direction * facet < 0.0) != outside {
+        return None;
     }
 
-    let Some((hit, normal, material)) = scene_intersect(origin, direction, shapes) else {
-        return BACKGROUND_COLOR;
+    let facet = if outside { facet } else { -facet };
+    let (reflected, refracted, _) = secondary_rays(
+        &SurfaceHit {
+            normal: facet,
+            ..*surface
+        },
+        direction,
+        media,
+        epsilon,
+    );
+    Some((reflected, refracted))
+}
+
+/// The Halton point `sample` rotated by a hash of `point`, so neighbouring pixels don't
+/// share a sample pattern.
+#[allow(clippy::cast_possible_truncation)]
+fn rotated_halton(point: Vec3f, sample: u32) -> (f64, f64) {
+    let hash = |value: f64| {
+        let bits = value.to_bits();
+        (bits ^ (bits >> 32)) as u32
     };
+    let rotation = pixel_rotation(hash(point.x()) ^ hash(point.z()), hash(point.y()));
 
-    let reflect_direction = reflect(direction, normal).normalize(None);
-    let reflect_origin = adjust_ray_origin(reflect_direction, hit, normal);
-    let reflect_color = cast_ray(reflect_origin, reflect_direction, shapes, lights, depth + 1);
+    (
+        (halton(sample, 2) + rotation.0).fract(),
+        (halton(sample, 3) + rotation.1).fract(),
+    )
+}
+
+/// Averages `trace` over rough bounce samples: several for the first bounce off a rough
+/// surface and one further down, where the pixel's own samples average the noise out.
+fn average_rough_samples(point: Vec3f, depth: u32, trace: impl Fn((f64, f64)) -> Vec3f) -> Vec3f {
+    let samples = if depth == 0 { ROUGH_SAMPLES } else { 1 };
+
+    (1..=samples)
+        .map(|sample| trace(rotated_halton(point, sample)))
+        .fold(Vec3f::new(0.0), |total, color| total + color)
+        / f64::from(samples)
+}
+
+fn shading_terms(
+    origin: Vec3f,
+    direction: Vec3f,
+    geometry: Geometry<'_>,
+    lights: &[LightType],
+    background: Vec3f,
+    media: MediumStack,
+    depth: u32,
+) -> Option<ShadingTerms> {
+    let surface = scene_intersect(origin, direction, geometry)?;
+    Some(surface_terms(
+        &surface, direction, geometry, lights, background, media, depth,
+    ))
+}
+
+fn surface_terms(
+    surface: &SurfaceHit,
+    direction: Vec3f,
+    geometry: Geometry<'_>,
+    lights: &[LightType],
+    background: Vec3f,
+    media: MediumStack,
+    depth: u32,
+) -> ShadingTerms {
+    let material = surface.material;
+
+    let (reflected, refracted, _) = secondary_rays(surface, direction, media, geometry.epsilon);
+    let trace_reflection = |reflected: SecondaryRay| {
+        cast_ray(
+            reflected.origin,
+            reflected.direction,
+            geometry,
+            lights,
+            background,
+            reflected.media,
+            depth + 1,
+        )
+    };
+    let reflect_color = if material.reflect_roughness() > 0.0 {
+        average_rough_samples(surface.point, depth, |sample| {
+            let glossy = rough_secondary_rays(
+                surface,
+                direction,
+                media,
+                material.reflect_roughness(),
+                sample,
+                geometry.epsilon,
+            )
+            .map(|(glossy, _)| glossy)
+            .filter(|glossy| {
+                (glossy.direction * surface.normal) * (direction * surface.normal) < 0.0
+            });
+            trace_reflection(glossy.unwrap_or(reflected))
+        })
+    } else {
+        match geometry.mirror_cache {
+            Some(cell_size) if mirror_cache::is_pure_mirror(&material) => {
+                mirror_cache::trace_cached(
+                    cell_size,
+                    (reflected.origin, reflected.direction),
+                    depth + 1,
+                    reflected.media.current(),
+                    || trace_reflection(reflected),
+                )
+            }
+            _ => trace_reflection(reflected),
+        }
+    };
+    let trace_refraction = |refracted: SecondaryRay| {
+        cast_ray(
+            refracted.origin,
+            refracted.direction,
+            geometry,
+            lights,
+            background,
+            refracted.media,
+            depth + 1,
+        )
+    };
+    let trace_through = |media: MediumStack, refracted: SecondaryRay| {
+        if material.refract_roughness() <= 0.0 {
+            return trace_refraction(refracted);
+        }
 
-    let refract_direction = refract(direction, normal, material.refractive_index()).normalize(None);
-    let refract_origin = adjust_ray_origin(refract_direction, hit, normal);
-    let refract_color = cast_ray(refract_origin, refract_direction, shapes, lights, depth + 1);
+        average_rough_samples(surface.point, depth, |sample| {
+            let frosted = rough_secondary_rays(
+                surface,
+                direction,
+                media,
+                material.refract_roughness(),
+                sample,
+                geometry.epsilon,
+            )
+            .map(|(_, frosted)| frosted)
+            .filter(|frosted| {
+                (frosted.direction * surface.normal) * (direction * surface.normal) > 0.0
+            });
+            trace_refraction(frosted.unwrap_or(refracted))
+        })
+    };
+    let refract_color = if material.disperses() && media.channel.is_none() {
+        // Each channel bends by its own index, which fans white light out into fringes.
+        Vec3f::new_with_data([0, 1, 2].map(|channel| {
+            let media = media.split(channel);
+            let (_, refracted, _) = secondary_rays(surface, direction, media, geometry.epsilon);
+            trace_through(media, refracted)[channel]
+        }))
+    } else {
+        trace_through(media, refracted)
+    };
 
-    let (ambient, diffuse, specular) =
-        compute_lighthing(hit, normal, direction, lights, material, shapes);
+    let lit = surface.lit();
+    let (ambient, diffuse, specular) = compute_lighthing(&lit, direction, lights, geometry);
 
-    calculate_final_color(
+    ShadingTerms {
+        surface: surface.facing(),
         material,
         ambient,
+        sky: sky_irradiance(lit.normal, lights)
+            + environment_irradiance(&lit, lights, geometry, depth),
         diffuse,
         specular,
         reflect_color,
         refract_color,
-    )
+        caustic: geometry.caustics.map_or(Vec3f::new(0.0), |caustics| {
+            caustics.irradiance(lit.point, lit.normal)
+        }),
+    }
 }
 
-fn calculate_final_color(
-    material: Material,
-    ambient_light_intensity: f64,
-    diffuse_light_intensity: f64,
-    specular_light_intensity: f64,
-    reflect_color: Vec3f,
-    refract_color: Vec3f,
-) -> Vec3f {
+fn is_specular(material: &Material) -> bool {
     let albedo = material.albedo();
-    material.ambient_color() * ambient_light_intensity
-        + material.diffuse_color() * diffuse_light_intensity * albedo[0]
-        + Vec3f::new_with_data([1.0, 1.0, 1.0]) * specular_light_intensity * albedo[1]
-        + reflect_color * albedo[2]
-        + refract_color * albedo[3]
+    albedo[2] > 0.0 || albedo[3] > 0.0
 }
 
-pub struct Scene {
-    shapes: Vec<ShapeType>,
-    lights: Vec<LightType>,
+#[derive(Clone, Copy, Debug)]
+struct PhotonRay {
+    origin: Vec3f,
+    direction: Vec3f,
+    power: Vec3f,
+    media: MediumStack,
+    travelled: f64,
+    /// Point lights don't fall off with distance in this renderer, so photons from them
+    /// are scaled back up by the squared length of their path when they land.
+    from_point: bool,
 }
 
-impl Scene {
-    pub const fn new(shapes: Vec<ShapeType>, lights: Vec<LightType>) -> Self {
-        Self { shapes, lights }
+/// Follows a photon through mirrors and glass, storing it on every diffuse surface it lands
+/// on after at least one bounce. Photons that don't hit `target` first are dropped, since
+/// the cone aimed at whatever they did hit covers them already.
+fn trace_photon(
+    ray: &PhotonRay,
+    target: usize,
+    geometry: Geometry<'_>,
+    depth: u32,
+    photons: &mut Vec<Photon>,
+) {
+    if depth > geometry.max_depth {
+        return;
+    }
+    let Some(surface) = scene_intersect(ray.origin, ray.direction, geometry) else {
+        return;
+    };
+    if depth == 0 && surface.shape != target {
+        return;
     }
 
-    #[allow(dead_code)]
-    pub fn push_light(&mut self, light: LightType) {
-        self.lights.push(light);
+    let material = surface.material;
+    let albedo = material.albedo();
+    let travelled = ray.travelled + surface.distance;
+    let power = ray
+        .power
+        .hadamard(ray.media.transmittance(surface.distance));
+
+    if depth > 0 && albedo[0] > 0.0 {
+        let scale = if ray.from_point {
+            travelled * travelled
+        } else {
+            1.0
+        };
+        photons.push(Photon::new(surface.point, ray.direction, power * scale));
     }
 
-    #[allow(dead_code)]
-    pub fn push_shape(&mut self, shape: ShapeType) {
-        self.shapes.push(shape);
+    let bounce = |secondary: SecondaryRay, power: Vec3f, photons: &mut Vec<Photon>| {
+        let ray = PhotonRay {
+            origin: secondary.origin,
+            direction: secondary.direction,
+            power,
+            media: secondary.media,
+            travelled,
+            from_point: ray.from_point,
+        };
+        trace_photon(&ray, target, geometry, depth + 1, photons);
+    };
+
+    let (reflected, refracted, _) =
+        secondary_rays(&surface, ray.direction, ray.media, geometry.epsilon);
+    if albedo[2] > 0.0 {
+        bounce(reflected, power * albedo[2], photons);
+    }
+    if albedo[3] > 0.0 {
+        if material.disperses() && ray.media.channel.is_none() {
+            for channel in 0..3 {
+                let (_, refracted, _) = secondary_rays(
+                    &surface,
+                    ray.direction,
+                    ray.media.split(channel),
+                    geometry.epsilon,
+                );
+                let mask = Vec3f::new_with_data(
+                    [0, 1, 2].map(|other| if other == channel { albedo[3] } else { 0.0 }),
+                );
+                bounce(refracted, power.hadamard(mask), photons);
+            }
+        } else {
+            bounce(refracted, power * albedo[3], photons);
+        }
     }
+}
 
-    pub fn render_scene(&self, frame: &mut [u8], height: u32, width: u32, fov: f64) {
-        let fov_tan = (fov / 2.0).tan();
-        let origin = Vec3f::new_with_data([0.0, 0.0, 2.0]);
-        frame
-            .par_chunks_mut(4)
-            .enumerate()
-            .for_each(|(index, pixel)| {
-                let i_usize = index % (width as usize);
-                let j_usize = index / (width as usize);
+/// Shoots `count` photons from the point and directional lights, aimed at the bounding
+/// spheres of the bounded mirror and glass shapes, and returns the ones that land on
+/// diffuse surfaces. Photon power is in the units the direct lighting uses, so the map's
+/// irradiance can be added to it as is.
+fn emit_caustic_photons(geometry: Geometry<'_>, lights: &[LightType], count: u32) -> Vec<Photon> {
+    let targets: Vec<_> = geometry
+        .shapes
+        .iter()
+        .enumerate()
+        .filter(|(_, shape)| is_specular(&shape.get_material()))
+        .filter_map(|(index, shape)| {
+            shape.bounds().map(|bounds| {
+                let center = (bounds.min() + bounds.max()) * 0.5;
+                (index, center, bounds.extent().length() * 0.5)
+            })
+        })
+        .collect();
+    let paths: Vec<_> = lights
+        .iter()
+        .filter(|light| {
+            matches!(
+                light,
+                LightType::Point(_) | LightType::Directional(_) | LightType::SunSky(_)
+            )
+        })
+        .flat_map(|light| targets.iter().map(move |&target| (light, target)))
+        .collect();
+    let Some((path_count, per_path)) = u32::try_from(paths.len())
+        .ok()
+        .and_then(|path_count| Some((path_count, count.checked_div(path_count)?)))
+    else {
+        return Vec::new();
+    };
+    let reach = geometry
+        .bvh
+        .bounds()
+        .map_or(0.0, |bounds| bounds.extent().length());
 
-                let Ok(i) = u32::try_from(i_usize) else {
-                    eprintln!("Index i out of u32 range: {i_usize}");
-                    return;
-                };
+    (0..per_path * path_count)
+        .into_par_iter()
+        .flat_map_iter(|photon| {
+            let (light, (target, center, radius)) = paths[(photon / per_path) as usize];
+            let index = photon % per_path + 1;
+            let (u1, u2) = (halton(index, 2), halton(index, 3));
+            let emitted = match light {
+                LightType::Point(point) => {
+                    let axis = center - point.position();
+                    let distance = axis.length();
+                    let sin_max = radius / distance;
+                    let cos_max = if distance > radius {
+                        sin_max.mul_add(-sin_max, 1.0).sqrt()
+                    } else {
+                        -1.0
+                    };
+                    let solid_angle = 2.0 * PI * (1.0 - cos_max);
+                    Some((
+                        point.position(),
+                        cone_direction(axis.normalize(None), cos_max, u1, u2),
+                        point.intensity() * solid_angle,
+                        true,
+                    ))
+                }
+                LightType::Directional(_) | LightType::SunSky(_) => {
+                    let direction = -light.get_direction(center);
+                    let offset = disc_offset(direction, radius, u1, u2);
+                    Some((
+                        center + offset - direction * (radius + reach),
+                        direction,
+                        light.intensity() * PI * radius * radius,
+                        false,
+                    ))
+                }
+                _ => None,
+            };
 
-                let Ok(j) = u32::try_from(j_usize) else {
-                    eprintln!("Index j out of u64 range: {j_usize}");
-                    return;
+            let mut photons = Vec::new();
+            if let Some((origin, direction, power, from_point)) = emitted {
+                let ray = PhotonRay {
+                    origin,
+                    direction,
+                    power: Vec3f::new(power / f64::from(per_path)),
+                    media: MediumStack::AIR,
+                    travelled: 0.0,
+                    from_point,
                 };
+                trace_photon(&ray, target, geometry, 0, &mut photons);
+            }
+            photons
+        })
+        .collect()
+}
 
-                let x = (2.0 * (f64::from(i) + 0.5) / f64::from(width) - 1.0)
-                    * fov_tan
-                    * f64::from(width)
-                    / f64::from(height);
-                let y = -(2.0 * (f64::from(j) + 0.5) / f64::from(height) - 1.0) * fov_tan;
-                let dir = Vec3f::new_with_data([x, y, -1.0]).normalize(None);
-                let color = cast_ray(origin, dir, &self.shapes, &self.lights, 0);
-
-                pixel[0] = to_u8(color[0]);
-                pixel[1] = to_u8(color[1]);
-                pixel[2] = to_u8(color[2]);
-                pixel[3] = 255;
-            });
+fn sky_irradiance(normal: Vec3f, lights: &[LightType]) -> Vec3f {
+    lights
+        .iter()
+        .fold(Vec3f::new(0.0), |total, light| match light {
+            LightType::Sky(sky) => total + sky.irradiance(normal),
+            LightType::SunSky(sky) => total + sky.irradiance(normal),
+            _ => total,
+        })
+}
+
+/// Light from environment maps reaching `surface`, in the units of [`sky_irradiance`]:
+/// shadow rays go towards directions drawn by luminance and each unblocked one adds the
+/// map's radiance over its sampling density. Primary hits send the light's full sample
+/// count and deeper hits one.
+fn environment_irradiance(
+    surface: &SurfaceHit,
+    lights: &[LightType],
+    geometry: Geometry<'_>,
+    depth: u32,
+) -> Vec3f {
+    let shadow_origin = SurfaceHit {
+        point: surface.shadow_point,
+        ..*surface
+    };
+
+    lights
+        .iter()
+        .filter_map(|light| match light {
+            LightType::Environment(environment) => Some(environment),
+            _ => None,
+        })
+        .fold(Vec3f::new(0.0), |total, environment| {
+            let samples = if depth == 0 { environment.samples() } else { 1 };
+            let sum = (1..=samples)
+                .filter_map(|sample| {
+                    let (u1, u2) = rotated_halton(surface.point, sample);
+                    let (direction, pdf) = environment.map().sample(u1, u2)?;
+                    let cosine = direction * surface.normal;
+                    if cosine <= 0.0 {
+                        return None;
+                    }
+
+                    let origin = shadow_origin.offset_origin(direction, geometry.epsilon);
+                    stats::count_shadow_ray();
+                    if geometry.occluded(origin, direction, f64::INFINITY) {
+                        return None;
+                    }
+                    Some(environment.radiance(direction) * (cosine / (pdf * PI)))
+                })
+                .fold(Vec3f::new(0.0), |sum, light| sum + light);
+            total + sum / f64::from(samples)
+        })
+}
+
+fn sky_background(direction: Vec3f, lights: &[LightType], background: Vec3f) -> Vec3f {
+    lights
+        .iter()
+        .filter_map(|light| match light {
+            LightType::Sky(sky) => Some(sky.radiance(direction)),
+            LightType::SunSky(sky) => Some(sky.radiance(direction)),
+            LightType::Environment(environment) => Some(environment.radiance(direction)),
+            _ => None,
+        })
+        .reduce(|total, radiance| total + radiance)
+        .unwrap_or(background)
+}
+
+fn emitter_intersect(
+    origin: Vec3f,
+    direction: Vec3f,
+    lights: &[LightType],
+) -> Option<(f64, Vec3f)> {
+    lights
+        .iter()
+        .filter_map(|light| match light {
+            LightType::Area(area) => area.ray_intersect(origin, direction).map(|distance| {
+                let visible = area.emission_towards(-direction) > 0.0;
+                let emission = if visible { area.intensity() } else { 0.0 };
+                (distance, Vec3f::new(emission))
+            }),
+            LightType::Ambient(_)
+            | LightType::Point(_)
+            | LightType::Directional(_)
+            | LightType::Sky(_)
+            | LightType::SunSky(_)
+            | LightType::Environment(_)
+            | LightType::Custom(_) => None,
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+fn cast_ray(
+    origin: Vec3f,
+    direction: Vec3f,
+    geometry: Geometry<'_>,
+    lights: &[LightType],
+    background: Vec3f,
+    media: MediumStack,
+    depth: u32,
+) -> Vec3f {
+    if depth > geometry.max_depth {
+        return sky_background(direction, lights, background);
+    }
+
+    let surface = scene_intersect(origin, direction, geometry);
+    shade_ray(
+        (origin, direction),
+        surface.as_ref(),
+        geometry,
+        lights,
+        background,
+        media,
+        depth,
+    )
+}
+
+fn shade_ray(
+    (origin, direction): (Vec3f, Vec3f),
+    surface: Option<&SurfaceHit>,
+    geometry: Geometry<'_>,
+    lights: &[LightType],
+    background: Vec3f,
+    media: MediumStack,
+    depth: u32,
+) -> Vec3f {
+    let (color, distance) = if let Some((distance, emission)) =
+        emitter_intersect(origin, direction, lights)
+        && surface.is_none_or(|surface| (surface.point - origin).length() > distance)
+    {
+        (geometry.fog.apply(emission, distance, background), distance)
+    } else if let Some(terms) = surface.map(|surface| {
+        surface_terms(
+            surface, direction, geometry, lights, background, media, depth,
+        )
+    }) {
+        let distance = (terms.surface.point - origin).length();
+        (
+            geometry.fog.apply(terms.color(), distance, background),
+            distance,
+        )
+    } else {
+        (sky_background(direction, lights, background), f64::INFINITY)
+    };
+
+    integrate_volumes(origin, direction, distance, color, lights, geometry)
+        .hadamard(media.transmittance(distance))
+}
+
+fn volume_transmittance(
+    origin: Vec3f,
+    direction: Vec3f,
+    max_distance: f64,
+    geometry: Geometry<'_>,
+) -> f64 {
+    geometry
+        .volumes
+        .iter()
+        .filter_map(|volume| {
+            let (near, far) = volume.interval(origin, direction, max_distance)?;
+            let step = (far - near) / f64::from(VOLUME_SHADOW_STEPS);
+            let optical_depth: f64 = (0..VOLUME_SHADOW_STEPS)
+                .map(|index| {
+                    let distance = (f64::from(index) + 0.5).mul_add(step, near);
+                    volume.density_at(origin + direction * distance) * step
+                })
+                .sum();
+            Some((-optical_depth).exp())
+        })
+        .product()
+}
+
+fn volume_in_scattering(point: Vec3f, lights: &[LightType], geometry: Geometry<'_>) -> f64 {
+    lights
+        .iter()
+        .map(|light| {
+            if light.is_ambient() {
+                return light.intensity();
+            }
+
+            let sample = light.sample(point, 0);
+            stats::count_shadow_ray();
+            let occluded = geometry.occluded(point, sample.direction(), sample.distance());
+            if occluded {
+                0.0
+            } else {
+                light.intensity()
+                    * volume_transmittance(point, sample.direction(), sample.distance(), geometry)
+            }
+        })
+        .sum()
+}
+
+fn integrate_volumes(
+    origin: Vec3f,
+    direction: Vec3f,
+    distance: f64,
+    color: Vec3f,
+    lights: &[LightType],
+    geometry: Geometry<'_>,
+) -> Vec3f {
+    arena::with_segments(|segments| {
+        segments.extend(
+            geometry
+                .volumes
+                .iter()
+                .enumerate()
+                .filter_map(|(index, volume)| {
+                    volume
+                        .interval(origin, direction, distance)
+                        .map(|interval| (index, interval))
+                }),
+        );
+        segments.sort_by(|(_, first), (_, second)| second.0.total_cmp(&first.0));
+
+        segments.iter().fold(color, |color, &(index, (near, far))| {
+            let volume = &geometry.volumes[index];
+            let step = (far - near) / f64::from(VOLUME_STEPS);
+            let mut transmittance = 1.0;
+            let mut scattered = 0.0;
+            for index in 0..VOLUME_STEPS {
+                let point = origin + direction * (f64::from(index) + 0.5).mul_add(step, near);
+                let density = volume.density_at(point);
+                if density <= 0.0 {
+                    continue;
+                }
+
+                let step_transmittance = (-density * step).exp();
+                scattered += transmittance
+                    * (1.0 - step_transmittance)
+                    * volume_in_scattering(point, lights, geometry);
+                transmittance *= step_transmittance;
+            }
+
+            color * transmittance + volume.albedo() * scattered
+        })
+    })
+}
+
+const fn components(vector: Vec3f) -> [f64; 3] {
+    [vector.x(), vector.y(), vector.z()]
+}
+
+const fn light_kind(light: &LightType) -> &'static str {
+    match light {
+        LightType::Ambient(_) => "ambient",
+        LightType::Point(_) => "point",
+        LightType::Directional(_) => "directional",
+        LightType::Area(_) => "area",
+        LightType::Sky(_) => "sky",
+        LightType::SunSky(_) => "sun_sky",
+        LightType::Environment(_) => "environment",
+        LightType::Custom(_) => "custom",
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MaterialRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    albedo: [f64; 4],
+    diffuse: [f64; 3],
+    ambient: [f64; 3],
+    specular_exponent: f64,
+    ior: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct LightRecord {
+    light: usize,
+    kind: &'static str,
+    samples: u32,
+    shadowed_samples: u32,
+    ambient: f64,
+    diffuse: f64,
+    specular: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct HitRecord {
+    shape: usize,
+    distance: f64,
+    point: [f64; 3],
+    normal: [f64; 3],
+    front_face: bool,
+    material: MaterialRecord,
+    lights: Vec<LightRecord>,
+    reflected: Box<RayRecord>,
+    refracted: Box<RayRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct RayRecord {
+    kind: &'static str,
+    depth: u32,
+    origin: [f64; 3],
+    direction: [f64; 3],
+    outcome: &'static str,
+    color: [f64; 3],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hit: Option<HitRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct SampleRecord {
+    offset: (f64, f64),
+    ray: RayRecord,
+}
+
+/// Every ray traced for one pixel, as written by `--debug-pixel`.
+#[derive(Debug, Serialize)]
+pub struct PixelTrace {
+    pixel: (u32, u32),
+    integrator: &'static str,
+    rgba: [u8; 4],
+    samples: Vec<SampleRecord>,
+}
+
+const TILE_SIZE: u32 = 32;
+const PACKET_SIZE: usize = 8;
+
+/// Shared flag that stops a render between tiles.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Asks the render using this token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Clears a previous cancellation before the next render.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether [`Self::cancel`] was called since the last [`Self::reset`].
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A band of whole rows out of a `width` x `height` image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageRegion {
+    width: u32,
+    height: u32,
+    first_row: u32,
+    rows: u32,
+}
+
+impl ImageRegion {
+    /// The rows `first_row..first_row + rows` of a `width` x `height` image.
+    pub const fn stripe(width: u32, height: u32, first_row: u32, rows: u32) -> Self {
+        Self {
+            width,
+            height,
+            first_row,
+            rows,
+        }
+    }
+
+    /// First image row covered by the band.
+    pub const fn first_row(&self) -> u32 {
+        self.first_row
+    }
+
+    /// Pixels in the band, not in the whole image.
+    pub const fn pixel_count(&self) -> usize {
+        self.width as usize * self.rows as usize
+    }
+}
+
+/// Stable handle to a shape; stays valid while other shapes are added or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShapeId(usize);
+
+impl fmt::Display for ShapeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Stable handle to a light; stays valid while other lights are added or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightId(usize);
+
+impl fmt::Display for LightId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Shapes, lights and the scene-wide render parameters, with a BVH kept up to date
+/// through every edit made with the methods below.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "SceneData"))]
+pub struct Scene {
+    shapes: Vec<ShapeType>,
+    shape_ids: Vec<ShapeId>,
+    lights: Vec<LightType>,
+    light_ids: Vec<LightId>,
+    next_id: usize,
+    background: Vec3f,
+    max_depth: u32,
+    epsilon: f64,
+    max_distance: f64,
+    fog: Fog,
+    soft_shadows: Option<f64>,
+    mirror_cache: Option<f64>,
+    shadow_quality: Option<f64>,
+    specular_model: SpecularModel,
+    volumes: Vec<Volume>,
+    materials: MaterialRegistry,
+    material_bindings: HashMap<ShapeId, String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bvh: Bvh,
+    /// Photon count and gather radius for caustics, when they are turned on.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    caustics: Option<(u32, f64)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    photon_map: Option<PhotonMap>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SceneData {
+    shapes: Vec<ShapeType>,
+    shape_ids: Vec<ShapeId>,
+    lights: Vec<LightType>,
+    light_ids: Vec<LightId>,
+    next_id: usize,
+    background: Vec3f,
+    max_depth: u32,
+    epsilon: f64,
+    #[serde(default = "default_max_distance")]
+    max_distance: f64,
+    fog: Fog,
+    soft_shadows: Option<f64>,
+    mirror_cache: Option<f64>,
+    shadow_quality: Option<f64>,
+    specular_model: SpecularModel,
+    volumes: Vec<Volume>,
+    materials: MaterialRegistry,
+    material_bindings: HashMap<ShapeId, String>,
+}
+
+#[cfg(feature = "serde")]
+const fn default_max_distance() -> f64 {
+    MAX_DISTANCE
+}
+
+#[cfg(feature = "serde")]
+impl From<SceneData> for Scene {
+    fn from(data: SceneData) -> Self {
+        let mut scene = Self {
+            shapes: data.shapes,
+            shape_ids: data.shape_ids,
+            lights: data.lights,
+            light_ids: data.light_ids,
+            next_id: data.next_id,
+            background: data.background,
+            max_depth: data.max_depth,
+            epsilon: data.epsilon,
+            max_distance: data.max_distance,
+            fog: data.fog,
+            soft_shadows: data.soft_shadows,
+            mirror_cache: data.mirror_cache,
+            shadow_quality: data.shadow_quality,
+            specular_model: data.specular_model,
+            volumes: data.volumes,
+            materials: data.materials,
+            material_bindings: data.material_bindings,
+            bvh: Bvh::default(),
+            caustics: None,
+            photon_map: None,
+        };
+        scene.rebuild_bvh();
+        scene
+    }
+}
+
+fn build_bvh(shapes: &[ShapeType]) -> Bvh {
+    let start = Instant::now();
+    let bvh = Bvh::new(shapes.iter().map(Shape::bounds));
+    debug!(
+        "Built BVH over {} shapes in {:.3} ms",
+        shapes.len(),
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    bvh
+}
+
+impl Scene {
+    /// Builds a scene with default parameters, numbering the shapes and then the lights
+    /// from zero.
+    pub fn new(shapes: Vec<ShapeType>, lights: Vec<LightType>) -> Self {
+        let shape_ids = (0..shapes.len()).map(ShapeId).collect();
+        let light_ids = (shapes.len()..shapes.len() + lights.len())
+            .map(LightId)
+            .collect();
+        let next_id = shapes.len() + lights.len();
+        let bvh = build_bvh(&shapes);
+
+        Self {
+            shapes,
+            shape_ids,
+            lights,
+            light_ids,
+            next_id,
+            background: BACKGROUND_COLOR,
+            max_depth: MAX_DEPTH,
+            epsilon: EPSILON,
+            max_distance: MAX_DISTANCE,
+            fog: Fog::NONE,
+            soft_shadows: None,
+            mirror_cache: None,
+            shadow_quality: None,
+            specular_model: SpecularModel::default(),
+            volumes: Vec::new(),
+            materials: MaterialRegistry::default(),
+            material_bindings: HashMap::new(),
+            bvh,
+            caustics: None,
+            photon_map: None,
+        }
+    }
+
+    /// Builder form of [`Self::set_background`].
+    pub const fn with_background(mut self, background: Vec3f) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Color returned by rays that escape the scene.
+    pub const fn set_background(&mut self, background: Vec3f) {
+        self.background = background;
+    }
+
+    /// Bounces traced before a ray gives up.
+    pub const fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Sets how many bounces a ray may take.
+    pub const fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    /// Offset that keeps secondary rays from hitting the surface they start on.
+    pub const fn set_epsilon(&mut self, epsilon: f64) {
+        self.epsilon = epsilon;
+    }
+
+    /// Far clip distance; nothing beyond it is hit.
+    pub const fn set_max_distance(&mut self, max_distance: f64) {
+        self.max_distance = max_distance;
+    }
+
+    /// Replaces the fog, or clears it with [`Fog::NONE`].
+    pub const fn set_fog(&mut self, fog: Fog) {
+        self.fog = fog;
+    }
+
+    /// Marches shadow rays for penumbrae of the given `sharpness`, or casts hard
+    /// shadows with `None`.
+    pub const fn set_soft_shadows(&mut self, sharpness: Option<f64>) {
+        self.soft_shadows = sharpness;
+    }
+
+    /// Caches reflections off pure mirrors on a grid of `cell_size`, or traces them
+    /// exactly with `None`.
+    pub const fn set_mirror_cache(&mut self, cell_size: Option<f64>) {
+        self.mirror_cache = cell_size;
+    }
+
+    /// Probes this share of each area light's shadow samples and skips the rest when the
+    /// probes agree, or takes every sample with `None`.
+    pub const fn set_shadow_quality(&mut self, quality: Option<f64>) {
+        self.shadow_quality = quality;
+    }
+
+    /// Turns caustics on with `(photons, radius)`, or off with `None`. The photon map is
+    /// traced here and again whenever the shapes change.
+    pub fn set_caustics(&mut self, caustics: Option<(u32, f64)>) {
+        self.caustics = caustics;
+        self.rebuild_photon_map();
+    }
+
+    /// The caustic photons traced by [`Self::set_caustics`], if any.
+    pub const fn photon_map(&self) -> Option<&PhotonMap> {
+        self.photon_map.as_ref()
+    }
+
+    /// Highlight model used by materials that don't pick their own.
+    pub const fn set_specular_model(&mut self, specular_model: SpecularModel) {
+        self.specular_model = specular_model;
+    }
+
+    /// Shapes in the order of [`Self::shape_ids`].
+    pub fn shapes(&self) -> &[ShapeType] {
+        &self.shapes
+    }
+
+    /// Adds participating media that rays scatter through on their way to surfaces.
+    pub fn with_volumes(mut self, volumes: Vec<Volume>) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    /// The participating media added with [`Self::with_volumes`].
+    pub fn volumes(&self) -> &[Volume] {
+        &self.volumes
+    }
+
+    /// Lights in the order of [`Self::light_ids`].
+    pub fn lights(&self) -> &[LightType] {
+        &self.lights
+    }
+
+    /// Writes the scene and `camera` as a scene file.
+    pub fn save(&self, camera: &Camera, path: &Path) -> anyhow::Result<()> {
+        SceneFile::new(self.clone(), *camera).save(path)
+    }
+
+    fn geometry(&self) -> Geometry<'_> {
+        Geometry {
+            shapes: &self.shapes,
+            bvh: &self.bvh,
+            max_depth: self.max_depth,
+            epsilon: self.epsilon,
+            max_distance: self.max_distance,
+            fog: self.fog,
+            soft_shadows: self.soft_shadows,
+            mirror_cache: self.mirror_cache,
+            shadow_quality: self.shadow_quality,
+            specular_model: self.specular_model,
+            volumes: &self.volumes,
+            caustics: self.photon_map.as_ref(),
+        }
+    }
+
+    fn rebuild_bvh(&mut self) {
+        self.bvh = build_bvh(&self.shapes);
+        self.rebuild_photon_map();
+    }
+
+    fn rebuild_photon_map(&mut self) {
+        self.photon_map = self.caustics.map(|(count, radius)| {
+            let start = Instant::now();
+            let photons = emit_caustic_photons(self.geometry(), &self.lights, count);
+            let photon_map = PhotonMap::new(photons, radius);
+            debug!(
+                "Traced {} caustic photons in {:.3} ms",
+                photon_map.len(),
+                start.elapsed().as_secs_f64() * 1000.0
+            );
+            photon_map
+        });
+    }
+
+    /// Ids of the shapes, in the order of [`Self::shapes`].
+    pub fn shape_ids(&self) -> &[ShapeId] {
+        &self.shape_ids
+    }
+
+    /// Ids of the lights, in the order of [`Self::lights`].
+    pub fn light_ids(&self) -> &[LightId] {
+        &self.light_ids
+    }
+
+    /// Ids paired with their shapes.
+    pub fn shape_entries(&self) -> impl Iterator<Item = (ShapeId, &ShapeType)> {
+        self.shape_ids.iter().copied().zip(&self.shapes)
+    }
+
+    /// Ids paired with their lights.
+    pub fn light_entries(&self) -> impl Iterator<Item = (LightId, &LightType)> {
+        self.light_ids.iter().copied().zip(&self.lights)
+    }
+
+    /// Shapes whose material equals `material`.
+    pub fn shapes_with_material(
+        &self,
+        material: Material,
+    ) -> impl Iterator<Item = (ShapeId, &ShapeType)> {
+        self.shape_entries()
+            .filter(move |(_, shape)| shape.get_material() == material)
+    }
+
+    /// Shapes whose [`ShapeType::kind`] is `kind`, e.g. `"sphere"`.
+    pub fn shapes_of_kind(&self, kind: &str) -> impl Iterator<Item = (ShapeId, &ShapeType)> {
+        self.shape_entries()
+            .filter(move |(_, shape)| shape.kind() == kind)
+    }
+
+    /// Shapes whose bounds lie entirely inside `region`. Unbounded shapes never match.
+    pub fn shapes_within(&self, region: Aabb) -> impl Iterator<Item = (ShapeId, &ShapeType)> {
+        self.shape_entries().filter(move |(_, shape)| {
+            shape
+                .bounds()
+                .is_some_and(|bounds| region.encloses(&bounds))
+        })
+    }
+
+    /// Lights whose [`LightType::kind`] is `kind`, e.g. `"point_light"`.
+    pub fn lights_of_kind(&self, kind: &str) -> impl Iterator<Item = (LightId, &LightType)> {
+        self.light_entries()
+            .filter(move |(_, light)| light.kind() == kind)
+    }
+
+    const fn allocate_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn shape_index(&self, id: ShapeId) -> Option<usize> {
+        self.shape_ids.iter().position(|&shape_id| shape_id == id)
+    }
+
+    fn light_index(&self, id: LightId) -> Option<usize> {
+        self.light_ids.iter().position(|&light_id| light_id == id)
+    }
+
+    /// Adds a shape and rebuilds the BVH around it.
+    pub fn push_shape(&mut self, shape: ShapeType) -> ShapeId {
+        let id = ShapeId(self.allocate_id());
+        self.shapes.push(shape);
+        self.shape_ids.push(id);
+        self.rebuild_bvh();
+        id
+    }
+
+    /// The shape behind `id`, or `None` once it was removed.
+    pub fn get_shape(&self, id: ShapeId) -> Option<&ShapeType> {
+        self.shape_index(id).map(|index| &self.shapes[index])
+    }
+
+    /// Changes the shape behind `id` in place, then rebuilds the BVH and caustics around
+    /// it. Returns `false` when no shape has that id.
+    pub fn update_shape(&mut self, id: ShapeId, update: impl FnOnce(&mut ShapeType)) -> bool {
+        let Some(index) = self.shape_index(id) else {
+            return false;
+        };
+
+        update(&mut self.shapes[index]);
+        self.rebuild_bvh();
+        true
+    }
+
+    /// Moves the shape by `offset`. Returns `false` when no shape has that id.
+    pub fn translate_shape(&mut self, id: ShapeId, offset: Vec3f) -> bool {
+        self.update_shape(id, |shape| shape.translate(offset))
+    }
+
+    /// Gives the shape its own material, dropping any registry binding it had.
+    pub fn set_shape_material(&mut self, id: ShapeId, material: Material) -> bool {
+        let updated = self.update_shape(id, |shape| shape.set_material(material));
+        if updated {
+            self.material_bindings.remove(&id);
+        }
+        updated
+    }
+
+    /// Moves animated surfaces, such as wave planes, to `time` seconds.
+    pub fn set_time(&mut self, time: f64) {
+        for shape in &mut self.shapes {
+            if let ShapeType::InfinityPlane(plane) = shape {
+                plane.set_time(time);
+            }
+        }
+    }
+
+    /// Turns billboards to face a camera at `viewer`.
+    pub fn set_viewer(&mut self, viewer: Vec3f) {
+        for shape in &mut self.shapes {
+            if let ShapeType::Billboard(billboard) = shape {
+                billboard.face(viewer);
+            }
+        }
+    }
+
+    /// Toggles the shadow terminator fix on smooth-shaded meshes.
+    pub fn set_terminator_fix(&mut self, enabled: bool) {
+        for shape in &mut self.shapes {
+            if let ShapeType::Mesh(mesh) = shape {
+                mesh.set_terminator_fix(enabled);
+            }
+        }
+    }
+
+    /// Sets the named materials shapes can be bound to.
+    pub fn with_materials(mut self, materials: MaterialRegistry) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    /// The named materials shapes can be bound to.
+    pub const fn materials(&self) -> &MaterialRegistry {
+        &self.materials
+    }
+
+    /// Name of the registry material the shape is bound to, if any.
+    pub fn material_binding(&self, id: ShapeId) -> Option<&str> {
+        self.material_bindings.get(&id).map(String::as_str)
+    }
+
+    /// Gives the shape the registry material `name` and keeps it in sync with later
+    /// [`Self::set_material`] calls. Returns `false` when the shape or the material is
+    /// missing.
+    pub fn bind_material(&mut self, id: ShapeId, name: &str) -> bool {
+        let (Some(index), Some(material)) = (self.shape_index(id), self.materials.get(name)) else {
+            return false;
+        };
+
+        self.shapes[index].set_material(material);
+        self.material_bindings.insert(id, name.to_owned());
+        self.rebuild_photon_map();
+        true
+    }
+
+    /// Adds or replaces the registry material `name` and updates every shape bound to it.
+    pub fn set_material(&mut self, name: &str, material: Material) {
+        self.materials.insert(name, material);
+        for (index, id) in self.shape_ids.iter().enumerate() {
+            if self
+                .material_bindings
+                .get(id)
+                .is_some_and(|bound| bound == name)
+            {
+                self.shapes[index].set_material(material);
+            }
+        }
+        self.rebuild_photon_map();
+    }
+
+    /// Removes the shape and returns it, or `None` when no shape has that id.
+    pub fn remove_shape(&mut self, id: ShapeId) -> Option<ShapeType> {
+        let index = self.shape_index(id)?;
+        self.material_bindings.remove(&id);
+        self.shape_ids.remove(index);
+        let shape = self.shapes.remove(index);
+        self.rebuild_bvh();
+        Some(shape)
+    }
+
+    /// Keeps only the shapes `keep` returns `true` for, letting it edit them on the way,
+    /// and rebuilds the BVH once.
+    pub fn retain_shapes(&mut self, mut keep: impl FnMut(ShapeId, &mut ShapeType) -> bool) {
+        let shape_ids = std::mem::take(&mut self.shape_ids);
+        let shapes = std::mem::take(&mut self.shapes);
+
+        for (id, mut shape) in shape_ids.into_iter().zip(shapes) {
+            if keep(id, &mut shape) {
+                self.shape_ids.push(id);
+                self.shapes.push(shape);
+            } else {
+                self.material_bindings.remove(&id);
+            }
+        }
+        self.rebuild_bvh();
+    }
+
+    /// Adds a light and returns its id.
+    pub fn push_light(&mut self, light: LightType) -> LightId {
+        let id = LightId(self.allocate_id());
+        self.lights.push(light);
+        self.light_ids.push(id);
+        self.rebuild_photon_map();
+        id
+    }
+
+    /// The light behind `id`, or `None` once it was removed.
+    pub fn get_light(&self, id: LightId) -> Option<&LightType> {
+        self.light_index(id).map(|index| &self.lights[index])
+    }
+
+    /// Changes the light behind `id` in place and re-emits the caustic photons it casts.
+    /// Returns `false` when no light has that id.
+    pub fn update_light(&mut self, id: LightId, update: impl FnOnce(&mut LightType)) -> bool {
+        let Some(index) = self.light_index(id) else {
+            return false;
+        };
+
+        update(&mut self.lights[index]);
+        self.rebuild_photon_map();
+        true
+    }
+
+    /// Removes the light and returns it, or `None` when no light has that id.
+    pub fn remove_light(&mut self, id: LightId) -> Option<LightType> {
+        let index = self.light_index(id)?;
+        self.light_ids.remove(index);
+        let light = self.lights.remove(index);
+        self.rebuild_photon_map();
+        Some(light)
+    }
+
+    /// Traces one ray and returns its linear color.
+    pub fn trace(&self, origin: Vec3f, direction: Vec3f) -> Vec3f {
+        cast_ray(
+            origin,
+            direction,
+            self.geometry(),
+            &self.lights,
+            self.background,
+            MediumStack::AIR,
+            0,
+        )
+    }
+
+    /// Traces many primary rays, intersecting them with the scene in packets of coherent
+    /// rays before shading each one exactly like [`Self::trace`].
+    pub fn trace_packet(&self, rays: &[(Vec3f, Vec3f)], colors: &mut [Vec3f]) {
+        assert_eq!(rays.len(), colors.len());
+        let geometry = self.geometry();
+        let mut surfaces = [None; PACKET_SIZE];
+
+        for (packet, colors) in rays.chunks(PACKET_SIZE).zip(colors.chunks_mut(PACKET_SIZE)) {
+            let surfaces = &mut surfaces[..packet.len()];
+            scene_intersect_packet(packet, geometry, surfaces);
+            for ((&ray, color), surface) in packet.iter().zip(colors).zip(surfaces.iter()) {
+                *color = shade_ray(
+                    ray,
+                    surface.as_ref(),
+                    geometry,
+                    &self.lights,
+                    self.background,
+                    MediumStack::AIR,
+                    0,
+                );
+            }
+        }
+    }
+
+    /// The shape under the pixel position `(x, y)` of a `width` x `height` view.
+    pub fn pick(
+        &self,
+        x: f64,
+        y: f64,
+        width: u32,
+        height: u32,
+        camera: &Camera,
+    ) -> Option<ShapeId> {
+        let direction = camera.ray_direction(x, y, width, height);
+        self.pick_hit(camera.position(), direction)
+            .map(|(_, index)| self.shape_ids[index])
+    }
+
+    /// The point hit under the pixel position `(x, y)` of a `width` x `height` view.
+    pub fn pick_point(
+        &self,
+        x: f64,
+        y: f64,
+        width: u32,
+        height: u32,
+        camera: &Camera,
+    ) -> Option<Vec3f> {
+        let origin = camera.position();
+        let direction = camera.ray_direction(x, y, width, height);
+        self.pick_hit(origin, direction)
+            .map(|(distance, _)| origin + direction * distance)
+    }
+
+    fn pick_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<(f64, usize)> {
+        let inverse_direction = inverse_direction(direction);
+        self.bvh.closest_hit(origin, inverse_direction, |index| {
+            self.geometry()
+                .visible_hit(index, origin, direction, inverse_direction)
+                .map(|hit| hit.distance())
+        })
+    }
+
+    fn ambient_occlusion(
+        &self,
+        surface: &SurfaceHit,
+        samples: u32,
+        first_sample: u32,
+        max_distance: f64,
+        rotation: (f64, f64),
+    ) -> (f64, Vec3f) {
+        let mut unoccluded = 0;
+        let mut bent = Vec3f::new(0.0);
+
+        for sample in first_sample..first_sample + samples {
+            let u1 = (halton(sample + 1, 2) + rotation.0).fract();
+            let u2 = (halton(sample + 1, 3) + rotation.1).fract();
+            let direction = cosine_hemisphere(surface.normal, u1, u2);
+            let origin = surface.offset_origin(direction, self.epsilon);
+
+            let occluded = self.geometry().occluded(origin, direction, max_distance);
+            if !occluded {
+                unoccluded += 1;
+                bent += direction;
+            }
+        }
+
+        let bent_normal = if unoccluded == 0 {
+            surface.normal
+        } else {
+            bent.normalize(None)
+        };
+
+        (f64::from(unoccluded) / f64::from(samples), bent_normal)
+    }
+
+    fn path_step(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        sample: u32,
+        rotation: (f64, f64),
+        depth: u32,
+    ) -> PathStep {
+        if depth > self.max_depth {
+            return PathStep::Done(sky_background(direction, &self.lights, self.background));
+        }
+
+        let geometry = self.geometry();
+        if let Some((distance, emission)) = emitter_intersect(origin, direction, &self.lights)
+            && scene_intersect(origin, direction, geometry)
+                .is_none_or(|surface| (surface.point - origin).length() > distance)
+        {
+            return PathStep::Done(emission);
+        }
+
+        let Some(terms) = shading_terms(
+            origin,
+            direction,
+            geometry,
+            &self.lights,
+            self.background,
+            MediumStack::AIR,
+            depth,
+        ) else {
+            return PathStep::Done(sky_background(direction, &self.lights, self.background));
+        };
+
+        let weight = terms.material.diffuse_color() * terms.material.albedo()[0];
+        if weight * weight == 0.0 {
+            return PathStep::Done(terms.direct_color());
+        }
+
+        let (first_base, second_base) = PATH_BASES[depth as usize % PATH_BASES.len()];
+        let u1 = (halton(sample + 1, first_base) + rotation.0).fract();
+        let u2 = (halton(sample + 1, second_base) + rotation.1).fract();
+        let bounce_direction = cosine_hemisphere(terms.surface.normal, u1, u2);
+
+        PathStep::Bounce {
+            direct: terms.direct_color(),
+            weight,
+            origin: terms.surface.offset_origin(bounce_direction, self.epsilon),
+            direction: bounce_direction,
+        }
+    }
+
+    fn trace_path(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        sample: u32,
+        rotation: (f64, f64),
+        depth: u32,
+    ) -> Vec3f {
+        match self.path_step(origin, direction, sample, rotation, depth) {
+            PathStep::Done(radiance) => radiance,
+            PathStep::Bounce {
+                direct,
+                weight,
+                origin,
+                direction,
+            } => {
+                direct
+                    + weight.hadamard(self.trace_path(
+                        origin,
+                        direction,
+                        sample,
+                        rotation,
+                        depth + 1,
+                    ))
+            }
+        }
+    }
+
+    fn trace_paths_sorted(&self, mut paths: Vec<PathState>, radiance: &mut [Vec3f]) {
+        while !paths.is_empty() {
+            if paths[0].depth > 0 {
+                reorder::sort_rays(&mut paths, |path| (path.origin, path.direction));
+            }
+
+            let mut next = Vec::with_capacity(paths.len());
+            for path in paths {
+                let step = self.path_step(
+                    path.origin,
+                    path.direction,
+                    path.sample,
+                    path.rotation,
+                    path.depth,
+                );
+                match step {
+                    PathStep::Done(color) => {
+                        radiance[path.slot] += path.throughput.hadamard(color);
+                    }
+                    PathStep::Bounce {
+                        direct,
+                        weight,
+                        origin,
+                        direction,
+                    } => {
+                        radiance[path.slot] += path.throughput.hadamard(direct);
+                        next.push(PathState {
+                            origin,
+                            direction,
+                            throughput: path.throughput.hadamard(weight),
+                            depth: path.depth + 1,
+                            ..path
+                        });
+                    }
+                }
+            }
+            paths = next;
+        }
+    }
+
+    fn trace_toon(&self, origin: Vec3f, direction: Vec3f) -> Vec3f {
+        self.primary_shading_terms(origin, direction).map_or_else(
+            || sky_background(direction, &self.lights, self.background),
+            |terms| {
+                let silhouette = terms.surface.thickness.is_finite()
+                    && (terms.surface.normal * direction).abs() < TOON_OUTLINE;
+                if silhouette {
+                    Vec3f::new(0.0)
+                } else {
+                    terms.toon_color()
+                }
+            },
+        )
+    }
+
+    fn sample_integrator(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        pixel: (u32, u32),
+        sample: u32,
+        settings: &RenderSettings,
+    ) -> Vec3f {
+        match settings.integrator() {
+            Integrator::Whitted => self.trace(origin, direction),
+            Integrator::PathTracing => self.trace_path(
+                origin,
+                direction,
+                sample,
+                pixel_rotation(pixel.0, pixel.1),
+                0,
+            ),
+            Integrator::AmbientOcclusion => {
+                self.sample_occlusion(false, origin, direction, pixel, sample, settings)
+            }
+            Integrator::Toon => self.trace_toon(origin, direction),
+        }
+    }
+
+    fn in_scattering(&self, point: Vec3f) -> f64 {
+        self.lights
+            .iter()
+            .filter(|light| {
+                matches!(
+                    light,
+                    LightType::Point(_) | LightType::Directional(_) | LightType::SunSky(_)
+                )
+            })
+            .map(|light| {
+                let sample = light.sample(point, 0);
+                stats::count_shadow_ray();
+                let occluded =
+                    self.geometry()
+                        .occluded(point, sample.direction(), sample.distance());
+                if occluded { 0.0 } else { light.intensity() }
+            })
+            .sum()
+    }
+
+    fn light_shafts(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        radiance: Vec3f,
+        density: f64,
+        jitter: f64,
+    ) -> Vec3f {
+        let distance = scene_intersect(origin, direction, self.geometry())
+            .map_or(SHAFT_RANGE, |surface| (surface.point - origin).length());
+        let step = distance / f64::from(SHAFT_STEPS);
+
+        let scattered: f64 = (0..SHAFT_STEPS)
+            .map(|index| {
+                let travelled = (f64::from(index) + jitter) * step;
+                let transmittance = (-density * travelled).exp();
+                density * transmittance * self.in_scattering(origin + direction * travelled) * step
+            })
+            .sum::<f64>()
+            * ISOTROPIC_PHASE;
+
+        radiance * (-density * distance).exp() + Vec3f::new(scattered)
+    }
+
+    fn debug_lights(&self, surface: &SurfaceHit, direction: Vec3f) -> Vec<LightRecord> {
+        self.lights
+            .iter()
+            .zip(&self.light_ids)
+            .map(|(light, id)| {
+                let mut record = LightRecord {
+                    light: id.0,
+                    kind: light_kind(light),
+                    samples: light.sample_count(),
+                    shadowed_samples: 0,
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                };
+                for index in 0..light.sample_count() {
+                    match light_sample_terms(surface, direction, light, index, self.geometry()) {
+                        Some((ambient, specular, diffuse)) => {
+                            record.ambient += ambient;
+                            record.diffuse += diffuse;
+                            record.specular += specular;
+                        }
+                        None => record.shadowed_samples += 1,
+                    }
+                }
+                record
+            })
+            .collect()
+    }
+
+    fn debug_ray(
+        &self,
+        kind: &'static str,
+        origin: Vec3f,
+        direction: Vec3f,
+        media: MediumStack,
+        depth: u32,
+    ) -> RayRecord {
+        let geometry = self.geometry();
+        let color = cast_ray(
+            origin,
+            direction,
+            geometry,
+            &self.lights,
+            self.background,
+            media,
+            depth,
+        );
+        let mut record = RayRecord {
+            kind,
+            depth,
+            origin: components(origin),
+            direction: components(direction),
+            outcome: "miss",
+            color: components(color),
+            hit: None,
+        };
+
+        if depth > self.max_depth {
+            record.outcome = "max_depth";
+            return record;
+        }
+
+        let surface = scene_intersect(origin, direction, geometry);
+        if let Some((distance, _)) = emitter_intersect(origin, direction, &self.lights)
+            && surface.is_none_or(|surface| (surface.point - origin).length() > distance)
+        {
+            record.outcome = "emitter";
+            return record;
+        }
+        let Some(surface) = surface else {
+            return record;
+        };
+
+        let (reflected, refracted, _) = secondary_rays(&surface, direction, media, self.epsilon);
+        let material = surface.material;
+        let shape = self.shape_ids[surface.shape];
+        record.outcome = "surface";
+        record.hit = Some(HitRecord {
+            shape: shape.0,
+            distance: (surface.point - origin).length(),
+            point: components(surface.point),
+            normal: components(surface.normal),
+            front_face: surface.front_face,
+            material: MaterialRecord {
+                name: self.material_binding(shape).map(str::to_owned),
+                albedo: [
+                    material.albedo()[0],
+                    material.albedo()[1],
+                    material.albedo()[2],
+                    material.albedo()[3],
+                ],
+                diffuse: components(material.diffuse_color()),
+                ambient: components(material.ambient_color()),
+                specular_exponent: material.specular_exponent(),
+                ior: material.refractive_index(),
+            },
+            lights: self.debug_lights(&surface.lit(), direction),
+            reflected: Box::new(self.debug_ray(
+                "reflect",
+                reflected.origin,
+                reflected.direction,
+                reflected.media,
+                depth + 1,
+            )),
+            refracted: Box::new(self.debug_ray(
+                "refract",
+                refracted.origin,
+                refracted.direction,
+                refracted.media,
+                depth + 1,
+            )),
+        });
+
+        record
+    }
+
+    /// Renders pixel `(i, j)` alone and records every ray it traced.
+    pub fn debug_pixel(
+        &self,
+        (i, j): (u32, u32),
+        (width, height): (u32, u32),
+        camera: &Camera,
+        settings: &RenderSettings,
+    ) -> PixelTrace {
+        let samples_per_pixel = settings.samples_per_pixel();
+        let rays = camera.ray_generator(width, height);
+        let samples = (0..samples_per_pixel)
+            .map(|sample| {
+                let offset = sample_offset(sample, samples_per_pixel);
+                let (origin, direction) = primary_ray(&rays, (i, j), sample, samples_per_pixel);
+                SampleRecord {
+                    offset,
+                    ray: self.debug_ray("camera", origin, direction, MediumStack::AIR, 0),
+                }
+            })
+            .collect();
+
+        PixelTrace {
+            pixel: (i, j),
+            integrator: settings.integrator().name(),
+            rgba: self.shade_pixel(i, j, &rays, settings),
+            samples,
+        }
+    }
+
+    fn primary_shading_terms(&self, origin: Vec3f, direction: Vec3f) -> Option<ShadingTerms> {
+        shading_terms(
+            origin,
+            direction,
+            self.geometry(),
+            &self.lights,
+            self.background,
+            MediumStack::AIR,
+            0,
+        )
+    }
+
+    fn sample_aov(
+        &self,
+        aov: Aov,
+        origin: Vec3f,
+        direction: Vec3f,
+        pixel: (u32, u32),
+        sample: u32,
+        settings: &RenderSettings,
+    ) -> Vec3f {
+        match aov {
+            Aov::Diffuse => self
+                .primary_shading_terms(origin, direction)
+                .map_or(self.background, |terms| terms.diffuse_color()),
+            Aov::Specular => self
+                .primary_shading_terms(origin, direction)
+                .map_or(Vec3f::new(0.0), |terms| terms.specular_color()),
+            Aov::AmbientOcclusion | Aov::BentNormal => {
+                let bent = aov == Aov::BentNormal;
+                self.sample_occlusion(bent, origin, direction, pixel, sample, settings)
+            }
+        }
+    }
+
+    fn sample_occlusion(
+        &self,
+        bent: bool,
+        origin: Vec3f,
+        direction: Vec3f,
+        (i, j): (u32, u32),
+        sample: u32,
+        settings: &RenderSettings,
+    ) -> Vec3f {
+        let Some(surface) = scene_intersect(origin, direction, self.geometry()) else {
+            return if bent {
+                Vec3f::new(0.0)
+            } else {
+                Vec3f::new(1.0)
+            };
+        };
+
+        let (occlusion, bent_normal) = self.ambient_occlusion(
+            &surface.facing(),
+            settings.ao_samples(),
+            sample * settings.ao_samples(),
+            settings.ao_distance(),
+            pixel_rotation(i, j),
+        );
+
+        if bent {
+            bent_normal * 0.5 + Vec3f::new(0.5)
+        } else {
+            Vec3f::new(occlusion)
+        }
+    }
+
+    fn shade_pixel(
+        &self,
+        i: u32,
+        j: u32,
+        rays: &RayGenerator,
+        settings: &RenderSettings,
+    ) -> [u8; 4] {
+        pixel_color(self.pixel_radiance(i, j, rays, settings), settings)
+    }
+
+    fn pixel_radiance(
+        &self,
+        i: u32,
+        j: u32,
+        rays: &RayGenerator,
+        settings: &RenderSettings,
+    ) -> Vec3f {
+        let samples_per_pixel = settings.samples_per_pixel();
+
+        (0..samples_per_pixel)
+            .map(|sample| {
+                let (origin, dir) = primary_ray(rays, (i, j), sample, samples_per_pixel);
+                settings.aov().map_or_else(
+                    || {
+                        let radiance =
+                            self.sample_integrator(origin, dir, (i, j), sample, settings);
+                        self.finish_sample(radiance, origin, dir, (i, j), sample, settings)
+                    },
+                    |aov| self.sample_aov(aov, origin, dir, (i, j), sample, settings),
+                )
+            })
+            .fold(Vec3f::new(0.0), |acc, color| acc + color)
+            / f64::from(samples_per_pixel)
+    }
+
+    fn finish_sample(
+        &self,
+        radiance: Vec3f,
+        origin: Vec3f,
+        direction: Vec3f,
+        pixel: (u32, u32),
+        sample: u32,
+        settings: &RenderSettings,
+    ) -> Vec3f {
+        let radiance = settings.light_shafts().map_or(radiance, |density| {
+            let jitter = (pixel_rotation(pixel.0, pixel.1).0 + halton(sample + 1, 2)).fract();
+            self.light_shafts(origin, direction, radiance, density, jitter)
+        });
+        clamp_radiance(radiance, settings.clamp())
+    }
+
+    fn render_tile_sorted(
+        &self,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        rays: &RayGenerator,
+        settings: &RenderSettings,
+    ) -> Vec<u8> {
+        let samples_per_pixel = settings.samples_per_pixel();
+        let pixels: Vec<(u32, u32)> = rows
+            .flat_map(|j| columns.clone().map(move |i| (i, j)))
+            .collect();
+
+        let paths = pixels
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &pixel)| {
+                (0..samples_per_pixel).map(move |sample| {
+                    let (origin, direction) = primary_ray(rays, pixel, sample, samples_per_pixel);
+                    PathState {
+                        slot: index * samples_per_pixel as usize + sample as usize,
+                        sample,
+                        rotation: pixel_rotation(pixel.0, pixel.1),
+                        origin,
+                        direction,
+                        throughput: Vec3f::new(1.0),
+                        depth: 0,
+                    }
+                })
+            })
+            .collect();
+        let mut radiance = vec![Vec3f::new(0.0); pixels.len() * samples_per_pixel as usize];
+        self.trace_paths_sorted(paths, &mut radiance);
+
+        pixels
+            .iter()
+            .zip(radiance.chunks_exact(samples_per_pixel as usize))
+            .flat_map(|(&pixel, samples)| {
+                let total =
+                    samples
+                        .iter()
+                        .zip(0..)
+                        .fold(Vec3f::new(0.0), |acc, (&radiance, sample)| {
+                            let (origin, dir) = primary_ray(rays, pixel, sample, samples_per_pixel);
+                            acc + self.finish_sample(radiance, origin, dir, pixel, sample, settings)
+                        });
+                pixel_color(total / f64::from(samples_per_pixel), settings)
+            })
+            .collect()
+    }
+
+    fn render_tile_packets(
+        &self,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        rays: &RayGenerator,
+        settings: &RenderSettings,
+    ) -> Vec<u8> {
+        let samples_per_pixel = settings.samples_per_pixel();
+        let pixels: Vec<(u32, u32)> = rows
+            .flat_map(|j| columns.clone().map(move |i| (i, j)))
+            .collect();
+        let primary: Vec<_> = pixels
+            .iter()
+            .flat_map(|&pixel| {
+                (0..samples_per_pixel)
+                    .map(move |sample| primary_ray(rays, pixel, sample, samples_per_pixel))
+            })
+            .collect();
+        let mut radiance = vec![Vec3f::new(0.0); primary.len()];
+        self.trace_packet(&primary, &mut radiance);
+
+        pixels
+            .iter()
+            .zip(primary.chunks_exact(samples_per_pixel as usize))
+            .zip(radiance.chunks_exact(samples_per_pixel as usize))
+            .flat_map(|((&pixel, primary), radiance)| {
+                let total = primary
+                    .iter()
+                    .zip(radiance)
+                    .zip(0..)
+                    .map(|((&(origin, dir), &radiance), sample)| {
+                        self.finish_sample(radiance, origin, dir, pixel, sample, settings)
+                    })
+                    .fold(Vec3f::new(0.0), |acc, color| acc + color);
+                pixel_color(total / f64::from(samples_per_pixel), settings)
+            })
+            .collect()
+    }
+
+    fn render_tile(
+        &self,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        rays: &RayGenerator,
+        settings: &RenderSettings,
+    ) -> Vec<u8> {
+        if settings.sort_rays()
+            && settings.integrator() == Integrator::PathTracing
+            && settings.aov().is_none()
+        {
+            return self.render_tile_sorted(columns, rows, rays, settings);
+        }
+        if settings.integrator() == Integrator::Whitted && settings.aov().is_none() {
+            return self.render_tile_packets(columns, rows, rays, settings);
+        }
+
+        rows.flat_map(|j| {
+            columns
+                .clone()
+                .flat_map(move |i| self.shade_pixel(i, j, rays, settings))
+        })
+        .collect()
+    }
+
+    /// Renders `region` into `frame`, which holds just its rows, calling `progress` after
+    /// each tile with the pixels finished so far and the rays that tile traced. Returns
+    /// `false` when cancelled.
+    pub fn render_scene(
+        &self,
+        frame: &mut [u8],
+        region: ImageRegion,
+        camera: &Camera,
+        settings: &RenderSettings,
+        cancel: &CancelToken,
+        progress: &(dyn Fn(usize, RayCounts) + Sync),
+    ) -> bool {
+        let row_bytes = region.width as usize * 4;
+        let completed_pixels = AtomicUsize::new(0);
+        let rays = camera.ray_generator(region.width, region.height);
+
+        frame
+            .par_chunks_mut(row_bytes * TILE_SIZE as usize)
+            .zip(
+                (region.first_row..region.first_row + region.rows)
+                    .into_par_iter()
+                    .step_by(TILE_SIZE as usize),
+            )
+            .all(|(band, band_first_row)| {
+                let band_rows = band_first_row
+                    ..(band_first_row + TILE_SIZE).min(region.first_row + region.rows);
+
+                let tiles: Vec<Option<(u32, Vec<u8>)>> = (0..region.width)
+                    .into_par_iter()
+                    .step_by(TILE_SIZE as usize)
+                    .map(|tile_x| {
+                        if cancel.is_cancelled() {
+                            return None;
+                        }
+
+                        let columns = tile_x..(tile_x + TILE_SIZE).min(region.width);
+                        let tile = self.render_tile(columns, band_rows.clone(), &rays, settings);
+                        let counts = stats::finish_tile();
+                        arena::finish_tile(counts.rays());
+                        mirror_cache::finish_tile();
+
+                        let pixels = tile.len() / 4;
+                        progress(
+                            completed_pixels.fetch_add(pixels, Ordering::Relaxed) + pixels,
+                            counts,
+                        );
+                        Some((tile_x, tile))
+                    })
+                    .collect();
+
+                for (tile_x, tile) in tiles.iter().flatten() {
+                    let tile_row_bytes = tile.len() / band_rows.len();
+                    for (row, tile_row) in tile.chunks_exact(tile_row_bytes).enumerate() {
+                        let start = row * row_bytes + *tile_x as usize * 4;
+                        band[start..start + tile_row_bytes].copy_from_slice(tile_row);
+                    }
+                }
+
+                tiles.iter().all(Option::is_some)
+            })
+    }
+
+    /// Shades the whole frame to radiance before tone mapping it into `frame`, so
+    /// post-processing that spreads light between pixels, like bloom, sees HDR values.
+    /// Finished rows are written to `frame` without it straight away, so a render that
+    /// stops early still leaves what it got through. Progress is reported per row like
+    /// [`Self::render_scene`] does per tile. Returns `false` when the render was cancelled.
+    pub fn render_hdr_into(
+        &self,
+        frame: &mut [u8],
+        (width, height): (u32, u32),
+        camera: &Camera,
+        settings: &RenderSettings,
+        cancel: &CancelToken,
+        progress: &(dyn Fn(usize, RayCounts) + Sync),
+    ) -> bool {
+        let completed_pixels = AtomicUsize::new(0);
+        let rays = camera.ray_generator(width, height);
+        let rows: Option<Vec<Vec<Vec3f>>> = frame
+            .par_chunks_mut(width as usize * 4)
+            .zip(0..height)
+            .map(|(pixels, j)| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+
+                let row: Vec<_> = (0..width)
+                    .map(|i| self.pixel_radiance(i, j, &rays, settings))
+                    .collect();
+                let counts = stats::finish_tile();
+                arena::finish_tile(counts.rays());
+                mirror_cache::finish_tile();
+
+                for (pixel, &radiance) in pixels.chunks_exact_mut(4).zip(&row) {
+                    pixel.copy_from_slice(&pixel_color(radiance, settings));
+                }
+                let pixels = row.len();
+                progress(
+                    completed_pixels.fetch_add(pixels, Ordering::Relaxed) + pixels,
+                    counts,
+                );
+                Some(row)
+            })
+            .collect();
+        let Some(rows) = rows else {
+            return false;
+        };
+
+        let mut radiance: Vec<_> = rows.into_iter().flatten().collect();
+        if let Some(bloom) = settings.post().bloom()
+            && is_post_processed(settings)
+        {
+            bloom.apply(&mut radiance, (width, height));
+        }
+        for (pixel, radiance) in frame.chunks_exact_mut(4).zip(radiance) {
+            pixel.copy_from_slice(&pixel_color(radiance, settings));
+        }
+
+        true
+    }
+
+    /// Renders the requested auxiliary buffers from one primary ray through each pixel
+    /// center, so depth and ids are never blended across edges.
+    pub fn render_aovs(
+        &self,
+        camera: &Camera,
+        (width, height): (u32, u32),
+        buffers: &[AovBuffer],
+    ) -> Vec<FloatBuffer> {
+        let rays = camera.ray_generator(width, height);
+        let surfaces: Vec<_> = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|j| {
+                let rays = &rays;
+                (0..width).map(move |i| {
+                    let (origin, direction) = rays.ray(f64::from(i) + 0.5, f64::from(j) + 0.5);
+                    scene_intersect(origin, direction, self.geometry()).map(SurfaceHit::facing)
+                })
+            })
+            .collect();
+
+        buffers
+            .iter()
+            .map(|&kind| {
+                let data = surfaces
+                    .iter()
+                    .flat_map(|surface| self.aov_values(kind, surface.as_ref()))
+                    .collect();
+                FloatBuffer::new(kind, width, height, data)
+            })
+            .collect()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn aov_values(&self, kind: AovBuffer, surface: Option<&SurfaceHit>) -> Vec<f64> {
+        let channels = |vector: Vec3f| vec![vector.x(), vector.y(), vector.z()];
+        match (kind, surface) {
+            (AovBuffer::Depth, Some(surface)) => vec![surface.distance],
+            (AovBuffer::Depth, None) => vec![f64::INFINITY],
+            (AovBuffer::Normal, Some(surface)) => channels(surface.normal),
+            (AovBuffer::Normal, None) => vec![0.0; 3],
+            (AovBuffer::Albedo, Some(surface)) => channels(surface.material.diffuse_color()),
+            (AovBuffer::Albedo, None) => channels(self.background),
+            (AovBuffer::ShapeId, Some(surface)) => {
+                vec![self.shape_ids[surface.shape].0 as f64 + 1.0]
+            }
+            (AovBuffer::ShapeId, None) => vec![0.0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use crate::{
+        environment::EnvironmentMap,
+        geometry::Transform,
+        lights::{
+            AmbientLight, AreaLight, CustomLight, DirectionalLight, EnvironmentLight, PointLight,
+            SkyLight, SunSkyLight, init_default_lights,
+        },
+        materials::{
+            FROSTED_GLASS_MATERIAL, GLASS_MATERIAL, GOLD_MATERIAL, MIRROR_MATERIAL, WATER_MATERIAL,
+        },
+        scenes::CORNELL_WHITE,
+        shapes::{BoxShape, CustomShape, InfinityPlane, Instance, Sphere, init_default_shapes},
+        sky::SunSky,
+        volume::DensityGrid,
+    };
+
+    fn sphere(x: f64) -> ShapeType {
+        ShapeType::Sphere(Sphere::new(
+            Vec3f::new_with_data([x, 0.0, -5.0]),
+            1.0,
+            GOLD_MATERIAL,
+        ))
+    }
+
+    #[test]
+    fn shape_ids_survive_removal_of_other_shapes() {
+        let mut scene = Scene::new(Vec::new(), Vec::new());
+        let first = scene.push_shape(sphere(0.0));
+        let second = scene.push_shape(sphere(1.0));
+        let third = scene.push_shape(sphere(2.0));
+
+        assert_eq!(scene.remove_shape(first), Some(sphere(0.0)));
+        assert_eq!(scene.remove_shape(first), None);
+        assert_eq!(scene.get_shape(second), Some(&sphere(1.0)));
+        assert_eq!(scene.get_shape(third), Some(&sphere(2.0)));
+
+        let fourth = scene.push_shape(sphere(3.0));
+        assert_ne!(fourth, first);
+        assert_eq!(scene.shape_ids(), &[second, third, fourth]);
+    }
+
+    #[test]
+    fn updated_shapes_are_traced_where_they_moved_to() {
+        let mut scene = Scene::new(Vec::new(), vec![LightType::Ambient(AmbientLight::new(1.0))])
+            .with_background(Vec3f::new(0.0));
+        let id = scene.push_shape(sphere(0.0));
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let aside = Vec3f::new_with_data([6.0, 0.0, 0.0]);
+        assert_eq!(scene.trace(aside, forward), Vec3f::new(0.0));
+
+        assert!(scene.update_shape(id, |shape| *shape = sphere(6.0)));
+        assert_eq!(scene.get_shape(id), Some(&sphere(6.0)));
+        assert_ne!(scene.trace(aside, forward), Vec3f::new(0.0));
+
+        assert!(scene.remove_shape(id).is_some());
+        assert!(!scene.update_shape(id, |shape| *shape = sphere(0.0)));
+    }
+
+    #[test]
+    fn scene_objects_filter_by_material_kind_and_region() {
+        let mut scene = Scene::new(init_default_shapes(), init_default_lights());
+        let gold = scene.push_shape(ShapeType::Sphere(Sphere::new(
+            Vec3f::new_with_data([0.0, 0.0, -20.0]),
+            1.0,
+            GOLD_MATERIAL,
+        )));
+
+        let golden: Vec<_> = scene.shapes_with_material(GOLD_MATERIAL).collect();
+        assert_eq!(golden.len(), 1);
+        assert_eq!(golden[0].0, gold);
+        assert!(scene.shapes_of_kind("sphere").any(|(id, _)| id == gold));
+        assert!(
+            scene
+                .shapes_of_kind("box")
+                .all(|(_, shape)| shape.kind() == "box")
+        );
+
+        let region = Aabb::from_points(&[
+            Vec3f::new_with_data([-2.0, -2.0, -22.0]),
+            Vec3f::new_with_data([2.0, 2.0, -18.0]),
+        ]);
+        let within: Vec<_> = scene.shapes_within(region).map(|(id, _)| id).collect();
+        assert_eq!(within, [gold]);
+
+        assert_eq!(
+            scene.lights_of_kind("point_light").count(),
+            scene
+                .lights()
+                .iter()
+                .filter(|light| matches!(light, LightType::Point(_)))
+                .count()
+        );
+        assert_eq!(scene.light_entries().count(), scene.lights().len());
+    }
+
+    #[test]
+    fn saved_scenes_reload_with_shapes_lights_and_camera() -> anyhow::Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new_with_data([1.0, 2.0, 3.0]), crate::FOV);
+        let path = std::env::temp_dir().join(format!("saved-scene-{}.scene", std::process::id()));
+
+        scene.save(&camera, &path)?;
+        let loaded = SceneFile::load(&path);
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+
+        assert_eq!(loaded.scene().shapes(), scene.shapes());
+        assert_eq!(loaded.scene().lights(), scene.lights());
+        assert_eq!(loaded.camera(), &camera);
+        Ok(())
+    }
+
+    #[test]
+    fn debug_pixel_matches_render_and_pick() -> anyhow::Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let settings = RenderSettings::default();
+        let region = ImageRegion::stripe(40, 30, 0, 30);
+        let mut frame = vec![0; region.pixel_count() * 4];
+        scene.render_scene(
+            &mut frame,
+            region,
+            &camera,
+            &settings,
+            &CancelToken::default(),
+            &|_, _| {},
+        );
+
+        let (x, y) = (20, 15);
+        let trace = scene.debug_pixel((x, y), (40, 30), &camera, &settings);
+        let start = (y as usize * 40 + x as usize) * 4;
+        assert_eq!(trace.rgba[..], frame[start..start + 4]);
+
+        let hit = trace.samples[0]
+            .ray
+            .hit
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("center pixel missed the scene"))?;
+        let picked = scene
+            .pick(20.5, 15.5, 40, 30, &camera)
+            .ok_or_else(|| anyhow::anyhow!("pick missed the scene"))?;
+        assert_eq!(hit.shape, picked.0);
+        let point = scene
+            .pick_point(20.5, 15.5, 40, 30, &camera)
+            .ok_or_else(|| anyhow::anyhow!("pick missed the scene"))?;
+        assert!(point.distance(&Vec3f::new_with_data(hit.point)) < 1e-9);
+        assert_eq!(hit.lights.len(), scene.lights().len());
+        Ok(())
+    }
+
+    #[test]
+    fn packet_traced_tiles_match_rays_traced_one_by_one() -> anyhow::Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let rays = camera.ray_generator(19, 1);
+        let fan: Vec<_> = (0..19).map(|x| rays.ray(f64::from(x), 0.5)).collect();
+        let mut colors = vec![Vec3f::new(0.0); fan.len()];
+        scene.trace_packet(&fan, &mut colors);
+        for (&(origin, direction), color) in fan.iter().zip(&colors) {
+            assert_eq!(*color, scene.trace(origin, direction));
+        }
+
+        let settings = RenderSettings::default().with_args(["--spp", "2"].map(String::from))?;
+        let region = ImageRegion::stripe(24, 16, 0, 16);
+        let mut frame = vec![0; region.pixel_count() * 4];
+        scene.render_scene(
+            &mut frame,
+            region,
+            &camera,
+            &settings,
+            &CancelToken::default(),
+            &|_, _| {},
+        );
+        let rays = camera.ray_generator(24, 16);
+        let expected: Vec<u8> = (0..16)
+            .flat_map(|j| (0..24).map(move |i| (i, j)))
+            .flat_map(|(i, j)| scene.shade_pixel(i, j, &rays, &settings))
+            .collect();
+        assert_eq!(frame, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_path_tracing_matches_recursive_paths() -> anyhow::Result<()> {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let region = ImageRegion::stripe(24, 16, 0, 16);
+        let render = |sort: &str| -> anyhow::Result<Vec<u8>> {
+            let settings = RenderSettings::default().with_args(
+                ["--integrator", "path", "--spp", "4", "--sort-rays", sort].map(String::from),
+            )?;
+            let mut frame = vec![0; region.pixel_count() * 4];
+            scene.render_scene(
+                &mut frame,
+                region,
+                &camera,
+                &settings,
+                &CancelToken::default(),
+                &|_, _| {},
+            );
+            Ok(frame)
+        };
+
+        let before = reorder::stats();
+        let (recursive, sorted) = (render("off")?, render("on")?);
+        assert!(
+            recursive
+                .iter()
+                .zip(&sorted)
+                .all(|(a, b)| a.abs_diff(*b) <= 1)
+        );
+        assert!(reorder::stats().rays() > before.rays());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn scenes_and_cameras_round_trip_through_json() -> anyhow::Result<()> {
+        let mut shapes = init_default_shapes();
+        shapes.push(ShapeType::Instance(Instance::new(
+            Arc::new(sphere(0.0)),
+            Transform::new(
+                [
+                    Vec3f::new_with_data([1.0, 0.0, 0.0]),
+                    Vec3f::new_with_data([0.0, 2.0, 0.0]),
+                    Vec3f::new_with_data([0.0, 0.0, 1.0]),
+                ],
+                Vec3f::new(0.0),
+            )
+            .ok_or_else(|| anyhow::anyhow!("singular transform"))?,
+        )));
+        let mut lights = init_default_lights();
+        lights.push(LightType::Sky(SkyLight::new(
+            0.5,
+            Vec3f::new_with_data([0.2, 0.4, 1.0]),
+            Vec3f::new(1.0),
+            Vec3f::new(0.1),
+        )));
+        let mut scene = Scene::new(shapes, lights).with_background(Vec3f::new(0.25));
+        scene.set_fog(Fog::new(0.1, Some(Vec3f::new(0.5))));
+
+        let json = serde_json::to_string(&scene)?;
+        let restored: Scene = serde_json::from_str(&json)?;
+
+        assert_eq!(restored, scene);
+        let direction = Vec3f::new_with_data([0.1, -0.1, -1.0]).normalize(None);
+        assert_eq!(
+            restored.trace(Vec3f::new(0.0), direction),
+            scene.trace(Vec3f::new(0.0), direction)
+        );
+
+        let camera = Camera::new(Vec3f::new_with_data([1.0, 2.0, 3.0]), crate::FOV).oriented(
+            Vec3f::new_with_data([1.0, 0.0, 0.0]),
+            Vec3f::new_with_data([0.0, 1.0, 0.0]),
+        );
+        let restored: Camera = serde_json::from_str(&serde_json::to_string(&camera)?)?;
+        assert_eq!(restored, camera);
+        Ok(())
+    }
+
+    #[test]
+    fn cancelled_render_reports_incomplete() {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let settings = RenderSettings::default();
+        let region = ImageRegion::stripe(40, 40, 0, 40);
+        let mut frame = vec![0; region.pixel_count() * 4];
+        let cancel = CancelToken::default();
+
+        assert!(scene.render_scene(&mut frame, region, &camera, &settings, &cancel, &|_, _| {}));
+
+        cancel.cancel();
+        assert!(!scene.render_scene(&mut frame, region, &camera, &settings, &cancel, &|_, _| {}));
+    }
+
+    #[test]
+    fn open_plane_is_unoccluded_with_bent_normal_along_normal() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let plane =
+            ShapeType::InfinityPlane(InfinityPlane::new(Vec3f::new(0.0), up, GOLD_MATERIAL));
+        let mut scene = Scene::new(vec![plane], Vec::new());
+        let ao_at_origin = |scene: &Scene| {
+            let surface = scene_intersect(up, -up, scene.geometry())?;
+            Some(scene.ambient_occlusion(&surface, 256, 0, 10.0, (0.0, 0.0)))
+        };
+
+        let (occlusion, bent_normal) =
+            ao_at_origin(&scene).ok_or_else(|| anyhow::anyhow!("plane was missed"))?;
+        assert!((occlusion - 1.0).abs() < f64::EPSILON);
+        assert!(bent_normal * up > 0.99);
+
+        let blocker = Vec3f::new_with_data([2.0, 1.0, 0.0]);
+        scene.push_shape(ShapeType::Sphere(Sphere::new(blocker, 1.0, GOLD_MATERIAL)));
+
+        let (occlusion, bent_normal) =
+            ao_at_origin(&scene).ok_or_else(|| anyhow::anyhow!("plane was missed"))?;
+        assert!(occlusion < 1.0);
+        assert!(bent_normal[0] < 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instances_share_geometry_under_their_own_transforms() -> anyhow::Result<()> {
+        let prototype = Arc::new(ShapeType::Sphere(Sphere::new(
+            Vec3f::new(0.0),
+            1.0,
+            GOLD_MATERIAL,
+        )));
+        let scaled = Transform::from_scale(2.0)
+            .ok_or_else(|| anyhow::anyhow!("scale is singular"))?
+            .then(Transform::from_translation(Vec3f::new_with_data([
+                3.0, 0.0, -10.0,
+            ])));
+        let shifted = Transform::from_translation(Vec3f::new_with_data([-3.0, 0.0, -10.0]));
+        let scene = Scene::new(
+            vec![
+                ShapeType::Instance(Instance::new(Arc::clone(&prototype), scaled)),
+                ShapeType::Instance(Instance::new(Arc::clone(&prototype), shifted)),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(Arc::strong_count(&prototype), 3);
+
+        let toward = |x: f64| {
+            let direction = Vec3f::new_with_data([x, 0.0, -10.0]).normalize(None);
+            scene_intersect(Vec3f::new(0.0), direction, scene.geometry())
+                .ok_or_else(|| anyhow::anyhow!("instance at x={x} was missed"))
+        };
+
+        let big = toward(3.0)?;
+        assert!((big.point.length() - (109.0_f64.sqrt() - 2.0)).abs() < 1e-9);
+        assert!((big.normal * big.point.normalize(None) + 1.0).abs() < 1e-9);
+
+        let small = toward(-3.0)?;
+        assert!((small.point.length() - (109.0_f64.sqrt() - 1.0)).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn thin_walls_do_not_leak_light() -> anyhow::Result<()> {
+        let wall = ShapeType::BoxShape(BoxShape::new(
+            Vec3f::new_with_data([5.0, 1e-4, 5.0]),
+            Vec3f::new_with_data([-5.0, 0.0, -5.0]),
+            GOLD_MATERIAL,
+        ));
+        let scene = Scene::new(vec![wall], Vec::new());
+        let below = Vec3f::new_with_data([0.0, -1.0, 0.0]);
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+
+        let surface = scene_intersect(below, up, scene.geometry())
+            .ok_or_else(|| anyhow::anyhow!("wall was missed"))?;
+        let sample = LightSample::new(up, 5.0, 1.0);
+        assert!(is_in_shadow(&surface, &sample, scene.geometry()));
+        Ok(())
+    }
+
+    #[test]
+    fn directional_lights_are_blocked_by_any_hit_and_point_lights_only_before_the_light()
+    -> anyhow::Result<()> {
+        let floor = ShapeType::BoxShape(BoxShape::new(
+            Vec3f::new_with_data([5.0, 0.0, 5.0]),
+            Vec3f::new_with_data([-5.0, -1.0, -5.0]),
+            GOLD_MATERIAL,
+        ));
+        let far_blocker = ShapeType::BoxShape(BoxShape::new(
+            Vec3f::new_with_data([5.0, 2001.0, 5.0]),
+            Vec3f::new_with_data([-5.0, 2000.0, -5.0]),
+            GOLD_MATERIAL,
+        ));
+        let open = Scene::new(vec![floor.clone()], Vec::new());
+        let covered = Scene::new(vec![floor, far_blocker], Vec::new());
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let surface = scene_intersect(up, -up, covered.geometry())
+            .ok_or_else(|| anyhow::anyhow!("floor was missed"))?;
+
+        let sun = DirectionalLight::new(1.0, up).sample(surface.point, 0);
+        assert!(is_in_shadow(&surface, &sun, covered.geometry()));
+        assert!(!is_in_shadow(&surface, &sun, open.geometry()));
+
+        let below = PointLight::new(1.0, up * 1500.0).sample(surface.point, 0);
+        let above = PointLight::new(1.0, up * 3000.0).sample(surface.point, 0);
+        assert!(!is_in_shadow(&surface, &below, covered.geometry()));
+        assert!(is_in_shadow(&surface, &above, covered.geometry()));
+        Ok(())
+    }
+
+    #[test]
+    fn far_clip_distance_is_a_scene_parameter() {
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let mut scene = Scene::new(
+            vec![ShapeType::Sphere(Sphere::new(
+                forward * 5000.0,
+                1.0,
+                GOLD_MATERIAL,
+            ))],
+            Vec::new(),
+        );
+        assert!(scene_intersect(Vec3f::new(0.0), forward, scene.geometry()).is_none());
+
+        scene.set_max_distance(1e4);
+        assert!(scene_intersect(Vec3f::new(0.0), forward, scene.geometry()).is_some());
+        assert!(scene_intersect(Vec3f::new(0.0), -forward, scene.geometry()).is_none());
+    }
+
+    #[test]
+    fn close_up_hits_keep_contact_shadows_under_small_objects() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let radius = 2e-4;
+        let scene = Scene::new(
+            vec![
+                ShapeType::InfinityPlane(InfinityPlane::new(Vec3f::new(0.0), up, GOLD_MATERIAL)),
+                ShapeType::Sphere(Sphere::new(up * radius, radius, GOLD_MATERIAL)),
+            ],
+            Vec::new(),
+        );
+        let under = Vec3f::new_with_data([1.5e-4, 0.0, 0.0]);
+        let origin = under + Vec3f::new_with_data([3e-3, 3e-3, 0.0]);
+        let surface = scene_intersect(origin, (under - origin).normalize(None), scene.geometry())
+            .ok_or_else(|| anyhow::anyhow!("floor was missed"))?;
+        assert_eq!(surface.shape, 0);
+
+        let light = PointLight::new(1.0, up * 5.0).sample(surface.point, 0);
+        assert!(is_in_shadow(&surface, &light, scene.geometry()));
+        Ok(())
+    }
+
+    #[test]
+    fn culled_back_faces_let_the_camera_see_out_of_a_box() {
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let seen = |sidedness| {
+            let scene = Scene::new(
+                vec![
+                    ShapeType::BoxShape(BoxShape::new(
+                        Vec3f::new(1.0),
+                        Vec3f::new(-1.0),
+                        GOLD_MATERIAL.with_sidedness(sidedness),
+                    )),
+                    ShapeType::Sphere(Sphere::new(forward * 5.0, 1.0, GOLD_MATERIAL)),
+                ],
+                Vec::new(),
+            );
+            scene_intersect(Vec3f::new(0.0), forward, scene.geometry())
+                .map(|surface| (surface.shape, surface.front_face))
+        };
+
+        assert_eq!(seen(Sidedness::Double), Some((0, false)));
+        assert_eq!(seen(Sidedness::Single), Some((0, false)));
+        assert_eq!(seen(Sidedness::Culled), Some((1, true)));
+    }
+
+    #[test]
+    fn picking_skips_culled_faces_and_stops_at_the_far_clip() {
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let mut scene = Scene::new(
+            vec![
+                ShapeType::BoxShape(BoxShape::new(
+                    Vec3f::new(1.0),
+                    Vec3f::new(-1.0),
+                    GOLD_MATERIAL.with_sidedness(Sidedness::Culled),
+                )),
+                ShapeType::Sphere(Sphere::new(forward * 5.0, 1.0, GOLD_MATERIAL)),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(
+            scene
+                .pick_hit(Vec3f::new(0.0), forward)
+                .map(|(_, index)| index),
+            Some(1)
+        );
+
+        scene.set_max_distance(3.0);
+        assert_eq!(scene.pick_hit(Vec3f::new(0.0), forward), None);
+    }
+
+    #[test]
+    fn double_sided_planes_are_not_lit_through_from_behind() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let diffuse = |sidedness, origin: Vec3f, direction: Vec3f| {
+            let scene = Scene::new(
+                vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                    Vec3f::new(0.0),
+                    up,
+                    GOLD_MATERIAL.with_sidedness(sidedness),
+                ))],
+                vec![LightType::Point(PointLight::new(1.0, up * 5.0))],
+            );
+            let surface = scene_intersect(origin, direction, scene.geometry())
+                .ok_or_else(|| anyhow::anyhow!("plane was missed"))?;
+            anyhow::Ok(
+                compute_lighthing(&surface.lit(), direction, scene.lights(), scene.geometry()).1,
+            )
+        };
+
+        let front = diffuse(Sidedness::Double, up, -up)?;
+        assert!(front > 0.0);
+        assert!(diffuse(Sidedness::Double, -up, up)?.abs() < f64::EPSILON);
+        assert!((diffuse(Sidedness::Single, -up, up)? - front).abs() < 1e-12);
+        Ok(())
+    }
+
+    #[test]
+    fn path_traced_white_lambert_vanishes_in_white_environment() {
+        let (_, lambert) = crate::furnace::FURNACE_MATERIALS[0];
+        let scene = Scene::new(
+            vec![ShapeType::Sphere(Sphere::new(
+                Vec3f::new_with_data([0.0, 0.0, -3.0]),
+                1.0,
+                lambert,
+            ))],
+            vec![LightType::Ambient(AmbientLight::new(1.0))],
+        )
+        .with_background(Vec3f::new(1.0));
+        let direction = Vec3f::new_with_data([0.1, 0.2, -1.0]).normalize(None);
+
+        for sample in 0..16 {
+            let radiance = scene.trace_path(Vec3f::new(0.0), direction, sample, (0.3, 0.7), 0);
+            assert!((radiance - Vec3f::new(1.0)).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn diffuse_and_specular_aovs_sum_to_beauty() {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let settings = RenderSettings::default();
+
+        for (x, y) in [(20.0, 20.0), (50.0, 60.0), (80.0, 45.0), (10.0, 90.0)] {
+            let direction = camera.ray_direction(x, y, 100, 100);
+            let beauty = scene.trace(Vec3f::new(0.0), direction);
+            let split = [Aov::Diffuse, Aov::Specular]
+                .map(|aov| scene.sample_aov(aov, Vec3f::new(0.0), direction, (0, 0), 0, &settings));
+
+            assert!((beauty - split[0] - split[1]).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn aov_buffers_agree_with_picking_and_mark_escaped_rays() -> anyhow::Result<()> {
+        let mut shapes = init_default_shapes();
+        shapes.retain(|shape| shape.kind() != "plane");
+        let scene = Scene::new(shapes, init_default_lights());
+        let camera = Camera::new(Vec3f::new(0.0), crate::FOV);
+        let size = (40, 30);
+        let buffers = scene.render_aovs(
+            &camera,
+            size,
+            &[AovBuffer::Depth, AovBuffer::Normal, AovBuffer::ShapeId],
+        );
+        let [depth, normal, id] = buffers.as_slice() else {
+            panic!("expected three AOV buffers, got {}", buffers.len());
+        };
+
+        let mut escaped = 0;
+        for (x, y) in (0..size.1).flat_map(|y| (0..size.0).map(move |x| (x, y))) {
+            let center = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+            let picked = scene.pick(center.0, center.1, size.0, size.1, &camera);
+            let Some(picked) = picked else {
+                assert_eq!(depth.pixel(x, y), [f64::INFINITY]);
+                assert_eq!(id.pixel(x, y), [0.0]);
+                escaped += 1;
+                continue;
+            };
+
+            let point = scene
+                .pick_point(center.0, center.1, size.0, size.1, &camera)
+                .ok_or_else(|| anyhow::anyhow!("picked a shape without a point"))?;
+            assert!((depth.pixel(x, y)[0] - point.length()).abs() < 1e-9);
+            #[allow(clippy::cast_precision_loss)]
+            let expected = picked.0 as f64 + 1.0;
+            assert_eq!(id.pixel(x, y), [expected]);
+
+            let direction = camera.ray_direction(center.0, center.1, size.0, size.1);
+            let [nx, ny, nz] = normal.pixel(x, y) else {
+                panic!("normal buffer should have three channels");
+            };
+            let normal = Vec3f::new_with_data([*nx, *ny, *nz]);
+            assert!((normal.length() - 1.0).abs() < 1e-9);
+            assert!(normal * direction <= 0.0);
+        }
+        assert!(escaped > 0);
+
+        assert_eq!(
+            crate::aov::buffer_path(Path::new("out/render.png"), AovBuffer::Depth),
+            Path::new("out/render.depth.png")
+        );
+        assert_eq!(depth.to_rgba().len(), 40 * 30 * 4);
+        Ok(())
+    }
+
+    #[test]
+    fn absorbing_glass_darkens_with_thickness() {
+        let absorption = Vec3f::new_with_data([0.5, 0.5, 0.5]);
+        let trace_through = |radius: f64, material: Material| {
+            let center = Vec3f::new_with_data([0.0, 0.0, -5.0]);
+            let scene = Scene::new(
+                vec![ShapeType::Sphere(Sphere::new(center, radius, material))],
+                Vec::new(),
+            );
+            scene.trace(Vec3f::new(0.0), Vec3f::new_with_data([0.0, 0.0, -1.0]))
+        };
+
+        let clear = trace_through(1.0, GLASS_MATERIAL);
+        let thin = trace_through(0.5, GLASS_MATERIAL.with_absorption(absorption));
+        let thick = trace_through(2.0, GLASS_MATERIAL.with_absorption(absorption));
+
+        for channel in 0..3 {
+            assert!(thin[channel] < clear[channel]);
+            assert!(thick[channel] < thin[channel]);
+        }
+    }
+
+    #[test]
+    fn bubbles_in_absorbing_glass_only_skip_their_own_thickness() {
+        let absorption = Vec3f::new_with_data([0.5, 0.25, 0.1]);
+        let refractive =
+            GLASS_MATERIAL.with_albedo(crate::Vec4f::new_with_data([0.0, 0.0, 0.0, 1.0]));
+        let bubble = Material::new(
+            refractive.albedo(),
+            Vec3f::new(0.0),
+            Vec3f::new(0.0),
+            0.0,
+            1.0,
+        );
+        let trace_through = |glass: Material| {
+            let center = Vec3f::new_with_data([0.0, 0.0, -5.0]);
+            let scene = Scene::new(
+                vec![
+                    ShapeType::Sphere(Sphere::new(center, 2.0, glass)),
+                    ShapeType::Sphere(Sphere::new(center, 1.0, bubble)),
+                ],
+                Vec::new(),
+            );
+            scene.trace(Vec3f::new(0.0), Vec3f::new_with_data([0.0, 0.0, -1.0]))
+        };
+
+        // Two units of glass on either side of the bubble, none inside it.
+        let clear = trace_through(refractive);
+        let tinted = trace_through(refractive.with_absorption(absorption));
+        for channel in 0..3 {
+            let expected = clear[channel] * (-absorption[channel] * 2.0).exp();
+            assert!((tinted[channel] - expected).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn dispersive_glass_refracts_blue_more_steeply_than_red() -> anyhow::Result<()> {
+        let flint = GLASS_MATERIAL.with_dispersion(0.015);
+        let scene = Scene::new(
+            vec![ShapeType::Sphere(Sphere::new(
+                Vec3f::new_with_data([0.0, 0.0, -5.0]),
+                1.0,
+                flint,
+            ))],
+            Vec::new(),
+        );
+        let direction = Vec3f::new_with_data([0.1, 0.0, -1.0]).normalize(None);
+        let surface = scene_intersect(Vec3f::new(0.0), direction, scene.geometry())
+            .ok_or_else(|| anyhow::anyhow!("sphere was missed"))?;
+
+        let bend = |media: MediumStack| {
+            let (_, refracted, _) = secondary_rays(&surface, direction, media, EPSILON);
+            refracted.direction * -surface.normal
+        };
+        let [red, green, blue] = [0, 1, 2].map(|channel| bend(MediumStack::AIR.split(channel)));
+        assert!(red < green && green < blue);
+        assert!(red < bend(MediumStack::AIR) && bend(MediumStack::AIR) < blue);
+
+        Ok(())
+    }
+
+    #[test]
+    fn glass_spheres_focus_photons_into_a_caustic_below_them() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let mut scene = Scene::new(
+            vec![
+                ShapeType::InfinityPlane(InfinityPlane::new(Vec3f::new(0.0), up, CORNELL_WHITE)),
+                ShapeType::Sphere(Sphere::new(up * 0.8, 0.5, GLASS_MATERIAL)),
+            ],
+            vec![LightType::Point(PointLight::new(1.0, up * 5.0))],
+        );
+        scene.set_caustics(Some((20_000, 0.1)));
+        let caustics = scene
+            .photon_map()
+            .ok_or_else(|| anyhow::anyhow!("caustics were not traced"))?;
+        assert!(!caustics.is_empty());
+
+        // The ball lens focuses just above the floor, far brighter than the unit direct light.
+        let focus = caustics.irradiance(Vec3f::new(0.0), up);
+        let aside = caustics.irradiance(Vec3f::new_with_data([1.5, 0.0, 0.0]), up);
+        assert!(focus[0] > 1.0, "{focus:?}");
+        assert!(aside[0] < focus[0] * 0.25, "{aside:?} {focus:?}");
+
+        scene.set_caustics(None);
+        assert!(scene.photon_map().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn caustics_follow_light_and_material_edits() {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let mut scene = Scene::new(
+            vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new(0.0),
+                up,
+                CORNELL_WHITE,
+            ))],
+            Vec::new(),
+        );
+        let lens = scene.push_shape(ShapeType::Sphere(Sphere::new(
+            up * 0.8,
+            0.5,
+            GLASS_MATERIAL,
+        )));
+        scene.set_caustics(Some((2_000, 0.1)));
+        let photons = |scene: &Scene| scene.photon_map().map_or(0, PhotonMap::len);
+        assert_eq!(photons(&scene), 0);
+
+        let light = scene.push_light(LightType::Point(PointLight::new(1.0, up * 5.0)));
+        assert!(photons(&scene) > 0);
+
+        assert!(scene.set_shape_material(lens, CORNELL_WHITE));
+        assert_eq!(photons(&scene), 0);
+        assert!(scene.set_shape_material(lens, GLASS_MATERIAL));
+        assert!(photons(&scene) > 0);
+
+        assert!(scene.update_light(light, |light| light.translate(up * -10.0)));
+        assert_eq!(photons(&scene), 0);
+        assert!(scene.remove_light(light).is_some());
+        assert_eq!(photons(&scene), 0);
+    }
+
+    #[test]
+    fn rough_mirrors_scatter_reflections_around_the_mirror_direction() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let scene = Scene::new(
+            vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new(0.0),
+                up,
+                MIRROR_MATERIAL,
+            ))],
+            Vec::new(),
+        );
+        let direction = Vec3f::new_with_data([1.0, -1.0, 0.0]).normalize(None);
+        let surface = scene_intersect(
+            Vec3f::new_with_data([-1.0, 1.0, 0.0]),
+            direction,
+            scene.geometry(),
+        )
+        .ok_or_else(|| anyhow::anyhow!("mirror was missed"))?;
+        let (mirror, _, _) = secondary_rays(&surface, direction, MediumStack::AIR, EPSILON);
+
+        let glossy = |roughness: f64, sample: (f64, f64)| {
+            rough_secondary_rays(
+                &surface,
+                direction,
+                MediumStack::AIR,
+                roughness,
+                sample,
+                EPSILON,
+            )
+            .map(|(reflected, _)| reflected.direction)
+        };
+        let samples = [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)];
+        for sample in samples {
+            assert_eq!(glossy(0.0, sample), Some(mirror.direction));
+        }
+
+        let scattered: Vec<_> = samples
+            .iter()
+            .filter_map(|&sample| glossy(0.3, sample))
+            .collect();
+        assert_eq!(scattered.len(), samples.len());
+        for (index, &reflected) in scattered.iter().enumerate() {
+            assert!(reflected * up > 0.0);
+            assert!(reflected * mirror.direction > 0.8);
+            assert!(reflected != mirror.direction);
+            assert!(
+                scattered[index + 1..]
+                    .iter()
+                    .all(|&other| other != reflected)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn frosted_glass_scatters_refractions_but_still_lets_them_through() -> anyhow::Result<()> {
+        let scene = Scene::new(
+            vec![ShapeType::Sphere(Sphere::new(
+                Vec3f::new_with_data([0.0, 0.0, -5.0]),
+                1.0,
+                FROSTED_GLASS_MATERIAL,
+            ))],
+            Vec::new(),
+        );
+        let direction = Vec3f::new_with_data([0.1, 0.0, -1.0]).normalize(None);
+        let surface = scene_intersect(Vec3f::new(0.0), direction, scene.geometry())
+            .ok_or_else(|| anyhow::anyhow!("sphere was missed"))?;
+        let (_, clear, _) = secondary_rays(&surface, direction, MediumStack::AIR, EPSILON);
+
+        let roughness = FROSTED_GLASS_MATERIAL.refract_roughness();
+        let frosted: Vec<_> = [(0.1, 0.2), (0.5, 0.5), (0.8, 0.7)]
+            .into_iter()
+            .filter_map(|sample| {
+                rough_secondary_rays(
+                    &surface,
+                    direction,
+                    MediumStack::AIR,
+                    roughness,
+                    sample,
+                    EPSILON,
+                )
+            })
+            .map(|(_, refracted)| refracted)
+            .collect();
+        assert_eq!(frosted.len(), 3);
+        for refracted in &frosted {
+            assert!(refracted.direction * surface.normal < 0.0);
+            assert!(refracted.direction * clear.direction > 0.8);
+            assert!(refracted.direction != clear.direction);
+            assert_eq!(refracted.media, clear.media);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn medium_stack_tracks_nested_dielectrics() {
+        let clear = Vec3f::new(0.0);
+        let in_water = MediumStack::AIR.entered(1.33, clear);
+        let in_glass = in_water.entered(1.5, clear);
+        assert!((in_glass.current() - 1.5).abs() < f64::EPSILON);
+
+        let bubble = in_glass.entered(1.0, clear);
+        assert!((bubble.exited(1.0).current() - 1.5).abs() < f64::EPSILON);
+        assert!((in_glass.exited(1.5).current() - 1.33).abs() < f64::EPSILON);
+        assert!((in_water.exited(1.33).current() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(MediumStack::AIR.exited(1.5), MediumStack::AIR);
+    }
+
+    #[test]
+    fn area_light_respects_emission_sides() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let floor = Scene::new(
+            vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new(0.0),
+                up,
+                GOLD_MATERIAL,
+            ))],
+            Vec::new(),
+        );
+        let corner = Vec3f::new_with_data([-0.5, 2.0, -0.5]);
+        let x = Vec3f::new_with_data([1.0, 0.0, 0.0]);
+        let z = Vec3f::new_with_data([0.0, 0.0, 1.0]);
+        let facing_down = AreaLight::new(1.0, corner, x, z);
+        let facing_up = AreaLight::new(1.0, corner, z, x);
+
+        let surface = scene_intersect(up, -up, floor.geometry())
+            .ok_or_else(|| anyhow::anyhow!("floor was missed"))?;
+        let diffuse = |light: AreaLight| {
+            compute_lighthing(&surface, -up, &[LightType::Area(light)], floor.geometry()).1
+        };
+
+        assert!(diffuse(facing_down) > 0.0);
+        assert!(diffuse(facing_up).abs() < f64::EPSILON);
+        assert!((diffuse(facing_up.with_two_sided(true)) - diffuse(facing_down)).abs() < 1e-12);
+
+        let seen = |light: AreaLight| {
+            Scene::new(Vec::new(), vec![LightType::Area(light)]).trace(Vec3f::new(0.0), up)
+        };
+        assert_eq!(seen(facing_down), Vec3f::new(1.0));
+        assert_eq!(seen(facing_up), Vec3f::new(0.0));
+        assert_eq!(seen(facing_up.with_two_sided(true)), Vec3f::new(1.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn adaptive_shadows_only_refine_penumbrae() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let mut scene = Scene::new(
+            vec![
+                ShapeType::InfinityPlane(InfinityPlane::new(Vec3f::new(0.0), up, GOLD_MATERIAL)),
+                ShapeType::BoxShape(BoxShape::new(
+                    Vec3f::new_with_data([0.0, 1.2, 10.0]),
+                    Vec3f::new_with_data([-10.0, 1.0, -10.0]),
+                    GOLD_MATERIAL,
+                )),
+            ],
+            vec![LightType::Area(
+                AreaLight::new(
+                    1.0,
+                    Vec3f::new_with_data([-1.0, 4.0, -1.0]),
+                    Vec3f::new_with_data([2.0, 0.0, 0.0]),
+                    Vec3f::new_with_data([0.0, 0.0, 2.0]),
+                )
+                .with_samples(64),
+            )],
+        );
+        let mut diffuse = |x: f64, quality: Option<f64>| {
+            scene.set_shadow_quality(quality);
+            let origin = Vec3f::new_with_data([x, 0.5, 0.0]);
+            let surface = scene_intersect(origin, -up, scene.geometry())
+                .ok_or_else(|| anyhow::anyhow!("floor was missed"))?;
+            anyhow::Ok(compute_lighthing(&surface, -up, scene.lights(), scene.geometry()).1)
+        };
+
+        assert!(diffuse(-5.0, Some(0.25))?.abs() < f64::EPSILON);
+        let (lit, lit_adaptive) = (diffuse(1.5, None)?, diffuse(1.5, Some(0.25))?);
+        assert!(lit > 0.0 && (lit_adaptive - lit).abs() < 0.1 * lit);
+        let (penumbra, penumbra_adaptive) = (diffuse(0.0, None)?, diffuse(0.0, Some(0.25))?);
+        assert!(penumbra > 0.0 && penumbra < lit);
+        assert!((penumbra_adaptive - penumbra).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn light_ids_do_not_collide_with_shape_ids() {
+        let mut scene = Scene::new(init_default_shapes(), init_default_lights());
+        let light = scene.push_light(LightType::Ambient(AmbientLight::new(0.5)));
+
+        assert!(
+            scene
+                .shape_ids()
+                .iter()
+                .all(|shape| shape.to_string() != light.to_string())
+        );
+        assert!(scene.update_light(light, |_| ()));
+        assert!(scene.remove_light(light).is_some());
+        assert!(scene.get_light(light).is_none());
+    }
+
+    #[test]
+    fn penumbra_softens_rays_grazing_distance_fields() {
+        let mut scene = Scene::new(vec![sphere(0.0)], Vec::new());
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let visibility = |scene: &Scene, height: f64| {
+            let origin = Vec3f::new_with_data([0.0, height, 0.0]);
+            penumbra(origin, forward, 10.0, usize::MAX, scene.geometry())
+        };
+
+        assert!((visibility(&scene, 1.2) - 1.0).abs() < f64::EPSILON);
+
+        scene.set_soft_shadows(Some(4.0));
+        let grazing = visibility(&scene, 1.2);
+        assert!(grazing > 0.0 && grazing < 1.0);
+        assert!(visibility(&scene, 1.5) > grazing);
+        assert!((visibility(&scene, 5.0) - 1.0).abs() < f64::EPSILON);
+        assert!(visibility(&scene, 0.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn light_shafts_are_darker_inside_shadows() {
+        let scene = Scene::new(
+            vec![sphere(0.0)],
+            vec![LightType::Point(PointLight::new(
+                1.0,
+                Vec3f::new_with_data([0.0, 10.0, -5.0]),
+            ))],
+        );
+        let across = Vec3f::new_with_data([1.0, 0.0, 0.0]);
+        let scattered = |z: f64| {
+            let origin = Vec3f::new_with_data([-5.0, -2.0, z]);
+            scene.light_shafts(origin, across, Vec3f::new(0.0), 0.05, 0.5)[0]
+        };
+
+        let shadowed = scattered(-5.0);
+        let lit = scattered(-15.0);
+        assert!(shadowed > 0.0);
+        assert!(shadowed < lit);
+
+        let attenuated = scene.light_shafts(
+            Vec3f::new(0.0),
+            Vec3f::new_with_data([0.0, 0.0, -1.0]),
+            Vec3f::new(1.0),
+            0.05,
+            0.5,
+        );
+        assert!(attenuated[2] < 1.0);
+    }
+
+    #[test]
+    fn volumes_scatter_light_and_cast_shadows() -> anyhow::Result<()> {
+        let grid = DensityGrid::parse("1 1 1\n1\n", std::path::PathBuf::new())?;
+        let scene = Scene::new(
+            Vec::new(),
+            vec![LightType::Point(PointLight::new(
+                1.0,
+                Vec3f::new_with_data([0.0, 10.0, -5.0]),
+            ))],
+        )
+        .with_background(Vec3f::new(0.0))
+        .with_volumes(vec![
+            Volume::new(
+                Vec3f::new_with_data([-1.0, -1.0, -6.0]),
+                Vec3f::new_with_data([1.0, 1.0, -4.0]),
+                grid,
+            )
+            .with_density(2.0),
+        ]);
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+
+        let through = scene.trace(Vec3f::new(0.0), forward);
+        let beside = scene.trace(Vec3f::new_with_data([3.0, 0.0, 0.0]), forward);
+        assert!(through[0] > 0.0 && through[0] < 1.0);
+        assert_eq!(beside, Vec3f::new(0.0));
+
+        let down = Vec3f::new_with_data([0.0, -1.0, 0.0]);
+        let shadowed = volume_transmittance(
+            Vec3f::new_with_data([0.0, 5.0, -5.0]),
+            down,
+            10.0,
+            scene.geometry(),
+        );
+        assert!((shadowed - (-4.0_f64).exp()).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct Floor;
+
+    impl Intersectable for Floor {
+        fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+            let distance = -(origin.y() + 1.0) / direction.y();
+            (distance > 0.0).then(|| Hit::new(distance, self.get_normal(origin)))
+        }
+    }
+
+    impl Shape for Floor {
+        fn get_normal(&self, _hit_point: Vec3f) -> Vec3f {
+            Vec3f::new_with_data([0.0, 1.0, 0.0])
+        }
+
+        fn get_material(&self) -> Material {
+            GOLD_MATERIAL
+        }
+
+        fn set_material(&mut self, _material: Material) {}
+
+        fn translate(&mut self, _offset: Vec3f) {}
+
+        fn thickness(&self) -> f64 {
+            f64::INFINITY
+        }
+
+        fn bounds(&self) -> Option<Aabb> {
+            None
+        }
+    }
+
+    #[test]
+    fn custom_shapes_trace_move_and_take_materials() {
+        let floor: Arc<dyn Shape + Send + Sync> = Arc::new(Floor);
+        let mut scene = Scene::new(Vec::new(), vec![LightType::Ambient(AmbientLight::new(1.0))])
+            .with_background(Vec3f::new(0.0));
+        let id = scene.push_shape(ShapeType::Custom(CustomShape::new(Arc::clone(&floor))));
+        let down = Vec3f::new_with_data([0.0, -1.0, 0.0]);
+
+        assert!(scene.trace(Vec3f::new(0.0), down).length() > 0.0);
+        assert!(scene.translate_shape(id, Vec3f::new_with_data([0.0, 2.0, 0.0])));
+        assert_eq!(scene.trace(Vec3f::new(0.0), down), Vec3f::new(0.0));
+        assert!(
+            scene
+                .trace(Vec3f::new_with_data([0.0, 2.0, 0.0]), down)
+                .length()
+                > 0.0
+        );
+
+        assert!(scene.set_shape_material(id, GLASS_MATERIAL));
+        assert_eq!(
+            scene.get_shape(id).map(Shape::get_material),
+            Some(GLASS_MATERIAL)
+        );
+        assert_eq!(floor.get_material(), GOLD_MATERIAL);
+    }
+
+    #[derive(Debug)]
+    struct Spot {
+        position: Vec3f,
+    }
+
+    impl Light for Spot {
+        fn intensity(&self) -> f64 {
+            1.0
+        }
+
+        fn get_direction(&self, point: Vec3f) -> Vec3f {
+            (self.position - point).normalize(None)
+        }
+
+        fn get_distance(&self, point: Vec3f) -> f64 {
+            (self.position - point).length()
+        }
+
+        fn sample(&self, point: Vec3f, _index: u32) -> LightSample {
+            let direction = self.get_direction(point);
+            LightSample::new(
+                direction,
+                self.get_distance(point),
+                direction.y().max(0.0).powi(8),
+            )
+        }
+    }
+
+    #[test]
+    fn custom_lights_shade_move_and_scale() {
+        let mut scene = Scene::new(
+            vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new_with_data([0.0, -1.0, 0.0]),
+                Vec3f::new_with_data([0.0, 1.0, 0.0]),
+                GOLD_MATERIAL,
+            ))],
+            Vec::new(),
+        )
+        .with_background(Vec3f::new(0.0));
+        let id = scene.push_light(LightType::Custom(CustomLight::new(Arc::new(Spot {
+            position: Vec3f::new_with_data([0.0, 4.0, 0.0]),
+        }))));
+        let down = Vec3f::new_with_data([0.0, -1.0, 0.0]);
+        let brightness = |scene: &Scene, x: f64| {
+            scene
+                .trace(Vec3f::new_with_data([x, 0.0, 0.0]), down)
+                .length()
+        };
+
+        let (below, aside) = (brightness(&scene, 0.0), brightness(&scene, 6.0));
+        assert!(below > aside);
+
+        assert!(scene.update_light(id, |light| {
+            light.translate(Vec3f::new_with_data([6.0, 0.0, 0.0]));
+            light.scale_intensity(2.0);
+        }));
+        assert!(brightness(&scene, 6.0) > below);
+        assert_eq!(scene.get_light(id).map(Light::intensity), Some(2.0));
+    }
+
+    #[test]
+    fn sky_light_blends_background_and_lights_upward_faces() {
+        let zenith = Vec3f::new_with_data([0.2, 0.4, 1.0]);
+        let horizon = Vec3f::new(1.0);
+        let ground = Vec3f::new(0.1);
+        let scene = Scene::new(
+            vec![sphere(0.0)],
+            vec![LightType::Sky(SkyLight::new(1.0, zenith, horizon, ground))],
+        );
+        let origin = Vec3f::new(0.0);
+
+        for (direction, expected) in [
+            ([0.0, 1.0, 0.0], zenith),
+            ([1.0, 0.0, 0.0], horizon),
+            ([0.0, -1.0, 0.0], ground),
+        ] {
+            let missed = scene.trace(origin, Vec3f::new_with_data(direction));
+            assert!((missed - expected).length() < 1e-9);
+        }
+
+        let top = scene.trace(
+            origin,
+            Vec3f::new_with_data([0.0, 0.8, -5.0]).normalize(None),
+        );
+        let bottom = scene.trace(
+            origin,
+            Vec3f::new_with_data([0.0, -0.8, -5.0]).normalize(None),
+        );
+        assert!(top.length() > bottom.length());
+    }
+
+    #[test]
+    fn sun_sky_lights_the_sunward_side_and_fills_the_background() -> anyhow::Result<()> {
+        let sun_sky = SunSkyLight::new(1.0, SunSky::new(20.0, 90.0, 3.0)?);
+        let scene = Scene::new(
+            vec![sphere(0.0)],
+            vec![LightType::SunSky(Box::new(sun_sky))],
+        );
+        let origin = Vec3f::new(0.0);
+
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        assert!((scene.trace(origin, up) - sun_sky.radiance(up)).length() < 1e-9);
+
+        let sunward = scene.trace(
+            origin,
+            Vec3f::new_with_data([0.6, 0.0, -5.0]).normalize(None),
+        );
+        let shaded = scene.trace(
+            origin,
+            Vec3f::new_with_data([-0.6, 0.0, -5.0]).normalize(None),
+        );
+        assert!(sunward.length() > shaded.length(), "{sunward:?} {shaded:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn environment_maps_light_the_floor_except_under_an_occluder() -> anyhow::Result<()> {
+        let map = EnvironmentMap::new(PathBuf::new(), 8, 4, vec![Vec3f::new(1.0); 32])?;
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let scene = Scene::new(
+            vec![
+                ShapeType::InfinityPlane(InfinityPlane::new(up * -1.0, up, CORNELL_WHITE)),
+                ShapeType::Sphere(Sphere::new(
+                    Vec3f::new_with_data([0.0, 0.0, -5.0]),
+                    0.8,
+                    CORNELL_WHITE,
+                )),
+            ],
+            vec![LightType::Environment(EnvironmentLight::new(
+                1.0,
+                Arc::new(map),
+            ))],
+        );
+        let origin = Vec3f::new(0.0);
+
+        assert!((scene.trace(origin, up) - Vec3f::new(1.0)).length() < 1e-9);
+        let open = scene.trace(origin, Vec3f::new_with_data([3.0, -1.0, -5.0]).unit());
+        let shadowed = scene.trace(origin, Vec3f::new_with_data([0.0, -1.0, -5.0]).unit());
+        assert!(open.y() > shadowed.y() * 1.5, "{open:?} {shadowed:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn colors_brighter_than_white_keep_their_hue_in_the_output() {
+        let settings = RenderSettings::default();
+        assert_eq!(
+            pixel_color(Vec3f::new_with_data([2.0, 1.0, 0.5]), &settings),
+            [255, 128, 64, 255]
+        );
+        assert_eq!(
+            pixel_color(Vec3f::new_with_data([0.2, 0.4, 0.6]), &settings),
+            [51, 102, 153, 255]
+        );
+        assert_eq!([to_u8(1.7), to_u8(-0.3), to_u8(f64::NAN)], [255, 0, 0]);
+    }
+
+    #[test]
+    fn fog_fades_distant_hits_towards_its_color() {
+        let mut scene = Scene::new(
+            vec![sphere(0.0)],
+            vec![LightType::Ambient(AmbientLight::new(1.0))],
+        );
+        let background = Vec3f::new_with_data([0.0, 0.0, 1.0]);
+        let fog_color = Vec3f::new_with_data([1.0, 0.0, 0.0]);
+        let origin = Vec3f::new(0.0);
+        let direction = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let trace = |scene: &Scene| {
+            cast_ray(
+                origin,
+                direction,
+                scene.geometry(),
+                scene.lights(),
+                background,
+                MediumStack::AIR,
+                0,
+            )
+        };
+
+        let clear = trace(&scene);
+        scene.set_fog(Fog::new(0.1, Some(fog_color)));
+        let light_fog = trace(&scene);
+        scene.set_fog(Fog::new(10.0, Some(fog_color)));
+        let dense_fog = trace(&scene);
+        scene.set_fog(Fog::new(10.0, None));
+        let background_fog = trace(&scene);
+
+        let expected = fog_color + (clear - fog_color) * (-0.4_f64).exp();
+        assert!((light_fog - expected).length() < 1e-9);
+        assert!((dense_fog - fog_color).length() < 1e-9);
+        assert!((background_fog - background).length() < 1e-9);
+
+        let miss = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let missed = cast_ray(
+            origin,
+            miss,
+            scene.geometry(),
+            scene.lights(),
+            background,
+            MediumStack::AIR,
+            0,
+        );
+        assert_eq!(missed, background);
+    }
+
+    #[test]
+    fn water_plane_normals_move_with_scene_time() -> anyhow::Result<()> {
+        let up = Vec3f::new_with_data([0.0, 1.0, 0.0]);
+        let point = Vec3f::new_with_data([0.7, 0.0, -3.2]);
+        let mut scene = Scene::new(
+            vec![ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new_with_data([0.0, 0.0, 0.0]),
+                up,
+                WATER_MATERIAL,
+            ))],
+            Vec::new(),
+        );
+        let normal = |scene: &Scene| -> anyhow::Result<Vec3f> {
+            match scene.shapes().first() {
+                Some(ShapeType::InfinityPlane(plane)) => {
+                    assert_eq!(plane.get_geometric_normal(point), up);
+                    Ok(plane.get_normal(point))
+                }
+                _ => Err(anyhow::anyhow!("plane missing")),
+            }
+        };
+
+        let before = normal(&scene)?;
+        scene.set_time(0.75);
+        let after = normal(&scene)?;
+
+        assert_ne!(before, after);
+        for perturbed in [before, after] {
+            assert!((perturbed.length() - 1.0).abs() < 1e-9);
+            assert!(perturbed * up > 0.9);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,1742 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::warn;
+
+use crate::{
+    Vec3f, Vec4f,
+    billboard::{Billboard, Facing, Sprite, SpriteSource},
+    camera::Camera,
+    environment::EnvironmentMap,
+    error::RaytracerError,
+    geometry::Transform,
+    heightfield::{HeightGrid, HeightSource, Heightfield},
+    lights::{
+        AmbientLight, AreaLight, DirectionalLight, EnvironmentLight, Light, LightType, PointLight,
+        SkyLight, SunSkyLight,
+    },
+    materials::{Material, MaterialRegistry, Sidedness, SpecularModel, Waves},
+    mesh::TriangleMesh,
+    pbrt,
+    prism::{Polygon, Prism},
+    quad::Quad,
+    scene::Scene,
+    scenes,
+    sdf::{Sdf, SdfShape},
+    settings::{OVERRIDE_NAMES, RenderOverrides},
+    shapes::{BoxShape, InfinityPlane, Instance, OrientedBox, Shape, ShapeType, Sphere},
+    sky::SunSky,
+    text::TextLabel,
+    volume::{DensityGrid, GridSource, Volume},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneFile {
+    scene: Scene,
+    camera: Camera,
+    overrides: RenderOverrides,
+}
+
+impl SceneFile {
+    pub const fn new(scene: Scene, camera: Camera) -> Self {
+        Self {
+            scene,
+            camera,
+            overrides: RenderOverrides::NONE,
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: RenderOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    pub const fn overrides(&self) -> &RenderOverrides {
+        &self.overrides
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RaytracerError> {
+        let parse_error = |source| RaytracerError::SceneParse {
+            path: path.to_owned(),
+            source,
+        };
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "pbrt")
+        {
+            return pbrt::load(path).map_err(parse_error);
+        }
+
+        let source = fs::read_to_string(path).map_err(|source| RaytracerError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&source, base_dir).map_err(parse_error)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())
+            .with_context(|| format!("Failed to write scene file {}", path.display()))
+    }
+
+    pub const fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub const fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    pub const fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn into_parts(self) -> (Scene, Camera) {
+        (self.scene, self.camera)
+    }
+}
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        let (scene, camera) = scenes::spheres();
+        Self::new(scene, camera)
+    }
+}
+
+struct Record<'a> {
+    kind: &'a str,
+    line: usize,
+    fields: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Record<'a> {
+    fn parse(line: &'a str, line_number: usize) -> Result<Option<Self>> {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(kind) = tokens.next() else {
+            return Ok(None);
+        };
+
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        for token in tokens {
+            let (key, value) = token.split_once('=').ok_or_else(|| {
+                anyhow!("Expected key=value, found '{token}' on line {line_number}")
+            })?;
+            if fields.iter().any(|(existing, _)| *existing == key) {
+                bail!("Duplicate field '{key}' on line {line_number}");
+            }
+            fields.push((key, value));
+        }
+
+        Ok(Some(Self {
+            kind,
+            line: line_number,
+            fields,
+        }))
+    }
+
+    fn value(&self, key: &str) -> Result<&'a str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == key)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Missing field '{key}' for {} on line {}",
+                    self.kind,
+                    self.line
+                )
+            })
+    }
+
+    fn check_fields(&self, allowed: &[&str]) -> Result<()> {
+        for (key, _) in &self.fields {
+            if !allowed.contains(key) {
+                bail!(
+                    "Unknown field '{key}' for {} on line {}",
+                    self.kind,
+                    self.line
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn components<const N: usize>(&self, key: &str) -> Result<[f64; N]> {
+        let value = self.value(key)?;
+        let components = value
+            .split(',')
+            .map(|component| {
+                component.parse::<f64>().with_context(|| {
+                    format!(
+                        "Invalid number '{component}' in '{key}' on line {}",
+                        self.line
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        components.try_into().map_err(|components: Vec<f64>| {
+            anyhow!(
+                "Expected {N} components in '{key}' on line {}, found {}",
+                self.line,
+                components.len()
+            )
+        })
+    }
+
+    fn parsed<T: FromStr>(&self, key: &str) -> Result<T> {
+        let value = self.value(key)?;
+        value
+            .parse()
+            .map_err(|_| anyhow!("Invalid value '{value}' for '{key}' on line {}", self.line))
+    }
+
+    fn number(&self, key: &str) -> Result<f64> {
+        let [value] = self.components(key)?;
+        Ok(value)
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vec3f> {
+        Ok(Vec3f::new_with_data(self.components(key)?))
+    }
+
+    fn vec4(&self, key: &str) -> Result<Vec4f> {
+        Ok(Vec4f::new_with_data(self.components(key)?))
+    }
+
+    fn optional(&self, key: &str) -> Option<&'a str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == key)
+            .map(|(_, value)| *value)
+    }
+
+    fn material(&self, materials: &MaterialRegistry) -> Result<Material> {
+        let Some(name) = self.optional("material") else {
+            return self.inline_material();
+        };
+
+        if self
+            .fields
+            .iter()
+            .any(|(field, _)| MATERIAL_FIELDS.contains(field))
+        {
+            bail!(
+                "Material '{name}' cannot be combined with inline material fields on line {}",
+                self.line
+            );
+        }
+        materials
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown material '{name}' on line {}", self.line))
+    }
+
+    fn inline_material(&self) -> Result<Material> {
+        let material = Material::new(
+            self.vec4("albedo")?,
+            self.vec3("diffuse")?,
+            self.vec3("ambient")?,
+            self.number("specular")?,
+            self.number("ior")?,
+        );
+
+        let material = if self.optional("dispersion").is_some() {
+            material.with_dispersion(self.number("dispersion")?)
+        } else {
+            material
+        };
+
+        let material = if self.optional("reflect_roughness").is_some() {
+            material.with_reflect_roughness(self.number("reflect_roughness")?)
+        } else {
+            material
+        };
+
+        let material = if self.optional("refract_roughness").is_some() {
+            material.with_refract_roughness(self.number("refract_roughness")?)
+        } else {
+            material
+        };
+
+        let material = if self.optional("absorption").is_some() {
+            material.with_absorption(self.vec3("absorption")?)
+        } else {
+            material
+        };
+
+        let material = match self.optional("specular_model") {
+            Some(name) => material.with_specular_model(
+                SpecularModel::from_name(name)
+                    .with_context(|| format!("Invalid material on line {}", self.line))?,
+            ),
+            None => material,
+        };
+
+        let material = match self.optional("sides") {
+            Some(name) => material.with_sidedness(
+                Sidedness::from_name(name)
+                    .with_context(|| format!("Invalid material on line {}", self.line))?,
+            ),
+            None => material,
+        };
+
+        if self.optional("waves").is_some() {
+            let waves = self.vec3("waves")?;
+            return Ok(material.with_waves(Waves::new(waves.x(), waves.y(), waves.z())));
+        }
+
+        Ok(material)
+    }
+
+    fn overrides(&self, base_dir: &Path) -> Result<RenderOverrides> {
+        self.check_fields(&OVERRIDE_NAMES)?;
+
+        let mut overrides = RenderOverrides::NONE;
+        for (key, value) in &self.fields {
+            if *key == "lut" {
+                overrides.set_lut(value, base_dir)
+            } else {
+                overrides.set(key, value)
+            }
+            .with_context(|| format!("Invalid render record on line {}", self.line))?;
+        }
+
+        Ok(overrides)
+    }
+
+    fn mesh(&self, base_dir: &Path, materials: &MaterialRegistry) -> Result<TriangleMesh> {
+        let source = PathBuf::from(self.value("path")?);
+        let mesh = TriangleMesh::load(&base_dir.join(&source), source, self.material(materials)?)?;
+        Ok(mesh.with_smooth_shading(self.parsed("smooth")?))
+    }
+
+    fn volume(&self, base_dir: &Path) -> Result<Volume> {
+        self.check_fields(&[
+            "min",
+            "max",
+            "density",
+            "albedo",
+            "grid",
+            "resolution",
+            "frequency",
+            "seed",
+        ])?;
+
+        let grid = if let Some(path) = self.optional("grid") {
+            let source = PathBuf::from(path);
+            DensityGrid::load(&base_dir.join(&source), source)?
+        } else {
+            DensityGrid::noise(
+                self.parsed("resolution")?,
+                self.number("frequency")?,
+                self.parsed("seed")?,
+            )
+        };
+
+        Ok(Volume::new(self.vec3("min")?, self.vec3("max")?, grid)
+            .with_density(self.number("density")?)
+            .with_albedo(self.vec3("albedo")?))
+    }
+
+    fn heightfield(&self, base_dir: &Path, materials: &MaterialRegistry) -> Result<Heightfield> {
+        let grid = if let Some(path) = self.optional("image") {
+            let source = PathBuf::from(path);
+            HeightGrid::load(&base_dir.join(&source), source)?
+        } else {
+            HeightGrid::noise(
+                self.parsed("resolution")?,
+                self.number("frequency")?,
+                self.parsed("seed")?,
+            )
+        };
+
+        Ok(Heightfield::new(
+            self.vec3("min")?,
+            self.vec3("size")?,
+            grid,
+            self.material(materials)?,
+        ))
+    }
+
+    fn billboard(&self, base_dir: &Path, materials: &MaterialRegistry) -> Result<Billboard> {
+        let sprite = if let Some(path) = self.optional("image") {
+            let source = PathBuf::from(path);
+            Sprite::load(&base_dir.join(&source), source)?
+        } else {
+            Sprite::glow()
+        };
+        let facing = if self.optional("axis").is_some() {
+            Facing::Cylindrical(self.vec3("axis")?)
+        } else {
+            Facing::Spherical
+        };
+
+        Billboard::new(
+            self.vec3("center")?,
+            self.number("width")?,
+            self.number("height")?,
+            facing,
+            sprite,
+            self.material(materials)?,
+        )
+        .with_context(|| format!("Invalid billboard on line {}", self.line))
+    }
+
+    fn quad(&self, base_dir: &Path, materials: &MaterialRegistry) -> Result<Quad> {
+        let quad = Quad::new(
+            self.vec3("origin")?,
+            self.vec3("edge_u")?,
+            self.vec3("edge_v")?,
+            self.material(materials)?,
+        )
+        .with_context(|| format!("Invalid quad on line {}", self.line))?;
+
+        Ok(if let Some(path) = self.optional("image") {
+            let source = PathBuf::from(path);
+            quad.with_texture(Sprite::load(&base_dir.join(&source), source)?)
+        } else {
+            quad
+        })
+    }
+
+    fn text(&self, materials: &MaterialRegistry) -> Result<TriangleMesh> {
+        let depth = if self.optional("depth").is_some() {
+            self.number("depth")?
+        } else {
+            0.0
+        };
+
+        TextLabel::new(
+            self.value("value")?.replace('_', " "),
+            self.vec3("position")?,
+            self.vec3("right")?,
+            self.vec3("up")?,
+            self.number("size")?,
+        )
+        .with_depth(depth)
+        .mesh(self.material(materials)?)
+        .with_context(|| format!("Invalid text on line {}", self.line))
+    }
+
+    fn oriented_box(&self, materials: &MaterialRegistry) -> Result<OrientedBox> {
+        Ok(OrientedBox::new(
+            self.vec3("center")?,
+            self.vec3("half_extents")?,
+            self.vec3("axis")?,
+            self.number("angle")?,
+            self.material(materials)?,
+        ))
+    }
+
+    fn rounded_box(&self, materials: &MaterialRegistry) -> Result<SdfShape> {
+        Ok(SdfShape::new(
+            Sdf::RoundedBox {
+                center: self.vec3("center")?,
+                half_extent: self.vec3("half_extents")?,
+                radius: self.number("radius")?,
+            },
+            self.material(materials)?,
+        ))
+    }
+
+    fn sdf(&self, materials: &MaterialRegistry) -> Result<SdfShape> {
+        let expression = self.value("expression")?;
+        let sdf: Sdf = expression.parse().with_context(|| {
+            format!(
+                "Invalid SDF expression '{expression}' on line {}",
+                self.line
+            )
+        })?;
+        Ok(SdfShape::new(sdf, self.material(materials)?))
+    }
+
+    fn prism(&self, materials: &MaterialRegistry) -> Result<Prism> {
+        let polygon: Polygon = self
+            .value("polygon")?
+            .parse()
+            .with_context(|| format!("Invalid prism polygon on line {}", self.line))?;
+        Prism::new(
+            polygon,
+            self.vec3("base")?,
+            self.vec3("axis")?,
+            self.number("height")?,
+            self.material(materials)?,
+        )
+        .with_context(|| format!("Invalid prism on line {}", self.line))
+    }
+
+    fn instance(&self, prototypes: &HashMap<&str, Arc<ShapeType>>) -> Result<Instance> {
+        let name = self.value("prototype")?;
+        let prototype = prototypes
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown prototype '{name}' on line {}", self.line))?;
+
+        let [m00, m01, m02, m10, m11, m12, m20, m21, m22, tx, ty, tz] =
+            self.components("transform")?;
+        let transform = Transform::new(
+            [
+                Vec3f::new_with_data([m00, m01, m02]),
+                Vec3f::new_with_data([m10, m11, m12]),
+                Vec3f::new_with_data([m20, m21, m22]),
+            ],
+            Vec3f::new_with_data([tx, ty, tz]),
+        )
+        .ok_or_else(|| anyhow!("Singular instance transform on line {}", self.line))?;
+
+        Ok(Instance::new(Arc::clone(prototype), transform))
+    }
+
+    fn light(&self, kind: &str, base_dir: &Path) -> Result<Option<LightType>> {
+        let light = match kind {
+            "ambient_light" => {
+                self.check_fields(&["intensity"])?;
+                LightType::Ambient(AmbientLight::new(self.number("intensity")?))
+            }
+            "point_light" => {
+                self.check_fields(&["intensity", "position"])?;
+                LightType::Point(PointLight::new(
+                    self.number("intensity")?,
+                    self.vec3("position")?,
+                ))
+            }
+            "directional_light" => {
+                self.check_fields(&["intensity", "direction"])?;
+                LightType::Directional(DirectionalLight::new(
+                    self.number("intensity")?,
+                    self.vec3("direction")?,
+                ))
+            }
+            "area_light" => {
+                self.check_fields(&["intensity", "corner", "u", "v", "two_sided", "samples"])?;
+                LightType::Area(
+                    AreaLight::new(
+                        self.number("intensity")?,
+                        self.vec3("corner")?,
+                        self.vec3("u")?,
+                        self.vec3("v")?,
+                    )
+                    .with_two_sided(self.parsed("two_sided")?)
+                    .with_samples(self.parsed("samples")?),
+                )
+            }
+            "sky_light" => {
+                self.check_fields(&["intensity", "zenith", "horizon", "ground"])?;
+                LightType::Sky(SkyLight::new(
+                    self.number("intensity")?,
+                    self.vec3("zenith")?,
+                    self.vec3("horizon")?,
+                    self.vec3("ground")?,
+                ))
+            }
+            "sun_sky" => {
+                self.check_fields(&["intensity", "elevation", "azimuth", "turbidity"])?;
+                let sky = SunSky::new(
+                    self.number("elevation")?,
+                    self.number("azimuth")?,
+                    self.number("turbidity")?,
+                )
+                .map_err(|err| anyhow!("{err} on line {}", self.line))?;
+                LightType::SunSky(Box::new(SunSkyLight::new(self.number("intensity")?, sky)))
+            }
+            "environment_light" => {
+                self.check_fields(&["intensity", "path", "samples"])?;
+                let source = PathBuf::from(self.value("path")?);
+                let map = EnvironmentMap::load(&base_dir.join(&source), source)?;
+                LightType::Environment(
+                    EnvironmentLight::new(self.number("intensity")?, Arc::new(map))
+                        .with_samples(self.parsed("samples")?),
+                )
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(light))
+    }
+
+    fn shape(
+        &self,
+        kind: &str,
+        extra_fields: &[&str],
+        base_dir: &Path,
+        prototypes: &HashMap<&str, Arc<ShapeType>>,
+        materials: &MaterialRegistry,
+    ) -> Result<Option<ShapeType>> {
+        let allowed = |fields: &[&'a str]| -> Vec<&str> {
+            let mut allowed = shape_fields(fields);
+            allowed.extend_from_slice(extra_fields);
+            allowed
+        };
+
+        let shape = match kind {
+            "sphere" => {
+                self.check_fields(&allowed(&["center", "radius"]))?;
+                ShapeType::Sphere(Sphere::new(
+                    self.vec3("center")?,
+                    self.number("radius")?,
+                    self.material(materials)?,
+                ))
+            }
+            "box" => {
+                self.check_fields(&allowed(&["min", "max"]))?;
+                ShapeType::BoxShape(BoxShape::new(
+                    self.vec3("max")?,
+                    self.vec3("min")?,
+                    self.material(materials)?,
+                ))
+            }
+            "oriented_box" => {
+                self.check_fields(&allowed(&["center", "half_extents", "axis", "angle"]))?;
+                ShapeType::OrientedBox(self.oriented_box(materials)?)
+            }
+            "rounded_box" => {
+                self.check_fields(&allowed(&["center", "half_extents", "radius"]))?;
+                ShapeType::Sdf(self.rounded_box(materials)?)
+            }
+            "plane" => {
+                self.check_fields(&allowed(&["position", "normal"]))?;
+                ShapeType::InfinityPlane(InfinityPlane::new(
+                    self.vec3("position")?,
+                    self.vec3("normal")?,
+                    self.material(materials)?,
+                ))
+            }
+            "mesh" => {
+                self.check_fields(&allowed(&["path", "smooth"]))?;
+                ShapeType::Mesh(self.mesh(base_dir, materials)?)
+            }
+            "sdf" => {
+                self.check_fields(&allowed(&["expression"]))?;
+                ShapeType::Sdf(self.sdf(materials)?)
+            }
+            "heightfield" => {
+                self.check_fields(&allowed(&[
+                    "min",
+                    "size",
+                    "image",
+                    "resolution",
+                    "frequency",
+                    "seed",
+                ]))?;
+                ShapeType::Heightfield(self.heightfield(base_dir, materials)?)
+            }
+            "prism" => {
+                self.check_fields(&allowed(&["polygon", "base", "axis", "height"]))?;
+                ShapeType::Prism(self.prism(materials)?)
+            }
+            "billboard" => {
+                self.check_fields(&allowed(&["center", "width", "height", "axis", "image"]))?;
+                ShapeType::Billboard(self.billboard(base_dir, materials)?)
+            }
+            "quad" => {
+                self.check_fields(&allowed(&["origin", "edge_u", "edge_v", "image"]))?;
+                ShapeType::Quad(self.quad(base_dir, materials)?)
+            }
+            "text" => {
+                self.check_fields(&allowed(&[
+                    "value", "position", "right", "up", "size", "depth",
+                ]))?;
+                ShapeType::Mesh(self.text(materials)?)
+            }
+            "instance" => {
+                let mut fields = vec!["prototype", "transform"];
+                fields.extend_from_slice(extra_fields);
+                self.check_fields(&fields)?;
+                ShapeType::Instance(self.instance(prototypes)?)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(shape))
+    }
+}
+
+pub const SCENE_FORMAT_VERSION: u32 = 2;
+
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&str) -> String,
+}
+
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        from: 0,
+        description: "unversioned scene files gained a 'format version' header",
+        apply: str::to_owned,
+    },
+    Migration {
+        from: 1,
+        description: "added material, mesh, instance, render, area_light, sky_light, sun_sky, \
+                      environment_light, volume, sdf, heightfield, prism, billboard, text, \
+                      oriented_box, rounded_box and quad records and new material and render keys",
+        apply: str::to_owned,
+    },
+];
+
+fn detect_version(source: &str) -> Result<u32> {
+    for (index, line) in source.lines().enumerate() {
+        let Some(record) = Record::parse(line, index + 1)? else {
+            continue;
+        };
+
+        if record.kind != "format" {
+            return Ok(0);
+        }
+
+        record.check_fields(&["version"])?;
+        let version = record.value("version")?;
+        return version.parse().with_context(|| {
+            format!("Invalid format version '{version}' on line {}", record.line)
+        });
+    }
+
+    Ok(0)
+}
+
+fn migrate(source: &str) -> Result<Cow<'_, str>> {
+    let mut version = detect_version(source)?;
+    if version > SCENE_FORMAT_VERSION {
+        bail!(
+            "Scene format version {version} is newer than the supported version {SCENE_FORMAT_VERSION}"
+        );
+    }
+
+    let original = version;
+    let mut source = Cow::Borrowed(source);
+    let mut descriptions = Vec::new();
+    while version < SCENE_FORMAT_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| anyhow!("No migration from scene format version {version}"))?;
+
+        descriptions.push(migration.description);
+        source = Cow::Owned((migration.apply)(&source));
+        version += 1;
+    }
+
+    if !descriptions.is_empty() {
+        warn!(
+            "Upgrading scene format from version {original} to {SCENE_FORMAT_VERSION}: {}",
+            descriptions.join("; ")
+        );
+    }
+
+    Ok(source)
+}
+
+const MATERIAL_FIELDS: [&str; 12] = [
+    "albedo",
+    "diffuse",
+    "ambient",
+    "specular",
+    "ior",
+    "dispersion",
+    "reflect_roughness",
+    "refract_roughness",
+    "absorption",
+    "waves",
+    "specular_model",
+    "sides",
+];
+
+fn shape_fields<'a>(fields: &[&'a str]) -> Vec<&'a str> {
+    fields
+        .iter()
+        .copied()
+        .chain(MATERIAL_FIELDS)
+        .chain(["material"])
+        .collect()
+}
+
+impl FromStr for SceneFile {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        Self::parse(source, Path::new(""))
+    }
+}
+
+impl SceneFile {
+    fn parse(source: &str, base_dir: &Path) -> Result<Self> {
+        let source = migrate(source)?;
+        let mut camera = None;
+        let mut overrides = None;
+        let mut shapes = Vec::new();
+        let mut lights = Vec::new();
+        let mut volumes = Vec::new();
+        let mut prototypes = HashMap::new();
+        let mut materials = MaterialRegistry::default();
+        let mut bindings = Vec::new();
+        let mut first_record = true;
+
+        for (index, line) in source.lines().enumerate() {
+            let Some(record) = Record::parse(line, index + 1)? else {
+                continue;
+            };
+
+            let is_first_record = first_record;
+            first_record = false;
+
+            match record.kind {
+                "format" => {
+                    if !is_first_record {
+                        bail!(
+                            "Format header must come first, found on line {}",
+                            record.line
+                        );
+                    }
+                }
+                "camera" => {
+                    record.check_fields(&["position", "fov"])?;
+                    if camera.is_some() {
+                        bail!("Duplicate camera on line {}", record.line);
+                    }
+                    camera = Some(Camera::new(record.vec3("position")?, record.number("fov")?));
+                }
+                "render" => {
+                    if overrides.is_some() {
+                        bail!("Duplicate render record on line {}", record.line);
+                    }
+                    overrides = Some(record.overrides(base_dir)?);
+                }
+                "material" => {
+                    let mut fields = vec!["name"];
+                    fields.extend_from_slice(&MATERIAL_FIELDS);
+                    record.check_fields(&fields)?;
+                    let name = record.value("name")?;
+                    if materials.insert(name, record.inline_material()?).is_some() {
+                        bail!("Duplicate material '{name}' on line {}", record.line);
+                    }
+                }
+                "volume" => volumes.push(record.volume(base_dir)?),
+                "prototype" => {
+                    let name = record.value("name")?;
+                    let kind = record.value("shape")?;
+                    let shape = record
+                        .shape(kind, &["name", "shape"], base_dir, &prototypes, &materials)?
+                        .ok_or_else(|| {
+                            anyhow!("Unknown prototype shape '{kind}' on line {}", record.line)
+                        })?;
+                    if prototypes.insert(name, Arc::new(shape)).is_some() {
+                        bail!("Duplicate prototype '{name}' on line {}", record.line);
+                    }
+                }
+                kind => {
+                    if let Some(light) = record.light(kind, base_dir)? {
+                        lights.push(light);
+                        continue;
+                    }
+
+                    let shape = record.shape(kind, &[], base_dir, &prototypes, &materials)?;
+                    let Some(shape) = shape else {
+                        bail!("Unknown record '{kind}' on line {}", record.line);
+                    };
+                    if let Some(name) = record.optional("material") {
+                        bindings.push((shapes.len(), name));
+                    }
+                    shapes.push(shape);
+                }
+            }
+        }
+
+        let camera = camera.ok_or_else(|| anyhow!("Scene file has no camera"))?;
+
+        let mut scene = Scene::new(shapes, lights)
+            .with_materials(materials)
+            .with_volumes(volumes);
+        for (index, name) in bindings {
+            let id = scene.shape_ids()[index];
+            scene.bind_material(id, name);
+        }
+
+        Ok(Self::new(scene, camera).with_overrides(overrides.unwrap_or_default()))
+    }
+}
+
+struct Components<'a>(&'a [f64]);
+
+impl fmt::Display for Components<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, component) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{component}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn vec3(vector: Vec3f) -> String {
+    Components(&[vector.x(), vector.y(), vector.z()]).to_string()
+}
+
+fn vec4(vector: Vec4f) -> String {
+    Components(&[vector[0], vector[1], vector[2], vector[3]]).to_string()
+}
+
+fn material(material: Material) -> String {
+    let fields = format!(
+        "albedo={} diffuse={} ambient={} specular={} ior={}",
+        vec4(material.albedo()),
+        vec3(material.diffuse_color()),
+        vec3(material.ambient_color()),
+        material.specular_exponent(),
+        material.refractive_index()
+    );
+
+    let fields = if material.disperses() {
+        format!("{fields} dispersion={}", material.dispersion())
+    } else {
+        fields
+    };
+
+    let fields = if material.reflect_roughness() > 0.0 {
+        format!(
+            "{fields} reflect_roughness={}",
+            material.reflect_roughness()
+        )
+    } else {
+        fields
+    };
+
+    let fields = if material.refract_roughness() > 0.0 {
+        format!(
+            "{fields} refract_roughness={}",
+            material.refract_roughness()
+        )
+    } else {
+        fields
+    };
+
+    let fields = if material.absorbs() {
+        format!("{fields} absorption={}", vec3(material.absorption()))
+    } else {
+        fields
+    };
+
+    let fields = match material.specular_model() {
+        Some(specular_model) => format!("{fields} specular_model={}", specular_model.name()),
+        None => fields,
+    };
+
+    let fields = match material.sidedness() {
+        Sidedness::Double => fields,
+        sidedness => format!("{fields} sides={}", sidedness.name()),
+    };
+
+    if let Some(waves) = material.waves() {
+        return format!(
+            "{fields} waves={}",
+            Components(&[waves.amplitude(), waves.wavelength(), waves.speed()])
+        );
+    }
+
+    fields
+}
+
+fn shape_material(shape_material: Material, binding: Option<&str>) -> String {
+    binding.map_or_else(
+        || material(shape_material),
+        |name| format!("material={name}"),
+    )
+}
+
+impl fmt::Display for SceneFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "format version={SCENE_FORMAT_VERSION}")?;
+        writeln!(
+            f,
+            "camera position={} fov={}",
+            vec3(self.camera.position()),
+            self.camera.fov()
+        )?;
+        if !self.overrides.is_empty() {
+            writeln!(f, "render {}", self.overrides)?;
+        }
+
+        for light in self.scene.lights() {
+            match light {
+                LightType::Ambient(light) => {
+                    writeln!(f, "ambient_light intensity={}", light.intensity())?;
+                }
+                LightType::Point(light) => writeln!(
+                    f,
+                    "point_light intensity={} position={}",
+                    light.intensity(),
+                    vec3(light.position())
+                )?,
+                LightType::Directional(light) => writeln!(
+                    f,
+                    "directional_light intensity={} direction={}",
+                    light.intensity(),
+                    vec3(light.direction())
+                )?,
+                LightType::Area(light) => writeln!(
+                    f,
+                    "area_light intensity={} corner={} u={} v={} two_sided={} samples={}",
+                    light.intensity(),
+                    vec3(light.corner()),
+                    vec3(light.edge_u()),
+                    vec3(light.edge_v()),
+                    light.two_sided(),
+                    light.samples()
+                )?,
+                LightType::Sky(light) => writeln!(
+                    f,
+                    "sky_light intensity={} zenith={} horizon={} ground={}",
+                    light.intensity(),
+                    vec3(light.zenith()),
+                    vec3(light.horizon()),
+                    vec3(light.ground())
+                )?,
+                LightType::SunSky(light) => writeln!(
+                    f,
+                    "sun_sky intensity={} elevation={} azimuth={} turbidity={}",
+                    light.scale(),
+                    light.sky().elevation(),
+                    light.sky().azimuth(),
+                    light.sky().turbidity()
+                )?,
+                LightType::Environment(light) => writeln!(
+                    f,
+                    "environment_light intensity={} path={} samples={}",
+                    light.intensity(),
+                    light.map().source().display(),
+                    light.samples()
+                )?,
+                LightType::Custom(_) => writeln!(f, "# custom light omitted")?,
+            }
+        }
+
+        for (name, registered) in self.scene.materials().iter() {
+            writeln!(f, "material name={name} {}", material(registered))?;
+        }
+
+        let mut prototypes = Vec::new();
+        for (shape, id) in self.scene.shapes().iter().zip(self.scene.shape_ids()) {
+            let binding = self.scene.material_binding(*id);
+            write_shape(f, shape, "", binding, &mut prototypes)?;
+        }
+
+        for volume in self.scene.volumes() {
+            let grid = match volume.grid().source() {
+                GridSource::Noise {
+                    resolution,
+                    frequency,
+                    seed,
+                } => format!("resolution={resolution} frequency={frequency} seed={seed}"),
+                GridSource::File(path) => format!("grid={}", path.display()),
+            };
+            writeln!(
+                f,
+                "volume min={} max={} density={} albedo={} {grid}",
+                vec3(volume.min_point()),
+                vec3(volume.max_point()),
+                volume.density(),
+                vec3(volume.albedo())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn transform(transform: Transform) -> String {
+    let [row0, row1, row2] = transform.rows();
+    let translation = transform.translation();
+    Components(&[
+        row0.x(),
+        row0.y(),
+        row0.z(),
+        row1.x(),
+        row1.y(),
+        row1.z(),
+        row2.x(),
+        row2.y(),
+        row2.z(),
+        translation.x(),
+        translation.y(),
+        translation.z(),
+    ])
+    .to_string()
+}
+
+fn write_mesh(
+    f: &mut fmt::Formatter<'_>,
+    mesh: &TriangleMesh,
+    prefix: &str,
+    binding: Option<&str>,
+) -> fmt::Result {
+    let material = shape_material(mesh.get_material(), binding);
+    match mesh.label() {
+        Some(label) => writeln!(
+            f,
+            "{prefix}text value={} position={} right={} up={} size={} depth={} {material}",
+            label.text().replace(' ', "_"),
+            vec3(label.position()),
+            vec3(label.right()),
+            vec3(label.up()),
+            label.size(),
+            label.depth(),
+        ),
+        None => writeln!(
+            f,
+            "{prefix}mesh path={} smooth={} {material}",
+            mesh.source().display(),
+            mesh.smooth(),
+        ),
+    }
+}
+
+fn write_heightfield(
+    f: &mut fmt::Formatter<'_>,
+    heightfield: &Heightfield,
+    prefix: &str,
+    binding: Option<&str>,
+) -> fmt::Result {
+    let grid = match heightfield.grid().source() {
+        HeightSource::Noise {
+            resolution,
+            frequency,
+            seed,
+        } => format!("resolution={resolution} frequency={frequency} seed={seed}"),
+        HeightSource::Image(path) => format!("image={}", path.display()),
+    };
+    writeln!(
+        f,
+        "{prefix}heightfield min={} size={} {grid} {}",
+        vec3(heightfield.min_point()),
+        vec3(heightfield.size()),
+        shape_material(heightfield.get_material(), binding)
+    )
+}
+
+fn write_billboard(
+    f: &mut fmt::Formatter<'_>,
+    billboard: &Billboard,
+    prefix: &str,
+    binding: Option<&str>,
+) -> fmt::Result {
+    let axis = match billboard.facing() {
+        Facing::Spherical => String::new(),
+        Facing::Cylindrical(axis) => format!(" axis={}", vec3(axis)),
+    };
+    let image = match billboard.sprite().source() {
+        SpriteSource::Glow => String::new(),
+        SpriteSource::Image(path) => format!(" image={}", path.display()),
+    };
+    writeln!(
+        f,
+        "{prefix}billboard center={} width={} height={}{axis}{image} {}",
+        vec3(billboard.center()),
+        billboard.width(),
+        billboard.height(),
+        shape_material(billboard.get_material(), binding)
+    )
+}
+
+fn write_quad(
+    f: &mut fmt::Formatter<'_>,
+    quad: &Quad,
+    prefix: &str,
+    binding: Option<&str>,
+) -> fmt::Result {
+    let image = match quad.texture().map(Sprite::source) {
+        Some(SpriteSource::Image(path)) => format!(" image={}", path.display()),
+        Some(SpriteSource::Glow) | None => String::new(),
+    };
+    writeln!(
+        f,
+        "{prefix}quad origin={} edge_u={} edge_v={}{image} {}",
+        vec3(quad.origin()),
+        vec3(quad.edge_u()),
+        vec3(quad.edge_v()),
+        shape_material(quad.get_material(), binding)
+    )
+}
+
+fn write_shape(
+    f: &mut fmt::Formatter<'_>,
+    shape: &ShapeType,
+    prefix: &str,
+    binding: Option<&str>,
+    prototypes: &mut Vec<*const ShapeType>,
+) -> fmt::Result {
+    match shape {
+        ShapeType::Sphere(sphere) => writeln!(
+            f,
+            "{prefix}sphere center={} radius={} {}",
+            vec3(sphere.center()),
+            sphere.radius(),
+            shape_material(sphere.get_material(), binding)
+        ),
+        ShapeType::BoxShape(box_shape) => writeln!(
+            f,
+            "{prefix}box min={} max={} {}",
+            vec3(box_shape.min_point()),
+            vec3(box_shape.max_point()),
+            shape_material(box_shape.get_material(), binding)
+        ),
+        ShapeType::OrientedBox(oriented) => writeln!(
+            f,
+            "{prefix}oriented_box center={} half_extents={} axis={} angle={} {}",
+            vec3(oriented.center()),
+            vec3(oriented.half_extents()),
+            vec3(oriented.axis()),
+            oriented.angle(),
+            shape_material(oriented.get_material(), binding)
+        ),
+        ShapeType::InfinityPlane(plane) => writeln!(
+            f,
+            "{prefix}plane position={} normal={} {}",
+            vec3(plane.position()),
+            vec3(plane.normal()),
+            shape_material(plane.get_material(), binding)
+        ),
+        ShapeType::Mesh(mesh) => write_mesh(f, mesh, prefix, binding),
+        ShapeType::Sdf(sdf) => writeln!(
+            f,
+            "{prefix}sdf expression={} {}",
+            sdf.sdf(),
+            shape_material(sdf.get_material(), binding)
+        ),
+        ShapeType::Heightfield(heightfield) => write_heightfield(f, heightfield, prefix, binding),
+        ShapeType::Prism(prism) => writeln!(
+            f,
+            "{prefix}prism polygon={} base={} axis={} height={} {}",
+            prism.polygon(),
+            vec3(prism.base()),
+            vec3(prism.axis()),
+            prism.height(),
+            shape_material(prism.get_material(), binding)
+        ),
+        ShapeType::Billboard(billboard) => write_billboard(f, billboard, prefix, binding),
+        ShapeType::Quad(quad) => write_quad(f, quad, prefix, binding),
+        ShapeType::Instance(instance) => {
+            let pointer = Arc::as_ptr(instance.shape());
+            let index = if let Some(index) = prototypes.iter().position(|&p| p == pointer) {
+                index
+            } else {
+                prototypes.push(pointer);
+                let index = prototypes.len() - 1;
+                let prefix = format!("prototype name=p{index} shape=");
+                write_shape(f, instance.shape(), &prefix, None, prototypes)?;
+                index
+            };
+
+            writeln!(
+                f,
+                "{prefix}instance prototype=p{index} transform={}",
+                transform(instance.transform())
+            )
+        }
+        ShapeType::Custom(_) => writeln!(f, "# custom shape omitted"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        FOV,
+        integrator::Integrator,
+        materials::{GLASS_MATERIAL, GOLD_MATERIAL},
+        settings::RenderSettings,
+    };
+
+    fn assert_round_trip(scene_file: &SceneFile) -> Result<()> {
+        let saved = scene_file.to_string();
+        let loaded: SceneFile = saved.parse()?;
+        let saved_again = loaded.to_string();
+        let loaded_again: SceneFile = saved_again.parse()?;
+
+        assert_eq!(saved, saved_again);
+        assert_eq!(loaded, loaded_again);
+        assert_eq!(&loaded, scene_file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_scene_round_trips() -> Result<()> {
+        assert_round_trip(&SceneFile::default())
+    }
+
+    /// One of every shape, built from values that only survive exact float formatting.
+    fn awkward_shapes(awkward: Vec3f) -> Result<Vec<ShapeType>> {
+        Ok(vec![
+            ShapeType::Sphere(Sphere::new(awkward, f64::MIN_POSITIVE, GOLD_MATERIAL)),
+            ShapeType::BoxShape(BoxShape::new(
+                Vec3f::new_with_data([1.0 / 3.0, 2.0, 3.0]),
+                Vec3f::new_with_data([-1.0, -2.0, -3.0]),
+                GLASS_MATERIAL
+                    .with_dispersion(0.1 + 0.2)
+                    .with_refract_roughness(1.0 / 3.0)
+                    .with_absorption(Vec3f::new_with_data([0.5, 0.1, 1.0 / 3.0]))
+                    .with_specular_model(SpecularModel::Ggx)
+                    .with_sidedness(Sidedness::Culled),
+            )),
+            ShapeType::OrientedBox(OrientedBox::new(
+                awkward,
+                Vec3f::new_with_data([0.5, 1.0 / 3.0, 2.0]),
+                Vec3f::new_with_data([0.3, 0.7, -0.2]),
+                0.1 + 0.2,
+                GOLD_MATERIAL,
+            )),
+            ShapeType::InfinityPlane(InfinityPlane::new(
+                Vec3f::new_with_data([0.0, -2.9, 0.0]),
+                Vec3f::new_with_data([0.3, 0.7, -0.2]),
+                GOLD_MATERIAL
+                    .with_reflect_roughness(1.0 / 3.0)
+                    .with_waves(Waves::new(0.1 + 0.2, 1.0 / 3.0, -0.5)),
+            )),
+            ShapeType::Sdf(SdfShape::new(
+                Sdf::smooth_union(
+                    0.1 + 0.2,
+                    Sdf::Torus {
+                        center: awkward,
+                        major_radius: 1.0 / 3.0,
+                        minor_radius: 0.1,
+                    },
+                    Sdf::Box {
+                        center: Vec3f::new(-0.0),
+                        half_extent: Vec3f::new_with_data([0.5, 1e-3, 2.0]),
+                    },
+                ),
+                GLASS_MATERIAL,
+            )),
+            ShapeType::Sdf(SdfShape::new(
+                Sdf::RoundedBox {
+                    center: awkward,
+                    half_extent: Vec3f::new_with_data([0.5, 1.0 / 3.0, 2.0]),
+                    radius: 0.1 + 0.2,
+                },
+                GOLD_MATERIAL.with_specular_model(SpecularModel::Ggx),
+            )),
+            ShapeType::Heightfield(Heightfield::new(
+                awkward,
+                Vec3f::new_with_data([4.0, 1.0 / 3.0, 2.5]),
+                HeightGrid::noise(5, 0.1 + 0.2, 3),
+                GOLD_MATERIAL,
+            )),
+            ShapeType::Prism(Prism::new(
+                "0,0;1,0;0.5,1|0.4,0.2;0.5,0.6;0.6,0.2".parse()?,
+                awkward,
+                Vec3f::new_with_data([0.3, 0.7, -0.2]),
+                1.0 / 3.0,
+                GLASS_MATERIAL,
+            )?),
+            ShapeType::Quad(Quad::new(
+                awkward,
+                Vec3f::new_with_data([0.1 + 0.2, 0.0, 1.0 / 3.0]),
+                Vec3f::new_with_data([0.0, 2.0, -0.0]),
+                GOLD_MATERIAL.with_sidedness(Sidedness::Single),
+            )?),
+            ShapeType::Billboard(Billboard::new(
+                awkward,
+                0.1 + 0.2,
+                2.0,
+                Facing::Cylindrical(Vec3f::new_with_data([0.0, 1.0, 1.0 / 3.0])),
+                Sprite::glow(),
+                GLASS_MATERIAL,
+            )?),
+        ])
+    }
+
+    /// One of every light, built from the same awkward values as [`awkward_shapes`].
+    fn awkward_lights(awkward: Vec3f) -> Result<Vec<LightType>> {
+        Ok(vec![
+            LightType::Ambient(AmbientLight::new(1.0 / 7.0)),
+            LightType::Point(PointLight::new(1e10, awkward)),
+            LightType::Directional(DirectionalLight::new(
+                0.5,
+                Vec3f::new_with_data([1.0, 2.0, 3.0]),
+            )),
+            LightType::Area(
+                AreaLight::new(
+                    1.5,
+                    awkward,
+                    Vec3f::new_with_data([1.0 / 3.0, 0.0, 0.0]),
+                    Vec3f::new_with_data([0.0, 0.0, 0.7]),
+                )
+                .with_two_sided(true)
+                .with_samples(9),
+            ),
+            LightType::Sky(SkyLight::new(
+                0.9,
+                Vec3f::new_with_data([0.2, 0.4, 1.0 / 3.0]),
+                awkward,
+                Vec3f::new(0.1),
+            )),
+            LightType::SunSky(Box::new(SunSkyLight::new(
+                0.7,
+                SunSky::new(100.0 / 3.0, -123.456, 2.0 + 0.1)?,
+            ))),
+        ])
+    }
+
+    #[test]
+    fn every_shape_and_light_round_trips_bit_for_bit() -> Result<()> {
+        let awkward = Vec3f::new_with_data([0.1 + 0.2, -0.0, 1e-300]);
+        let scene =
+            Scene::new(awkward_shapes(awkward)?, awkward_lights(awkward)?).with_volumes(vec![
+                Volume::new(
+                    awkward,
+                    Vec3f::new(1.0),
+                    DensityGrid::noise(4, 1.0 / 3.0, 11),
+                )
+                .with_density(2.5)
+                .with_albedo(Vec3f::new_with_data([0.9, 0.8, 0.1 + 0.2])),
+            ]);
+        let scene_file = SceneFile::new(scene, Camera::new(awkward, 0.123_456_789));
+
+        assert_round_trip(&scene_file)?;
+
+        let loaded: SceneFile = scene_file.to_string().parse()?;
+        let ShapeType::Sphere(sphere) = &loaded.scene().shapes()[0] else {
+            panic!("first shape should be a sphere");
+        };
+        assert_eq!(sphere.center().y().to_bits(), (-0.0_f64).to_bits());
+        assert_eq!(sphere.center().x().to_bits(), (0.1_f64 + 0.2).to_bits());
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_labels_round_trip_as_text_records() -> Result<()> {
+        let label = TextLabel::new(
+            "HELLO WORLD",
+            Vec3f::new_with_data([0.1 + 0.2, -1.0, -6.0]),
+            Vec3f::new_with_data([1.0, 0.0, 1.0 / 3.0]),
+            Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            0.5,
+        )
+        .with_depth(0.1);
+        let scene = Scene::new(
+            vec![ShapeType::Mesh(label.mesh(GOLD_MATERIAL)?)],
+            Vec::new(),
+        );
+        let scene_file = SceneFile::new(scene, Camera::new(Vec3f::new(0.0), FOV));
+
+        assert_round_trip(&scene_file)?;
+        assert!(
+            scene_file
+                .to_string()
+                .contains("text value=HELLO_WORLD position=")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn instances_round_trip_with_a_shared_prototype() -> Result<()> {
+        let prototype = Arc::new(ShapeType::Sphere(Sphere::new(
+            Vec3f::new(0.0),
+            0.5,
+            GOLD_MATERIAL,
+        )));
+        let nested = Arc::new(ShapeType::Instance(Instance::new(
+            Arc::clone(&prototype),
+            Transform::from_rotation(Vec3f::new_with_data([1.0, 1.0, 0.0]), 0.3),
+        )));
+        let shapes = [-1.0, 1.0 / 3.0, 2.0]
+            .map(|x| {
+                let transform = Transform::from_translation(Vec3f::new_with_data([x, 0.0, -4.0]));
+                ShapeType::Instance(Instance::new(Arc::clone(&prototype), transform))
+            })
+            .into_iter()
+            .chain([ShapeType::Instance(Instance::new(
+                nested,
+                Transform::from_translation(Vec3f::new(1.0)),
+            ))])
+            .collect();
+        let scene_file = SceneFile::new(
+            Scene::new(shapes, Vec::new()),
+            Camera::new(Vec3f::new(0.0), FOV),
+        );
+
+        assert_round_trip(&scene_file)?;
+
+        let saved = scene_file.to_string();
+        assert_eq!(saved.matches("prototype name=").count(), 2);
+
+        let loaded: SceneFile = saved.parse()?;
+        let shared: Vec<_> = loaded
+            .scene()
+            .shapes()
+            .iter()
+            .filter_map(|shape| match shape {
+                ShapeType::Instance(instance) => Some(Arc::as_ptr(instance.shape())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(shared[0], shared[1]);
+        assert_eq!(shared[1], shared[2]);
+
+        assert!(
+            "camera position=0,0,0 fov=1\ninstance prototype=missing transform=1,0,0,0,1,0,0,0,1,0,0,0"
+                .parse::<SceneFile>()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_scene_snapshot() {
+        let expected = "\
+format version=2
+camera position=0,0,2 fov=1.0471975511965976
+ambient_light intensity=0.1
+directional_light intensity=2 direction=-0.5773502691896258,-0.5773502691896258,-0.5773502691896258
+point_light intensity=2 position=2,5,0
+point_light intensity=0.5 position=-1,-1,5
+sphere center=0,-1,-7 radius=2 albedo=0.6,0.3,0,0.1 diffuse=1,0.1,0.1 ambient=0.2,0.05,0.05 specular=250 ior=1
+sphere center=2,0,-4 radius=1 albedo=0.6,0.3,0,0.1 diffuse=0.1,1,0.1 ambient=0.05,0.2,0.05 specular=125 ior=1
+sphere center=-2,1,-5 radius=1.5 albedo=0.6,0.3,0,0.1 diffuse=0.1,0.1,1 ambient=0.05,0.05,0.2 specular=125 ior=1
+sphere center=-0.5,-0.75,-2 radius=0.25 albedo=0,0.5,0.1,0.8 diffuse=0.6,0.7,0.8 ambient=0.1,0.1,0.2 specular=300 ior=1.5
+sphere center=0.5,1.5,-3.5 radius=0.4 albedo=0,0,0.9,0.03 diffuse=1,1,1 ambient=0,0,0 specular=1000 ior=1
+plane position=0,-2.9,0 normal=0,-0.9950371902099892,-0.09950371902099892 albedo=0,0,0.9,0.03 diffuse=1,1,1 ambient=0,0,0 specular=1000 ior=1
+";
+
+        assert_eq!(SceneFile::default().to_string(), expected);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_records() {
+        let unknown_field = "camera position=0,0,0 fov=1 zoom=2";
+        assert!(unknown_field.parse::<SceneFile>().is_err());
+
+        let unknown_record = "camera position=0,0,0 fov=1\ncone radius=1";
+        assert!(unknown_record.parse::<SceneFile>().is_err());
+
+        let missing_camera = "ambient_light intensity=0.1";
+        assert!(missing_camera.parse::<SceneFile>().is_err());
+
+        let wrong_arity = "camera position=0,0 fov=1";
+        assert!(wrong_arity.parse::<SceneFile>().is_err());
+    }
+
+    #[test]
+    fn render_overrides_round_trip_and_yield_to_cli_flags() -> Result<()> {
+        let source = "camera position=0,0,2 fov=1\n\
+                      render max_depth=6 epsilon=0.0001 max_distance=5000 background=0,0,0.5 clamp=10 \
+                      integrator=path fog_density=0.05 fog_color=0.5,0.5,0.5 specular_model=blinn\n";
+        let loaded: SceneFile = source.parse()?;
+        assert_round_trip(&loaded)?;
+
+        let cli =
+            RenderSettings::from_sources(["--integrator".to_owned(), "toon".to_owned()], |_| None)?;
+        let settings = cli.with_scene_overrides(loaded.overrides());
+        assert_eq!(settings.integrator(), Integrator::Toon);
+        assert_eq!(settings.max_depth(), Some(6));
+        assert_eq!(settings.max_distance(), Some(5000.0));
+        assert_eq!(settings.clamp(), Some(10.0));
+        assert_eq!(settings.fog_density(), Some(0.05));
+        assert_eq!(settings.specular_model(), Some(SpecularModel::BlinnPhong));
+        assert_eq!(
+            settings.background(),
+            Some(Vec3f::new_with_data([0.0, 0.0, 0.5]))
+        );
+
+        let invalid = "camera position=0,0,2 fov=1\nrender epsilon=-1";
+        assert!(invalid.parse::<SceneFile>().is_err());
+        let unclipped = "camera position=0,0,2 fov=1\nrender max_distance=nan";
+        assert!(unclipped.parse::<SceneFile>().is_err());
+        let duplicate = "camera position=0,0,2 fov=1\nrender clamp=1\nrender clamp=2";
+        assert!(duplicate.parse::<SceneFile>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scenes_pick_tone_mapping_and_a_lut_next_to_them() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("scene-lut-{}", std::process::id()));
+        fs::create_dir_all(&directory)?;
+        fs::write(
+            directory.join("flat.cube"),
+            format!("LUT_3D_SIZE 2\n{}", "0.5 0.5 0.5\n".repeat(8)),
+        )?;
+        let path = directory.join("graded.scene");
+        fs::write(
+            &path,
+            "camera position=0,0,2 fov=1\nrender tone_map=reinhard lut=flat.cube\n",
+        )?;
+
+        let loaded = SceneFile::load(&path)?;
+        assert!(
+            loaded
+                .to_string()
+                .contains("render tone_map=reinhard lut=flat.cube")
+        );
+        let settings = RenderSettings::default().with_scene_overrides(loaded.overrides());
+        assert_eq!(settings.post().apply(Vec3f::new(0.9)), Vec3f::new(0.5));
+
+        let cli =
+            RenderSettings::from_sources(["--tone-map".to_owned(), "clamp".to_owned()], |_| None)?;
+        let mut overrides = RenderOverrides::NONE;
+        overrides.set("tone_map", "reinhard")?;
+        let settings = cli.with_scene_overrides(&overrides);
+        assert_eq!(settings.post().apply(Vec3f::new(1.0)), Vec3f::new(1.0));
+
+        fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+
+    #[test]
+    fn named_materials_are_shared_and_round_trip() -> Result<()> {
+        let source = "camera position=0,0,2 fov=1\n\
+                      material name=paint albedo=1,0,0,0 diffuse=0.5,0.5,0.5 ambient=0,0,0 specular=1 ior=1\n\
+                      sphere center=0,0,-5 radius=1 material=paint\n\
+                      sphere center=2,0,-5 radius=1 material=paint\n\
+                      sphere center=4,0,-5 radius=1 material=gold\n";
+        let mut loaded: SceneFile = source.parse()?;
+        assert_round_trip(&loaded)?;
+        assert_eq!(loaded.scene().shapes()[2].get_material(), GOLD_MATERIAL);
+
+        loaded.scene.set_material("paint", GLASS_MATERIAL);
+        let materials: Vec<_> = loaded
+            .scene()
+            .shapes()
+            .iter()
+            .map(Shape::get_material)
+            .collect();
+        assert_eq!(materials, [GLASS_MATERIAL, GLASS_MATERIAL, GOLD_MATERIAL]);
+        assert_round_trip(&loaded)?;
+
+        let unknown = "camera position=0,0,2 fov=1\nsphere center=0,0,0 radius=1 material=nope";
+        assert!(unknown.parse::<SceneFile>().is_err());
+        let mixed = "camera position=0,0,2 fov=1\nsphere center=0,0,0 radius=1 material=gold ior=2";
+        assert!(mixed.parse::<SceneFile>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rounded_boxes_load_as_sdf_shapes() -> Result<()> {
+        let source = "camera position=0,0,0 fov=1\nrounded_box center=0,0,-4 half_extents=1,0.5,2 radius=0.25 albedo=0.6,0.3,0,0.1 diffuse=1,1,1 ambient=0.1,0.1,0.1 specular=250 ior=1\n";
+        let loaded: SceneFile = source.parse()?;
+
+        let [ShapeType::Sdf(shape)] = loaded.scene().shapes() else {
+            panic!("rounded box did not load as one SDF shape");
+        };
+        assert_eq!(shape.sdf().to_string(), "rounded_box(0,0,-4,1,0.5,2,0.25)");
+        assert_round_trip(&loaded)
+    }
+
+    #[test]
+    fn unversioned_scene_is_migrated() -> Result<()> {
+        let legacy = "camera position=0,0,2 fov=1\nambient_light intensity=0.1\n";
+        let loaded: SceneFile = legacy.parse()?;
+
+        assert_eq!(loaded.scene().lights().len(), 1);
+        let header = format!("format version={SCENE_FORMAT_VERSION}\n");
+        assert!(loaded.to_string().starts_with(&header));
+
+        Ok(())
+    }
+
+    #[test]
+    fn every_older_version_has_a_migration() -> Result<()> {
+        for version in 0..SCENE_FORMAT_VERSION {
+            assert!(
+                MIGRATIONS.iter().any(|migration| migration.from == version),
+                "no migration from scene format version {version}"
+            );
+        }
+
+        let first = "format version=1\ncamera position=0,0,2 fov=1\nambient_light intensity=0.1\n";
+        let loaded: SceneFile = first.parse()?;
+        assert_eq!(loaded.scene().lights().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_newer_and_misplaced_versions() {
+        let newer = format!(
+            "format version={}\ncamera position=0,0,0 fov=1",
+            SCENE_FORMAT_VERSION + 1
+        );
+        assert!(newer.parse::<SceneFile>().is_err());
+
+        let misplaced = "camera position=0,0,0 fov=1\nformat version=1";
+        assert!(misplaced.parse::<SceneFile>().is_err());
+    }
+
+    #[test]
+    fn mesh_paths_resolve_against_the_scene_directory() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("mesh-scene-{}", std::process::id()));
+        fs::create_dir_all(directory.join("models"))?;
+        fs::write(
+            directory.join("models/triangle.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )?;
+
+        let mesh_line = format!(
+            "mesh path=models/triangle.obj smooth=false {}",
+            material(GOLD_MATERIAL)
+        );
+        let scene_path = directory.join("scene.txt");
+        fs::write(
+            &scene_path,
+            format!("camera position=0,0,2 fov=1\n{mesh_line}\n"),
+        )?;
+
+        let loaded = SceneFile::load(&scene_path)?;
+        fs::remove_dir_all(&directory)?;
+
+        let ShapeType::Mesh(mesh) = &loaded.scene().shapes()[0] else {
+            bail!("expected a mesh");
+        };
+        assert!(!mesh.smooth());
+        assert!(loaded.to_string().contains(&mesh_line));
+
+        Ok(())
+    }
+
+    #[test]
+    fn environment_maps_load_next_to_the_scene_and_round_trip() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("environment-scene-{}", std::process::id()));
+        fs::create_dir_all(&directory)?;
+        let mut image = b"#?RADIANCE\n\n-Y 1 +X 2\n".to_vec();
+        image.extend([128, 128, 128, 129, 64, 64, 64, 129]);
+        fs::write(directory.join("sky.hdr"), image)?;
+
+        let light_line = "environment_light intensity=2 path=sky.hdr samples=4";
+        let scene_path = directory.join("scene.txt");
+        fs::write(
+            &scene_path,
+            format!("camera position=0,0,2 fov=1\n{light_line}\n"),
+        )?;
+
+        let loaded = SceneFile::load(&scene_path)?;
+        fs::remove_dir_all(&directory)?;
+
+        let [LightType::Environment(environment)] = loaded.scene().lights() else {
+            bail!("expected one environment light");
+        };
+        assert_eq!(
+            (environment.map().width(), environment.map().height()),
+            (2, 1)
+        );
+        assert_eq!(environment.samples(), 4);
+        assert!(loaded.to_string().contains(light_line));
+
+        Ok(())
+    }
+}
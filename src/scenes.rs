@@ -0,0 +1,431 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    FOV, GLASS_MATERIAL, MIRROR_MATERIAL, Vec3f, Vec4f,
+    camera::Camera,
+    lights::{
+        AmbientLight, AreaLight, DirectionalLight, LightType, PointLight, SunSkyLight,
+        init_default_lights,
+    },
+    materials::{GOLD_MATERIAL, Material},
+    quad::Quad,
+    scene::Scene,
+    sdf::{Sdf, SdfShape},
+    shapes::{InfinityPlane, OrientedBox, ShapeType, Sphere, init_default_shapes},
+    sky::SunSky,
+};
+
+const AMBIENT: Vec3f = Vec3f::const_new_with_data([0.05, 0.05, 0.05]);
+const CORNELL_LIGHT_SAMPLES: u32 = 16;
+const FIELD_SEED: u64 = 1;
+const FIELD_COUNT: usize = 400;
+const FIELD_SPACING: f64 = 1.0;
+const FEATURE_RADIUS: f64 = 1.0;
+const DIFFUSE_SHARE: f64 = 0.8;
+const METAL_SHARE: f64 = 0.15;
+const FIELD_SUN_ELEVATION: f64 = 55.0;
+const FIELD_SUN_AZIMUTH: f64 = 45.0;
+const FIELD_TURBIDITY: f64 = 3.0;
+
+const fn lambert(color: Vec3f) -> Material {
+    Material::new(
+        Vec4f::const_new_with_data([1.0, 0.0, 0.0, 0.0]),
+        color,
+        AMBIENT,
+        1.0,
+        1.0,
+    )
+}
+
+pub const CORNELL_WHITE: Material = lambert(Vec3f::const_new_with_data([0.73, 0.73, 0.73]));
+pub const CORNELL_RED: Material = lambert(Vec3f::const_new_with_data([0.65, 0.05, 0.05]));
+pub const CORNELL_GREEN: Material = lambert(Vec3f::const_new_with_data([0.12, 0.45, 0.15]));
+const FLOOR: Material = lambert(Vec3f::const_new_with_data([0.8, 0.8, 0.75]));
+
+/// The scenes that can be rendered without a scene file, picked with `--scene builtin:NAME`
+/// or the number keys in the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuiltinScene {
+    Spheres,
+    Glass,
+    MirrorRoom,
+    Field,
+    Cornell,
+}
+
+impl BuiltinScene {
+    /// Every preset in number-key order, so `ALL[0]` is on key 1.
+    pub const ALL: [Self; 5] = [
+        Self::Spheres,
+        Self::Glass,
+        Self::MirrorRoom,
+        Self::Field,
+        Self::Cornell,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "spheres" => Ok(Self::Spheres),
+            "glass" => Ok(Self::Glass),
+            "mirror_room" => Ok(Self::MirrorRoom),
+            "field" => Ok(Self::Field),
+            "cornell" => Ok(Self::Cornell),
+            _ => bail!("Unknown built-in scene: {name}"),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Spheres => "spheres",
+            Self::Glass => "glass",
+            Self::MirrorRoom => "mirror_room",
+            Self::Field => "field",
+            Self::Cornell => "cornell",
+        }
+    }
+
+    pub fn build(self) -> Result<(Scene, Camera)> {
+        match self {
+            Self::Spheres => Ok(spheres()),
+            Self::Glass => Ok(glass_showcase()),
+            Self::MirrorRoom => mirror_room(),
+            Self::Field => random_spheres(FIELD_SEED, FIELD_COUNT),
+            Self::Cornell => cornell_box(),
+        }
+    }
+}
+
+const fn vec3(x: f64, y: f64, z: f64) -> Vec3f {
+    Vec3f::new_with_data([x, y, z])
+}
+
+fn floor(height: f64) -> ShapeType {
+    ShapeType::InfinityPlane(InfinityPlane::new(
+        vec3(0.0, height, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        FLOOR,
+    ))
+}
+
+fn wall(origin: Vec3f, edge_u: Vec3f, edge_v: Vec3f, material: Material) -> Result<ShapeType> {
+    Ok(ShapeType::Quad(Quad::new(
+        origin, edge_u, edge_v, material,
+    )?))
+}
+
+/// The default scene: three colored spheres, a glass ball and a mirror over a mirror floor.
+pub fn spheres() -> (Scene, Camera) {
+    (
+        Scene::new(init_default_shapes(), init_default_lights()),
+        Camera::new(vec3(0.0, 0.0, 2.0), FOV),
+    )
+}
+
+/// Clear, tinted and rounded glass in front of colored spheres, to show off refraction.
+pub fn glass_showcase() -> (Scene, Camera) {
+    let tinted = GLASS_MATERIAL.with_absorption(vec3(0.6, 0.05, 0.4));
+    let flint = GLASS_MATERIAL.with_dispersion(0.015);
+    let shapes = vec![
+        floor(-1.0),
+        ShapeType::Sphere(Sphere::new(vec3(0.0, 0.0, -6.0), 1.0, GLASS_MATERIAL)),
+        ShapeType::Sphere(Sphere::new(vec3(-2.3, -0.3, -5.5), 0.7, tinted)),
+        ShapeType::Sdf(SdfShape::new(
+            Sdf::RoundedBox {
+                center: vec3(2.3, -0.4, -5.5),
+                half_extent: vec3(0.6, 0.6, 0.6),
+                radius: 0.15,
+            },
+            flint,
+        )),
+        ShapeType::Sphere(Sphere::new(vec3(-1.0, -0.4, -10.0), 0.6, CORNELL_RED)),
+        ShapeType::Sphere(Sphere::new(vec3(1.2, -0.4, -10.0), 0.6, CORNELL_GREEN)),
+        ShapeType::Sphere(Sphere::new(vec3(0.0, 0.5, -12.0), 1.5, GOLD_MATERIAL)),
+    ];
+    let lights = vec![
+        LightType::Ambient(AmbientLight::new(0.1)),
+        LightType::Point(PointLight::new(2.0, vec3(2.0, 5.0, 0.0))),
+        LightType::Directional(DirectionalLight::new(
+            1.0,
+            vec3(1.0, -1.0, -1.0).normalize(None),
+        )),
+    ];
+
+    (
+        Scene::new(shapes, lights),
+        Camera::new(vec3(0.0, 0.3, 0.0), FOV),
+    )
+}
+
+/// A corridor between two facing mirrors, so the spheres inside repeat off into the distance.
+pub fn mirror_room() -> Result<(Scene, Camera)> {
+    let up = vec3(0.0, 4.0, 0.0);
+    let deep = vec3(0.0, 0.0, -10.0);
+    let shapes = vec![
+        floor(-1.0),
+        wall(vec3(-3.0, -1.0, -2.0), deep, up, MIRROR_MATERIAL)?,
+        wall(vec3(3.0, -1.0, -2.0), up, deep, MIRROR_MATERIAL)?,
+        wall(vec3(-3.0, -1.0, -12.0), vec3(6.0, 0.0, 0.0), up, FLOOR)?,
+        ShapeType::Sphere(Sphere::new(vec3(-1.2, -0.4, -6.0), 0.6, CORNELL_RED)),
+        ShapeType::Sphere(Sphere::new(vec3(0.0, -0.2, -8.0), 0.8, GLASS_MATERIAL)),
+        ShapeType::Sphere(Sphere::new(vec3(1.3, -0.5, -5.0), 0.5, CORNELL_GREEN)),
+    ];
+    let lights = vec![
+        LightType::Ambient(AmbientLight::new(0.1)),
+        LightType::Point(PointLight::new(2.0, vec3(0.0, 2.5, -5.0))),
+    ];
+
+    Ok((
+        Scene::new(shapes, lights),
+        Camera::new(vec3(0.0, 0.5, 0.0), FOV),
+    ))
+}
+
+/// A small seeded generator, so a random scene is the same every time it is built from
+/// the same seed.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut mixed = self.state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^= mixed >> 31;
+
+        #[allow(clippy::cast_precision_loss)]
+        let unit = (mixed >> 11) as f64 / (1_u64 << 53) as f64;
+        unit
+    }
+
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        self.next().mul_add(max - min, min)
+    }
+
+    fn color(&mut self, min: f64, max: f64) -> Vec3f {
+        vec3(
+            self.range(min, max),
+            self.range(min, max),
+            self.range(min, max),
+        )
+    }
+
+    fn material(&mut self) -> Material {
+        let choice = self.next();
+        if choice < DIFFUSE_SHARE {
+            lambert(self.color(0.0, 1.0).hadamard(self.color(0.0, 1.0)))
+        } else if choice < DIFFUSE_SHARE + METAL_SHARE {
+            metal(self.color(0.5, 1.0), self.range(100.0, 1000.0))
+        } else {
+            GLASS_MATERIAL
+        }
+    }
+}
+
+const fn metal(color: Vec3f, specular: f64) -> Material {
+    Material::new(
+        Vec4f::const_new_with_data([0.2, 0.4, 0.7, 0.0]),
+        color,
+        AMBIENT,
+        specular,
+        1.0,
+    )
+}
+
+/// The cells of a square grid `side` cells across, centered on the origin, nearest first.
+fn field_cells(side: usize) -> Vec<Vec3f> {
+    #[allow(clippy::cast_precision_loss)]
+    let offset = |index: usize| (index as f64 - (side as f64 - 1.0) / 2.0) * FIELD_SPACING;
+    let mut cells: Vec<_> = (0..side * side)
+        .map(|cell| vec3(offset(cell % side), 0.0, offset(cell / side)))
+        .collect();
+    cells.sort_by(|a, b| a.length().total_cmp(&b.length()));
+    cells
+}
+
+/// The "Ray Tracing in One Weekend" cover.
+///
+/// `count` small spheres with random sizes and a mix of diffuse, metal and glass materials
+/// are scattered over a floor around three large feature spheres. Each small sphere gets
+/// its own grid cell, so none of them overlap. A sun-and-sky light supplies the afternoon
+/// sun, the sky behind the spheres and the skylight filling their shadows.
+pub fn random_spheres(seed: u64, count: usize) -> Result<(Scene, Camera)> {
+    let features = [
+        (vec3(-4.0, 1.0, 0.0), lambert(vec3(0.4, 0.2, 0.1))),
+        (vec3(0.0, 1.0, 0.0), GLASS_MATERIAL),
+        (vec3(4.0, 1.0, 0.0), metal(vec3(0.7, 0.6, 0.5), 1000.0)),
+    ];
+    let clear_of_features = |cell: &Vec3f| {
+        features.iter().all(|&(center, _)| {
+            (*cell - vec3(center.x(), 0.0, center.z())).length()
+                > FIELD_SPACING.mul_add(0.5, FEATURE_RADIUS)
+        })
+    };
+
+    let mut side = 1;
+    let cells = loop {
+        let cells: Vec<_> = field_cells(side)
+            .into_iter()
+            .filter(clear_of_features)
+            .collect();
+        if cells.len() >= count {
+            break cells;
+        }
+        side += 1;
+    };
+
+    let mut random = Random::new(seed);
+    let mut shapes = vec![floor(0.0)];
+    shapes.extend(features.map(|(center, material)| {
+        ShapeType::Sphere(Sphere::new(center, FEATURE_RADIUS, material))
+    }));
+    for cell in cells.into_iter().take(count) {
+        let radius = random.range(0.12, 0.25);
+        let slack = FIELD_SPACING.mul_add(0.5, -radius);
+        let center = cell
+            + vec3(
+                random.range(-slack, slack),
+                radius,
+                random.range(-slack, slack),
+            );
+        shapes.push(ShapeType::Sphere(Sphere::new(
+            center,
+            radius,
+            random.material(),
+        )));
+    }
+
+    let sky = SunSky::new(FIELD_SUN_ELEVATION, FIELD_SUN_AZIMUTH, FIELD_TURBIDITY)?;
+    let lights = vec![LightType::SunSky(Box::new(SunSkyLight::new(2.5, sky)))];
+
+    Ok((
+        Scene::new(shapes, lights),
+        Camera::new(vec3(13.0, 2.0, 3.0), FOV / 3.0)
+            .oriented(vec3(-13.0, -2.0, -3.0), vec3(0.0, 1.0, 0.0)),
+    ))
+}
+
+/// The classic Cornell box.
+///
+/// A white room two units across with a red left wall, a green right wall, a square area
+/// light under the ceiling and two turned white boxes. The open side faces a camera at the
+/// origin.
+pub fn cornell_box() -> Result<(Scene, Camera)> {
+    let across = vec3(2.0, 0.0, 0.0);
+    let up = vec3(0.0, 2.0, 0.0);
+    let deep = vec3(0.0, 0.0, -2.0);
+    let front = -1.6;
+
+    let shapes = vec![
+        wall(vec3(-1.0, -1.0, front), across, deep, CORNELL_WHITE)?,
+        wall(vec3(-1.0, 1.0, front), deep, across, CORNELL_WHITE)?,
+        wall(vec3(-1.0, -1.0, front - 2.0), across, up, CORNELL_WHITE)?,
+        wall(vec3(-1.0, -1.0, front), deep, up, CORNELL_RED)?,
+        wall(vec3(1.0, -1.0, front), up, deep, CORNELL_GREEN)?,
+        ShapeType::OrientedBox(OrientedBox::new(
+            vec3(-0.35, -0.4, front - 1.3),
+            vec3(0.3, 0.6, 0.3),
+            vec3(0.0, 1.0, 0.0),
+            0.3,
+            CORNELL_WHITE,
+        )),
+        ShapeType::OrientedBox(OrientedBox::new(
+            vec3(0.35, -0.7, front - 0.7),
+            vec3(0.3, 0.3, 0.3),
+            vec3(0.0, 1.0, 0.0),
+            -0.3,
+            CORNELL_WHITE,
+        )),
+    ];
+    let lights = vec![
+        LightType::Ambient(AmbientLight::new(0.1)),
+        LightType::Area(
+            AreaLight::new(
+                2.0,
+                vec3(-0.25, 0.99, front - 1.25),
+                vec3(0.5, 0.0, 0.0),
+                vec3(0.0, 0.0, 0.5),
+            )
+            .with_samples(CORNELL_LIGHT_SAMPLES),
+        ),
+    ];
+
+    Ok((
+        Scene::new(shapes, lights).with_background(Vec3f::new(0.0)),
+        Camera::new(Vec3f::new(0.0), FOV),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::Shape;
+
+    #[test]
+    fn cornell_box_shows_colored_side_walls_and_a_lit_room() -> Result<()> {
+        let (scene, camera) = BuiltinScene::from_name("cornell")?.build()?;
+        let (width, height) = (64, 64);
+        let material_at = |x: f64, y: f64| {
+            scene
+                .pick(x, y, width, height, &camera)
+                .and_then(|id| scene.get_shape(id))
+                .map(Shape::get_material)
+        };
+
+        assert_eq!(material_at(1.0, 32.0), Some(CORNELL_RED));
+        assert_eq!(material_at(63.0, 32.0), Some(CORNELL_GREEN));
+        assert_eq!(material_at(32.0, 1.0), Some(CORNELL_WHITE));
+
+        let floor = scene.trace(camera.position(), vec3(0.0, -0.6, -1.0).unit());
+        assert!(floor.x() > 0.0 && floor.y() > 0.0 && floor.z() > 0.0);
+        assert!(BuiltinScene::from_name("teapot").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn every_preset_builds_and_is_found_by_name() -> Result<()> {
+        for preset in BuiltinScene::ALL {
+            assert_eq!(BuiltinScene::from_name(preset.name())?, preset);
+            let (scene, camera) = preset.build()?;
+            assert!(!scene.shapes().is_empty() && !scene.lights().is_empty());
+            assert!(
+                scene.pick(32.0, 24.0, 64, 48, &camera).is_some(),
+                "{} shows nothing in the middle of the frame",
+                preset.name()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn random_spheres_are_reproducible_and_do_not_overlap() -> Result<()> {
+        let (scene, _) = random_spheres(7, 60)?;
+        let (again, _) = random_spheres(7, 60)?;
+        let (other, _) = random_spheres(8, 60)?;
+        assert_eq!(scene.shapes(), again.shapes());
+        assert_ne!(scene.shapes(), other.shapes());
+
+        let spheres: Vec<_> = scene
+            .shapes()
+            .iter()
+            .filter_map(|shape| match shape {
+                ShapeType::Sphere(sphere) => Some(sphere),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spheres.len(), 60 + 3);
+        for (index, sphere) in spheres.iter().enumerate() {
+            assert!((sphere.center().y() - sphere.radius()).abs() < 1e-12);
+            for other in &spheres[index + 1..] {
+                let gap = (sphere.center() - other.center()).length();
+                assert!(gap >= sphere.radius() + other.radius());
+            }
+        }
+        Ok(())
+    }
+}
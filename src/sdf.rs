@@ -0,0 +1,502 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    Material, Vec3f,
+    bvh::Aabb,
+    shapes::{Hit, Intersectable, Shape},
+};
+
+const MAX_STEPS: usize = 256;
+const HIT_TOLERANCE: f64 = 1e-5;
+const NORMAL_DELTA: f64 = 1e-4;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sdf {
+    Sphere {
+        center: Vec3f,
+        radius: f64,
+    },
+    Box {
+        center: Vec3f,
+        half_extent: Vec3f,
+    },
+    RoundedBox {
+        center: Vec3f,
+        half_extent: Vec3f,
+        radius: f64,
+    },
+    Torus {
+        center: Vec3f,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    SmoothUnion {
+        smoothness: f64,
+        left: Box<Self>,
+        right: Box<Self>,
+    },
+    SmoothSubtraction {
+        smoothness: f64,
+        base: Box<Self>,
+        cut: Box<Self>,
+    },
+}
+
+fn mix(from: f64, to: f64, amount: f64) -> f64 {
+    (to - from).mul_add(amount, from)
+}
+
+fn smooth_min(left: f64, right: f64, smoothness: f64) -> f64 {
+    if smoothness <= 0.0 {
+        return left.min(right);
+    }
+
+    let blend = (0.5 * (right - left) / smoothness + 0.5).clamp(0.0, 1.0);
+    (smoothness * blend).mul_add(blend - 1.0, mix(right, left, blend))
+}
+
+impl Sdf {
+    pub fn smooth_union(smoothness: f64, left: Self, right: Self) -> Self {
+        Self::SmoothUnion {
+            smoothness,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    pub fn smooth_subtraction(smoothness: f64, base: Self, cut: Self) -> Self {
+        Self::SmoothSubtraction {
+            smoothness,
+            base: Box::new(base),
+            cut: Box::new(cut),
+        }
+    }
+
+    pub fn distance(&self, point: Vec3f) -> f64 {
+        match self {
+            Self::Sphere { center, radius } => (point - *center).length() - radius,
+            Self::Box {
+                center,
+                half_extent,
+            } => {
+                let offset = point - *center;
+                let [x, y, z] = [0, 1, 2].map(|axis| offset[axis].abs() - half_extent[axis].abs());
+                let outside = Vec3f::new_with_data([x.max(0.0), y.max(0.0), z.max(0.0)]).length();
+                outside + x.max(y).max(z).min(0.0)
+            }
+            Self::RoundedBox {
+                center,
+                half_extent,
+                radius,
+            } => {
+                // A box shrunk by the radius and then inflated by it again, so the faces
+                // stay put and every edge and corner becomes a quarter cylinder or sphere.
+                let half_extent =
+                    Vec3f::new_with_data([0, 1, 2].map(|axis| half_extent[axis].abs()));
+                let radius = radius
+                    .abs()
+                    .min(half_extent.x().min(half_extent.y()).min(half_extent.z()));
+                let offset = point - *center;
+                let [x, y, z] =
+                    [0, 1, 2].map(|axis| offset[axis].abs() - (half_extent[axis] - radius));
+                let outside = Vec3f::new_with_data([x.max(0.0), y.max(0.0), z.max(0.0)]).length();
+                outside + x.max(y).max(z).min(0.0) - radius
+            }
+            Self::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let offset = point - *center;
+                let ring = offset.x().hypot(offset.z()) - major_radius;
+                ring.hypot(offset.y()) - minor_radius
+            }
+            Self::SmoothUnion {
+                smoothness,
+                left,
+                right,
+            } => smooth_min(left.distance(point), right.distance(point), *smoothness),
+            Self::SmoothSubtraction {
+                smoothness,
+                base,
+                cut,
+            } => -smooth_min(-base.distance(point), cut.distance(point), *smoothness),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let around =
+            |center: Vec3f, extent: Vec3f| Aabb::from_points(&[center - extent, center + extent]);
+
+        match self {
+            Self::Sphere { center, radius } => around(*center, Vec3f::new(radius.abs())),
+            Self::Box {
+                center,
+                half_extent,
+            }
+            | Self::RoundedBox {
+                center,
+                half_extent,
+                ..
+            } => around(
+                *center,
+                Vec3f::new_with_data([0, 1, 2].map(|axis| half_extent[axis].abs())),
+            ),
+            Self::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius.abs() + minor_radius.abs();
+                around(
+                    *center,
+                    Vec3f::new_with_data([outer, minor_radius.abs(), outer]),
+                )
+            }
+            Self::SmoothUnion {
+                smoothness,
+                left,
+                right,
+            } => {
+                let bounds = left.bounds().union(right.bounds());
+                let padding = Vec3f::new(smoothness.max(0.0) * 0.25);
+                Aabb::from_points(&[bounds.min() - padding, bounds.max() + padding])
+            }
+            Self::SmoothSubtraction { base, .. } => base.bounds(),
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vec3f) {
+        match self {
+            Self::Sphere { center, .. }
+            | Self::Box { center, .. }
+            | Self::RoundedBox { center, .. }
+            | Self::Torus { center, .. } => {
+                *center += offset;
+            }
+            Self::SmoothUnion { left, right, .. } => {
+                left.translate(offset);
+                right.translate(offset);
+            }
+            Self::SmoothSubtraction { base, cut, .. } => {
+                base.translate(offset);
+                cut.translate(offset);
+            }
+        }
+    }
+
+    fn parse_prefix(input: &str) -> Result<(Self, &str)> {
+        let (name, rest) = input
+            .split_once('(')
+            .with_context(|| format!("Expected '(' in SDF expression '{input}'"))?;
+
+        match name {
+            "sphere" | "box" | "rounded_box" | "torus" => {
+                let (arguments, rest) = rest
+                    .split_once(')')
+                    .with_context(|| format!("Missing ')' after {name}"))?;
+                let values = arguments
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse::<f64>()
+                            .with_context(|| format!("Invalid number '{value}' in {name}"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let vector = |start: usize| {
+                    Vec3f::new_with_data([values[start], values[start + 1], values[start + 2]])
+                };
+
+                let sdf = match (name, values.len()) {
+                    ("sphere", 4) => Self::Sphere {
+                        center: vector(0),
+                        radius: values[3],
+                    },
+                    ("box", 6) => Self::Box {
+                        center: vector(0),
+                        half_extent: vector(3),
+                    },
+                    ("rounded_box", 7) => Self::RoundedBox {
+                        center: vector(0),
+                        half_extent: vector(3),
+                        radius: values[6],
+                    },
+                    ("torus", 5) => Self::Torus {
+                        center: vector(0),
+                        major_radius: values[3],
+                        minor_radius: values[4],
+                    },
+                    (name, count) => bail!("Wrong number of arguments for {name}: {count}"),
+                };
+                Ok((sdf, rest))
+            }
+            "smooth_union" | "smooth_subtraction" => {
+                let (smoothness, rest) = rest
+                    .split_once(',')
+                    .with_context(|| format!("Missing operands for {name}"))?;
+                let smoothness = smoothness
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid smoothness '{smoothness}' in {name}"))?;
+                let (first, rest) = Self::parse_prefix(rest)?;
+                let rest = rest
+                    .strip_prefix(',')
+                    .with_context(|| format!("Missing second operand for {name}"))?;
+                let (second, rest) = Self::parse_prefix(rest)?;
+                let rest = rest
+                    .strip_prefix(')')
+                    .with_context(|| format!("Missing ')' after {name}"))?;
+
+                let sdf = if name == "smooth_union" {
+                    Self::smooth_union(smoothness, first, second)
+                } else {
+                    Self::smooth_subtraction(smoothness, first, second)
+                };
+                Ok((sdf, rest))
+            }
+            _ => bail!("Unknown SDF primitive '{name}'"),
+        }
+    }
+}
+
+impl FromStr for Sdf {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let (sdf, rest) = Self::parse_prefix(expression)?;
+        if !rest.is_empty() {
+            bail!("Unexpected '{rest}' after SDF expression");
+        }
+
+        Ok(sdf)
+    }
+}
+
+impl fmt::Display for Sdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sphere { center, radius } => write!(
+                f,
+                "sphere({},{},{},{radius})",
+                center.x(),
+                center.y(),
+                center.z()
+            ),
+            Self::Box {
+                center,
+                half_extent,
+            } => write!(
+                f,
+                "box({},{},{},{},{},{})",
+                center.x(),
+                center.y(),
+                center.z(),
+                half_extent.x(),
+                half_extent.y(),
+                half_extent.z()
+            ),
+            Self::RoundedBox {
+                center,
+                half_extent,
+                radius,
+            } => write!(
+                f,
+                "rounded_box({},{},{},{},{},{},{radius})",
+                center.x(),
+                center.y(),
+                center.z(),
+                half_extent.x(),
+                half_extent.y(),
+                half_extent.z()
+            ),
+            Self::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => write!(
+                f,
+                "torus({},{},{},{major_radius},{minor_radius})",
+                center.x(),
+                center.y(),
+                center.z()
+            ),
+            Self::SmoothUnion {
+                smoothness,
+                left,
+                right,
+            } => write!(f, "smooth_union({smoothness},{left},{right})"),
+            Self::SmoothSubtraction {
+                smoothness,
+                base,
+                cut,
+            } => write!(f, "smooth_subtraction({smoothness},{base},{cut})"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SdfShape {
+    sdf: Sdf,
+    bounds: Aabb,
+    material: Material,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Sdf, material: Material) -> Self {
+        Self {
+            bounds: sdf.bounds(),
+            sdf,
+            material,
+        }
+    }
+
+    pub const fn sdf(&self) -> &Sdf {
+        &self.sdf
+    }
+}
+
+impl Intersectable for SdfShape {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let (near, far) = self.bounds.interval(origin, direction)?;
+
+        let mut distance = near;
+        for _ in 0..MAX_STEPS {
+            if distance > far + HIT_TOLERANCE {
+                return None;
+            }
+            let point = origin + direction * distance;
+            let step = self.sdf.distance(point).abs();
+            if step < HIT_TOLERANCE {
+                return Some(Hit::new(distance, self.get_normal(point)));
+            }
+            distance += step;
+        }
+
+        None
+    }
+}
+
+impl Shape for SdfShape {
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        let gradient = Vec3f::new_with_data([0, 1, 2].map(|axis| {
+            let mut delta = [0.0; 3];
+            delta[axis] = NORMAL_DELTA;
+            let delta = Vec3f::new_with_data(delta);
+            self.sdf.distance(hit_point + delta) - self.sdf.distance(hit_point - delta)
+        }));
+
+        gradient.normalize(None)
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        Some(self.sdf.distance(point))
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.sdf.translate(offset);
+        self.bounds = self.sdf.bounds();
+    }
+
+    fn thickness(&self) -> f64 {
+        let extent = self.bounds.extent();
+        extent.x().min(extent.y()).min(extent.z())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RED_MATERIAL, shapes::Sphere};
+
+    #[test]
+    fn sphere_tracing_matches_analytic_shapes_and_parses_combinators() -> Result<()> {
+        let center = Vec3f::new_with_data([0.5, -0.25, -4.0]);
+        let analytic = Sphere::new(center, 1.0, RED_MATERIAL);
+        let traced = SdfShape::new(
+            Sdf::Sphere {
+                center,
+                radius: 1.0,
+            },
+            RED_MATERIAL,
+        );
+
+        let origin = Vec3f::new(0.0);
+        for direction in [
+            Vec3f::new_with_data([0.1, 0.0, -1.0]),
+            Vec3f::new_with_data([0.2, -0.1, -1.0]),
+        ] {
+            let direction = direction.normalize(None);
+            let expected = analytic
+                .ray_intersect(origin, direction)
+                .ok_or_else(|| anyhow::anyhow!("analytic sphere missed"))?;
+            let found = traced
+                .ray_intersect(origin, direction)
+                .ok_or_else(|| anyhow::anyhow!("sphere tracing missed"))?;
+            assert!((found - expected).abs() < 1e-4);
+
+            let point = origin + direction * found;
+            let normal_error = traced.get_normal(point) - analytic.get_normal(point);
+            assert!(normal_error.length() < 1e-3);
+        }
+        assert!(
+            traced
+                .ray_intersect(origin, Vec3f::new_with_data([0.0, 1.0, 0.0]))
+                .is_none()
+        );
+
+        let expression = "smooth_subtraction(0.1,smooth_union(0.5,sphere(0,0,-4,1),box(1,0,-4,0.5,0.5,0.5)),torus(0,0,-4,1,0.25))";
+        let sdf: Sdf = expression.parse()?;
+        assert_eq!(sdf.to_string(), expression);
+        assert!(sdf.distance(Vec3f::new_with_data([0.0, 0.0, -4.0])) < 0.0);
+        assert!(sdf.distance(Vec3f::new_with_data([1.0, 0.0, -4.0])) > 0.0);
+        assert!("sphere(0,0,1)".parse::<Sdf>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rounded_boxes_keep_their_faces_and_round_their_corners() -> Result<()> {
+        let sdf: Sdf = "rounded_box(0,0,-4,1,0.5,2,0.25)".parse()?;
+        assert_eq!(sdf.to_string(), "rounded_box(0,0,-4,1,0.5,2,0.25)");
+
+        let face = sdf.distance(Vec3f::new_with_data([0.0, 0.5, -4.0]));
+        assert!(face.abs() < 1e-12);
+
+        // The corner sits a radius in from the inner box's corner along the diagonal, so
+        // it is pulled in compared with the sharp box.
+        let corner = Vec3f::new_with_data([1.0, 0.5, -2.0]);
+        let inner = Vec3f::new_with_data([0.75, 0.25, -2.25]);
+        let expected = (corner - inner).length() - 0.25;
+        assert!((sdf.distance(corner) - expected).abs() < 1e-12);
+        assert!(sdf.distance(corner) > 0.0);
+
+        let traced = SdfShape::new(sdf, RED_MATERIAL);
+        let direction = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let found = traced
+            .ray_hit(Vec3f::new_with_data([0.2, 0.1, 0.0]), direction)
+            .ok_or_else(|| anyhow::anyhow!("rounded box missed"))?;
+        assert!((found.distance() - 2.0).abs() < 1e-4);
+        assert!((found.normal() - Vec3f::new_with_data([0.0, 0.0, 1.0])).length() < 1e-3);
+
+        // An over-large radius is clamped to the thinnest half extent instead of
+        // turning the box inside out.
+        let pill: Sdf = "rounded_box(0,0,0,1,0.5,0.5,3)".parse()?;
+        assert!(pill.distance(Vec3f::new(0.0)) < 0.0);
+        assert!(pill.distance(Vec3f::new_with_data([1.0, 0.0, 0.0])).abs() < 1e-12);
+        Ok(())
+    }
+}
@@ -0,0 +1,907 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    Vec3f,
+    aov::{Aov, AovBuffer},
+    export::ExportFormat,
+    integrator::Integrator,
+    materials::{GLASS_MATERIAL, Material, MaterialRegistry, SpecularModel},
+    panorama::PanoramaLayout,
+    post::{Lut3d, PostProcess, ToneMapping},
+    priority::{self, ThreadPriority},
+    scenes::BuiltinScene,
+    sweep::SweepAxis,
+};
+
+const DOTENV_PATH: &str = ".env";
+const SPP_VAR: &str = "RAYTRACER_SPP";
+const THREADS_VAR: &str = "RAYTRACER_THREADS";
+const OUTPUT_DIR_VAR: &str = "RAYTRACER_OUTPUT_DIR";
+const DEFAULT_STRIPE_ROWS: u32 = 64;
+const DEFAULT_PANORAMA_SIZE: u32 = 512;
+const DEFAULT_AO_SAMPLES: u32 = 16;
+const DEFAULT_AO_DISTANCE: f64 = 2.0;
+const DEFAULT_TIME_BUDGET: f64 = 2.0;
+const DEFAULT_PREVIEW_SCALE: u32 = 4;
+const DEFAULT_CAUSTIC_RADIUS: f64 = 0.1;
+
+pub const OVERRIDE_NAMES: [&str; 12] = [
+    "max_depth",
+    "epsilon",
+    "max_distance",
+    "background",
+    "clamp",
+    "integrator",
+    "fog_density",
+    "fog_color",
+    "specular_model",
+    "shadow_quality",
+    "tone_map",
+    "lut",
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderOverrides {
+    max_depth: Option<u32>,
+    epsilon: Option<f64>,
+    max_distance: Option<f64>,
+    background: Option<Vec3f>,
+    clamp: Option<f64>,
+    integrator: Option<Integrator>,
+    fog_density: Option<f64>,
+    fog_color: Option<Vec3f>,
+    specular_model: Option<SpecularModel>,
+    shadow_quality: Option<f64>,
+    tone_mapping: Option<ToneMapping>,
+    /// The LUT as it was named, for writing it back out, and its loaded table.
+    lut: Option<(String, Lut3d)>,
+}
+
+fn parse_color(name: &str, value: &str) -> Result<Vec3f> {
+    let components = value
+        .split(',')
+        .map(|component| parse_value(name, component))
+        .collect::<Result<Vec<f64>>>()?;
+    let [red, green, blue] = components[..] else {
+        bail!("Expected 3 components for {name}, got {value}");
+    };
+
+    Ok(Vec3f::new_with_data([red, green, blue]))
+}
+
+fn format_color(color: Vec3f) -> String {
+    format!("{},{},{}", color.x(), color.y(), color.z())
+}
+
+impl RenderOverrides {
+    pub const NONE: Self = Self {
+        max_depth: None,
+        epsilon: None,
+        max_distance: None,
+        background: None,
+        clamp: None,
+        integrator: None,
+        fog_density: None,
+        fog_color: None,
+        specular_model: None,
+        shadow_quality: None,
+        tone_mapping: None,
+        lut: None,
+    };
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "max_depth" => self.max_depth = Some(parse_value(name, value)?),
+            "epsilon" => self.epsilon = Some(parse_value(name, value)?),
+            "max_distance" => self.max_distance = Some(parse_value(name, value)?),
+            "background" => self.background = Some(parse_color(name, value)?),
+            "clamp" => self.clamp = Some(parse_value(name, value)?),
+            "integrator" => self.integrator = Some(Integrator::from_name(value)?),
+            "fog_density" => self.fog_density = Some(parse_value(name, value)?),
+            "fog_color" => self.fog_color = Some(parse_color(name, value)?),
+            "specular_model" => self.specular_model = Some(SpecularModel::from_name(value)?),
+            "shadow_quality" => self.shadow_quality = Some(parse_value(name, value)?),
+            "tone_map" => self.tone_mapping = Some(ToneMapping::from_name(value)?),
+            "lut" => self.set_lut(value, Path::new(""))?,
+            _ => bail!("Unknown override: {name}"),
+        }
+
+        self.validate()
+    }
+
+    /// Loads the LUT at `source`, which is relative to `base_dir` unless absolute.
+    pub fn set_lut(&mut self, source: &str, base_dir: &Path) -> Result<()> {
+        self.lut = Some((source.to_owned(), Lut3d::load(&base_dir.join(source))?));
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self
+            .epsilon
+            .is_some_and(|epsilon| epsilon.is_nan() || epsilon <= 0.0)
+        {
+            bail!("Epsilon must be positive");
+        }
+        if self
+            .max_distance
+            .is_some_and(|distance| distance.is_nan() || distance <= 0.0)
+        {
+            bail!("Max distance must be positive");
+        }
+        if self
+            .clamp
+            .is_some_and(|clamp| clamp.is_nan() || clamp <= 0.0)
+        {
+            bail!("Clamp value must be positive");
+        }
+        if self
+            .fog_density
+            .is_some_and(|density| density.is_nan() || density < 0.0)
+        {
+            bail!("Fog density must not be negative");
+        }
+        if self
+            .shadow_quality
+            .is_some_and(|quality| !(quality > 0.0 && quality <= 1.0))
+        {
+            bail!("Shadow quality must be in (0, 1]");
+        }
+
+        Ok(())
+    }
+
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            max_depth: self.max_depth.or(fallback.max_depth),
+            epsilon: self.epsilon.or(fallback.epsilon),
+            max_distance: self.max_distance.or(fallback.max_distance),
+            background: self.background.or(fallback.background),
+            clamp: self.clamp.or(fallback.clamp),
+            integrator: self.integrator.or(fallback.integrator),
+            fog_density: self.fog_density.or(fallback.fog_density),
+            fog_color: self.fog_color.or(fallback.fog_color),
+            specular_model: self.specular_model.or(fallback.specular_model),
+            shadow_quality: self.shadow_quality.or(fallback.shadow_quality),
+            tone_mapping: self.tone_mapping.or(fallback.tone_mapping),
+            lut: self.lut.or(fallback.lut),
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.max_depth.is_none()
+            && self.epsilon.is_none()
+            && self.max_distance.is_none()
+            && self.background.is_none()
+            && self.clamp.is_none()
+            && self.integrator.is_none()
+            && self.fog_density.is_none()
+            && self.fog_color.is_none()
+            && self.specular_model.is_none()
+            && self.shadow_quality.is_none()
+            && self.tone_mapping.is_none()
+            && self.lut.is_none()
+    }
+}
+
+impl fmt::Display for RenderOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(max_depth) = self.max_depth {
+            fields.push(format!("max_depth={max_depth}"));
+        }
+        if let Some(epsilon) = self.epsilon {
+            fields.push(format!("epsilon={epsilon}"));
+        }
+        if let Some(max_distance) = self.max_distance {
+            fields.push(format!("max_distance={max_distance}"));
+        }
+        if let Some(background) = self.background {
+            fields.push(format!("background={}", format_color(background)));
+        }
+        if let Some(clamp) = self.clamp {
+            fields.push(format!("clamp={clamp}"));
+        }
+        if let Some(integrator) = self.integrator {
+            fields.push(format!("integrator={}", integrator.name()));
+        }
+        if let Some(fog_density) = self.fog_density {
+            fields.push(format!("fog_density={fog_density}"));
+        }
+        if let Some(fog_color) = self.fog_color {
+            fields.push(format!("fog_color={}", format_color(fog_color)));
+        }
+        if let Some(specular_model) = self.specular_model {
+            fields.push(format!("specular_model={}", specular_model.name()));
+        }
+        if let Some(shadow_quality) = self.shadow_quality {
+            fields.push(format!("shadow_quality={shadow_quality}"));
+        }
+        if let Some(tone_mapping) = self.tone_mapping {
+            fields.push(format!("tone_map={}", tone_mapping.name()));
+        }
+        if let Some((source, _)) = &self.lut {
+            fields.push(format!("lut={source}"));
+        }
+
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RenderSettings {
+    post: PostProcess,
+    scene_path: Option<PathBuf>,
+    builtin_scene: Option<BuiltinScene>,
+    jobs_path: Option<PathBuf>,
+    bench: Option<u32>,
+    samples_per_pixel: u32,
+    threads: Option<usize>,
+    physical_cores: bool,
+    priority: ThreadPriority,
+    output_dir: PathBuf,
+    output_path: Option<PathBuf>,
+    resolution: Option<(u32, u32)>,
+    stripe_rows: u32,
+    panorama: Option<PanoramaLayout>,
+    panorama_size: u32,
+    stereo: Option<f64>,
+    probes_path: Option<PathBuf>,
+    export: Option<(ExportFormat, PathBuf)>,
+    aov: Option<Aov>,
+    aov_buffers: Vec<AovBuffer>,
+    ao_samples: u32,
+    ao_distance: f64,
+    terminator_fix: bool,
+    sort_rays: bool,
+    hud: bool,
+    denoise: bool,
+    soft_shadows: Option<f64>,
+    mirror_cache: Option<f64>,
+    caustics: Option<u32>,
+    caustic_radius: f64,
+    light_shafts: Option<f64>,
+    furnace_test: bool,
+    cleanup: bool,
+    coordinator: Option<String>,
+    worker: Option<String>,
+    overrides: RenderOverrides,
+    compare_integrators: bool,
+    time_budget: f64,
+    sweeps: Vec<SweepAxis>,
+    sweep_material: Material,
+    debug_pixel: Option<(u32, u32)>,
+    time: f64,
+    preview_scale: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            post: PostProcess::default(),
+            scene_path: None,
+            builtin_scene: None,
+            jobs_path: None,
+            bench: None,
+            samples_per_pixel: 1,
+            threads: None,
+            physical_cores: false,
+            priority: ThreadPriority::NORMAL,
+            output_dir: PathBuf::from("."),
+            output_path: None,
+            resolution: None,
+            stripe_rows: DEFAULT_STRIPE_ROWS,
+            panorama: None,
+            panorama_size: DEFAULT_PANORAMA_SIZE,
+            stereo: None,
+            probes_path: None,
+            export: None,
+            aov: None,
+            aov_buffers: Vec::new(),
+            ao_samples: DEFAULT_AO_SAMPLES,
+            ao_distance: DEFAULT_AO_DISTANCE,
+            terminator_fix: true,
+            sort_rays: false,
+            hud: false,
+            denoise: false,
+            soft_shadows: None,
+            mirror_cache: None,
+            caustics: None,
+            caustic_radius: DEFAULT_CAUSTIC_RADIUS,
+            light_shafts: None,
+            furnace_test: false,
+            cleanup: false,
+            coordinator: None,
+            worker: None,
+            overrides: RenderOverrides::default(),
+            compare_integrators: false,
+            time_budget: DEFAULT_TIME_BUDGET,
+            sweeps: Vec::new(),
+            sweep_material: GLASS_MATERIAL,
+            debug_pixel: None,
+            time: 0.0,
+            preview_scale: DEFAULT_PREVIEW_SCALE,
+        }
+    }
+}
+
+fn read_dotenv(path: &Path) -> Result<HashMap<String, String>> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+
+    let mut variables = HashMap::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Expected KEY=VALUE on line {} of {}",
+                index + 1,
+                path.display()
+            )
+        })?;
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+
+        variables.insert(key.trim().to_owned(), value.to_owned());
+    }
+
+    Ok(variables)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid value for {name}: {value}"))
+}
+
+fn parse_switch(name: &str, value: &str) -> Result<bool> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => bail!("Expected on or off for {name}, got {value}"),
+    }
+}
+
+/// Looks variables up in `env` first and falls back to the ones read from `.env`.
+fn with_dotenv_fallback(
+    env: impl Fn(&str) -> Option<String>,
+    dotenv: HashMap<String, String>,
+) -> impl Fn(&str) -> Option<String> {
+    move |key| env(key).or_else(|| dotenv.get(key).cloned())
+}
+
+impl RenderSettings {
+    pub fn from_env_and_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let dotenv = read_dotenv(Path::new(DOTENV_PATH))?;
+        let lookup = with_dotenv_fallback(|key| env::var(key).ok(), dotenv);
+
+        Self::from_sources(args, lookup)
+    }
+
+    pub fn from_sources(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut settings = Self::default();
+
+        if let Some(value) = env(SPP_VAR) {
+            settings.samples_per_pixel = parse_value(SPP_VAR, &value)?;
+        }
+        if let Some(value) = env(THREADS_VAR) {
+            settings.threads = Some(parse_value(THREADS_VAR, &value)?);
+        }
+        if let Some(value) = env(OUTPUT_DIR_VAR) {
+            settings.output_dir = PathBuf::from(value);
+        }
+
+        settings.apply_args(args)?;
+
+        Ok(settings)
+    }
+
+    pub fn with_args(&self, args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut settings = self.clone();
+        settings.apply_args(args)?;
+
+        Ok(settings)
+    }
+
+    /// Splits a comma separated `--aov` list into buffers written next to the image and at
+    /// most one shading AOV that replaces it.
+    fn set_aovs(&mut self, names: &str) -> Result<()> {
+        self.aov = None;
+        self.aov_buffers.clear();
+        for name in names.split(',') {
+            if let Ok(buffer) = AovBuffer::from_name(name) {
+                if !self.aov_buffers.contains(&buffer) {
+                    self.aov_buffers.push(buffer);
+                }
+            } else if self.aov.replace(Aov::from_name(name)?).is_some() {
+                bail!("Only one of ao, bent-normal, diffuse and specular can be rendered at once");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: impl IntoIterator<Item = String>) -> Result<()> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for argument {arg}"))
+            };
+
+            match arg.as_str() {
+                "--tone-map" => self.overrides.set("tone_map", &value()?)?,
+                "--exposure" => self.post.set_exposure(parse_value(&arg, &value()?)?),
+                "--bloom" => self.post.set_bloom(Some(value()?.parse()?)),
+                "--lut" => self.overrides.set("lut", &value()?)?,
+                "--scene" => {
+                    let value = value()?;
+                    if let Some(name) = value.strip_prefix("builtin:") {
+                        self.builtin_scene = Some(BuiltinScene::from_name(name)?);
+                        self.scene_path = None;
+                    } else {
+                        self.builtin_scene = None;
+                        self.scene_path = Some(PathBuf::from(value));
+                    }
+                }
+                "--jobs" => self.jobs_path = Some(PathBuf::from(value()?)),
+                "--bench" => self.bench = Some(parse_value(&arg, &value()?)?),
+                "--spp" => self.samples_per_pixel = parse_value(&arg, &value()?)?,
+                "--threads" => self.threads = Some(parse_value(&arg, &value()?)?),
+                "--physical-cores" => self.physical_cores = true,
+                "--priority" => self.priority = ThreadPriority::from_name(&value()?)?,
+                "--output-dir" => self.output_dir = PathBuf::from(value()?),
+                "--output" => self.output_path = Some(PathBuf::from(value()?)),
+                "--resolution" => {
+                    let value = value()?;
+                    let (width, height) = value
+                        .split_once('x')
+                        .ok_or_else(|| anyhow!("Expected WIDTHxHEIGHT, got {value}"))?;
+                    self.resolution = Some((parse_value(&arg, width)?, parse_value(&arg, height)?));
+                }
+                "--stripe-rows" => self.stripe_rows = parse_value(&arg, &value()?)?,
+                "--panorama" => self.panorama = Some(PanoramaLayout::from_name(&value()?)?),
+                "--panorama-size" => self.panorama_size = parse_value(&arg, &value()?)?,
+                "--stereo" => self.stereo = Some(parse_value(&arg, &value()?)?),
+                "--probes" => self.probes_path = Some(PathBuf::from(value()?)),
+                "--export" => {
+                    let path = PathBuf::from(value()?);
+                    self.export = Some((ExportFormat::from_path(&path)?, path));
+                }
+                "--aov" => self.set_aovs(&value()?)?,
+                "--ao-samples" => self.ao_samples = parse_value(&arg, &value()?)?,
+                "--ao-distance" => self.ao_distance = parse_value(&arg, &value()?)?,
+                "--terminator-fix" => self.terminator_fix = parse_switch(&arg, &value()?)?,
+                "--sort-rays" => self.sort_rays = parse_switch(&arg, &value()?)?,
+                "--hud" => self.hud = parse_switch(&arg, &value()?)?,
+                "--denoise" => self.denoise = parse_switch(&arg, &value()?)?,
+                "--soft-shadows" => self.soft_shadows = Some(parse_value(&arg, &value()?)?),
+                "--mirror-cache" => self.mirror_cache = Some(parse_value(&arg, &value()?)?),
+                "--caustics" => self.caustics = Some(parse_value(&arg, &value()?)?),
+                "--caustic-radius" => self.caustic_radius = parse_value(&arg, &value()?)?,
+                "--light-shafts" => self.light_shafts = Some(parse_value(&arg, &value()?)?),
+                "--furnace-test" => self.furnace_test = true,
+                "--cleanup" => self.cleanup = true,
+                "--coordinator" => self.coordinator = Some(value()?),
+                "--worker" => self.worker = Some(value()?),
+                "--max-depth" => self.overrides.set("max_depth", &value()?)?,
+                "--epsilon" => self.overrides.set("epsilon", &value()?)?,
+                "--max-distance" => self.overrides.set("max_distance", &value()?)?,
+                "--background" => self.overrides.set("background", &value()?)?,
+                "--clamp" => self.overrides.set("clamp", &value()?)?,
+                "--integrator" => self.overrides.set("integrator", &value()?)?,
+                "--specular" => self.overrides.set("specular_model", &value()?)?,
+                "--shadow-quality" => self.overrides.set("shadow_quality", &value()?)?,
+                "--fog-density" => self.overrides.set("fog_density", &value()?)?,
+                "--fog-color" => self.overrides.set("fog_color", &value()?)?,
+                "--compare-integrators" => self.compare_integrators = true,
+                "--time-budget" => self.time_budget = parse_value(&arg, &value()?)?,
+                "--sweep" => self.sweeps.push(value()?.parse()?),
+                "--sweep-material" => {
+                    let name = value()?;
+                    self.sweep_material = MaterialRegistry::default()
+                        .get(&name)
+                        .ok_or_else(|| anyhow!("Unknown sweep material: {name}"))?;
+                }
+                "--time" => self.time = parse_value(&arg, &value()?)?,
+                "--preview-scale" => self.preview_scale = parse_value(&arg, &value()?)?,
+                "--debug-pixel" => {
+                    let x = parse_value(&arg, &value()?)?;
+                    let y = parse_value(&arg, &value()?)?;
+                    self.debug_pixel = Some((x, y));
+                }
+                _ => bail!("Unknown argument: {arg}"),
+            }
+        }
+
+        self.apply_post_overrides();
+        self.validate()
+    }
+
+    /// Hands the tone mapping and LUT picked on the command line or by the scene to the
+    /// post-processing chain.
+    fn apply_post_overrides(&mut self) {
+        if let Some(tone_mapping) = self.overrides.tone_mapping {
+            self.post.set_tone_mapping(tone_mapping);
+        }
+        if let Some((_, lut)) = &self.overrides.lut {
+            self.post.set_lut(Some(lut.clone()));
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.samples_per_pixel == 0 {
+            bail!("Samples per pixel must be at least 1");
+        }
+        if self.bench == Some(0) {
+            bail!("Benchmark needs at least one run");
+        }
+        if self.threads == Some(0) {
+            bail!("Thread count must be at least 1");
+        }
+        if self.stripe_rows == 0 {
+            bail!("Stripe height must be at least 1 row");
+        }
+        if self.ao_samples == 0 {
+            bail!("AO sample count must be at least 1");
+        }
+        if self.sweeps.len() > 2 {
+            bail!("Material sweeps take at most two --sweep parameters");
+        }
+        if self.ao_distance.is_nan() || self.ao_distance <= 0.0 {
+            bail!("AO distance must be positive");
+        }
+        if !self.post.exposure().is_finite() {
+            bail!("Exposure must be a finite number of stops");
+        }
+        if self.time_budget.is_nan() || self.time_budget <= 0.0 {
+            bail!("Time budget must be positive");
+        }
+        if self
+            .soft_shadows
+            .is_some_and(|sharpness| sharpness.is_nan() || sharpness <= 0.0)
+        {
+            bail!("Soft shadow sharpness must be positive");
+        }
+        if self
+            .mirror_cache
+            .is_some_and(|cell_size| cell_size.is_nan() || cell_size <= 0.0)
+        {
+            bail!("Mirror cache cell size must be positive");
+        }
+        if self.caustics == Some(0) {
+            bail!("Caustics need at least one photon");
+        }
+        if self.caustic_radius.is_nan() || self.caustic_radius <= 0.0 {
+            bail!("Caustic gather radius must be positive");
+        }
+        if self
+            .light_shafts
+            .is_some_and(|density| density.is_nan() || density <= 0.0)
+        {
+            bail!("Light shaft density must be positive");
+        }
+        if self.preview_scale == 0 {
+            bail!("Preview scale must be at least 1");
+        }
+        if self.panorama_size == 0 {
+            bail!("Panorama size must be at least 1 pixel");
+        }
+        if self.stereo.is_some_and(|ipd| ipd.is_nan() || ipd <= 0.0) {
+            bail!("Stereo inter-pupillary distance must be positive");
+        }
+        if self.coordinator.is_some() && self.worker.is_some() {
+            bail!("A process is either a --coordinator or a --worker, not both");
+        }
+        if let Some((0, _) | (_, 0)) = self.resolution {
+            bail!("Resolution must be at least 1x1");
+        }
+
+        Ok(())
+    }
+
+    pub const fn post(&self) -> &PostProcess {
+        &self.post
+    }
+
+    pub fn scene_path(&self) -> Option<&Path> {
+        self.scene_path.as_deref()
+    }
+
+    pub const fn builtin_scene(&self) -> Option<BuiltinScene> {
+        self.builtin_scene
+    }
+
+    pub fn jobs_path(&self) -> Option<&Path> {
+        self.jobs_path.as_deref()
+    }
+
+    pub const fn bench(&self) -> Option<u32> {
+        self.bench
+    }
+
+    pub const fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub const fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    pub fn render_threads(&self) -> Option<usize> {
+        self.threads
+            .or_else(|| self.physical_cores.then(priority::physical_cores))
+    }
+
+    pub const fn priority(&self) -> ThreadPriority {
+        self.priority
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    pub fn output_path(&self) -> Option<&Path> {
+        self.output_path.as_deref()
+    }
+
+    pub const fn resolution(&self) -> Option<(u32, u32)> {
+        self.resolution
+    }
+
+    pub const fn stripe_rows(&self) -> u32 {
+        self.stripe_rows
+    }
+
+    pub const fn panorama(&self) -> Option<PanoramaLayout> {
+        self.panorama
+    }
+
+    pub const fn panorama_size(&self) -> u32 {
+        self.panorama_size
+    }
+
+    pub const fn stereo(&self) -> Option<f64> {
+        self.stereo
+    }
+
+    pub fn probes_path(&self) -> Option<&Path> {
+        self.probes_path.as_deref()
+    }
+
+    pub fn export(&self) -> Option<(ExportFormat, &Path)> {
+        self.export
+            .as_ref()
+            .map(|(format, path)| (*format, path.as_path()))
+    }
+
+    pub const fn aov(&self) -> Option<Aov> {
+        self.aov
+    }
+
+    pub fn aov_buffers(&self) -> &[AovBuffer] {
+        &self.aov_buffers
+    }
+
+    pub const fn ao_samples(&self) -> u32 {
+        self.ao_samples
+    }
+
+    pub const fn ao_distance(&self) -> f64 {
+        self.ao_distance
+    }
+
+    pub const fn terminator_fix(&self) -> bool {
+        self.terminator_fix
+    }
+
+    pub const fn sort_rays(&self) -> bool {
+        self.sort_rays
+    }
+
+    pub const fn hud(&self) -> bool {
+        self.hud
+    }
+
+    pub const fn denoise(&self) -> bool {
+        self.denoise
+    }
+
+    pub const fn soft_shadows(&self) -> Option<f64> {
+        self.soft_shadows
+    }
+
+    pub const fn mirror_cache(&self) -> Option<f64> {
+        self.mirror_cache
+    }
+
+    /// The photon count and gather radius for caustics, if `--caustics` is set.
+    pub fn caustics(&self) -> Option<(u32, f64)> {
+        self.caustics.map(|photons| (photons, self.caustic_radius))
+    }
+
+    pub const fn light_shafts(&self) -> Option<f64> {
+        self.light_shafts
+    }
+
+    pub const fn furnace_test(&self) -> bool {
+        self.furnace_test
+    }
+
+    pub const fn cleanup(&self) -> bool {
+        self.cleanup
+    }
+
+    pub fn coordinator(&self) -> Option<&str> {
+        self.coordinator.as_deref()
+    }
+
+    pub fn worker(&self) -> Option<&str> {
+        self.worker.as_deref()
+    }
+
+    pub fn with_scene_overrides(&self, scene: &RenderOverrides) -> Self {
+        let mut settings = Self {
+            overrides: self.overrides.clone().or(scene.clone()),
+            ..self.clone()
+        };
+        settings.apply_post_overrides();
+        settings
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.overrides.integrator.unwrap_or_default()
+    }
+
+    pub const fn max_depth(&self) -> Option<u32> {
+        self.overrides.max_depth
+    }
+
+    pub const fn epsilon(&self) -> Option<f64> {
+        self.overrides.epsilon
+    }
+
+    pub const fn max_distance(&self) -> Option<f64> {
+        self.overrides.max_distance
+    }
+
+    pub const fn background(&self) -> Option<Vec3f> {
+        self.overrides.background
+    }
+
+    pub const fn clamp(&self) -> Option<f64> {
+        self.overrides.clamp
+    }
+
+    pub const fn fog_density(&self) -> Option<f64> {
+        self.overrides.fog_density
+    }
+
+    pub const fn fog_color(&self) -> Option<Vec3f> {
+        self.overrides.fog_color
+    }
+
+    pub const fn specular_model(&self) -> Option<SpecularModel> {
+        self.overrides.specular_model
+    }
+
+    pub const fn shadow_quality(&self) -> Option<f64> {
+        self.overrides.shadow_quality
+    }
+
+    pub const fn compare_integrators(&self) -> bool {
+        self.compare_integrators
+    }
+
+    pub const fn time_budget(&self) -> f64 {
+        self.time_budget
+    }
+
+    pub fn sweeps(&self) -> &[SweepAxis] {
+        &self.sweeps
+    }
+
+    pub const fn sweep_material(&self) -> Material {
+        self.sweep_material
+    }
+
+    pub const fn debug_pixel(&self) -> Option<(u32, u32)> {
+        self.debug_pixel
+    }
+
+    pub const fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub const fn preview_scale(&self) -> u32 {
+        self.preview_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn environment_beats_dotenv_and_arguments_beat_both() -> Result<()> {
+        let env = |key: &str| (key == SPP_VAR).then(|| "4".to_owned());
+        let dotenv = HashMap::from([
+            (SPP_VAR.to_owned(), "2".to_owned()),
+            (THREADS_VAR.to_owned(), "3".to_owned()),
+        ]);
+        let lookup = with_dotenv_fallback(env, dotenv);
+
+        let settings = RenderSettings::from_sources(Vec::new(), &lookup)?;
+        assert_eq!(settings.samples_per_pixel(), 4);
+        assert_eq!(settings.threads(), Some(3));
+        assert_eq!(settings.output_dir(), Path::new("."));
+
+        let settings =
+            RenderSettings::from_sources(args(&["--spp", "8", "--threads", "1"]), &lookup)?;
+        assert_eq!(settings.samples_per_pixel(), 8);
+        assert_eq!(settings.threads(), Some(1));
+
+        let invalid = |key: &str| (key == THREADS_VAR).then(|| "many".to_owned());
+        assert!(RenderSettings::from_sources(Vec::new(), invalid).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dotenv_files_take_exports_quotes_and_comments() -> Result<()> {
+        let directory = env::temp_dir().join(format!("dotenv-{}", std::process::id()));
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(".env");
+
+        fs::write(
+            &path,
+            "# render defaults\n\
+             \n\
+             export RAYTRACER_SPP=16\n\
+             RAYTRACER_OUTPUT_DIR = \"renders/final cut\"\n\
+             RAYTRACER_THREADS=\"2\n",
+        )?;
+        let variables = read_dotenv(&path)?;
+        assert_eq!(variables.len(), 3);
+        assert_eq!(variables["RAYTRACER_SPP"], "16");
+        assert_eq!(variables["RAYTRACER_OUTPUT_DIR"], "renders/final cut");
+        assert_eq!(variables["RAYTRACER_THREADS"], "\"2");
+
+        fs::write(&path, "RAYTRACER_SPP=16\nnot a variable\n")?;
+        let Err(malformed) = read_dotenv(&path) else {
+            panic!("a line without '=' was accepted");
+        };
+        assert!(malformed.to_string().contains("line 2"), "{malformed}");
+
+        assert!(read_dotenv(&directory.join("missing.env"))?.is_empty());
+        fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
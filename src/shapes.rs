@@ -1,6 +1,16 @@
+use std::{fmt, sync::Arc};
+
 use crate::EPSILON;
 use crate::Material;
 use crate::Vec3f;
+use crate::billboard::Billboard;
+use crate::bvh::Aabb;
+use crate::geometry::{Transform, inverse_direction};
+use crate::heightfield::Heightfield;
+use crate::mesh::TriangleMesh;
+use crate::prism::Prism;
+use crate::quad::Quad;
+use crate::sdf::SdfShape;
 
 use crate::{BLUE_MATERIAL, GLASS_MATERIAL, GREEN_MATERIAL, MIRROR_MATERIAL, RED_MATERIAL};
 
@@ -39,16 +49,94 @@ pub fn init_default_shapes() -> Vec<ShapeType> {
     ]
 }
 
+/// A ray hit together with the normals found while intersecting, so shading does not have
+/// to re-derive them from the hit point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    distance: f64,
+    normal: Vec3f,
+    geometric_normal: Vec3f,
+}
+
+impl Hit {
+    pub const fn new(distance: f64, normal: Vec3f) -> Self {
+        Self {
+            distance,
+            normal,
+            geometric_normal: normal,
+        }
+    }
+
+    pub const fn with_geometric_normal(self, geometric_normal: Vec3f) -> Self {
+        Self {
+            geometric_normal,
+            ..self
+        }
+    }
+
+    pub const fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    pub const fn normal(&self) -> Vec3f {
+        self.normal
+    }
+
+    pub const fn geometric_normal(&self) -> Vec3f {
+        self.geometric_normal
+    }
+}
+
 pub trait Intersectable {
-    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64>;
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit>;
+
+    fn ray_hit_inverse(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        _inverse_direction: Vec3f,
+    ) -> Option<Hit> {
+        self.ray_hit(origin, direction)
+    }
+
+    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
+        self.ray_hit(origin, direction).map(|hit| hit.distance)
+    }
+
+    fn intersects_any(&self, origin: Vec3f, direction: Vec3f, max_distance: f64) -> bool {
+        self.ray_intersect(origin, direction)
+            .is_some_and(|distance| distance < max_distance)
+    }
 }
 
 pub trait Shape: Intersectable {
     fn get_normal(&self, hit_point: Vec3f) -> Vec3f;
     fn get_material(&self) -> Material;
+    fn set_material(&mut self, material: Material);
+
+    fn get_material_at(&self, _hit_point: Vec3f) -> Material {
+        self.get_material()
+    }
+
+    fn translate(&mut self, offset: Vec3f);
+    fn thickness(&self) -> f64;
+    fn bounds(&self) -> Option<Aabb>;
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.get_normal(hit_point)
+    }
+
+    fn get_shadow_point(&self, hit_point: Vec3f) -> Vec3f {
+        hit_point
+    }
+
+    fn signed_distance(&self, _point: Vec3f) -> Option<f64> {
+        None
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sphere {
     center: Vec3f,
     radius: f64,
@@ -64,10 +152,18 @@ impl Sphere {
             material,
         }
     }
+
+    pub const fn center(&self) -> Vec3f {
+        self.center
+    }
+
+    pub const fn radius(&self) -> f64 {
+        self.radius
+    }
 }
 
 impl Intersectable for Sphere {
-    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
         let l = self.center - origin;
         let tca = l * direction;
         let d2 = tca.mul_add(-tca, l * l);
@@ -87,7 +183,7 @@ impl Intersectable for Sphere {
             return None;
         }
 
-        Some(t0)
+        Some(Hit::new(t0, self.get_normal(origin + direction * t0)))
     }
 }
 
@@ -96,12 +192,37 @@ impl Shape for Sphere {
         self.material
     }
 
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
     fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
         (hit_point - self.center).normalize(None)
     }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        Some((point - self.center).length() - self.radius)
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.center += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        self.radius * 2.0
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let radius = Vec3f::new(self.radius.abs());
+        Some(Aabb::from_points(&[
+            self.center - radius,
+            self.center + radius,
+        ]))
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoxShape {
     max_point: Vec3f,
     min_point: Vec3f,
@@ -117,70 +238,234 @@ impl BoxShape {
             material,
         }
     }
+
+    pub const fn max_point(&self) -> Vec3f {
+        self.max_point
+    }
+
+    pub const fn min_point(&self) -> Vec3f {
+        self.min_point
+    }
 }
 
 impl Intersectable for BoxShape {
-    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
-        let inv_dir = Vec3f::const_new_with_data([
-            1.0 / direction.x(),
-            1.0 / direction.y(),
-            1.0 / direction.z(),
-        ]);
-
-        let t1 = (self.min_point.x() - origin.x()) * inv_dir.x();
-        let t2 = (self.max_point.x() - origin.x()) * inv_dir.x();
-        let t3 = (self.min_point.y() - origin.y()) * inv_dir.y();
-        let t4 = (self.max_point.y() - origin.y()) * inv_dir.y();
-        let t5 = (self.min_point.z() - origin.z()) * inv_dir.z();
-        let t6 = (self.max_point.z() - origin.z()) * inv_dir.z();
-
-        let tmin = f64::max(
-            f64::max(f64::min(t1, t2), f64::min(t3, t4)),
-            f64::min(t5, t6),
-        );
-        let tmax = f64::min(
-            f64::min(f64::max(t1, t2), f64::max(t3, t4)),
-            f64::max(t5, t6),
-        );
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        self.ray_hit_inverse(origin, direction, inverse_direction(direction))
+    }
 
-        if tmax < 0.0 || tmin > tmax {
-            return None;
+    fn ray_hit_inverse(&self, origin: Vec3f, _direction: Vec3f, inv_dir: Vec3f) -> Option<Hit> {
+        slab_hit(self.min_point, self.max_point, origin, inv_dir)
+    }
+}
+
+fn slab_hit(min_point: Vec3f, max_point: Vec3f, origin: Vec3f, inv_dir: Vec3f) -> Option<Hit> {
+    let mut near = (f64::NEG_INFINITY, 0);
+    let mut far = (f64::INFINITY, 0);
+    for axis in 0..3 {
+        let t1 = (min_point[axis] - origin[axis]) * inv_dir[axis];
+        let t2 = (max_point[axis] - origin[axis]) * inv_dir[axis];
+        if t1.min(t2) > near.0 {
+            near = (t1.min(t2), axis);
         }
+        if t1.max(t2) < far.0 {
+            far = (t1.max(t2), axis);
+        }
+    }
 
-        Some(if tmin < 0.0 { tmax } else { tmin })
+    if far.0 < 0.0 || near.0 > far.0 {
+        return None;
     }
+
+    // The slab that bounds the hit gives the face: entering faces point against the
+    // ray, and a ray starting inside leaves through a face pointing along it.
+    let ((distance, axis), facing) = if near.0 < 0.0 {
+        (far, 1.0)
+    } else {
+        (near, -1.0)
+    };
+    let mut normal = Vec3f::new(0.0);
+    normal[axis] = facing * inv_dir[axis].signum();
+
+    Some(Hit::new(distance, normal))
+}
+
+fn box_face_normal(offset: Vec3f, half_extent: Vec3f) -> Vec3f {
+    let relative = |axis: usize| (offset[axis] / half_extent[axis]).abs();
+    let axis = (0..3)
+        .max_by(|&a, &b| relative(a).total_cmp(&relative(b)))
+        .unwrap_or(0);
+
+    let mut normal = Vec3f::new(0.0);
+    normal[axis] = offset[axis].signum();
+    normal
+}
+
+fn box_distance(offset: Vec3f, half_extent: Vec3f) -> f64 {
+    let [x, y, z] = [
+        offset.x().abs() - half_extent.x().abs(),
+        offset.y().abs() - half_extent.y().abs(),
+        offset.z().abs() - half_extent.z().abs(),
+    ];
+    let outside = Vec3f::new_with_data([x.max(0.0), y.max(0.0), z.max(0.0)]).length();
+
+    outside + x.max(y).max(z).min(0.0)
 }
 
 impl Shape for BoxShape {
     fn get_material(&self) -> Material {
         self.material
     }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
     fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
-        let mut normal = Vec3f::new_with_data([0.0, 0.0, 0.0]);
-
-        if f64::abs(hit_point.x() - self.min_point.x()) < EPSILON {
-            normal = Vec3f::new_with_data([-1.0, 0.0, 0.0]); // левая грань
-        } else if f64::abs(hit_point.x() - self.max_point.x()) < EPSILON {
-            normal = Vec3f::new_with_data([1.0, 0.0, 0.0]); // правая грань
-        } else if f64::abs(hit_point.y() - self.min_point.y()) < EPSILON {
-            normal = Vec3f::new_with_data([0.0, -1.0, 0.0]); // нижняя грань
-        } else if f64::abs(hit_point.y() - self.max_point.y()) < EPSILON {
-            normal = Vec3f::new_with_data([0.0, 1.0, 0.0]); // верхняя грань
-        } else if f64::abs(hit_point.z() - self.min_point.z()) < EPSILON {
-            normal = Vec3f::new_with_data([0.0, 0.0, -1.0]); // задняя грань
-        } else if f64::abs(hit_point.z() - self.max_point.z()) < EPSILON {
-            normal = Vec3f::new_with_data([0.0, 0.0, 1.0]); // передняя грань
+        let center = (self.min_point + self.max_point) * 0.5;
+        let half_extent = (self.max_point - self.min_point) * 0.5;
+        box_face_normal(hit_point - center, half_extent)
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.min_point += offset;
+        self.max_point += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        let extent = self.max_point - self.min_point;
+        extent.x().abs().min(extent.y().abs()).min(extent.z().abs())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[self.min_point, self.max_point]))
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        let center = (self.min_point + self.max_point) * 0.5;
+        let half_extent = (self.max_point - self.min_point) * 0.5;
+        Some(box_distance(point - center, half_extent))
+    }
+}
+
+/// A box rotated by `angle` radians around `axis`, so it does not have to line up with the
+/// world axes like [`BoxShape`]. Rays are intersected in the box's own frame.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrientedBox {
+    center: Vec3f,
+    half_extents: Vec3f,
+    axis: Vec3f,
+    angle: f64,
+    frame: Transform,
+    material: Material,
+}
+
+impl OrientedBox {
+    pub fn new(
+        center: Vec3f,
+        half_extents: Vec3f,
+        axis: Vec3f,
+        angle: f64,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            half_extents,
+            axis,
+            angle,
+            frame: Transform::from_rotation(axis, angle).then(Transform::from_translation(center)),
+            material,
         }
+    }
+
+    pub const fn center(&self) -> Vec3f {
+        self.center
+    }
+
+    pub const fn half_extents(&self) -> Vec3f {
+        self.half_extents
+    }
+
+    pub const fn axis(&self) -> Vec3f {
+        self.axis
+    }
+
+    pub const fn angle(&self) -> f64 {
+        self.angle
+    }
+}
+
+impl Intersectable for OrientedBox {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let local_direction = self.frame.invert_vector(direction);
+        slab_hit(
+            -self.half_extents,
+            self.half_extents,
+            self.frame.invert_point(origin),
+            inverse_direction(local_direction),
+        )
+        .map(|hit| Hit::new(hit.distance(), self.frame.apply_vector(hit.normal())))
+    }
+}
+
+impl Shape for OrientedBox {
+    fn get_material(&self) -> Material {
+        self.material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.frame.apply_vector(box_face_normal(
+            self.frame.invert_point(hit_point),
+            self.half_extents,
+        ))
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.center += offset;
+        self.frame.translate(offset);
+    }
 
-        normal
+    fn thickness(&self) -> f64 {
+        let extent = self.half_extents * 2.0;
+        extent.x().abs().min(extent.y().abs()).min(extent.z().abs())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let corners: Vec<_> = (0..8)
+            .map(|corner: usize| {
+                let pick = |axis: usize| {
+                    if corner & (1 << axis) == 0 {
+                        -self.half_extents[axis]
+                    } else {
+                        self.half_extents[axis]
+                    }
+                };
+                self.frame
+                    .apply_point(Vec3f::new_with_data([pick(0), pick(1), pick(2)]))
+            })
+            .collect();
+
+        Some(Aabb::from_points(&corners))
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        Some(box_distance(
+            self.frame.invert_point(point),
+            self.half_extents,
+        ))
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InfinityPlane {
     position: Vec3f,
     normal: Vec3f,
     material: Material,
+    time: f64,
 }
 
 impl InfinityPlane {
@@ -188,10 +473,23 @@ impl InfinityPlane {
     pub fn new(position: Vec3f, normal: Vec3f, material: Material) -> Self {
         Self {
             position,
-            normal: normal.normalize(None),
+            normal: normal.unit(),
             material,
+            time: 0.0,
         }
     }
+
+    pub const fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    pub const fn position(&self) -> Vec3f {
+        self.position
+    }
+
+    pub const fn normal(&self) -> Vec3f {
+        self.normal
+    }
 }
 
 impl Shape for InfinityPlane {
@@ -199,13 +497,35 @@ impl Shape for InfinityPlane {
         self.material
     }
 
-    fn get_normal(&self, _hit_point: Vec3f) -> Vec3f {
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.material.waves().map_or(self.normal, |waves| {
+            waves.perturb(self.normal, hit_point - self.position, self.time)
+        })
+    }
+
+    fn get_geometric_normal(&self, _hit_point: Vec3f) -> Vec3f {
         self.normal
     }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.position += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
 }
 
 impl Intersectable for InfinityPlane {
-    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
         let ray_point = direction * self.normal;
         if f64::abs(ray_point) < EPSILON {
             return None;
@@ -216,16 +536,252 @@ impl Intersectable for InfinityPlane {
             return None;
         }
 
-        Some(s)
+        Some(
+            Hit::new(s, self.get_normal(origin + direction * s)).with_geometric_normal(self.normal),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Instance {
+    shape: Arc<ShapeType>,
+    transform: Transform,
+}
+
+impl Instance {
+    pub const fn new(shape: Arc<ShapeType>, transform: Transform) -> Self {
+        Self { shape, transform }
+    }
+
+    pub const fn shape(&self) -> &Arc<ShapeType> {
+        &self.shape
+    }
+
+    pub const fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl Intersectable for Instance {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        let local_direction = self.transform.invert_vector(direction);
+        let scale = local_direction.length();
+
+        self.shape
+            .ray_hit(self.transform.invert_point(origin), local_direction / scale)
+            .map(|hit| {
+                Hit::new(
+                    hit.distance / scale,
+                    self.transform.apply_normal(hit.normal),
+                )
+                .with_geometric_normal(self.transform.apply_normal(hit.geometric_normal))
+            })
+    }
+
+    fn intersects_any(&self, origin: Vec3f, direction: Vec3f, max_distance: f64) -> bool {
+        let local_direction = self.transform.invert_vector(direction);
+        let scale = local_direction.length();
+
+        self.shape.intersects_any(
+            self.transform.invert_point(origin),
+            local_direction / scale,
+            max_distance * scale,
+        )
+    }
+}
+
+impl Shape for Instance {
+    fn get_material(&self) -> Material {
+        self.shape.get_material()
+    }
+
+    fn get_material_at(&self, hit_point: Vec3f) -> Material {
+        self.shape
+            .get_material_at(self.transform.invert_point(hit_point))
+    }
+
+    fn set_material(&mut self, material: Material) {
+        Arc::make_mut(&mut self.shape).set_material(material);
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        let local_point = self.transform.invert_point(hit_point);
+        self.transform
+            .apply_normal(self.shape.get_normal(local_point))
+    }
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        let local_point = self.transform.invert_point(hit_point);
+        self.transform
+            .apply_normal(self.shape.get_geometric_normal(local_point))
+    }
+
+    fn get_shadow_point(&self, hit_point: Vec3f) -> Vec3f {
+        let local_point = self.transform.invert_point(hit_point);
+        self.transform
+            .apply_point(self.shape.get_shadow_point(local_point))
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        self.shape
+            .signed_distance(self.transform.invert_point(point))
+            .map(|distance| distance * self.transform.min_scale())
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.transform.translate(offset);
+    }
+
+    fn thickness(&self) -> f64 {
+        self.shape.thickness() * self.transform.min_scale()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.shape.bounds()?;
+        let (min, max) = (bounds.min(), bounds.max());
+        let corners: Vec<_> = (0..8)
+            .map(|corner| {
+                let pick = |axis: usize| {
+                    if corner & (1 << axis) == 0 {
+                        min[axis]
+                    } else {
+                        max[axis]
+                    }
+                };
+                self.transform
+                    .apply_point(Vec3f::new_with_data([pick(0), pick(1), pick(2)]))
+            })
+            .collect();
+
+        Some(Aabb::from_points(&corners))
+    }
+}
+
+#[derive(Clone)]
+pub struct CustomShape {
+    shape: Arc<dyn Shape + Send + Sync>,
+    offset: Vec3f,
+    material: Option<Material>,
+}
+
+impl CustomShape {
+    pub fn new(shape: Arc<dyn Shape + Send + Sync>) -> Self {
+        Self {
+            shape,
+            offset: Vec3f::new(0.0),
+            material: None,
+        }
+    }
+
+    pub const fn shape(&self) -> &Arc<dyn Shape + Send + Sync> {
+        &self.shape
+    }
+
+    pub const fn offset(&self) -> Vec3f {
+        self.offset
+    }
+}
+
+impl fmt::Debug for CustomShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomShape")
+            .field("offset", &self.offset)
+            .field("material", &self.get_material())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for CustomShape {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shape, &other.shape)
+            && self.offset == other.offset
+            && self.material == other.material
+    }
+}
+
+impl Intersectable for CustomShape {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        self.shape.ray_hit(origin - self.offset, direction)
+    }
+}
+
+impl Shape for CustomShape {
+    fn get_material(&self) -> Material {
+        self.material.unwrap_or_else(|| self.shape.get_material())
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = Some(material);
+    }
+
+    fn get_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.shape.get_normal(hit_point - self.offset)
+    }
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        self.shape.get_geometric_normal(hit_point - self.offset)
+    }
+
+    fn get_shadow_point(&self, hit_point: Vec3f) -> Vec3f {
+        self.shape.get_shadow_point(hit_point - self.offset) + self.offset
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        self.shape.signed_distance(point - self.offset)
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        self.offset += offset;
+    }
+
+    fn thickness(&self) -> f64 {
+        self.shape.thickness()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.shape
+            .bounds()
+            .map(|bounds| bounds.translated(self.offset))
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub enum ShapeType {
     Sphere(Sphere),
     BoxShape(BoxShape),
+    OrientedBox(OrientedBox),
     InfinityPlane(InfinityPlane),
+    Mesh(TriangleMesh),
+    Instance(Instance),
+    Sdf(SdfShape),
+    Heightfield(Heightfield),
+    Prism(Prism),
+    Billboard(Billboard),
+    Quad(Quad),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(CustomShape),
+}
+
+impl ShapeType {
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Sphere(_) => "sphere",
+            Self::BoxShape(_) => "box",
+            Self::OrientedBox(_) => "oriented_box",
+            Self::InfinityPlane(_) => "plane",
+            Self::Mesh(_) => "mesh",
+            Self::Instance(_) => "instance",
+            Self::Sdf(_) => "sdf",
+            Self::Heightfield(_) => "heightfield",
+            Self::Prism(_) => "prism",
+            Self::Billboard(_) => "billboard",
+            Self::Quad(_) => "quad",
+            Self::Custom(_) => "custom",
+        }
+    }
 }
 
 impl Shape for ShapeType {
@@ -233,7 +789,42 @@ impl Shape for ShapeType {
         match self {
             Self::Sphere(sphere) => sphere.get_material(),
             Self::BoxShape(box_shape) => box_shape.get_material(),
+            Self::OrientedBox(oriented) => oriented.get_material(),
             Self::InfinityPlane(plane) => plane.get_material(),
+            Self::Mesh(mesh) => mesh.get_material(),
+            Self::Instance(instance) => instance.get_material(),
+            Self::Sdf(sdf) => sdf.get_material(),
+            Self::Heightfield(heightfield) => heightfield.get_material(),
+            Self::Prism(prism) => prism.get_material(),
+            Self::Billboard(billboard) => billboard.get_material(),
+            Self::Quad(quad) => quad.get_material(),
+            Self::Custom(custom) => custom.get_material(),
+        }
+    }
+
+    fn get_material_at(&self, hit_point: Vec3f) -> Material {
+        match self {
+            Self::Instance(instance) => instance.get_material_at(hit_point),
+            Self::Billboard(billboard) => billboard.get_material_at(hit_point),
+            Self::Quad(quad) => quad.get_material_at(hit_point),
+            _ => self.get_material(),
+        }
+    }
+
+    fn set_material(&mut self, material: Material) {
+        match self {
+            Self::Sphere(sphere) => sphere.set_material(material),
+            Self::BoxShape(box_shape) => box_shape.set_material(material),
+            Self::OrientedBox(oriented) => oriented.set_material(material),
+            Self::InfinityPlane(plane) => plane.set_material(material),
+            Self::Mesh(mesh) => mesh.set_material(material),
+            Self::Instance(instance) => instance.set_material(material),
+            Self::Sdf(sdf) => sdf.set_material(material),
+            Self::Heightfield(heightfield) => heightfield.set_material(material),
+            Self::Prism(prism) => prism.set_material(material),
+            Self::Billboard(billboard) => billboard.set_material(material),
+            Self::Quad(quad) => quad.set_material(material),
+            Self::Custom(custom) => custom.set_material(material),
         }
     }
 
@@ -241,17 +832,246 @@ impl Shape for ShapeType {
         match self {
             Self::Sphere(sphere) => sphere.get_normal(hit_point),
             Self::BoxShape(box_shape) => box_shape.get_normal(hit_point),
+            Self::OrientedBox(oriented) => oriented.get_normal(hit_point),
             Self::InfinityPlane(plane) => plane.get_normal(hit_point),
+            Self::Mesh(mesh) => mesh.get_normal(hit_point),
+            Self::Instance(instance) => instance.get_normal(hit_point),
+            Self::Sdf(sdf) => sdf.get_normal(hit_point),
+            Self::Heightfield(heightfield) => heightfield.get_normal(hit_point),
+            Self::Prism(prism) => prism.get_normal(hit_point),
+            Self::Billboard(billboard) => billboard.get_normal(hit_point),
+            Self::Quad(quad) => quad.get_normal(hit_point),
+            Self::Custom(custom) => custom.get_normal(hit_point),
+        }
+    }
+
+    fn translate(&mut self, offset: Vec3f) {
+        match self {
+            Self::Sphere(sphere) => sphere.translate(offset),
+            Self::BoxShape(box_shape) => box_shape.translate(offset),
+            Self::OrientedBox(oriented) => oriented.translate(offset),
+            Self::InfinityPlane(plane) => plane.translate(offset),
+            Self::Mesh(mesh) => mesh.translate(offset),
+            Self::Instance(instance) => instance.translate(offset),
+            Self::Sdf(sdf) => sdf.translate(offset),
+            Self::Heightfield(heightfield) => heightfield.translate(offset),
+            Self::Prism(prism) => prism.translate(offset),
+            Self::Billboard(billboard) => billboard.translate(offset),
+            Self::Quad(quad) => quad.translate(offset),
+            Self::Custom(custom) => custom.translate(offset),
+        }
+    }
+
+    fn thickness(&self) -> f64 {
+        match self {
+            Self::Sphere(sphere) => sphere.thickness(),
+            Self::BoxShape(box_shape) => box_shape.thickness(),
+            Self::OrientedBox(oriented) => oriented.thickness(),
+            Self::InfinityPlane(plane) => plane.thickness(),
+            Self::Mesh(mesh) => mesh.thickness(),
+            Self::Instance(instance) => instance.thickness(),
+            Self::Sdf(sdf) => sdf.thickness(),
+            Self::Heightfield(heightfield) => heightfield.thickness(),
+            Self::Prism(prism) => prism.thickness(),
+            Self::Billboard(billboard) => billboard.thickness(),
+            Self::Quad(quad) => quad.thickness(),
+            Self::Custom(custom) => custom.thickness(),
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        match self {
+            Self::Sphere(sphere) => sphere.bounds(),
+            Self::BoxShape(box_shape) => box_shape.bounds(),
+            Self::OrientedBox(oriented) => oriented.bounds(),
+            Self::InfinityPlane(plane) => plane.bounds(),
+            Self::Mesh(mesh) => mesh.bounds(),
+            Self::Instance(instance) => instance.bounds(),
+            Self::Sdf(sdf) => sdf.bounds(),
+            Self::Heightfield(heightfield) => heightfield.bounds(),
+            Self::Prism(prism) => prism.bounds(),
+            Self::Billboard(billboard) => billboard.bounds(),
+            Self::Quad(quad) => quad.bounds(),
+            Self::Custom(custom) => custom.bounds(),
+        }
+    }
+
+    fn get_geometric_normal(&self, hit_point: Vec3f) -> Vec3f {
+        match self {
+            Self::Sphere(sphere) => sphere.get_geometric_normal(hit_point),
+            Self::BoxShape(box_shape) => box_shape.get_geometric_normal(hit_point),
+            Self::OrientedBox(oriented) => oriented.get_geometric_normal(hit_point),
+            Self::InfinityPlane(plane) => plane.get_geometric_normal(hit_point),
+            Self::Mesh(mesh) => mesh.get_geometric_normal(hit_point),
+            Self::Instance(instance) => instance.get_geometric_normal(hit_point),
+            Self::Sdf(sdf) => sdf.get_geometric_normal(hit_point),
+            Self::Heightfield(heightfield) => heightfield.get_geometric_normal(hit_point),
+            Self::Prism(prism) => prism.get_geometric_normal(hit_point),
+            Self::Billboard(billboard) => billboard.get_geometric_normal(hit_point),
+            Self::Quad(quad) => quad.get_geometric_normal(hit_point),
+            Self::Custom(custom) => custom.get_geometric_normal(hit_point),
+        }
+    }
+
+    fn get_shadow_point(&self, hit_point: Vec3f) -> Vec3f {
+        match self {
+            Self::Mesh(mesh) => mesh.get_shadow_point(hit_point),
+            Self::Instance(instance) => instance.get_shadow_point(hit_point),
+            Self::Custom(custom) => custom.get_shadow_point(hit_point),
+            Self::Sphere(_)
+            | Self::BoxShape(_)
+            | Self::OrientedBox(_)
+            | Self::InfinityPlane(_)
+            | Self::Sdf(_)
+            | Self::Heightfield(_)
+            | Self::Prism(_)
+            | Self::Billboard(_)
+            | Self::Quad(_) => hit_point,
+        }
+    }
+
+    fn signed_distance(&self, point: Vec3f) -> Option<f64> {
+        match self {
+            Self::Sphere(sphere) => sphere.signed_distance(point),
+            Self::BoxShape(box_shape) => box_shape.signed_distance(point),
+            Self::OrientedBox(oriented) => oriented.signed_distance(point),
+            Self::Instance(instance) => instance.signed_distance(point),
+            Self::Sdf(sdf) => sdf.signed_distance(point),
+            Self::Prism(prism) => prism.signed_distance(point),
+            Self::Custom(custom) => custom.signed_distance(point),
+            Self::InfinityPlane(_)
+            | Self::Mesh(_)
+            | Self::Heightfield(_)
+            | Self::Billboard(_)
+            | Self::Quad(_) => None,
         }
     }
 }
 
 impl Intersectable for ShapeType {
-    fn ray_intersect(&self, origin: Vec3f, direction: Vec3f) -> Option<f64> {
+    fn ray_hit(&self, origin: Vec3f, direction: Vec3f) -> Option<Hit> {
+        match self {
+            Self::Sphere(sphere) => sphere.ray_hit(origin, direction),
+            Self::BoxShape(box_shape) => box_shape.ray_hit(origin, direction),
+            Self::OrientedBox(oriented) => oriented.ray_hit(origin, direction),
+            Self::InfinityPlane(plane) => plane.ray_hit(origin, direction),
+            Self::Mesh(mesh) => mesh.ray_hit(origin, direction),
+            Self::Instance(instance) => instance.ray_hit(origin, direction),
+            Self::Sdf(sdf) => sdf.ray_hit(origin, direction),
+            Self::Heightfield(heightfield) => heightfield.ray_hit(origin, direction),
+            Self::Prism(prism) => prism.ray_hit(origin, direction),
+            Self::Billboard(billboard) => billboard.ray_hit(origin, direction),
+            Self::Quad(quad) => quad.ray_hit(origin, direction),
+            Self::Custom(custom) => custom.ray_hit(origin, direction),
+        }
+    }
+
+    fn ray_hit_inverse(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        inverse_direction: Vec3f,
+    ) -> Option<Hit> {
         match self {
-            Self::Sphere(sphere) => sphere.ray_intersect(origin, direction),
-            Self::BoxShape(box_shape) => box_shape.ray_intersect(origin, direction),
-            Self::InfinityPlane(plane) => plane.ray_intersect(origin, direction),
+            Self::BoxShape(box_shape) => {
+                box_shape.ray_hit_inverse(origin, direction, inverse_direction)
+            }
+            Self::Mesh(mesh) => mesh.ray_hit_inverse(origin, direction, inverse_direction),
+            _ => self.ray_hit(origin, direction),
+        }
+    }
+
+    fn intersects_any(&self, origin: Vec3f, direction: Vec3f, max_distance: f64) -> bool {
+        match self {
+            Self::Mesh(mesh) => mesh.intersects_any(origin, direction, max_distance),
+            Self::Instance(instance) => instance.intersects_any(origin, direction, max_distance),
+            _ => self
+                .ray_intersect(origin, direction)
+                .is_some_and(|distance| distance < max_distance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> BoxShape {
+        BoxShape::new(Vec3f::new(1.0), Vec3f::new(-1.0), RED_MATERIAL)
+    }
+
+    #[test]
+    fn box_hits_on_edges_and_corners_get_a_face_normal() {
+        let unit_box = unit_box();
+        for (origin, direction) in [
+            ([3.0, 2.0, 0.0], [-2.0, -1.0, 0.0]),
+            ([3.0, 3.0, 0.0], [-1.0, -1.0, 0.0]),
+            ([3.0, 3.0, 3.0], [-1.0, -1.0, -1.0]),
+            ([2.0, 3.0, 4.0], [-1.0, -2.0, -3.0]),
+        ] {
+            let origin = Vec3f::new_with_data(origin);
+            let direction = Vec3f::new_with_data(direction).normalize(None);
+            let Some(hit) = unit_box.ray_hit(origin, direction) else {
+                panic!("ray from {origin:?} missed the box");
+            };
+
+            let normal = hit.normal();
+            assert!((normal.length() - 1.0).abs() < 1e-12, "{origin:?}");
+            assert!(normal * direction < 0.0, "{origin:?}");
+            let point = origin + direction * hit.distance();
+            assert!((unit_box.get_normal(point).length() - 1.0).abs() < 1e-12);
         }
     }
+
+    #[test]
+    fn box_exit_normals_point_out_of_the_face_the_ray_leaves_through() {
+        let unit_box = unit_box();
+        let direction = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let Some(hit) = unit_box.ray_hit(Vec3f::new(0.0), direction) else {
+            panic!("ray from inside missed the box");
+        };
+
+        assert!((hit.distance() - 1.0).abs() < 1e-12);
+        assert_eq!(hit.normal(), direction);
+        assert_eq!(
+            unit_box.get_normal(Vec3f::new_with_data([0.25, -0.5, -1.0 + 1e-9])),
+            direction
+        );
+    }
+
+    #[test]
+    fn oriented_boxes_hit_the_rotated_face() {
+        let oriented = OrientedBox::new(
+            Vec3f::new_with_data([0.0, 0.0, -5.0]),
+            Vec3f::new_with_data([1.0, 1.0, 1.0]),
+            Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            std::f64::consts::FRAC_PI_4,
+            RED_MATERIAL,
+        );
+        let direction = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let Some(hit) = oriented.ray_hit(Vec3f::new(0.0), direction) else {
+            panic!("ray missed the oriented box");
+        };
+
+        // Turned 45 degrees about y, the box meets the ray with a vertical edge.
+        assert!((hit.distance() - (5.0 - std::f64::consts::SQRT_2)).abs() < 1e-9);
+        assert!((hit.normal().y()).abs() < 1e-12);
+        assert!((hit.normal().x().abs() - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9);
+
+        let point = Vec3f::new_with_data([0.0, 0.0, -5.0 + std::f64::consts::SQRT_2]);
+        assert!(
+            oriented
+                .signed_distance(point)
+                .is_some_and(|d| d.abs() < 1e-9)
+        );
+        let Some(bounds) = oriented.bounds() else {
+            panic!("oriented box has no bounds");
+        };
+        assert!((bounds.max().x() - std::f64::consts::SQRT_2).abs() < 1e-9);
+        assert!(
+            oriented
+                .ray_hit(Vec3f::new_with_data([1.5, 0.0, 0.0]), direction)
+                .is_none()
+        );
+    }
 }
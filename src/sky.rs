@@ -0,0 +1,325 @@
+use anyhow::{Result, bail};
+
+use crate::{PI, Vec3f, aov::cosine_hemisphere};
+
+pub const MIN_TURBIDITY: f64 = 1.7;
+pub const MAX_TURBIDITY: f64 = 10.0;
+
+/// Scales Preetham luminance, in kcd/m², into the renderer's radiance range: a clear
+/// midday zenith of about 8 kcd/m² comes out near 0.16, leaving the sun most of the light.
+const LUMINANCE_SCALE: f64 = 0.02;
+/// Angular radius of the visible sun disc in radians, about twice the real one so the
+/// disc survives low resolutions.
+const SUN_RADIUS: f64 = 0.01;
+/// How much brighter the sun disc is than the light the sun casts on a facing surface.
+const SUN_DISC_SCALE: f64 = 20.0;
+/// Fraction of the light reaching it that the ground below the horizon reflects.
+const GROUND_ALBEDO: f64 = 0.3;
+/// Keeps the Perez `B / cos(theta)` term finite at the horizon.
+const MIN_COS_THETA: f64 = 0.01;
+/// Samples per side of the grid integrating each face of the ambient cube.
+const AMBIENT_GRID: u32 = 16;
+
+/// Perez coefficients A to E for luminance Y and chromaticities x and y, each a
+/// `(slope, offset)` line in turbidity (Preetham et al. 1999, appendix A.2).
+const PEREZ: [[(f64, f64); 5]; 3] = [
+    [
+        (0.1787, -1.4630),
+        (-0.3554, 0.4275),
+        (-0.0227, 5.3251),
+        (0.1206, -2.5771),
+        (-0.0670, 0.3703),
+    ],
+    [
+        (-0.0193, -0.2592),
+        (-0.0665, 0.0008),
+        (-0.0004, 0.2125),
+        (-0.0641, -0.8989),
+        (-0.0033, 0.0452),
+    ],
+    [
+        (-0.0167, -0.2608),
+        (-0.0950, 0.0092),
+        (-0.0079, 0.2102),
+        (-0.0441, -1.6537),
+        (-0.0109, 0.0529),
+    ],
+];
+
+/// Zenith chromaticity x and y as cubics in the sun's zenith angle, one per power of
+/// turbidity from T² down to 1 (Preetham et al. 1999, appendix A.2).
+const ZENITH_CHROMATICITY: [[[f64; 4]; 3]; 2] = [
+    [
+        [0.00166, -0.00375, 0.00209, 0.0],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ],
+    [
+        [0.00275, -0.00610, 0.00317, 0.0],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ],
+];
+
+/// The Preetham analytic daylight model: sky radiance for a sun position and atmospheric
+/// turbidity, plus how much of the sun's light makes it through the atmosphere.
+///
+/// Elevation and azimuth are in degrees. Azimuth 0 puts the sun towards +z and 90 towards
+/// +x. The model is only fitted for a sun above the horizon; lower suns keep the sky of a
+/// sun on the horizon and cast no sunlight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SunSky {
+    elevation: f64,
+    azimuth: f64,
+    turbidity: f64,
+    sun: Vec3f,
+    perez: [[f64; 5]; 3],
+    scale: [f64; 3],
+    transmittance: f64,
+    ground: Vec3f,
+    ambient: [Vec3f; 6],
+}
+
+impl SunSky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Result<Self> {
+        if !(-90.0..=90.0).contains(&elevation) {
+            bail!("sun elevation must be between -90 and 90 degrees, got {elevation}");
+        }
+        if !azimuth.is_finite() {
+            bail!("sun azimuth must be finite, got {azimuth}");
+        }
+        if !(MIN_TURBIDITY..=MAX_TURBIDITY).contains(&turbidity) {
+            bail!("turbidity must be between {MIN_TURBIDITY} and {MAX_TURBIDITY}, got {turbidity}");
+        }
+
+        let (altitude, bearing) = (elevation.to_radians(), azimuth.to_radians());
+        let sun = Vec3f::new_with_data([
+            altitude.cos() * bearing.sin(),
+            altitude.sin(),
+            altitude.cos() * bearing.cos(),
+        ]);
+        let sun_zenith = (PI / 2.0 - altitude).min(PI / 2.0);
+        let perez =
+            PEREZ.map(|channel| channel.map(|(slope, offset)| slope.mul_add(turbidity, offset)));
+        let zenith = zenith_values(sun_zenith, turbidity);
+        // Dividing out the distribution at the zenith makes it reproduce the zenith values.
+        let scale = [0, 1, 2].map(|channel| {
+            zenith[channel] / perez_distribution(&perez[channel], 1.0, sun_zenith.cos())
+        });
+
+        let mut sky = Self {
+            elevation,
+            azimuth,
+            turbidity,
+            sun,
+            perez,
+            scale,
+            transmittance: sun_transmittance(elevation, turbidity),
+            ground: Vec3f::new(0.0),
+            ambient: [Vec3f::new(0.0); 6],
+        };
+        // The upward face only sees sky, so it can light the ground the other faces see.
+        let up = sky.integrate(Vec3f::new_with_data([0.0, 1.0, 0.0]));
+        sky.ground = (up + Vec3f::new(sky.transmittance * sun.y().max(0.0))) * GROUND_ALBEDO;
+        sky.ambient = [
+            [1.0, 0.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+        ]
+        .map(|axis| sky.integrate(Vec3f::new_with_data(axis)));
+
+        Ok(sky)
+    }
+
+    pub const fn elevation(&self) -> f64 {
+        self.elevation
+    }
+
+    pub const fn azimuth(&self) -> f64 {
+        self.azimuth
+    }
+
+    pub const fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    /// Unit vector pointing towards the sun.
+    pub const fn sun_direction(&self) -> Vec3f {
+        self.sun
+    }
+
+    /// Fraction of the sun's light left after crossing the atmosphere, zero once it sets.
+    pub const fn sun_transmittance(&self) -> f64 {
+        self.transmittance
+    }
+
+    /// Radiance arriving from `direction`, including the sun disc.
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        let direction = direction.unit();
+        let sky = self.sky_radiance(direction);
+        if direction * self.sun >= SUN_RADIUS.cos() {
+            sky + Vec3f::new(self.transmittance * SUN_DISC_SCALE)
+        } else {
+            sky
+        }
+    }
+
+    /// Cosine-weighted average sky radiance over the hemisphere around `normal`, without
+    /// the sun, blended from an ambient cube.
+    pub fn irradiance(&self, normal: Vec3f) -> Vec3f {
+        let normal = normal.unit();
+        (0..3).fold(Vec3f::new(0.0), |total, axis| {
+            let face = if normal[axis] >= 0.0 {
+                self.ambient[2 * axis]
+            } else {
+                self.ambient[2 * axis + 1]
+            };
+            total + face * (normal[axis] * normal[axis])
+        })
+    }
+
+    /// Sky radiance averaged over all directions, roughly.
+    pub fn average(&self) -> Vec3f {
+        self.ambient
+            .iter()
+            .fold(Vec3f::new(0.0), |total, &face| total + face)
+            / 6.0
+    }
+
+    fn sky_radiance(&self, direction: Vec3f) -> Vec3f {
+        if direction.y() < 0.0 {
+            return self.ground;
+        }
+
+        let cos_theta = direction.y().max(MIN_COS_THETA);
+        let cos_gamma = (direction * self.sun).clamp(-1.0, 1.0);
+        let [luminance, x, y] = [0, 1, 2].map(|channel| {
+            self.scale[channel] * perez_distribution(&self.perez[channel], cos_theta, cos_gamma)
+        });
+
+        xyy_to_rgb(luminance * LUMINANCE_SCALE, x, y)
+    }
+
+    fn integrate(&self, normal: Vec3f) -> Vec3f {
+        let cells = AMBIENT_GRID * AMBIENT_GRID;
+        (0..cells).fold(Vec3f::new(0.0), |total, cell| {
+            let u1 = (f64::from(cell / AMBIENT_GRID) + 0.5) / f64::from(AMBIENT_GRID);
+            let u2 = (f64::from(cell % AMBIENT_GRID) + 0.5) / f64::from(AMBIENT_GRID);
+            total + self.sky_radiance(cosine_hemisphere(normal, u1, u2).unit())
+        }) / f64::from(cells)
+    }
+}
+
+/// The Perez sky distribution for a view at zenith cosine `cos_theta` and sun angle cosine
+/// `cos_gamma`.
+fn perez_distribution(coefficients: &[f64; 5], cos_theta: f64, cos_gamma: f64) -> f64 {
+    let [horizon, gradient, circumsolar, width, backscatter] = *coefficients;
+    let gamma = cos_gamma.acos();
+
+    horizon.mul_add((gradient / cos_theta).exp(), 1.0)
+        * (backscatter * cos_gamma)
+            .mul_add(cos_gamma, circumsolar.mul_add((width * gamma).exp(), 1.0))
+}
+
+/// Zenith luminance and chromaticity for a sun at zenith angle `sun_zenith`.
+fn zenith_values(sun_zenith: f64, turbidity: f64) -> [f64; 3] {
+    let chi = (4.0 / 9.0 - turbidity / 120.0) * (-2.0f64).mul_add(sun_zenith, PI);
+    let luminance = 4.0453f64
+        .mul_add(turbidity, -4.9710)
+        .mul_add(chi.tan(), (-0.2155f64).mul_add(turbidity, 2.4192));
+    let [x, y] = ZENITH_CHROMATICITY.map(|rows| {
+        let [squared, linear, constant] = rows.map(|[c3, c2, c1, c0]| {
+            c3.mul_add(sun_zenith, c2)
+                .mul_add(sun_zenith, c1)
+                .mul_add(sun_zenith, c0)
+        });
+        squared
+            .mul_add(turbidity, linear)
+            .mul_add(turbidity, constant)
+    });
+
+    [luminance, x, y]
+}
+
+/// Direct sunlight left after the atmosphere, from the Kasten-Young relative air mass and
+/// the Linke turbidity beam model of the ESRA clear-sky model.
+fn sun_transmittance(elevation: f64, turbidity: f64) -> f64 {
+    if elevation <= 0.0 {
+        return 0.0;
+    }
+
+    let air_mass = 1.0
+        / 0.50572_f64.mul_add(
+            (elevation + 6.07995).powf(-1.6364),
+            elevation.to_radians().sin(),
+        );
+    let rayleigh_depth = if air_mass <= 20.0 {
+        1.0 / (-0.00013_f64)
+            .mul_add(air_mass, 0.0065)
+            .mul_add(air_mass, -0.1202)
+            .mul_add(air_mass, 1.7513)
+            .mul_add(air_mass, 6.6296)
+    } else {
+        1.0 / 0.718_f64.mul_add(air_mass, 10.4)
+    };
+
+    (-0.8662 * turbidity * air_mass * rayleigh_depth).exp()
+}
+
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Vec3f {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    Vec3f::new_with_data([
+        3.2406_f64.mul_add(big_x, (-1.5372_f64).mul_add(luminance, -0.4986 * big_z)),
+        (-0.9689_f64).mul_add(big_x, 1.8758_f64.mul_add(luminance, 0.0415 * big_z)),
+        0.0557_f64.mul_add(big_x, (-0.2040_f64).mul_add(luminance, 1.0570 * big_z)),
+    ])
+    .max(Vec3f::new(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_skies_are_blue_overhead_and_brighten_towards_the_sun() -> Result<()> {
+        let sky = SunSky::new(30.0, 90.0, 2.5)?;
+        assert!((sky.sun_direction().length() - 1.0).abs() < 1e-12);
+        assert!(sky.sun_direction().x() > 0.8);
+
+        let zenith = sky.radiance(Vec3f::new_with_data([0.0, 1.0, 0.0]));
+        assert!(zenith.z() > zenith.x(), "{zenith:?}");
+
+        let towards = sky.radiance(Vec3f::new_with_data([1.0, 0.2, 0.0]));
+        let away = sky.radiance(Vec3f::new_with_data([-1.0, 0.2, 0.0]));
+        assert!(towards.y() > away.y());
+        assert!(sky.radiance(sky.sun_direction()).y() > towards.y() * 2.0);
+
+        let sunward = sky.irradiance(Vec3f::new_with_data([1.0, 0.0, 0.0]));
+        let shaded = sky.irradiance(Vec3f::new_with_data([-1.0, 0.0, 0.0]));
+        assert!(sunward.y() > shaded.y());
+        assert!(sky.average().y() > 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn the_sun_dims_towards_the_horizon_and_in_hazy_air() -> Result<()> {
+        let noon = SunSky::new(80.0, 0.0, 3.0)?.sun_transmittance();
+        let evening = SunSky::new(5.0, 0.0, 3.0)?.sun_transmittance();
+        let hazy = SunSky::new(80.0, 0.0, 8.0)?.sun_transmittance();
+
+        assert!(noon > 0.6 && noon < 1.0, "{noon}");
+        assert!(evening < noon * 0.5, "{evening}");
+        assert!(hazy < noon);
+        assert!(SunSky::new(-5.0, 0.0, 3.0)?.sun_transmittance().abs() < f64::EPSILON);
+
+        assert!(SunSky::new(100.0, 0.0, 3.0).is_err());
+        assert!(SunSky::new(45.0, 0.0, 0.5).is_err());
+        Ok(())
+    }
+}
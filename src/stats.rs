@@ -0,0 +1,205 @@
+use std::{
+    cell::Cell,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::overlay;
+
+const HUD_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+thread_local! {
+    static TILE_COUNTS: Cell<RayCounts> = const { Cell::new(RayCounts::ZERO) };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RayCounts {
+    rays: u64,
+    shadow_rays: u64,
+    bvh_visits: u64,
+}
+
+impl RayCounts {
+    pub const ZERO: Self = Self {
+        rays: 0,
+        shadow_rays: 0,
+        bvh_visits: 0,
+    };
+
+    pub const fn rays(&self) -> u64 {
+        self.rays
+    }
+
+    pub const fn shadow_rays(&self) -> u64 {
+        self.shadow_rays
+    }
+
+    pub const fn bvh_visits(&self) -> u64 {
+        self.bvh_visits
+    }
+
+    pub const fn since(self, earlier: Self) -> Self {
+        Self {
+            rays: self.rays.saturating_sub(earlier.rays),
+            shadow_rays: self.shadow_rays.saturating_sub(earlier.shadow_rays),
+            bvh_visits: self.bvh_visits.saturating_sub(earlier.bvh_visits),
+        }
+    }
+}
+
+/// Ray counts accumulated over every tile one renderer has finished.
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    rays: AtomicU64,
+    shadow_rays: AtomicU64,
+    bvh_visits: AtomicU64,
+}
+
+impl RenderStats {
+    pub const fn new() -> Self {
+        Self {
+            rays: AtomicU64::new(0),
+            shadow_rays: AtomicU64::new(0),
+            bvh_visits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add(&self, counts: RayCounts) {
+        self.rays.fetch_add(counts.rays, Ordering::Relaxed);
+        self.shadow_rays
+            .fetch_add(counts.shadow_rays, Ordering::Relaxed);
+        self.bvh_visits
+            .fetch_add(counts.bvh_visits, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> RayCounts {
+        RayCounts {
+            rays: self.rays.load(Ordering::Relaxed),
+            shadow_rays: self.shadow_rays.load(Ordering::Relaxed),
+            bvh_visits: self.bvh_visits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn update_tile(update: impl FnOnce(&mut RayCounts)) {
+    TILE_COUNTS.with(|counts| {
+        let mut tile = counts.get();
+        update(&mut tile);
+        counts.set(tile);
+    });
+}
+
+pub fn count_ray() {
+    update_tile(|counts| counts.rays += 1);
+}
+
+pub fn count_shadow_ray() {
+    update_tile(|counts| counts.shadow_rays += 1);
+}
+
+pub fn count_bvh_visits(visits: u64) {
+    update_tile(|counts| counts.bvh_visits += visits);
+}
+
+/// Returns what this thread traced since the last call, for the caller to add to the
+/// [`RenderStats`] of the render it belongs to.
+pub fn finish_tile() -> RayCounts {
+    TILE_COUNTS.with(|counts| counts.replace(RayCounts::ZERO))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    render_time: Duration,
+    counts: RayCounts,
+}
+
+impl FrameStats {
+    pub const fn new(render_time: Duration, counts: RayCounts) -> Self {
+        Self {
+            render_time,
+            counts,
+        }
+    }
+
+    pub fn since(start: Instant, stats: &RenderStats, before: RayCounts) -> Self {
+        Self::new(start.elapsed(), stats.counts().since(before))
+    }
+
+    pub const fn render_time(&self) -> Duration {
+        self.render_time
+    }
+
+    pub const fn counts(&self) -> RayCounts {
+        self.counts
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rays_per_second(&self) -> f64 {
+        self.counts.rays as f64 / self.render_time.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn lines(&self) -> [String; 4] {
+        [
+            format!("frame {:.1} ms", self.render_time.as_secs_f64() * 1000.0),
+            format!(
+                "rays {} shadow {}",
+                self.counts.rays, self.counts.shadow_rays
+            ),
+            format!("bvh visits {}", self.counts.bvh_visits),
+            format!("{:.2} mrays/s", self.rays_per_second() / 1e6),
+        ]
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ms, {} rays ({} shadow), {} BVH node visits, {:.2} Mrays/s",
+            self.render_time.as_millis(),
+            self.counts.rays,
+            self.counts.shadow_rays,
+            self.counts.bvh_visits,
+            self.rays_per_second() / 1e6
+        )
+    }
+}
+
+pub fn draw_hud(frame: &mut [u8], size: (u32, u32), stats: &FrameStats) {
+    let scale = (size.1 / 240).max(1);
+    overlay::draw_panel(frame, size, (0, 0), &stats.lines(), scale, HUD_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Vec3f, lights::init_default_lights, scene::Scene, shapes::init_default_shapes};
+
+    #[test]
+    fn traced_rays_are_counted_and_drawn_on_the_hud() {
+        let scene = Scene::new(init_default_shapes(), init_default_lights());
+        let start = Instant::now();
+        let stats = RenderStats::new();
+        for step in 0..16 {
+            let x = f64::from(step).mul_add(0.05, -0.4);
+            scene.trace(
+                Vec3f::new(0.0),
+                Vec3f::new_with_data([x, -0.1, -1.0]).normalize(None),
+            );
+        }
+        stats.add(finish_tile());
+
+        let stats = FrameStats::since(start, &stats, RayCounts::ZERO);
+        assert!(stats.counts().rays() >= 16);
+        assert!(stats.counts().shadow_rays() > 0);
+        assert!(stats.counts().bvh_visits() > 0);
+        assert!(stats.rays_per_second() > 0.0);
+        assert!(stats.to_string().contains("BVH node visits"));
+
+        let size = (64, 48);
+        let mut frame = vec![0; 64 * 48 * 4];
+        draw_hud(&mut frame, size, &stats);
+        assert!(frame.chunks_exact(4).any(|pixel| pixel == HUD_COLOR));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::info;
+
+use crate::{
+    BLUE_MATERIAL, FOV, GREEN_MATERIAL, RED_MATERIAL, Vec3f,
+    camera::Camera,
+    lights::init_default_lights,
+    materials::{Material, exponent_for_roughness},
+    output, overlay,
+    renderer::Renderer,
+    scene::Scene,
+    settings::RenderSettings,
+    shapes::{InfinityPlane, ShapeType, Sphere},
+};
+
+const MAX_VALUES: usize = 16;
+const SUBJECT_CENTER: Vec3f = Vec3f::const_new_with_data([0.0, 0.0, -4.0]);
+const SUBJECT_RADIUS: f64 = 1.2;
+const BACKDROP: [([f64; 3], Material); 2] = [
+    ([-2.0, 0.5, -9.0], RED_MATERIAL),
+    ([2.0, 0.5, -9.0], BLUE_MATERIAL),
+];
+const LABEL_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepParameter {
+    Diffuse,
+    Specular,
+    Reflect,
+    Refract,
+    Shininess,
+    Roughness,
+    Ior,
+}
+
+impl SweepParameter {
+    pub const ALL: [Self; 7] = [
+        Self::Diffuse,
+        Self::Specular,
+        Self::Reflect,
+        Self::Refract,
+        Self::Shininess,
+        Self::Roughness,
+        Self::Ior,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Diffuse => "diffuse",
+            Self::Specular => "specular",
+            Self::Reflect => "reflect",
+            Self::Refract => "refract",
+            Self::Shininess => "shininess",
+            Self::Roughness => "roughness",
+            Self::Ior => "ior",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|parameter| parameter.name() == name)
+            .ok_or_else(|| anyhow!("Unknown sweep parameter: {name}"))
+    }
+
+    fn check(self, value: f64) -> Result<()> {
+        let valid = match self {
+            Self::Roughness => (0.0..=1.0).contains(&value),
+            Self::Ior => value > 0.0,
+            _ => value >= 0.0,
+        };
+        if !(valid && value.is_finite()) {
+            bail!("Invalid {} value {value}", self.name());
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(self, material: Material, value: f64) -> Material {
+        let with_albedo = |channel: usize| {
+            let mut albedo = material.albedo();
+            albedo[channel] = value;
+            material.with_albedo(albedo)
+        };
+
+        match self {
+            Self::Diffuse => with_albedo(0),
+            Self::Specular => with_albedo(1),
+            Self::Reflect => with_albedo(2),
+            Self::Refract => with_albedo(3),
+            Self::Shininess => material.with_specular_exponent(value),
+            Self::Roughness => material.with_specular_exponent(exponent_for_roughness(value)),
+            Self::Ior => material.with_refractive_index(value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepAxis {
+    parameter: SweepParameter,
+    values: Vec<f64>,
+}
+
+fn parse_number(text: &str) -> Result<f64> {
+    text.parse()
+        .with_context(|| format!("Invalid sweep value: {text}"))
+}
+
+impl FromStr for SweepAxis {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, values) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Sweep must look like name=start:end:count or name=a,b,c"))?;
+        let parameter = SweepParameter::from_name(name)?;
+
+        let values = if let [start, end, count] = values.split(':').collect::<Vec<_>>()[..] {
+            let (start, end) = (parse_number(start)?, parse_number(end)?);
+            let count: u32 = count
+                .parse()
+                .with_context(|| format!("Invalid sweep count: {count}"))?;
+            let steps = f64::from(count.saturating_sub(1).max(1));
+            (0..count)
+                .map(|step| start + (end - start) * f64::from(step) / steps)
+                .collect()
+        } else {
+            values
+                .split(',')
+                .map(parse_number)
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        if values.is_empty() || values.len() > MAX_VALUES {
+            bail!(
+                "Sweeps take between 1 and {MAX_VALUES} values, got {}",
+                values.len()
+            );
+        }
+        for &value in &values {
+            parameter.check(value)?;
+        }
+
+        Ok(Self { parameter, values })
+    }
+}
+
+impl SweepAxis {
+    pub const fn parameter(&self) -> SweepParameter {
+        self.parameter
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepCell {
+    material: Material,
+    label: String,
+}
+
+impl SweepCell {
+    pub const fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+fn sweep_scene(material: Material) -> Scene {
+    let mut shapes = vec![
+        ShapeType::Sphere(Sphere::new(SUBJECT_CENTER, SUBJECT_RADIUS, material)),
+        ShapeType::InfinityPlane(InfinityPlane::new(
+            Vec3f::new_with_data([0.0, -SUBJECT_RADIUS, 0.0]),
+            Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            GREEN_MATERIAL,
+        )),
+    ];
+    shapes.extend(BACKDROP.map(|(center, material)| {
+        ShapeType::Sphere(Sphere::new(Vec3f::new_with_data(center), 1.0, material))
+    }));
+
+    Scene::new(shapes, init_default_lights())
+}
+
+fn sweep_cells(base: Material, axes: &[SweepAxis]) -> Result<(u32, Vec<SweepCell>)> {
+    let (columns, rows) = match axes {
+        [columns] => (columns, None),
+        [columns, rows] => (columns, Some(rows)),
+        _ => bail!("Material sweeps take one or two parameters"),
+    };
+    let row_values = rows.map_or_else(
+        || vec![None],
+        |rows| {
+            rows.values
+                .iter()
+                .map(|&value| Some((rows.parameter, value)))
+                .collect()
+        },
+    );
+
+    let cells = row_values
+        .into_iter()
+        .flat_map(|row| {
+            columns.values.iter().map(move |&value| {
+                let mut material = columns.parameter.apply(base, value);
+                let mut label = format!("{} {value:.2}", columns.parameter.name());
+                if let Some((parameter, value)) = row {
+                    material = parameter.apply(material, value);
+                    label = format!("{label} {} {value:.2}", parameter.name());
+                }
+                SweepCell { material, label }
+            })
+        })
+        .collect();
+
+    Ok((u32::try_from(columns.values.len())?, cells))
+}
+
+pub fn render_sweep(
+    settings: &RenderSettings,
+    base: Material,
+    axes: &[SweepAxis],
+    (width, height): (u32, u32),
+    path: &Path,
+) -> Result<Vec<SweepCell>> {
+    let (columns, cells) = sweep_cells(base, axes)?;
+    let rows = u32::try_from(cells.len())?.div_ceil(columns);
+    let (grid_width, grid_height) = (width * columns, height * rows);
+    let row_bytes = width as usize * 4;
+    let grid_row_bytes = grid_width as usize * 4;
+    let camera = Camera::new(Vec3f::new(0.0), FOV);
+    let scale = (height / 120).max(1);
+
+    let mut grid = vec![0; grid_width as usize * grid_height as usize * 4];
+    let mut cell = vec![0; row_bytes * height as usize];
+
+    for (index, sweep_cell) in (0..).zip(&cells) {
+        let scene = sweep_scene(sweep_cell.material);
+        Renderer::new(scene, settings.clone(), width, height, camera).render_into(&mut cell)?;
+        overlay::draw_panel(
+            &mut cell,
+            (width, height),
+            (0, 0),
+            std::slice::from_ref(&sweep_cell.label),
+            scale,
+            LABEL_COLOR,
+        );
+        info!("Rendered {}", sweep_cell.label);
+
+        let (left, top) = (index % columns * width, index / columns * height);
+        for (row, cell_row) in (0..).zip(cell.chunks_exact(row_bytes)) {
+            let start = (top + row) as usize * grid_row_bytes + left as usize * 4;
+            grid[start..start + row_bytes].copy_from_slice(cell_row);
+        }
+    }
+
+    output::write_png(path, grid_width, grid_height, &grid)?;
+
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GLASS_MATERIAL;
+
+    #[test]
+    fn sweeps_vary_one_parameter_per_axis() -> Result<()> {
+        let ior: SweepAxis = "ior=1:2:3".parse()?;
+        let roughness: SweepAxis = "roughness=0.1,0.5".parse()?;
+        assert_eq!(ior.values(), [1.0, 1.5, 2.0]);
+        assert!("gloss=1,2".parse::<SweepAxis>().is_err());
+        assert_eq!(
+            "roughness=0:1:3".parse::<SweepAxis>()?.values(),
+            [0.0, 0.5, 1.0]
+        );
+        assert!("roughness=0:1.5:3".parse::<SweepAxis>().is_err());
+
+        let (columns, cells) = sweep_cells(GLASS_MATERIAL, &[ior.clone(), roughness.clone()])?;
+        assert_eq!((columns, cells.len()), (3, 6));
+        assert_eq!(cells[4].label(), "ior 1.50 roughness 0.50");
+        assert_eq!(
+            cells[4].material(),
+            GLASS_MATERIAL
+                .with_refractive_index(1.5)
+                .with_specular_exponent(exponent_for_roughness(0.5))
+        );
+        assert!(sweep_cells(GLASS_MATERIAL, &[ior.clone(), roughness, ior.clone()]).is_err());
+
+        let path = std::env::temp_dir().join(format!("sweep-{}.png", std::process::id()));
+        render_sweep(
+            &RenderSettings::default(),
+            GLASS_MATERIAL,
+            &[ior],
+            (8, 6),
+            &path,
+        )?;
+        let (width, height, _) = output::read_rgba_png(&path)?;
+        assert_eq!((width, height), (24, 6));
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
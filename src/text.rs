@@ -0,0 +1,265 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    Vec3f,
+    materials::Material,
+    mesh::TriangleMesh,
+    overlay::{GLYPH_HEIGHT, GLYPH_SPACING, GLYPH_WIDTH, glyph},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextLabel {
+    text: String,
+    position: Vec3f,
+    right: Vec3f,
+    up: Vec3f,
+    size: f64,
+    depth: f64,
+}
+
+#[derive(Default)]
+struct Geometry {
+    positions: Vec<Vec3f>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl Geometry {
+    fn quad(&mut self, corners: [Vec3f; 4]) {
+        let first = self.positions.len();
+        self.positions.extend(corners);
+        self.indices.push([first, first + 1, first + 2]);
+        self.indices.push([first, first + 2, first + 3]);
+    }
+}
+
+fn glyph_runs(bits: u8) -> impl Iterator<Item = (u32, u32)> {
+    let mut column = 0;
+    std::iter::from_fn(move || {
+        let lit = |column: u32| bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0;
+        while column < GLYPH_WIDTH && !lit(column) {
+            column += 1;
+        }
+        let start = column;
+        while column < GLYPH_WIDTH && lit(column) {
+            column += 1;
+        }
+        (start < column).then_some((start, column))
+    })
+}
+
+impl TextLabel {
+    pub fn new(
+        text: impl Into<String>,
+        position: Vec3f,
+        right: Vec3f,
+        up: Vec3f,
+        size: f64,
+    ) -> Self {
+        Self {
+            text: text.into(),
+            position,
+            right,
+            up,
+            size,
+            depth: 0.0,
+        }
+    }
+
+    pub const fn with_depth(mut self, depth: f64) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub const fn position(&self) -> Vec3f {
+        self.position
+    }
+
+    pub const fn right(&self) -> Vec3f {
+        self.right
+    }
+
+    pub const fn up(&self) -> Vec3f {
+        self.up
+    }
+
+    pub const fn size(&self) -> f64 {
+        self.size
+    }
+
+    pub const fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    pub fn translate(&mut self, offset: Vec3f) {
+        self.position += offset;
+    }
+
+    fn frame(&self) -> Result<[Vec3f; 3]> {
+        if !(self.size > 0.0 && self.size.is_finite()) {
+            bail!("Text size must be positive, got {}", self.size);
+        }
+        if !(self.depth >= 0.0 && self.depth.is_finite()) {
+            bail!("Text depth must not be negative, got {}", self.depth);
+        }
+
+        let normal = self.right.cross(&self.up);
+        if !normal.length().is_normal() {
+            bail!("Text right and up directions must not be parallel");
+        }
+        let right = self.right.unit();
+        let normal = normal.unit();
+        Ok([right, normal.cross(&right), normal])
+    }
+
+    pub fn mesh(&self, material: Material) -> Result<TriangleMesh> {
+        let [right, up, normal] = self.frame()?;
+        let pixel = self.size / f64::from(GLYPH_HEIGHT);
+        let point = |x: u32, y: u32, z: f64| {
+            self.position
+                + right * (f64::from(x) * pixel)
+                + up * (f64::from(y) * pixel)
+                + normal * z
+        };
+
+        let mut geometry = Geometry::default();
+        for (index, character) in (0..).zip(self.text.chars()) {
+            let left = index * (GLYPH_WIDTH + GLYPH_SPACING);
+            for (row, bits) in (0..).zip(glyph(character)) {
+                let (bottom, top) = (GLYPH_HEIGHT - 1 - row, GLYPH_HEIGHT - row);
+                for (start, end) in glyph_runs(bits) {
+                    let (x0, x1) = (left + start, left + end);
+                    geometry.quad([
+                        point(x0, bottom, 0.0),
+                        point(x1, bottom, 0.0),
+                        point(x1, top, 0.0),
+                        point(x0, top, 0.0),
+                    ]);
+                    if self.depth > 0.0 {
+                        self.extrude(&mut geometry, &point, (x0, x1), (bottom, top));
+                    }
+                }
+            }
+        }
+
+        if geometry.indices.is_empty() {
+            bail!("Text '{}' has no visible glyphs", self.text);
+        }
+        let mesh = TriangleMesh::from_triangles(
+            geometry.positions,
+            &geometry.indices,
+            PathBuf::from(format!("{}.obj", self.text)),
+            material,
+        )?;
+        Ok(mesh.with_smooth_shading(false).with_label(self.clone()))
+    }
+
+    fn extrude(
+        &self,
+        geometry: &mut Geometry,
+        point: &impl Fn(u32, u32, f64) -> Vec3f,
+        (x0, x1): (u32, u32),
+        (bottom, top): (u32, u32),
+    ) {
+        let back = -self.depth;
+        geometry.quad([
+            point(x0, bottom, back),
+            point(x0, top, back),
+            point(x1, top, back),
+            point(x1, bottom, back),
+        ]);
+        geometry.quad([
+            point(x0, bottom, 0.0),
+            point(x0, bottom, back),
+            point(x1, bottom, back),
+            point(x1, bottom, 0.0),
+        ]);
+        geometry.quad([
+            point(x0, top, 0.0),
+            point(x1, top, 0.0),
+            point(x1, top, back),
+            point(x0, top, back),
+        ]);
+        geometry.quad([
+            point(x0, bottom, 0.0),
+            point(x0, top, 0.0),
+            point(x0, top, back),
+            point(x0, bottom, back),
+        ]);
+        geometry.quad([
+            point(x1, bottom, 0.0),
+            point(x1, bottom, back),
+            point(x1, top, back),
+            point(x1, top, 0.0),
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RED_MATERIAL,
+        shapes::{Intersectable, Shape, ShapeType},
+    };
+
+    #[test]
+    fn labels_become_flat_or_extruded_glyph_geometry() -> Result<()> {
+        let label = TextLabel::new(
+            "HI",
+            Vec3f::new_with_data([0.0, 0.0, -5.0]),
+            Vec3f::new_with_data([1.0, 0.0, 0.0]),
+            Vec3f::new_with_data([0.0, 1.0, 0.0]),
+            0.7,
+        );
+        let flat = label.mesh(RED_MATERIAL)?;
+        let solid = label.clone().with_depth(0.2).mesh(RED_MATERIAL)?;
+        assert_eq!(solid.triangles().count(), flat.triangles().count() * 6);
+        assert_eq!(flat.label(), Some(&label));
+
+        let mut moved = ShapeType::Mesh(flat.clone());
+        moved.translate(Vec3f::new_with_data([0.0, 1.0, 0.0]));
+        let ShapeType::Mesh(moved) = moved else {
+            panic!("translating should keep the mesh");
+        };
+        assert_eq!(
+            moved.label().map(TextLabel::position),
+            Some(Vec3f::new_with_data([0.0, 1.0, -5.0]))
+        );
+
+        let toward = |x: f64, y: f64| Vec3f::new_with_data([x, y, -5.0]).normalize(None);
+        let stem = flat
+            .ray_intersect(Vec3f::new(0.0), toward(0.05, 0.35))
+            .ok_or_else(|| anyhow::anyhow!("ray should hit the stem of the H"))?;
+        let hit = toward(0.05, 0.35) * stem;
+        assert!((hit.z() + 5.0).abs() < 1e-9);
+        assert!((flat.get_normal(hit) - Vec3f::new_with_data([0.0, 0.0, 1.0])).length() < 1e-9);
+        assert!(
+            flat.ray_intersect(Vec3f::new(0.0), toward(0.25, 0.65))
+                .is_none()
+        );
+        assert!(
+            solid
+                .ray_intersect(Vec3f::new(0.0), toward(0.05, 0.35))
+                .is_some()
+        );
+
+        assert!(
+            TextLabel::new("   ", Vec3f::new(0.0), label.right(), label.up(), 1.0)
+                .mesh(RED_MATERIAL)
+                .is_err()
+        );
+        assert!(
+            TextLabel::new("A", Vec3f::new(0.0), label.right(), label.right(), 1.0)
+                .mesh(RED_MATERIAL)
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::Vec3f;
+
+const NOISE_OCTAVES: u32 = 3;
+const NOISE_FLOOR: f64 = 0.6;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GridSource {
+    Noise {
+        resolution: usize,
+        frequency: f64,
+        seed: u32,
+    },
+    File(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DensityGrid {
+    source: GridSource,
+    resolution: [usize; 3],
+    values: Vec<f64>,
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn lattice_value(cell: [f64; 3], seed: u32) -> f64 {
+    let [x, y, z] = cell.map(|coordinate| coordinate as u64);
+    let mut hash = x.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ y.wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ z.wrapping_mul(0x1656_67b1_9e37_79f9)
+        ^ u64::from(seed);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+fn smoothstep(value: f64) -> f64 {
+    value * value * 2.0f64.mul_add(-value, 3.0)
+}
+
+fn lerp(from: f64, to: f64, amount: f64) -> f64 {
+    (to - from).mul_add(amount, from)
+}
+
+fn value_noise(point: [f64; 3], seed: u32) -> f64 {
+    let cell = point.map(f64::floor);
+    let [fx, fy, fz] = [0, 1, 2].map(|axis| smoothstep(point[axis] - cell[axis]));
+    let corner =
+        |dx: f64, dy: f64, dz: f64| lattice_value([cell[0] + dx, cell[1] + dy, cell[2] + dz], seed);
+
+    let bottom = lerp(
+        lerp(corner(0.0, 0.0, 0.0), corner(1.0, 0.0, 0.0), fx),
+        lerp(corner(0.0, 1.0, 0.0), corner(1.0, 1.0, 0.0), fx),
+        fy,
+    );
+    let top = lerp(
+        lerp(corner(0.0, 0.0, 1.0), corner(1.0, 0.0, 1.0), fx),
+        lerp(corner(0.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), fx),
+        fy,
+    );
+
+    lerp(bottom, top, fz)
+}
+
+pub fn fractal_noise(point: [f64; 3], seed: u32) -> f64 {
+    let (sum, weight) = (0..NOISE_OCTAVES).fold((0.0, 0.0), |(sum, weight), octave| {
+        let scale = f64::from(1_u32 << octave);
+        let amplitude = scale.recip();
+        let noise = value_noise(point.map(|coordinate| coordinate * scale), seed + octave);
+        (noise.mul_add(amplitude, sum), weight + amplitude)
+    });
+
+    sum / weight
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn cell_center(index: usize, resolution: usize) -> f64 {
+    (index as f64 + 0.5) / resolution as f64
+}
+
+impl DensityGrid {
+    pub fn noise(resolution: usize, frequency: f64, seed: u32) -> Self {
+        let resolution = resolution.max(1);
+        let mut values = Vec::with_capacity(resolution.pow(3));
+        for z in 0..resolution {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let local = [x, y, z].map(|index| cell_center(index, resolution));
+                    let radius = local
+                        .iter()
+                        .map(|coordinate| (coordinate - 0.5) * 2.0)
+                        .map(|offset| offset * offset)
+                        .sum::<f64>()
+                        .sqrt();
+                    let noise = fractal_noise(local.map(|coordinate| coordinate * frequency), seed);
+                    values.push((noise + NOISE_FLOOR - radius).clamp(0.0, 1.0));
+                }
+            }
+        }
+
+        Self {
+            source: GridSource::Noise {
+                resolution,
+                frequency,
+                seed,
+            },
+            resolution: [resolution; 3],
+            values,
+        }
+    }
+
+    pub fn load(path: &Path, source: PathBuf) -> Result<Self> {
+        let grid = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read density grid {}", path.display()))?;
+        Self::parse(&grid, source)
+            .with_context(|| format!("Failed to parse density grid {}", path.display()))
+    }
+
+    pub fn parse(grid: &str, source: PathBuf) -> Result<Self> {
+        let mut tokens = grid
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(str::split_whitespace);
+
+        let mut resolution = [0; 3];
+        for (axis, size) in resolution.iter_mut().enumerate() {
+            let token = tokens
+                .next()
+                .with_context(|| format!("Missing grid size for axis {axis}"))?;
+            *size = token
+                .parse()
+                .with_context(|| format!("Invalid grid size '{token}'"))?;
+            if *size == 0 {
+                bail!("Grid size for axis {axis} must be at least 1");
+            }
+        }
+
+        let values = tokens
+            .map(|token| {
+                token
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid density '{token}'"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let expected = resolution.iter().product::<usize>();
+        if values.len() != expected {
+            bail!("Expected {expected} densities, found {}", values.len());
+        }
+        if values.iter().any(|value| value.is_nan() || *value < 0.0) {
+            bail!("Densities must not be negative");
+        }
+
+        Ok(Self {
+            source: GridSource::File(source),
+            resolution,
+            values,
+        })
+    }
+
+    pub const fn source(&self) -> &GridSource {
+        &self.source
+    }
+
+    fn value(&self, [x, y, z]: [usize; 3]) -> f64 {
+        let [width, height, _] = self.resolution;
+        self.values[(z * height + y) * width + x]
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn sample(&self, local: [f64; 3]) -> f64 {
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut amount = [0.0; 3];
+        for axis in 0..3 {
+            let last = self.resolution[axis] - 1;
+            let position = local[axis]
+                .mul_add(self.resolution[axis] as f64, -0.5)
+                .clamp(0.0, last as f64);
+            lower[axis] = position.floor() as usize;
+            upper[axis] = (lower[axis] + 1).min(last);
+            amount[axis] = position - position.floor();
+        }
+
+        let corner = |pick: [bool; 3]| {
+            self.value([0, 1, 2].map(|axis| if pick[axis] { upper[axis] } else { lower[axis] }))
+        };
+        let bottom = lerp(
+            lerp(
+                corner([false, false, false]),
+                corner([true, false, false]),
+                amount[0],
+            ),
+            lerp(
+                corner([false, true, false]),
+                corner([true, true, false]),
+                amount[0],
+            ),
+            amount[1],
+        );
+        let top = lerp(
+            lerp(
+                corner([false, false, true]),
+                corner([true, false, true]),
+                amount[0],
+            ),
+            lerp(
+                corner([false, true, true]),
+                corner([true, true, true]),
+                amount[0],
+            ),
+            amount[1],
+        );
+
+        lerp(bottom, top, amount[2])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Volume {
+    min_point: Vec3f,
+    max_point: Vec3f,
+    density: f64,
+    albedo: Vec3f,
+    grid: Arc<DensityGrid>,
+}
+
+impl Volume {
+    pub fn new(min_point: Vec3f, max_point: Vec3f, grid: DensityGrid) -> Self {
+        Self {
+            min_point,
+            max_point,
+            density: 1.0,
+            albedo: Vec3f::new(1.0),
+            grid: Arc::new(grid),
+        }
+    }
+
+    pub const fn with_density(mut self, density: f64) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub const fn with_albedo(mut self, albedo: Vec3f) -> Self {
+        self.albedo = albedo;
+        self
+    }
+
+    pub const fn min_point(&self) -> Vec3f {
+        self.min_point
+    }
+
+    pub const fn max_point(&self) -> Vec3f {
+        self.max_point
+    }
+
+    pub const fn density(&self) -> f64 {
+        self.density
+    }
+
+    pub const fn albedo(&self) -> Vec3f {
+        self.albedo
+    }
+
+    pub fn grid(&self) -> &DensityGrid {
+        &self.grid
+    }
+
+    pub fn interval(
+        &self,
+        origin: Vec3f,
+        direction: Vec3f,
+        max_distance: f64,
+    ) -> Option<(f64, f64)> {
+        let (near, far) =
+            (0..3).try_fold((0.0, max_distance), |(near, far): (f64, f64), axis| {
+                let inverse = direction[axis].recip();
+                let first = (self.min_point[axis] - origin[axis]) * inverse;
+                let second = (self.max_point[axis] - origin[axis]) * inverse;
+                let near = near.max(first.min(second));
+                let far = far.min(first.max(second));
+                (near <= far).then_some((near, far))
+            })?;
+
+        (far > near).then_some((near, far))
+    }
+
+    pub fn density_at(&self, point: Vec3f) -> f64 {
+        let extent = self.max_point - self.min_point;
+        let local = [0, 1, 2].map(|axis| (point[axis] - self.min_point[axis]) / extent[axis]);
+        if local
+            .iter()
+            .any(|coordinate| !(0.0..=1.0).contains(coordinate))
+        {
+            return 0.0;
+        }
+
+        self.density * self.grid.sample(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grids_load_and_interpolate_between_cells() -> Result<()> {
+        let grid = DensityGrid::parse("2 1 1\n0 2\n", PathBuf::from("ramp.grid"))?;
+        assert!(grid.sample([0.0, 0.5, 0.5]).abs() < 1e-12);
+        assert!((grid.sample([0.5, 0.5, 0.5]) - 1.0).abs() < 1e-12);
+        assert!((grid.sample([1.0, 0.5, 0.5]) - 2.0).abs() < 1e-12);
+        assert!(DensityGrid::parse("2 1 1\n0\n", PathBuf::new()).is_err());
+
+        let cloud = Volume::new(
+            Vec3f::new(-1.0),
+            Vec3f::new(1.0),
+            DensityGrid::noise(8, 2.0, 7),
+        )
+        .with_density(3.0);
+        assert_eq!(cloud.grid(), &DensityGrid::noise(8, 2.0, 7));
+        assert!(cloud.density_at(Vec3f::new(0.0)) > 0.0);
+        assert!(cloud.density_at(Vec3f::new(2.0)).abs() < 1e-12);
+
+        let forward = Vec3f::new_with_data([0.0, 0.0, -1.0]);
+        let (near, far) = cloud
+            .interval(
+                Vec3f::new_with_data([0.0, 0.0, 5.0]),
+                forward,
+                f64::INFINITY,
+            )
+            .ok_or_else(|| anyhow::anyhow!("ray missed the volume"))?;
+        assert!((near - 4.0).abs() < 1e-12 && (far - 6.0).abs() < 1e-12);
+        assert!(
+            cloud
+                .interval(
+                    Vec3f::new_with_data([3.0, 0.0, 5.0]),
+                    forward,
+                    f64::INFINITY
+                )
+                .is_none()
+        );
+        Ok(())
+    }
+}